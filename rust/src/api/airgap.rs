@@ -0,0 +1,201 @@
+//! Handing a signed claim to another device for broadcast.
+//!
+//! An heir whose phone cannot reach any server can still get the claim out:
+//! [`export_tx_for_manual_broadcast`] turns the finalized transaction into
+//! animated QR frames (BBQr for Coldcard and Sparrow, UR for wallets built
+//! on the Blockchain Commons libraries) and a `.txn` file whose hex can be
+//! pasted into mempool.space's broadcast form.
+
+use serde::{Deserialize, Serialize};
+
+use super::{decode_tx, HeirError};
+
+/// Base32 characters per BBQr frame; a multiple of 8 so every frame but
+/// the last holds whole bytes.
+const BBQR_FRAME_CHARS: usize = 400;
+/// Transaction bytes per UR frame, about what a BBQr frame carries.
+const UR_FRAGMENT_BYTES: usize = 250;
+
+/// First and last letters of the 256 Bytewords, in byte order.
+const BYTEWORDS_MINIMAL: &str = concat!(
+    "aeadaoaxaaahamatayasbkbdbnbtbabsbebybgbwbbbzcmchcscfcycwcecackct",
+    "cxclcpcndkdadsdidedtdrdndwdpdmdldyeheyeoeeecenemetesftfrfnfsfmfh",
+    "fzfpfwfxfyfefgflfdgagegrgsgtglgwgdgygmgughgohfhghdhkhthphhhlhyhe",
+    "hnhsidiaieihiyioisinimjejzjnjtjljojsjpjkjykpkoktkskkknkgkekikblb",
+    "lalylflslrlplnltloldlelulklgmnmymhmemomumwmdmtmsmknlnyndnsntnnne",
+    "nboyoeotoxonolospdptpkpypspmplpepfpaprqdqzrerprlrorhrdrkrfryrnrs",
+    "rtsesasrssskswstspsosgsbsfsntotktitttdtetytltbtstptatnuyuoutueur",
+    "vtvyvovlvevwvavdvswlwdwmwpwewywswtwnwzwfwkykynylyaytzszoztzczezm",
+);
+
+/// A signed transaction in every form another device might accept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManualBroadcastExport {
+    pub txid: String,
+    /// Frames to show in a loop; a single frame needs no animation.
+    pub bbqr_frames: Vec<String>,
+    pub ur_frames: Vec<String>,
+    /// Suggested name for the file, `<txid>.txn`.
+    pub file_name: String,
+    /// Contents of the `.txn` file: the transaction as hex text.
+    pub file_bytes: Vec<u8>,
+}
+
+/// RFC 4648 base32 without padding, as BBQr uses it.
+fn base32(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | u16::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[usize::from((buffer >> bits) & 31)] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[usize::from((buffer << (5 - bits)) & 31)] as char);
+    }
+    out
+}
+
+/// Two uppercase base-36 digits, as BBQr headers count parts.
+fn base36_pair(n: usize) -> String {
+    const DIGITS: &[u8; 36] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    format!("{}{}", DIGITS[n / 36] as char, DIGITS[n % 36] as char)
+}
+
+/// BBQr frames for a raw transaction: base32 (`2`), file type `T`.
+pub(crate) fn bbqr_frames(tx_bytes: &[u8]) -> Vec<String> {
+    let encoded = base32(tx_bytes);
+    let chunks: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(BBQR_FRAME_CHARS)
+        .map(|chunk| std::str::from_utf8(chunk).expect("base32 is ASCII"))
+        .collect();
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            format!(
+                "B$2T{}{}{}",
+                base36_pair(chunks.len()),
+                base36_pair(index),
+                chunk
+            )
+        })
+        .collect()
+}
+
+/// Bytewords "minimal" encoding of `body` followed by its CRC-32.
+fn bytewords_minimal(body: &[u8]) -> String {
+    let mut crc = flate2::Crc::new();
+    crc.update(body);
+    body.iter()
+        .chain(&crc.sum().to_be_bytes())
+        .map(|&byte| &BYTEWORDS_MINIMAL[usize::from(byte) * 2..usize::from(byte) * 2 + 2])
+        .collect()
+}
+
+fn cbor(value: &ciborium::Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::into_writer(value, &mut out).expect("writing CBOR to memory cannot fail");
+    out
+}
+
+/// `ur:bytes` frames for a raw transaction. Longer transactions are split
+/// into the plain fragments `1-n` to `n-n` of a multi-part UR, which every
+/// fountain decoder accepts.
+pub(crate) fn ur_frames(tx_bytes: &[u8]) -> Vec<String> {
+    let message = cbor(&ciborium::Value::Bytes(tx_bytes.to_vec()));
+    if tx_bytes.len() <= UR_FRAGMENT_BYTES {
+        return vec![format!("ur:bytes/{}", bytewords_minimal(&message))];
+    }
+
+    let mut crc = flate2::Crc::new();
+    crc.update(&message);
+    let checksum = crc.sum();
+    let count = message.len().div_ceil(UR_FRAGMENT_BYTES);
+    let fragment_len = message.len().div_ceil(count);
+    (0..count)
+        .map(|index| {
+            let start = index * fragment_len;
+            let mut fragment = message[start..(start + fragment_len).min(message.len())].to_vec();
+            fragment.resize(fragment_len, 0);
+            let part = cbor(&ciborium::Value::Array(vec![
+                (index as u64 + 1).into(),
+                (count as u64).into(),
+                (message.len() as u64).into(),
+                u64::from(checksum).into(),
+                ciborium::Value::Bytes(fragment),
+            ]));
+            format!(
+                "ur:bytes/{}-{}/{}",
+                index + 1,
+                count,
+                bytewords_minimal(&part)
+            )
+        })
+        .collect()
+}
+
+/// QR frames and a `.txn` file for broadcasting `tx_hex` from another
+/// device or a website.
+pub fn export_tx_for_manual_broadcast(tx_hex: String) -> Result<ManualBroadcastExport, HeirError> {
+    let tx = decode_tx(&tx_hex)?;
+    let tx_bytes = bitcoin::consensus::encode::serialize(&tx);
+    let txid = tx.compute_txid().to_string();
+    Ok(ManualBroadcastExport {
+        bbqr_frames: bbqr_frames(&tx_bytes),
+        ur_frames: ur_frames(&tx_bytes),
+        file_name: format!("{}.txn", txid),
+        file_bytes: format!("{}\n", hex::encode(&tx_bytes)).into_bytes(),
+        txid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::vectors::generate_test_vectors;
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(base32(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base36_pair(1), "01");
+        assert_eq!(base36_pair(71), "1Z");
+        assert_eq!(bytewords_minimal(&[]).len(), 8);
+        assert!(BYTEWORDS_MINIMAL.starts_with("aead") && BYTEWORDS_MINIMAL.ends_with("zezm"));
+    }
+
+    #[test]
+    fn test_export_signed_claim() {
+        let v = generate_test_vectors(49).unwrap();
+        let export = export_tx_for_manual_broadcast(v.tx_hex.clone()).unwrap();
+        assert_eq!(export.txid, v.txid);
+        assert_eq!(export.file_name, format!("{}.txn", v.txid));
+        assert_eq!(
+            String::from_utf8(export.file_bytes).unwrap().trim(),
+            v.tx_hex.to_lowercase()
+        );
+        assert_eq!(export.bbqr_frames.len(), 1);
+        assert!(export.bbqr_frames[0].starts_with("B$2T0100"));
+        assert_eq!(export.ur_frames.len(), 1);
+        assert!(export.ur_frames[0].starts_with("ur:bytes/"));
+    }
+
+    #[test]
+    fn test_large_transactions_are_split() {
+        let tx = vec![0xab; 700];
+        let frames = bbqr_frames(&tx);
+        assert_eq!(frames.len(), 3);
+        assert!(frames[2].starts_with("B$2T0302"));
+        assert!(frames[..2].iter().all(|f| f.len() == 8 + BBQR_FRAME_CHARS));
+
+        let frames = ur_frames(&tx);
+        assert_eq!(frames.len(), 3);
+        assert!(frames[2].starts_with("ur:bytes/3-3/"));
+    }
+}
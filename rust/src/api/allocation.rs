@@ -0,0 +1,168 @@
+//! Per-heir payout shares recorded in the backup.
+//!
+//! Backup v2 lets the owner give each heir an `allocation_percent`. The
+//! upstream backup model does not know the field, so it is read from the
+//! raw JSON here and carried through import into the canonical backup.
+
+use std::str::FromStr;
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, Xpub};
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::{Address, CompressedPublicKey, Network};
+use serde_json::Value;
+
+use nostring_inherit::backup::HeirBackupEntry;
+
+use super::{
+    build_split_claim_psbt, parse_backup, parse_network, Backend, ClaimOptions, ClaimPsbt,
+    ErrorKind, HeirError,
+};
+
+const FIELD: &str = "allocation_percent";
+
+fn invalid(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidBackup, message)
+}
+
+/// Each heir's share, in backup order, or `None` if the backup has none.
+/// Shares must be given for every heir or for none, and sum to 100.
+pub(crate) fn heir_allocations(vault_json: &str) -> Result<Option<Vec<f64>>, HeirError> {
+    let value: Value = serde_json::from_str(vault_json)
+        .map_err(|_| invalid("Backup is not valid JSON"))?;
+    let heirs = value
+        .get("heirs")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("Backup has no heirs"))?;
+
+    let shares: Vec<Option<f64>> = heirs
+        .iter()
+        .map(|heir| match heir.get(FIELD) {
+            None | Some(Value::Null) => Ok(None),
+            Some(v) => v
+                .as_f64()
+                .filter(|pct| pct.is_finite() && (0.0..=100.0).contains(pct))
+                .map(Some)
+                .ok_or_else(|| invalid(format!("Invalid {}: {}", FIELD, v))),
+        })
+        .collect::<Result<_, _>>()?;
+
+    if shares.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+    let shares: Vec<f64> = shares
+        .into_iter()
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid("Allocations must be given for every heir or none"))?;
+    let total: f64 = shares.iter().sum();
+    if (total - 100.0).abs() > 1e-6 {
+        return Err(invalid(format!("Heir allocations sum to {}%, not 100%", total)));
+    }
+    Ok(Some(shares))
+}
+
+/// Copy validated allocations into a re-serialized backup.
+pub(crate) fn add_allocations(backup: &mut Value, shares: &[f64]) {
+    if let Some(heirs) = backup.get_mut("heirs").and_then(Value::as_array_mut) {
+        for (heir, share) in heirs.iter_mut().zip(shares) {
+            heir[FIELD] = serde_json::json!(share);
+        }
+    }
+}
+
+/// First receive address (`/0/0`) of the heir's account xpub, with the
+/// script type implied by the BIP44/49/84/86 purpose in its path.
+pub(crate) fn heir_payout_address(
+    heir: &HeirBackupEntry,
+    network: Network,
+) -> Result<Address, HeirError> {
+    let xpub = Xpub::from_str(&heir.xpub)
+        .map_err(|e| invalid(format!("Invalid xpub for {}: {}", heir.label, e)))?;
+    let path = DerivationPath::from_str(&heir.derivation_path)
+        .map_err(|e| invalid(format!("Invalid derivation path for {}: {}", heir.label, e)))?;
+
+    let secp = Secp256k1::verification_only();
+    let child = xpub
+        .derive_pub(&secp, &[ChildNumber::Normal { index: 0 }, ChildNumber::Normal { index: 0 }])
+        .map_err(|e| invalid(format!("Cannot derive address for {}: {}", heir.label, e)))?;
+    let key = CompressedPublicKey(child.public_key);
+
+    match path.into_iter().next() {
+        Some(ChildNumber::Hardened { index: 44 }) => Ok(Address::p2pkh(key.pubkey_hash(), network)),
+        Some(ChildNumber::Hardened { index: 49 }) => Ok(Address::p2shwpkh(&key, network)),
+        Some(ChildNumber::Hardened { index: 84 }) => Ok(Address::p2wpkh(&key, network)),
+        Some(ChildNumber::Hardened { index: 86 }) => Ok(Address::p2tr(
+            &secp,
+            child.public_key.x_only_public_key().0,
+            None,
+            network,
+        )),
+        _ => Err(invalid(format!(
+            "Cannot tell the address type for {} from path {}",
+            heir.label, heir.derivation_path
+        ))),
+    }
+}
+
+/// Payout address and share for every heir with a non-zero allocation.
+pub(crate) fn allocation_outputs(vault_json: &str) -> Result<Vec<(Address, f64)>, HeirError> {
+    let backup = parse_backup(vault_json)?;
+    let network = parse_network(&backup.network)?;
+    let shares = heir_allocations(vault_json)?.ok_or_else(|| {
+        HeirError::new(ErrorKind::InvalidInput, "This backup does not record heir allocations")
+    })?;
+    backup
+        .heirs
+        .iter()
+        .zip(shares)
+        .filter(|(_, share)| *share > 0.0)
+        .map(|(heir, share)| Ok((heir_payout_address(heir, network)?, share)))
+        .collect()
+}
+
+/// Build a claim paying every heir their recorded share, to the first
+/// receive address of their own xpub.
+pub fn build_allocated_claim_psbt(
+    vault_json: String,
+    backend: &Backend,
+    heir_index: usize,
+    fee_rate_sat_vb: u64,
+    options: ClaimOptions,
+) -> Result<ClaimPsbt, HeirError> {
+    let allocations = allocation_outputs(&vault_json)?
+        .into_iter()
+        .map(|(address, share)| (address.to_string(), share))
+        .collect();
+    build_split_claim_psbt(vault_json, backend, allocations, heir_index, fee_rate_sat_vb, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup_with(shares: &[Option<f64>]) -> String {
+        let heirs: Vec<Value> = shares
+            .iter()
+            .map(|share| match share {
+                Some(pct) => serde_json::json!({ "label": "h", FIELD: pct }),
+                None => serde_json::json!({ "label": "h" }),
+            })
+            .collect();
+        serde_json::json!({ "heirs": heirs }).to_string()
+    }
+
+    #[test]
+    fn test_heir_allocations() {
+        assert_eq!(heir_allocations(&backup_with(&[None, None])).unwrap(), None);
+        assert_eq!(
+            heir_allocations(&backup_with(&[Some(70.0), Some(30.0)])).unwrap(),
+            Some(vec![70.0, 30.0])
+        );
+        for bad in [
+            backup_with(&[Some(70.0), None]),
+            backup_with(&[Some(70.0), Some(20.0)]),
+            backup_with(&[Some(-10.0), Some(110.0)]),
+        ] {
+            assert_eq!(heir_allocations(&bad).unwrap_err().kind, ErrorKind::InvalidBackup);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Step-by-step re-derivation of the vault address.
+//!
+//! `reconstruct()` only says pass or fail. The audit recomputes the taproot
+//! output from the backup's aggregate internal key and recovery leaves using
+//! BIP341 directly, and returns every intermediate value so an auditor can
+//! check each step with independent tools.
+
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::key::TapTweak;
+use bitcoin::secp256k1::{Secp256k1, XOnlyPublicKey};
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TapNodeHash, TapTweakHash};
+use bitcoin::{Address, ScriptBuf};
+use serde::{Deserialize, Serialize};
+
+use super::{parse_backup, parse_network, ErrorKind, HeirError};
+
+/// One recovery leaf and the path from it to the merkle root.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeafAudit {
+    pub index: usize,
+    pub script_hex: String,
+    pub leaf_version: u8,
+    /// BIP341 `TapLeaf` tagged hash, hex.
+    pub leaf_hash: String,
+    /// Sibling hashes from the control block, leaf to root.
+    pub merkle_branch: Vec<String>,
+    /// Root reached by hashing the leaf up its branch.
+    pub computed_root: String,
+    /// Internal key carried in the control block.
+    pub control_block_internal_key: String,
+    /// Output key parity carried in the control block (0 even, 1 odd).
+    pub control_block_parity: u8,
+}
+
+/// Every intermediate value of the address derivation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DerivationAudit {
+    pub network: String,
+    /// Aggregate x-only internal key from the backup.
+    pub internal_key: String,
+    pub leaves: Vec<LeafAudit>,
+    /// Common root of all leaves; `None` for a key-path-only vault.
+    pub merkle_root: Option<String>,
+    /// BIP341 `TapTweak` hash of the internal key and merkle root.
+    pub tweak: String,
+    /// Tweaked x-only output key.
+    pub output_key: String,
+    pub output_key_parity: u8,
+    pub derived_address: String,
+    pub backup_address: String,
+    /// True if the derived address equals the backup's and no step
+    /// below disagreed.
+    pub matches: bool,
+    /// Human-readable description of every inconsistency found.
+    pub problems: Vec<String>,
+}
+
+fn audit_error(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidBackup, message)
+}
+
+fn hex_of(hash: impl Hash<Bytes = [u8; 32]>) -> String {
+    hex::encode(hash.to_byte_array())
+}
+
+/// Re-derive the vault address from the backup's internal key and leaves,
+/// returning each intermediate value.
+pub fn audit_address_derivation(vault_json: String) -> Result<DerivationAudit, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let network = parse_network(&backup.network)?;
+    let internal_hex = backup
+        .taproot_internal_key
+        .clone()
+        .ok_or_else(|| audit_error("Backup has no taproot internal key"))?;
+    let internal_key = XOnlyPublicKey::from_str(&internal_hex)
+        .map_err(|e| audit_error(format!("Invalid taproot internal key: {}", e)))?;
+
+    let mut problems = Vec::new();
+    let mut leaves = Vec::with_capacity(backup.recovery_leaves.len());
+    for (index, leaf) in backup.recovery_leaves.iter().enumerate() {
+        let script = ScriptBuf::from_hex(&leaf.script_hex)
+            .map_err(|e| audit_error(format!("Invalid script in leaf {}: {}", index, e)))?;
+        let control = hex::decode(&leaf.control_block_hex)
+            .map_err(|e| audit_error(format!("Invalid control block in leaf {}: {}", index, e)))?;
+        // Version and parity byte, internal key, then 32-byte siblings
+        if control.len() < 33 || !(control.len() - 33).is_multiple_of(32) {
+            return Err(audit_error(format!(
+                "Control block of leaf {} has invalid length {}",
+                index,
+                control.len()
+            )));
+        }
+
+        let leaf_version = control[0] & 0xfe;
+        let version = LeafVersion::from_consensus(leaf_version)
+            .map_err(|e| audit_error(format!("Invalid leaf version in leaf {}: {}", index, e)))?;
+        let leaf_hash = TapLeafHash::from_script(&script, version);
+        let branch: Vec<TapNodeHash> = control[33..]
+            .chunks_exact(32)
+            .map(|chunk| TapNodeHash::from_slice(chunk).expect("32-byte chunk"))
+            .collect();
+        let root = branch
+            .iter()
+            .fold(TapNodeHash::from(leaf_hash), |node, sibling| {
+                TapNodeHash::from_node_hashes(node, *sibling)
+            });
+
+        let control_key = hex::encode(&control[1..33]);
+        if control_key != internal_key.to_string() {
+            problems.push(format!(
+                "Leaf {} control block names internal key {}, not the backup's",
+                index, control_key
+            ));
+        }
+        if version != LeafVersion::TapScript {
+            problems.push(format!("Leaf {} is not a BIP342 tapscript leaf", index));
+        }
+
+        leaves.push((
+            root,
+            LeafAudit {
+                index,
+                script_hex: leaf.script_hex.clone(),
+                leaf_version,
+                leaf_hash: hex_of(leaf_hash),
+                merkle_branch: branch.iter().map(|node| hex_of(*node)).collect(),
+                computed_root: hex_of(root),
+                control_block_internal_key: control_key,
+                control_block_parity: control[0] & 1,
+            },
+        ));
+    }
+
+    let merkle_root = leaves.first().map(|(root, _)| *root);
+    if leaves.iter().any(|(root, _)| Some(*root) != merkle_root) {
+        problems.push("Recovery leaves do not commit to the same merkle root".to_string());
+    }
+
+    let secp = Secp256k1::verification_only();
+    let tweak = TapTweakHash::from_key_and_tweak(internal_key, merkle_root);
+    let (output_key, parity) = internal_key.tap_tweak(&secp, merkle_root);
+    let output_key_parity = parity.to_u8();
+    let derived_address = Address::p2tr_tweaked(output_key, network).to_string();
+
+    let leaves: Vec<LeafAudit> = leaves.into_iter().map(|(_, leaf)| leaf).collect();
+    for leaf in leaves
+        .iter()
+        .filter(|leaf| leaf.control_block_parity != output_key_parity)
+    {
+        problems.push(format!(
+            "Leaf {} control block has the wrong output key parity",
+            leaf.index
+        ));
+    }
+    if derived_address != backup.vault_address {
+        problems.push(format!(
+            "Derived address {} differs from the backup's {}",
+            derived_address, backup.vault_address
+        ));
+    }
+
+    Ok(DerivationAudit {
+        network: backup.network.clone(),
+        internal_key: internal_key.to_string(),
+        leaves,
+        merkle_root: merkle_root.map(hex_of),
+        tweak: hex_of(tweak),
+        output_key: output_key.to_string(),
+        output_key_parity,
+        derived_address,
+        backup_address: backup.vault_address.clone(),
+        matches: problems.is_empty(),
+        problems,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::vectors::generate_test_vectors;
+
+    #[test]
+    fn test_audit_reproduces_vector_address() {
+        let v = generate_test_vectors(15).unwrap();
+        let audit = audit_address_derivation(v.backup_json).unwrap();
+        assert!(audit.matches, "{:?}", audit.problems);
+        assert_eq!(audit.derived_address, v.vault_address);
+        assert_eq!(audit.leaves.len(), 1);
+        assert_eq!(
+            audit.merkle_root.as_deref(),
+            Some(audit.leaves[0].computed_root.as_str())
+        );
+        assert_eq!(audit.tweak.len(), 64);
+    }
+
+    #[test]
+    fn test_audit_reports_tampered_leaf() {
+        let v = generate_test_vectors(15).unwrap();
+        let mut value: serde_json::Value = serde_json::from_str(&v.backup_json).unwrap();
+        // Any change to the leaf script changes its hash and the address
+        let script = value["recovery_leaves"][0]["script_hex"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        value["recovery_leaves"][0]["script_hex"] = serde_json::json!(format!("{}51", script));
+        let audit = audit_address_derivation(value.to_string()).unwrap();
+        assert!(!audit.matches);
+        assert!(audit.problems.iter().any(|p| p.contains("differs")));
+    }
+}
@@ -0,0 +1,309 @@
+//! Chain data sources.
+//!
+//! The app creates one [`Backend`] per session and passes it to every
+//! network function. Each backend implements [`ChainBackend`]; the API code
+//! never talks to a server type directly.
+
+use std::sync::Arc;
+
+use bitcoin::{Address, Amount, Network, OutPoint, ScriptBuf, Transaction, Txid};
+
+use super::cache::{CacheConfig, CachedBackend};
+use super::info::BackendKind;
+use super::simulated::SimulatedBackend;
+use super::{BroadcastFailure, ErrorKind, HeirError};
+
+/// An unspent output as reported by a backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChainUtxo {
+    pub outpoint: OutPoint,
+    pub value: Amount,
+    pub script_pubkey: ScriptBuf,
+    /// Confirmation height, or 0 while unconfirmed.
+    pub height: u32,
+}
+
+/// A transaction touching an address, as reported by a backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChainHistoryEntry {
+    pub txid: Txid,
+    /// Confirmation height, or 0 while in the mempool.
+    pub height: u32,
+}
+
+/// Check a height reported by a server. Heights are `u32` on-chain; a
+/// negative or larger value means a broken or hostile server, never a
+/// real chain.
+pub(crate) fn block_height<T>(reported: T) -> Result<u32, HeirError>
+where
+    T: TryInto<u32> + TryInto<i64> + Copy + std::fmt::Display,
+{
+    reported.try_into().map_err(|_| {
+        let saturated: i64 = reported.try_into().unwrap_or(i64::MAX);
+        HeirError::new(
+            ErrorKind::HeightOutOfRange { reported: saturated },
+            format!("The server reported an impossible block height: {}", reported),
+        )
+    })
+}
+
+/// Bitcoin Core's default minimum relay fee rate, in sat/vB.
+pub(crate) const DEFAULT_RELAY_FEE_RATE: f64 = 1.0;
+
+/// Operations every chain data source provides.
+pub(crate) trait ChainBackend: Send + Sync {
+    /// Network this backend serves.
+    fn network(&self) -> Network;
+
+    /// Current chain tip height.
+    fn tip_height(&self) -> Result<u64, HeirError>;
+
+    /// Unspent outputs paying `address`.
+    fn list_unspent(&self, address: &Address) -> Result<Vec<ChainUtxo>, HeirError>;
+
+    /// Every transaction that pays to or spends from `address`, oldest first.
+    fn history(&self, address: &Address) -> Result<Vec<ChainHistoryEntry>, HeirError>;
+
+    /// [`ChainBackend::list_unspent`] for several addresses, in the order
+    /// given. Backends that can batch requests override this.
+    fn list_unspent_many(&self, addresses: &[Address]) -> Result<Vec<Vec<ChainUtxo>>, HeirError> {
+        addresses.iter().map(|address| self.list_unspent(address)).collect()
+    }
+
+    /// [`ChainBackend::history`] for several addresses, in the order given.
+    fn histories(&self, addresses: &[Address]) -> Result<Vec<Vec<ChainHistoryEntry>>, HeirError> {
+        addresses.iter().map(|address| self.history(address)).collect()
+    }
+
+    /// Timestamp (unix seconds) of the block at `height`.
+    fn block_time(&self, height: u32) -> Result<u64, HeirError>;
+
+    /// Fetch a transaction by id.
+    fn transaction(&self, txid: &Txid) -> Result<Transaction, HeirError>;
+
+    /// Fetch several transactions, in the order given. Backends that can
+    /// batch requests override this.
+    fn transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>, HeirError> {
+        txids.iter().map(|txid| self.transaction(txid)).collect()
+    }
+
+    /// Submit a transaction to the network.
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, HeirError>;
+
+    /// Whether [`ChainBackend::broadcast_package`] submits transactions
+    /// together (Bitcoin Core's `submitpackage`) rather than one by one.
+    fn relays_packages(&self) -> bool {
+        false
+    }
+
+    /// Submit a parent and its children, parents first. Backends with
+    /// package relay override this; the default broadcasts them in order,
+    /// treating a transaction the node already has as accepted.
+    fn broadcast_package(&self, txs: &[Transaction]) -> Result<Vec<Txid>, HeirError> {
+        txs.iter()
+            .map(|tx| match self.broadcast(tx) {
+                Err(HeirError {
+                    kind:
+                        ErrorKind::Broadcast {
+                            reason: BroadcastFailure::AlreadyInMempool,
+                        },
+                    ..
+                }) => Ok(tx.compute_txid()),
+                result => result,
+            })
+            .collect()
+    }
+
+    /// Fee rate (sat/vB) expected to confirm within `target_blocks`.
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<f64, HeirError>;
+
+    /// Lowest fee rate (sat/vB) the server's node relays. Backends that
+    /// can ask override this; the default is Bitcoin Core's.
+    fn relay_fee_rate(&self) -> Result<f64, HeirError> {
+        Ok(DEFAULT_RELAY_FEE_RATE)
+    }
+}
+
+/// Handle to a chain data source, created once per session.
+#[derive(Clone)]
+pub struct Backend {
+    inner: Arc<dyn ChainBackend>,
+}
+
+impl Backend {
+    /// Electrum server backend (`ssl://host:port` or `tcp://host:port`).
+    pub fn electrum(url: String, network: super::Network) -> Result<Backend, HeirError> {
+        let network = Network::from(network);
+        #[cfg(feature = "electrum")]
+        {
+            Ok(Backend {
+                inner: Arc::new(super::electrum::ElectrumBackend::new(url, network)),
+            })
+        }
+        #[cfg(not(feature = "electrum"))]
+        {
+            let _ = (url, network);
+            Err(HeirError::new(
+                ErrorKind::BackendUnavailable,
+                "This build does not include the Electrum backend",
+            ))
+        }
+    }
+
+    /// Electrum backend with control over name resolution and trusted roots.
+    #[cfg(feature = "electrum")]
+    pub fn electrum_with_options(
+        url: String,
+        network: super::Network,
+        options: super::socket::ConnectionOptions,
+    ) -> Result<Backend, HeirError> {
+        use super::electrum::{ElectrumBackend, NativeConnector};
+        use super::transport::TransportConnector;
+
+        let network = Network::from(network);
+        options.validate()?;
+        let inner: Arc<dyn ChainBackend> = if options.uses_native_client() {
+            let connector = NativeConnector {
+                proxy: options.socks5_proxy,
+            };
+            Arc::new(ElectrumBackend::with_connector(connector, url, network))
+        } else {
+            let connector = TransportConnector::new(super::socket::SocketTransport::new(options)?);
+            Arc::new(ElectrumBackend::with_connector(connector, url, network))
+        };
+        Ok(Backend { inner })
+    }
+
+    /// Electrum backend that sends its traffic through the app's own
+    /// sockets, for platforms where Rust cannot open them.
+    #[cfg(feature = "electrum")]
+    pub fn electrum_over_transport(
+        url: String,
+        network: super::Network,
+        transport: super::transport::HostTransport,
+    ) -> Result<Backend, HeirError> {
+        let network = Network::from(network);
+        Ok(Backend {
+            inner: Arc::new(super::transport::electrum_backend(url, network, transport)),
+        })
+    }
+
+    /// Electrum backend over several servers. Probes them all first, then
+    /// starts with the healthiest and fails over to the next whenever one
+    /// stops answering. With a SOCKS5 `proxy`, every server is reached
+    /// through it.
+    pub fn electrum_pool(
+        urls: Vec<String>,
+        network: super::Network,
+        proxy: Option<String>,
+    ) -> Result<Backend, HeirError> {
+        Ok(Backend {
+            inner: Arc::new(super::pool::electrum_pool(urls, network, proxy)?),
+        })
+    }
+
+    /// Esplora REST backend rooted at e.g. `https://blockstream.info/api`
+    /// or `https://mempool.space/api`.
+    pub fn esplora(base_url: String, network: super::Network) -> Result<Backend, HeirError> {
+        let network = Network::from(network);
+        #[cfg(feature = "esplora")]
+        {
+            Ok(Backend {
+                inner: Arc::new(super::esplora::EsploraBackend::new(base_url, network)?),
+            })
+        }
+        #[cfg(not(feature = "esplora"))]
+        {
+            let _ = (base_url, network);
+            Err(HeirError::new(
+                ErrorKind::BackendUnavailable,
+                "This build does not include the Esplora backend",
+            ))
+        }
+    }
+
+    /// Backend of the given kind, for apps that let the heir choose. `url`
+    /// is the server URL for Electrum or the API root for Esplora.
+    pub fn connect(
+        kind: BackendKind,
+        url: String,
+        network: super::Network,
+    ) -> Result<Backend, HeirError> {
+        match kind {
+            BackendKind::Electrum => Backend::electrum(url, network),
+            BackendKind::Esplora => Backend::esplora(url, network),
+            BackendKind::Simulated => Err(HeirError::new(
+                ErrorKind::InvalidInput,
+                "Simulated backends are created from a SimulatedBackend",
+            )),
+        }
+    }
+
+    /// Offline backend with scriptable chain state, for UI development.
+    pub fn simulated(sim: &SimulatedBackend) -> Backend {
+        Backend {
+            inner: Arc::new(sim.clone()),
+        }
+    }
+
+    /// This backend with tip heights and fee estimates reused for the
+    /// times in `config`. Pass the returned backend to every call of the
+    /// session so they share the cache.
+    pub fn with_cache(&self, config: CacheConfig) -> Backend {
+        Backend {
+            inner: Arc::new(CachedBackend::new(self.inner.clone(), config)),
+        }
+    }
+
+    pub(crate) fn chain(&self) -> &dyn ChainBackend {
+        self.inner.as_ref()
+    }
+
+    /// Fail unless this backend serves `network`.
+    pub(crate) fn require_network(&self, network: Network) -> Result<(), HeirError> {
+        if self.inner.network() == network {
+            Ok(())
+        } else {
+            Err(HeirError::new(
+                ErrorKind::NetworkMismatch,
+                format!(
+                    "Backend is configured for {} but the vault is on {}",
+                    self.inner.network(),
+                    network
+                ),
+            ))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "electrum"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_require_network() {
+        let backend =
+            Backend::electrum("ssl://localhost:50002".into(), crate::api::Network::Testnet).unwrap();
+        assert!(backend.require_network(Network::Testnet).is_ok());
+        let err = backend.require_network(Network::Bitcoin).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::NetworkMismatch);
+    }
+
+    #[test]
+    fn test_connect_by_kind() {
+        let esplora = Backend::connect(
+            BackendKind::Esplora,
+            "https://mempool.space/signet/api".into(),
+            crate::api::Network::Signet,
+        );
+        assert_eq!(esplora.is_ok(), cfg!(feature = "esplora"));
+        let err = Backend::connect(
+            BackendKind::Simulated,
+            "http://localhost:8332".into(),
+            crate::api::Network::Mainnet,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,286 @@
+//! Network calls that do not block the caller's thread.
+//!
+//! The plain functions wait for the server on the thread that calls them,
+//! which freezes the UI when that is the main isolate. The `_async`
+//! variants here run the same call on a worker thread and resolve when it
+//! returns, or as soon as their [`CancelToken`] is cancelled. The bridge
+//! drives them on its own async runtime, so the crate needs none.
+//!
+//! Cancelling abandons the result; it cannot interrupt a query already on
+//! the wire. A cancelled broadcast may therefore still have reached the
+//! server, and the app should check the vault status before retrying.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use super::{
+    broadcast_transaction, build_claim_psbt, fetch_vault_status, Backend, BroadcastResult,
+    ClaimPsbt, ErrorKind, HeirError, VaultStatus,
+};
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    /// Waker of each pending job, by job id. A job removes its own when it
+    /// is dropped, so a token that is never cancelled does not collect them.
+    wakers: Mutex<HashMap<u64, Waker>>,
+    next_job: AtomicU64,
+}
+
+/// Lets the app stop waiting for one or more `_async` calls.
+///
+/// Clones share the same state, so one token can cancel every call of a
+/// screen. A cancelled token stays cancelled; create a new one to retry.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    state: Arc<CancelState>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Make every call waiting on this token return
+    /// [`ErrorKind::Cancelled`].
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        let wakers = std::mem::take(&mut *self.state.wakers.lock().unwrap());
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+fn cancelled() -> HeirError {
+    HeirError::new(ErrorKind::Cancelled, "Cancelled")
+}
+
+struct Slot<T> {
+    result: Option<Result<T, HeirError>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves with the worker's result, or early when the token is cancelled.
+struct Job<T> {
+    id: u64,
+    slot: Arc<Mutex<Slot<T>>>,
+    cancel: CancelToken,
+}
+
+impl<T> Future for Job<T> {
+    type Output = Result<T, HeirError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(result) = self.slot.lock().unwrap().result.take() {
+            return Poll::Ready(result);
+        }
+        // Register before checking the flag so a cancel in between still
+        // wakes this task
+        {
+            let mut wakers = self.cancel.state.wakers.lock().unwrap();
+            if !wakers
+                .get(&self.id)
+                .is_some_and(|waker| waker.will_wake(cx.waker()))
+            {
+                wakers.insert(self.id, cx.waker().clone());
+            }
+        }
+        if self.cancel.is_cancelled() {
+            return Poll::Ready(Err(cancelled()));
+        }
+        let mut slot = self.slot.lock().unwrap();
+        if let Some(result) = slot.result.take() {
+            return Poll::Ready(result);
+        }
+        slot.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Job<T> {
+    fn drop(&mut self) {
+        self.cancel.state.wakers.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Run `f` on a new thread unless `cancel` already is.
+pub(crate) fn spawn_cancellable<T, F>(
+    cancel: &CancelToken,
+    f: F,
+) -> impl Future<Output = Result<T, HeirError>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, HeirError> + Send + 'static,
+{
+    let slot = Arc::new(Mutex::new(Slot {
+        result: None,
+        waker: None,
+    }));
+    if cancel.is_cancelled() {
+        slot.lock().unwrap().result = Some(Err(cancelled()));
+    } else {
+        let worker_slot = Arc::clone(&slot);
+        let worker_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            let result = if worker_cancel.is_cancelled() {
+                Err(cancelled())
+            } else {
+                f()
+            };
+            let mut slot = worker_slot.lock().unwrap();
+            slot.result = Some(result);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        });
+    }
+    Job {
+        id: cancel.state.next_job.fetch_add(1, Ordering::Relaxed),
+        slot,
+        cancel: cancel.clone(),
+    }
+}
+
+/// The backend's chain tip height, without blocking.
+pub async fn get_block_height_async(
+    backend: &Backend,
+    cancel: &CancelToken,
+) -> Result<u64, HeirError> {
+    let backend = backend.clone();
+    spawn_cancellable(cancel, move || backend.chain().tip_height()).await
+}
+
+/// [`fetch_vault_status`] without blocking.
+pub async fn fetch_vault_status_async(
+    vault_json: String,
+    backend: &Backend,
+    cancel: &CancelToken,
+) -> Result<VaultStatus, HeirError> {
+    let backend = backend.clone();
+    spawn_cancellable(cancel, move || fetch_vault_status(vault_json, &backend)).await
+}
+
+/// [`build_claim_psbt`] without blocking.
+pub async fn build_claim_psbt_async(
+    vault_json: String,
+    backend: &Backend,
+    destination_address: String,
+    heir_index: usize,
+    fee_rate_sat_vb: u64,
+    cancel: &CancelToken,
+) -> Result<ClaimPsbt, HeirError> {
+    let backend = backend.clone();
+    spawn_cancellable(cancel, move || {
+        build_claim_psbt(
+            vault_json,
+            &backend,
+            destination_address,
+            heir_index,
+            fee_rate_sat_vb,
+        )
+    })
+    .await
+}
+
+/// [`broadcast_transaction`] without blocking. See the module docs for
+/// what cancelling a broadcast means.
+pub async fn broadcast_transaction_async(
+    tx_hex: String,
+    backend: &Backend,
+    cancel: &CancelToken,
+) -> Result<BroadcastResult, HeirError> {
+    let backend = backend.clone();
+    spawn_cancellable(cancel, move || broadcast_transaction(tx_hex, &backend)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::Network;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+
+    #[test]
+    fn test_async_calls_match_blocking_ones() {
+        let v = generate_test_vectors(41).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(300);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+        let backend = Backend::simulated(&sim);
+        let cancel = CancelToken::new();
+
+        assert_eq!(
+            block_on(get_block_height_async(&backend, &cancel)).unwrap(),
+            300
+        );
+        let status = block_on(fetch_vault_status_async(
+            v.backup_json.clone(),
+            &backend,
+            &cancel,
+        ))
+        .unwrap();
+        assert!(status.eligible);
+        let result = block_on(broadcast_transaction_async(v.tx_hex, &backend, &cancel)).unwrap();
+        assert_eq!(result.txid, v.txid);
+    }
+
+    #[test]
+    fn test_cancel_returns_before_the_worker() {
+        let cancel = CancelToken::new();
+        let (release, wait) = mpsc::channel::<()>();
+        let job = spawn_cancellable(&cancel, move || {
+            wait.recv_timeout(Duration::from_secs(5)).ok();
+            Ok(1u32)
+        });
+        let canceller = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            canceller.cancel();
+        });
+        let err = block_on(job).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Cancelled);
+        release.send(()).ok();
+
+        // Already cancelled: the work never starts
+        let err = block_on(spawn_cancellable(&cancel, || -> Result<(), HeirError> {
+            panic!("should not run")
+        }))
+        .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Cancelled);
+    }
+
+    #[test]
+    fn test_finished_jobs_leave_no_waker_behind() {
+        let cancel = CancelToken::new();
+        for n in 0..3u32 {
+            let job = spawn_cancellable(&cancel, move || {
+                std::thread::sleep(Duration::from_millis(10));
+                Ok(n)
+            });
+            assert_eq!(block_on(job).unwrap(), n);
+        }
+        assert!(cancel.state.wakers.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+//! Short-lived answers to lookups the UI repeats.
+//!
+//! Screens re-render often, and each render may ask for the tip height or
+//! a fee estimate. [`super::Backend::with_cache`] wraps a backend so those
+//! answers are reused for a few seconds instead of becoming one server
+//! request per render. Everything else still goes to the server. The cache
+//! lives in the returned backend, so every call given that backend shares
+//! it.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bitcoin::{Address, Network, Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
+use super::backend::{ChainBackend, ChainHistoryEntry, ChainUtxo};
+use super::HeirError;
+
+/// How long cached answers are reused. 0 turns caching off for that
+/// lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheConfig {
+    pub height_ttl_secs: u64,
+    /// Fee estimates and the relay fee.
+    pub fee_ttl_secs: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            height_ttl_secs: 30,
+            fee_ttl_secs: 60,
+        }
+    }
+}
+
+/// Values by key, each reused until it is `ttl` old. Errors are not kept.
+struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Copy> TtlCache<K, V> {
+    fn new(ttl_secs: u64) -> Self {
+        Self {
+            ttl: Duration::from_secs(ttl_secs),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get_or_fetch(
+        &self,
+        key: K,
+        fetch: impl FnOnce() -> Result<V, HeirError>,
+    ) -> Result<V, HeirError> {
+        if let Some((fetched, value)) = self.entries.lock().unwrap().get(&key) {
+            if fetched.elapsed() < self.ttl {
+                return Ok(*value);
+            }
+        }
+        // Not locked while the server answers, so one slow lookup does not
+        // hold up the others
+        let value = fetch()?;
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), value));
+        Ok(value)
+    }
+}
+
+/// A backend whose height and fee lookups go through [`TtlCache`]s.
+pub(crate) struct CachedBackend {
+    inner: Arc<dyn ChainBackend>,
+    height: TtlCache<(), u64>,
+    fee_rates: TtlCache<u16, f64>,
+    relay_fee_rate: TtlCache<(), f64>,
+}
+
+impl CachedBackend {
+    pub(crate) fn new(inner: Arc<dyn ChainBackend>, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            height: TtlCache::new(config.height_ttl_secs),
+            fee_rates: TtlCache::new(config.fee_ttl_secs),
+            relay_fee_rate: TtlCache::new(config.fee_ttl_secs),
+        }
+    }
+}
+
+impl ChainBackend for CachedBackend {
+    fn network(&self) -> Network {
+        self.inner.network()
+    }
+
+    fn tip_height(&self) -> Result<u64, HeirError> {
+        self.height.get_or_fetch((), || self.inner.tip_height())
+    }
+
+    fn list_unspent(&self, address: &Address) -> Result<Vec<ChainUtxo>, HeirError> {
+        self.inner.list_unspent(address)
+    }
+
+    fn history(&self, address: &Address) -> Result<Vec<ChainHistoryEntry>, HeirError> {
+        self.inner.history(address)
+    }
+
+    fn list_unspent_many(&self, addresses: &[Address]) -> Result<Vec<Vec<ChainUtxo>>, HeirError> {
+        self.inner.list_unspent_many(addresses)
+    }
+
+    fn histories(&self, addresses: &[Address]) -> Result<Vec<Vec<ChainHistoryEntry>>, HeirError> {
+        self.inner.histories(addresses)
+    }
+
+    fn block_time(&self, height: u32) -> Result<u64, HeirError> {
+        self.inner.block_time(height)
+    }
+
+    fn transaction(&self, txid: &Txid) -> Result<Transaction, HeirError> {
+        self.inner.transaction(txid)
+    }
+
+    fn transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>, HeirError> {
+        self.inner.transactions(txids)
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, HeirError> {
+        self.inner.broadcast(tx)
+    }
+
+    fn relays_packages(&self) -> bool {
+        self.inner.relays_packages()
+    }
+
+    fn broadcast_package(&self, txs: &[Transaction]) -> Result<Vec<Txid>, HeirError> {
+        self.inner.broadcast_package(txs)
+    }
+
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<f64, HeirError> {
+        self.fee_rates.get_or_fetch(target_blocks, || {
+            self.inner.estimate_fee_rate(target_blocks)
+        })
+    }
+
+    fn relay_fee_rate(&self) -> Result<f64, HeirError> {
+        self.relay_fee_rate
+            .get_or_fetch((), || self.inner.relay_fee_rate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::Backend;
+
+    const LONG: CacheConfig = CacheConfig {
+        height_ttl_secs: 3600,
+        fee_ttl_secs: 3600,
+    };
+
+    #[test]
+    fn test_answers_are_reused_until_they_expire() {
+        let sim = SimulatedBackend::new(crate::api::Network::Regtest);
+        sim.set_height(10);
+        sim.set_fee_rate(5.0);
+        let cached = Backend::simulated(&sim).with_cache(LONG);
+        assert_eq!(cached.chain().tip_height().unwrap(), 10);
+        assert_eq!(cached.chain().estimate_fee_rate(6).unwrap(), 5.0);
+
+        sim.set_height(11);
+        sim.set_fee_rate(9.0);
+        assert_eq!(cached.chain().tip_height().unwrap(), 10);
+        assert_eq!(cached.chain().estimate_fee_rate(6).unwrap(), 5.0);
+        // Each target is its own entry
+        assert_eq!(cached.chain().estimate_fee_rate(2).unwrap(), 9.0);
+
+        let uncached = Backend::simulated(&sim).with_cache(CacheConfig {
+            height_ttl_secs: 0,
+            fee_ttl_secs: 0,
+        });
+        assert_eq!(uncached.chain().tip_height().unwrap(), 11);
+        sim.set_height(12);
+        assert_eq!(uncached.chain().tip_height().unwrap(), 12);
+    }
+
+    #[test]
+    fn test_errors_are_not_cached() {
+        let sim = SimulatedBackend::new(crate::api::Network::Regtest);
+        sim.set_height(10);
+        let cached = Backend::simulated(&sim).with_cache(LONG);
+
+        sim.set_offline(true);
+        assert!(cached.chain().tip_height().is_err());
+        sim.set_offline(false);
+        assert_eq!(cached.chain().tip_height().unwrap(), 10);
+    }
+}
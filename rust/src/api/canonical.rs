@@ -0,0 +1,95 @@
+//! Canonical JSON for hashing and fingerprints.
+//!
+//! Object keys are sorted, there is no insignificant whitespace, and numbers
+//! are written in the shortest form that round-trips, without exponents or a
+//! trailing `.0` (as in RFC 8785). Two tools that agree on the content of a
+//! backup therefore agree on its bytes and on its digest.
+
+use serde_json::{Number, Value};
+
+/// Canonical text of `value`.
+pub(crate) fn to_canonical_string(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write_number(out, n),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, key);
+                out.push(':');
+                write_value(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_number(out: &mut String, n: &Number) {
+    if let Some(i) = n.as_i64() {
+        out.push_str(&i.to_string());
+    } else if let Some(u) = n.as_u64() {
+        out.push_str(&u.to_string());
+    } else {
+        let f = n.as_f64().unwrap_or_default();
+        // Display already prints 100.0 as "100"; only negative zero needs care
+        if f == 0.0 {
+            out.push('0');
+        } else {
+            out.push_str(&f.to_string());
+        }
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    // serde_json escapes only what JSON requires, the same way every time
+    out.push_str(&Value::String(s.to_string()).to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(json: &str) -> String {
+        to_canonical_string(&serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn test_sorted_keys_and_compact() {
+        assert_eq!(
+            canonical(r#"{ "b": [1, {"z": null, "a": true}], "a": "x\"y" }"#),
+            r#"{"a":"x\"y","b":[1,{"a":true,"z":null}]}"#
+        );
+    }
+
+    #[test]
+    fn test_number_formatting() {
+        assert_eq!(
+            canonical("[100.0, 100, -0.0, 0.5, 1e3, 33.25]"),
+            "[100,100,0,0.5,1000,33.25]"
+        );
+        assert_eq!(canonical(r#"{"p": 50.0}"#), canonical(r#"{"p":50}"#));
+    }
+}
@@ -0,0 +1,355 @@
+//! Collecting signatures from several heirs.
+//!
+//! A vault whose recovery leaf needs k of n heir signatures is claimed by
+//! passing one PSBT around, or by each heir signing their own copy.
+//! [`merge_signed_psbts`] combines the copies, checks every signature
+//! against the input it signs, and reports who has signed and whether
+//! enough have. [`super::finalize_psbt`] refuses a PSBT whose leaves are
+//! still short of their threshold.
+
+use std::collections::BTreeMap;
+
+use base64::Engine;
+use bitcoin::hashes::Hash;
+use bitcoin::psbt::Input;
+use bitcoin::secp256k1::{Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoin::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::Psbt;
+use serde::{Deserialize, Serialize};
+
+use super::policy::analyze_leaf_script;
+use super::sponsor::is_sponsor_input;
+use super::{decode_psbt, ErrorKind, HeirError};
+
+/// One heir key named in the PSBT and whether it has signed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeirSignature {
+    /// Master fingerprint from the key origin, as hex.
+    pub fingerprint: String,
+    /// X-only public key, as hex.
+    pub public_key_hex: String,
+    /// Signed every vault input.
+    pub signed: bool,
+}
+
+/// Result of [`merge_signed_psbts`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CombinedPsbt {
+    pub psbt_base64: String,
+    pub heirs: Vec<HeirSignature>,
+    /// Signatures the recovery leaf needs.
+    pub threshold: u32,
+    /// Heirs who have signed every input.
+    pub signed_count: usize,
+    /// Every input has a leaf with enough signatures.
+    pub ready_to_finalize: bool,
+}
+
+/// Signatures on the best leaf of `input`, and what that leaf needs.
+/// Finalized inputs and inputs without leaves count as complete.
+fn leaf_progress(input: &Input) -> Option<(usize, u32)> {
+    if input.final_script_witness.is_some() {
+        return None;
+    }
+    input
+        .tap_scripts
+        .values()
+        .map(|(script, _)| {
+            let analysis = analyze_leaf_script(script);
+            let leaf_hash = TapLeafHash::from_script(script, LeafVersion::TapScript);
+            let signatures = analysis
+                .keys
+                .iter()
+                .filter(|key| input.tap_script_sigs.contains_key(&(**key, leaf_hash)))
+                .count();
+            (signatures, analysis.threshold.max(1))
+        })
+        .max_by_key(|&(signatures, threshold)| (signatures >= threshold as usize, signatures))
+}
+
+/// The first input whose leaves are still short of their threshold, with
+/// the signatures it has and needs.
+pub(crate) fn threshold_shortfall(psbt: &Psbt) -> Option<(usize, usize, u32)> {
+    psbt.inputs.iter().enumerate().find_map(|(index, input)| {
+        leaf_progress(input)
+            .filter(|&(signatures, threshold)| signatures < threshold as usize)
+            .map(|(signatures, threshold)| (index, signatures, threshold))
+    })
+}
+
+/// Check every script-path signature in `psbt` against its sighash.
+fn verify_script_sigs(psbt: &Psbt) -> Result<(), HeirError> {
+    let prevouts: Option<Vec<_>> = psbt
+        .inputs
+        .iter()
+        .map(|input| input.witness_utxo.clone())
+        .collect();
+    let Some(prevouts) = prevouts else {
+        return Err(HeirError::new(
+            ErrorKind::InvalidPsbt,
+            "Every input needs its witness UTXO to check signatures",
+        ));
+    };
+    let secp = Secp256k1::verification_only();
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        for ((key, leaf_hash), sig) in &input.tap_script_sigs {
+            let valid = cache
+                .taproot_script_spend_signature_hash(
+                    index,
+                    &Prevouts::All(&prevouts),
+                    *leaf_hash,
+                    sig.sighash_type,
+                )
+                .is_ok_and(|sighash| {
+                    let msg = Message::from_digest(sighash.to_byte_array());
+                    secp.verify_schnorr(&sig.signature, &msg, key).is_ok()
+                });
+            if !valid {
+                return Err(HeirError::new(
+                    ErrorKind::InvalidPsbt,
+                    format!(
+                        "The signature from key {} on input {} does not verify",
+                        key, index
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Combine copies of one claim PSBT signed by different heirs.
+///
+/// Every copy must spend the same unsigned transaction. The result carries
+/// every heir's signatures; it can be finalized once `ready_to_finalize`.
+pub fn merge_signed_psbts(psbts_base64: Vec<String>) -> Result<CombinedPsbt, HeirError> {
+    let mut copies = psbts_base64.iter().map(|psbt| decode_psbt(psbt));
+    let mut combined = copies
+        .next()
+        .ok_or_else(|| HeirError::new(ErrorKind::InvalidInput, "At least one PSBT is needed"))??;
+    for copy in copies {
+        combined.combine(copy?).map_err(|e| {
+            HeirError::new(
+                ErrorKind::InvalidInput,
+                format!("These PSBTs are not copies of the same claim: {}", e),
+            )
+        })?;
+    }
+    verify_script_sigs(&combined)?;
+
+    // Heir keys as the claim's key origins name them
+    let mut heirs: BTreeMap<XOnlyPublicKey, String> = BTreeMap::new();
+    for input in &combined.inputs {
+        for (key, (leaves, (fingerprint, _))) in &input.tap_key_origins {
+            if !leaves.is_empty() {
+                heirs.insert(*key, fingerprint.to_string());
+            }
+        }
+    }
+    let heirs: Vec<HeirSignature> = heirs
+        .into_iter()
+        .map(|(key, fingerprint)| HeirSignature {
+            fingerprint,
+            public_key_hex: key.to_string(),
+            signed: combined
+                .inputs
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !is_sponsor_input(&combined, *index))
+                .all(|(_, input)| {
+                    input.final_script_witness.is_some()
+                        || input
+                            .tap_script_sigs
+                            .keys()
+                            .any(|(signer, _)| *signer == key)
+                }),
+        })
+        .collect();
+
+    let threshold = combined
+        .inputs
+        .iter()
+        .find_map(leaf_progress)
+        .map_or(1, |(_, threshold)| threshold);
+    Ok(CombinedPsbt {
+        ready_to_finalize: threshold_shortfall(&combined).is_none(),
+        psbt_base64: base64::engine::general_purpose::STANDARD.encode(combined.serialize()),
+        signed_count: heirs.iter().filter(|heir| heir.signed).count(),
+        heirs,
+        threshold,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::bip32::Xpub;
+    use bitcoin::secp256k1::{Keypair, SecretKey};
+    use bitcoin::taproot::{Signature, TaprootBuilder};
+    use bitcoin::{Address, TapSighashType};
+    use nostring_inherit::backup::VaultBackup;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::api::legacy::LeafTemplate;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::weighted::{heir_keys, internal_key};
+    use crate::api::{build_claim_psbt, finalize_psbt, Backend, Network};
+
+    /// A claim on a vault whose leaf needs 2 of 3 heirs, and the heirs'
+    /// keys in leaf order.
+    fn two_of_three_claim(seed: u32) -> (Psbt, Vec<Keypair>) {
+        let secp = Secp256k1::new();
+        let v = generate_test_vectors(seed).unwrap();
+        let mut value: Value = serde_json::from_str(&v.backup_json).unwrap();
+        let mut keys = vec![SecretKey::from_str(&v.heir_secret_key_hex).unwrap()];
+        let template = value["heirs"][0].clone();
+        for (label, byte) in [("Child A", 0x31u8), ("Child B", 0x32)] {
+            let sk = SecretKey::from_slice(&[byte; 32]).unwrap();
+            let mut xpub = Xpub::from_str(template["xpub"].as_str().unwrap()).unwrap();
+            xpub.public_key = sk.public_key(&secp);
+            let mut heir = template.clone();
+            heir["label"] = serde_json::json!(label);
+            heir["xpub"] = serde_json::json!(xpub.to_string());
+            value["heirs"].as_array_mut().unwrap().push(heir);
+            keys.push(sk);
+        }
+
+        let backup: VaultBackup = serde_json::from_value(value.clone()).unwrap();
+        let leaf = LeafTemplate::TimelockFirst.leaf_script(
+            &heir_keys(&backup).unwrap(),
+            2,
+            u32::from(backup.timelock_blocks),
+        );
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, leaf.clone())
+            .unwrap()
+            .finalize(&secp, internal_key(&backup).unwrap())
+            .unwrap();
+        let control = spend_info
+            .control_block(&(leaf.clone(), LeafVersion::TapScript))
+            .unwrap();
+        let address = Address::p2tr_tweaked(spend_info.output_key(), bitcoin::Network::Testnet);
+        value["vault_address"] = serde_json::json!(address.to_string());
+        value["threshold"] = serde_json::json!(2);
+        value["recovery_leaves"][0]["script_hex"] = serde_json::json!(leaf.to_hex_string());
+        value["recovery_leaves"][0]["control_block_hex"] =
+            serde_json::json!(hex::encode(control.serialize()));
+
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(400);
+        sim.add_utxo(address.to_string(), "c7".repeat(32), 0, 80_000, 1)
+            .unwrap();
+        let claim = build_claim_psbt(
+            value.to_string(),
+            &Backend::simulated(&sim),
+            v.destination,
+            0,
+            2,
+        )
+        .unwrap();
+        let keypairs = keys
+            .iter()
+            .map(|sk| Keypair::from_secret_key(&secp, sk))
+            .collect();
+        (decode_psbt(&claim.psbt_base64).unwrap(), keypairs)
+    }
+
+    /// `psbt` with a script-path signature from `key` on every input.
+    fn sign(psbt: &Psbt, key: &Keypair) -> String {
+        let secp = Secp256k1::new();
+        let mut psbt = psbt.clone();
+        let prevouts: Vec<_> = psbt
+            .inputs
+            .iter()
+            .map(|i| i.witness_utxo.clone().unwrap())
+            .collect();
+        let (xonly, _) = key.x_only_public_key();
+        for index in 0..psbt.inputs.len() {
+            let (script, _) = psbt.inputs[index]
+                .tap_scripts
+                .values()
+                .next()
+                .unwrap()
+                .clone();
+            let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+            let sighash = SighashCache::new(&psbt.unsigned_tx)
+                .taproot_script_spend_signature_hash(
+                    index,
+                    &Prevouts::All(&prevouts),
+                    leaf_hash,
+                    TapSighashType::Default,
+                )
+                .unwrap();
+            let msg = Message::from_digest(sighash.to_byte_array());
+            let signature = Signature {
+                signature: secp.sign_schnorr_no_aux_rand(&msg, key),
+                sighash_type: TapSighashType::Default,
+            };
+            psbt.inputs[index]
+                .tap_script_sigs
+                .insert((xonly, leaf_hash), signature);
+        }
+        base64::engine::general_purpose::STANDARD.encode(psbt.serialize())
+    }
+
+    #[test]
+    fn test_threshold_is_tracked_across_copies() {
+        let (psbt, keys) = two_of_three_claim(60);
+        let alice = sign(&psbt, &keys[0]);
+        let bob = sign(&psbt, &keys[1]);
+
+        let one = merge_signed_psbts(vec![alice.clone()]).unwrap();
+        assert_eq!(one.threshold, 2);
+        assert_eq!(one.heirs.len(), 3);
+        assert_eq!(one.signed_count, 1);
+        assert!(!one.ready_to_finalize);
+        let err = finalize_psbt(one.psbt_base64).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::ThresholdNotMet {
+                signatures: 1,
+                threshold: 2
+            }
+        );
+
+        let both = merge_signed_psbts(vec![alice, bob]).unwrap();
+        assert_eq!(both.signed_count, 2);
+        assert!(both.ready_to_finalize);
+        assert!(finalize_psbt(both.psbt_base64).is_ok());
+    }
+
+    #[test]
+    fn test_bad_signatures_and_other_claims_are_refused() {
+        let (psbt, keys) = two_of_three_claim(61);
+        let alice = sign(&psbt, &keys[0]);
+
+        // Alice's signature presented as Bob's
+        let mut forged = decode_psbt(&alice).unwrap();
+        let ((_, leaf_hash), sig) = forged.inputs[0]
+            .tap_script_sigs
+            .iter()
+            .next()
+            .map(|(k, s)| (*k, *s))
+            .unwrap();
+        let bob = keys[1].x_only_public_key().0;
+        forged.inputs[0].tap_script_sigs.clear();
+        forged.inputs[0]
+            .tap_script_sigs
+            .insert((bob, leaf_hash), sig);
+        let forged = base64::engine::general_purpose::STANDARD.encode(forged.serialize());
+        let err = merge_signed_psbts(vec![forged]).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidPsbt);
+
+        let mut different = psbt.clone();
+        different.unsigned_tx.lock_time = bitcoin::absolute::LockTime::from_consensus(1);
+        let different = base64::engine::general_purpose::STANDARD.encode(different.serialize());
+        let err = merge_signed_psbts(vec![alice, different]).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+        assert!(merge_signed_psbts(Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! Size and fee of each way to spend the vault.
+//!
+//! The owner's key path carries one signature. A recovery leaf carries its
+//! signatures, the leaf script and a control block that grows with the tree
+//! depth, which is why an heir's claim costs more than a simple send.
+
+use bitcoin::ScriptBuf;
+use serde::{Deserialize, Serialize};
+
+use super::policy::{analyze_leaf_script, heir_labels_for_keys, ClauseKind};
+use super::{parse_backup, recovery_tree_depth, ErrorKind, HeirError, MAX_PSBT_INPUTS};
+
+/// Reference fee rates the breakdown is priced at (sat/vB).
+const REFERENCE_FEE_RATES: [u64; 4] = [1, 5, 20, 50];
+
+/// Version, locktime, input and output counts (up to 252 each).
+const TX_OVERHEAD_BYTES: u64 = 4 + 4 + 1 + 1;
+/// Segwit marker and flag, witness-only.
+const SEGWIT_MARKER_WEIGHT: u64 = 2;
+/// Outpoint, empty scriptSig and sequence.
+const INPUT_BYTES: u64 = 36 + 1 + 4;
+/// Value and a length-prefixed P2TR script.
+const P2TR_OUTPUT_BYTES: u64 = 8 + 1 + 34;
+/// A push of a 64-byte Schnorr signature (SIGHASH_DEFAULT).
+const SIGNATURE_PUSH: u64 = 1 + 64;
+
+/// Fee of a path at one reference rate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathFee {
+    pub fee_rate_sat_vb: u64,
+    pub fee_sat: u64,
+}
+
+/// Size of a transaction spending `num_inputs` vault outputs one way.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendPathCost {
+    pub kind: ClauseKind,
+    /// Index of the recovery leaf, for [`ClauseKind::HeirRecovery`].
+    pub leaf_index: Option<usize>,
+    pub heir_labels: Vec<String>,
+    pub required_signatures: u32,
+    pub weight: u64,
+    pub vbytes: u64,
+    /// How much larger than the owner's key-path spend.
+    pub extra_vbytes_vs_key_path: u64,
+    pub fees: Vec<PathFee>,
+}
+
+/// Costs for every spend path of a vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpendCostBreakdown {
+    pub num_inputs: usize,
+    /// Owner key path first, then each recovery leaf in backup order.
+    pub paths: Vec<SpendPathCost>,
+}
+
+/// Weight of a one-output transaction whose every input carries
+/// `witness_bytes` of witness.
+fn spend_weight(num_inputs: usize, witness_bytes: u64) -> u64 {
+    let inputs = num_inputs as u64;
+    (TX_OVERHEAD_BYTES + inputs * INPUT_BYTES + P2TR_OUTPUT_BYTES) * 4
+        + SEGWIT_MARKER_WEIGHT
+        + inputs * witness_bytes
+}
+
+/// Length of the compact-size prefix for `len`.
+fn varint_len(len: usize) -> u64 {
+    match len {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        _ => 5,
+    }
+}
+
+fn path_cost(
+    kind: ClauseKind,
+    leaf_index: Option<usize>,
+    heir_labels: Vec<String>,
+    required_signatures: u32,
+    weight: u64,
+    key_path_vbytes: u64,
+) -> SpendPathCost {
+    let vbytes = weight.div_ceil(4);
+    SpendPathCost {
+        kind,
+        leaf_index,
+        heir_labels,
+        required_signatures,
+        weight,
+        vbytes,
+        extra_vbytes_vs_key_path: vbytes.saturating_sub(key_path_vbytes),
+        fees: REFERENCE_FEE_RATES
+            .iter()
+            .map(|&rate| PathFee {
+                fee_rate_sat_vb: rate,
+                fee_sat: vbytes * rate,
+            })
+            .collect(),
+    }
+}
+
+/// Estimate the size and fee of spending `num_inputs` vault outputs to one
+/// taproot output via each available path.
+pub fn spend_cost_breakdown(
+    vault_json: String,
+    num_inputs: usize,
+) -> Result<SpendCostBreakdown, HeirError> {
+    if num_inputs == 0 || num_inputs > MAX_PSBT_INPUTS {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!("Number of inputs must be between 1 and {}", MAX_PSBT_INPUTS),
+        ));
+    }
+    let backup = parse_backup(&vault_json)?;
+    let depth = recovery_tree_depth(&backup) as u64;
+
+    // Witness item count plus one signature
+    let key_path_weight = spend_weight(num_inputs, 1 + SIGNATURE_PUSH);
+    let key_path_vbytes = key_path_weight.div_ceil(4);
+    let mut paths = vec![path_cost(
+        ClauseKind::OwnerKeyPath,
+        None,
+        Vec::new(),
+        1,
+        key_path_weight,
+        key_path_vbytes,
+    )];
+
+    for (index, leaf) in backup.recovery_leaves.iter().enumerate() {
+        let script = ScriptBuf::from_hex(&leaf.script_hex).map_err(|e| {
+            HeirError::new(
+                ErrorKind::InvalidBackup,
+                format!("Invalid recovery leaf script: {}", e),
+            )
+        })?;
+        let analysis = analyze_leaf_script(&script);
+        let keys = analysis.keys.len().max(1) as u64;
+        let signatures = u64::from(analysis.threshold).min(keys);
+        let control_block = 33 + 32 * depth;
+        // Keys that do not sign still need an empty push
+        let witness = varint_len((keys + 2) as usize)
+            + signatures * SIGNATURE_PUSH
+            + (keys - signatures)
+            + varint_len(script.len())
+            + script.len() as u64
+            + varint_len(control_block as usize)
+            + control_block;
+        paths.push(path_cost(
+            ClauseKind::HeirRecovery,
+            Some(index),
+            heir_labels_for_keys(&backup, &analysis.keys),
+            analysis.threshold,
+            spend_weight(num_inputs, witness),
+            key_path_vbytes,
+        ));
+    }
+
+    // Older backups do not carry leaf scripts; fall back to the generic estimate
+    if backup.recovery_leaves.is_empty() {
+        let vbytes =
+            nostring_inherit::taproot::estimate_heir_claim_vbytes(num_inputs, 1, depth as usize)
+                as u64;
+        paths.push(path_cost(
+            ClauseKind::HeirRecovery,
+            None,
+            backup.heirs.iter().map(|h| h.label.clone()).collect(),
+            1,
+            vbytes * 4,
+            key_path_vbytes,
+        ));
+    }
+
+    Ok(SpendCostBreakdown { num_inputs, paths })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::vectors::generate_test_vectors;
+
+    #[test]
+    fn test_key_path_matches_standard_size() {
+        // A 1-in, 1-out P2TR key-path spend is 111 vbytes
+        assert_eq!(spend_weight(1, 1 + SIGNATURE_PUSH).div_ceil(4), 111);
+    }
+
+    #[test]
+    fn test_recovery_path_costs_more() {
+        let v = generate_test_vectors(14).unwrap();
+        let breakdown = spend_cost_breakdown(v.backup_json.clone(), 3).unwrap();
+        assert_eq!(breakdown.paths.len(), 2);
+        let (owner, heir) = (&breakdown.paths[0], &breakdown.paths[1]);
+        assert_eq!(owner.kind, ClauseKind::OwnerKeyPath);
+        assert_eq!(heir.kind, ClauseKind::HeirRecovery);
+        assert_eq!(heir.leaf_index, Some(0));
+        assert!(heir.extra_vbytes_vs_key_path > 0);
+        assert_eq!(heir.fees[2].fee_sat, heir.vbytes * 20);
+
+        assert_eq!(
+            spend_cost_breakdown(v.backup_json, 0).unwrap_err().kind,
+            ErrorKind::InvalidInput
+        );
+    }
+}
@@ -0,0 +1,558 @@
+//! Signet demo vault for the onboarding tutorial.
+//!
+//! Builds a real signet vault with fresh random keys and a 1–2 block
+//! timelock, and asks a faucet to fund it, so a new heir can walk through a
+//! genuine claim without any real money at stake.
+//!
+//! A rehearsal vault does the same for a specific real vault. Every
+//! recovery leaf is rebuilt on signet in the same shape and order, with
+//! each key swapped for a fresh one and each delay shortened to a few
+//! blocks, so heirs can practise the exact claim they will one day make:
+//! a threshold still needs that many signatures, and an executor override
+//! still opens before the heirs' leaf.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bitcoin::opcodes::all::OP_CSV;
+use bitcoin::script::{Builder, Instruction};
+use bitcoin::secp256k1::{Secp256k1, SecretKey, XOnlyPublicKey};
+use bitcoin::taproot::LeafVersion;
+use bitcoin::{Address, Network, Script, ScriptBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use nostring_inherit::backup::extract_recovery_leaves;
+
+use super::entropy::EntropySource;
+use super::executor::{self, Executor};
+use super::policy::{analyze_leaf_script, instruction_int};
+use super::vectors::{bare_xpub, synthetic_backup, SyntheticKeys};
+use super::weighted::{self, heir_keys, internal_key};
+use super::{
+    import_vault_backup, parse_backup, reconstruction_error, verified_vault, ErrorKind, HeirError,
+};
+use crate::redact::REDACTED;
+
+/// Longest timelock a demo vault may use, in blocks.
+const MAX_DEMO_TIMELOCK_BLOCKS: u16 = 2;
+
+/// Placeholder in the faucet URL replaced by the vault address.
+const FAUCET_ADDRESS_PLACEHOLDER: &str = "{address}";
+
+/// A funded (or funding) signet demo vault.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DemoVault {
+    pub backup_json: String,
+    pub vault_address: String,
+    pub timelock_blocks: u16,
+    /// Heir secret key (hex) for signing the demo claim. Signet only.
+    pub heir_secret_key_hex: String,
+    /// Raw faucet response, shown if funding needs troubleshooting.
+    pub faucet_response: String,
+}
+
+// Hand-written so the demo key never ends up in logs
+impl std::fmt::Debug for DemoVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DemoVault")
+            .field("vault_address", &self.vault_address)
+            .field("timelock_blocks", &self.timelock_blocks)
+            .field("heir_secret_key_hex", &REDACTED)
+            .field("faucet_response", &self.faucet_response)
+            .finish()
+    }
+}
+
+/// Signet practice copy of a real vault. Fund it from any signet faucet.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RehearsalVault {
+    pub backup_json: String,
+    pub vault_address: String,
+    pub heir_label: String,
+    /// Heirs' timelock in the rehearsal, shortened so practice takes
+    /// minutes.
+    pub timelock_blocks: u16,
+    /// Timelock of the real vault, for the "in the real claim you will
+    /// wait..." explanation.
+    pub original_timelock_blocks: u32,
+    /// Heir secret key (hex) for signing the rehearsal claim. Signet only.
+    pub heir_secret_key_hex: String,
+    /// Secret keys (hex) of the other heirs in backup order, then of the
+    /// executor if there is one, so a leaf needing several signatures can
+    /// be rehearsed alone. Signet only.
+    pub other_secret_keys_hex: Vec<String>,
+}
+
+// Hand-written so the rehearsal keys never end up in logs
+impl std::fmt::Debug for RehearsalVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RehearsalVault")
+            .field("vault_address", &self.vault_address)
+            .field("heir_label", &self.heir_label)
+            .field("timelock_blocks", &self.timelock_blocks)
+            .field("original_timelock_blocks", &self.original_timelock_blocks)
+            .field("heir_secret_key_hex", &REDACTED)
+            .field("other_secret_keys_hex", &REDACTED)
+            .finish()
+    }
+}
+
+/// Generate fresh keys and build a signet backup. Returns the backup JSON
+/// and the heir's secret key.
+fn build_demo_backup(
+    timelock_blocks: u16,
+    entropy: &EntropySource,
+) -> Result<(String, String, SecretKey), HeirError> {
+    if timelock_blocks == 0 || timelock_blocks > MAX_DEMO_TIMELOCK_BLOCKS {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Demo timelock must be 1 to {} blocks",
+                MAX_DEMO_TIMELOCK_BLOCKS
+            ),
+        ));
+    }
+
+    let secp = Secp256k1::new();
+    let mut rng = entropy.rng();
+    let heir_sk = SecretKey::new(&mut rng);
+    let mut chain_code = [0u8; 32];
+    rng.fill_bytes(&mut chain_code);
+
+    let keys = SyntheticKeys {
+        owner: SecretKey::new(&mut rng).public_key(&secp),
+        cosigner: SecretKey::new(&mut rng).public_key(&secp),
+        heir: heir_sk.public_key(&secp),
+        chain_code,
+    };
+
+    let backup = synthetic_backup(&keys, timelock_blocks, Network::Signet)?;
+    let json = serde_json::to_string(&backup).map_err(|e| {
+        HeirError::new(ErrorKind::Internal, format!("Serialization failed: {}", e))
+    })?;
+    Ok((json, backup.vault_address, heir_sk))
+}
+
+/// Create a signet demo vault and request funding from a faucet.
+///
+/// `faucet_url` must contain `{address}`, which is replaced with the vault
+/// address before the URL is POSTed to.
+pub fn create_demo_vault(faucet_url: String, timelock_blocks: u16) -> Result<DemoVault, HeirError> {
+    create_demo_vault_with_entropy(faucet_url, timelock_blocks, EntropySource::Os)
+}
+
+/// [`create_demo_vault`] with keys drawn from `entropy`.
+pub fn create_demo_vault_with_entropy(
+    faucet_url: String,
+    timelock_blocks: u16,
+    entropy: EntropySource,
+) -> Result<DemoVault, HeirError> {
+    if !faucet_url.contains(FAUCET_ADDRESS_PLACEHOLDER) {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!("Faucet URL must contain {}", FAUCET_ADDRESS_PLACEHOLDER),
+        ));
+    }
+
+    let (backup_json, vault_address, heir_sk) = build_demo_backup(timelock_blocks, &entropy)?;
+
+    let url = faucet_url.replace(FAUCET_ADDRESS_PLACEHOLDER, &vault_address);
+    let faucet_response = request_faucet(&url)?;
+
+    Ok(DemoVault {
+        backup_json,
+        vault_address,
+        timelock_blocks,
+        heir_secret_key_hex: hex::encode(heir_sk.secret_bytes()),
+        faucet_response,
+    })
+}
+
+/// POST to the faucet and return its raw response.
+fn request_faucet(url: &str) -> Result<String, HeirError> {
+    #[cfg(feature = "esplora")]
+    {
+        ureq::post(url)
+            .call()
+            .map_err(|e| {
+                HeirError::new(
+                    ErrorKind::Connection,
+                    format!("Faucet request failed: {}", e),
+                )
+            })?
+            .into_string()
+            .map_err(|e| {
+                HeirError::new(
+                    ErrorKind::ServerQuery,
+                    format!("Faucet response unreadable: {}", e),
+                )
+            })
+    }
+    #[cfg(not(feature = "esplora"))]
+    {
+        let _ = url;
+        Err(HeirError::new(
+            ErrorKind::BackendUnavailable,
+            "This build does not include the HTTP client the faucet request needs",
+        ))
+    }
+}
+
+/// Rehearsal delay for each of the original delays, by rank: the shortest
+/// becomes 1 block, the next 2, and so on, so their order is kept.
+fn shortened_delays(delays: impl IntoIterator<Item = u32>) -> BTreeMap<u32, u16> {
+    let distinct: BTreeSet<u32> = delays.into_iter().collect();
+    distinct.into_iter().zip(1..).collect()
+}
+
+/// `script` with every key found in `keys` swapped for its stand-in and its
+/// CSV delay swapped through `delays`. Every other instruction is copied.
+fn rehearsal_leaf(
+    script: &Script,
+    keys: &BTreeMap<XOnlyPublicKey, XOnlyPublicKey>,
+    delays: &BTreeMap<u32, u16>,
+) -> ScriptBuf {
+    let instructions: Vec<Instruction<'_>> = script.instructions().filter_map(Result::ok).collect();
+    let mut builder = Builder::new();
+    for (i, instruction) in instructions.iter().enumerate() {
+        let before_csv =
+            matches!(instructions.get(i + 1), Some(Instruction::Op(op)) if *op == OP_CSV);
+        let delay = instruction_int(instruction)
+            .filter(|_| before_csv)
+            .and_then(|n| u32::try_from(n).ok())
+            .and_then(|n| delays.get(&n));
+        let key = match instruction {
+            Instruction::PushBytes(bytes) => XOnlyPublicKey::from_slice(bytes.as_bytes())
+                .ok()
+                .and_then(|key| keys.get(&key)),
+            Instruction::Op(_) => None,
+        };
+        builder = match (instruction, delay, key) {
+            (_, Some(delay), _) => builder.push_int(i64::from(*delay)),
+            (_, _, Some(key)) => builder.push_x_only_key(key),
+            (Instruction::PushBytes(bytes), _, _) => builder.push_slice(bytes),
+            (Instruction::Op(op), _, _) => builder.push_opcode(*op),
+        };
+    }
+    builder.into_script()
+}
+
+/// Mirror `vault_json` on signet with fresh keys, so the heir at
+/// `heir_index` can rehearse building, signing and broadcasting the claim.
+///
+/// Each recovery leaf keeps its shape, its place in the tree and the order
+/// of its delay relative to the others; only the keys and the length of the
+/// delays change. The other participants' keys are returned too, so
+/// threshold and override leaves can be rehearsed by one person.
+pub fn create_rehearsal_vault(
+    vault_json: String,
+    heir_index: usize,
+) -> Result<RehearsalVault, HeirError> {
+    create_rehearsal_vault_with_entropy(vault_json, heir_index, EntropySource::Os)
+}
+
+/// [`create_rehearsal_vault`] with keys drawn from `entropy`.
+pub fn create_rehearsal_vault_with_entropy(
+    vault_json: String,
+    heir_index: usize,
+    entropy: EntropySource,
+) -> Result<RehearsalVault, HeirError> {
+    let original = parse_backup(&vault_json)?;
+    let heir = original.heirs.get(heir_index).ok_or_else(|| {
+        HeirError::new(
+            ErrorKind::HeirIndexOutOfRange {
+                given: heir_index,
+                max: original.heirs.len().saturating_sub(1),
+            },
+            format!("This vault has {} heir(s)", original.heirs.len()),
+        )
+    })?;
+    let (_, scripts) = verified_vault(&vault_json, &original)?;
+    let original_executor = executor::load_executor(&vault_json)?;
+    // Trees upstream builds are rebuilt by upstream; the rest from their leaves
+    let upstream_tree = original_executor.is_none()
+        && weighted::heir_weights(&vault_json)?.is_none()
+        && original.reconstruct().is_ok();
+
+    let executor_delay = original_executor.as_ref().map(|e| e.timelock_blocks);
+    let leaf_delays = scripts
+        .iter()
+        .filter_map(|s| analyze_leaf_script(s).csv_blocks);
+    let delays = shortened_delays(
+        [Some(original.timelock_blocks), executor_delay]
+            .into_iter()
+            .flatten()
+            .map(u32::from)
+            .chain(leaf_delays),
+    );
+    let shortened = |blocks: u16| delays[&u32::from(blocks)];
+
+    let secp = Secp256k1::new();
+    let mut rng = entropy.rng();
+    let original_keys: Vec<XOnlyPublicKey> = heir_keys(&original)?
+        .into_iter()
+        .chain(original_executor.iter().map(Executor::key))
+        .collect();
+    let secret_keys: Vec<SecretKey> = original_keys
+        .iter()
+        .map(|_| SecretKey::new(&mut rng))
+        .collect();
+    let keys: BTreeMap<XOnlyPublicKey, XOnlyPublicKey> = original_keys
+        .iter()
+        .zip(&secret_keys)
+        .map(|(key, sk)| (*key, sk.public_key(&secp).x_only_public_key().0))
+        .collect();
+    let mut chain_code = [0u8; 32];
+    rng.fill_bytes(&mut chain_code);
+    let owner = SecretKey::new(&mut rng).public_key(&secp);
+    let cosigner = SecretKey::new(&mut rng).public_key(&secp);
+
+    let serialization_failed = |e: serde_json::Error| {
+        HeirError::new(ErrorKind::Internal, format!("Serialization failed: {}", e))
+    };
+    // Edited in place so weights, allocations and leaf allowances carry over
+    let mut value: Value = serde_json::from_str(&vault_json).map_err(serialization_failed)?;
+    value["network"] = serde_json::json!("signet");
+    value["owner_pubkey"] = serde_json::json!(hex::encode(owner.serialize()));
+    value["cosigner_pubkey"] = serde_json::json!(hex::encode(cosigner.serialize()));
+    value["chain_code"] = serde_json::json!(hex::encode(chain_code));
+    value["timelock_blocks"] = serde_json::json!(shortened(original.timelock_blocks));
+    value["created_at"] = Value::Null;
+    if let Some(heirs) = value["heirs"].as_array_mut() {
+        for (entry, sk) in heirs.iter_mut().zip(&secret_keys) {
+            let xpub = bare_xpub(sk.public_key(&secp), Network::Signet)?;
+            entry["xpub"] = serde_json::json!(xpub.to_string());
+            entry["fingerprint"] = serde_json::json!("00000000");
+            entry["npub"] = Value::Null;
+        }
+    }
+    if let (Some(e), Some(sk)) = (&original_executor, secret_keys.last()) {
+        let rehearsal_executor = Executor {
+            label: e.label.clone(),
+            xpub: bare_xpub(sk.public_key(&secp), Network::Signet)?,
+            source: (Default::default(), e.source.1.clone()),
+            timelock_blocks: shortened(e.timelock_blocks),
+        };
+        executor::add_executor(&mut value, &rehearsal_executor);
+    }
+
+    if upstream_tree {
+        let vault = parse_backup(&value.to_string())?
+            .reconstruct()
+            .map_err(reconstruction_error)?;
+        let mut leaves =
+            serde_json::to_value(extract_recovery_leaves(&vault)).map_err(serialization_failed)?;
+        // Keep per-leaf fields upstream does not write
+        if let (Some(leaves), Some(originals)) =
+            (leaves.as_array_mut(), value["recovery_leaves"].as_array())
+        {
+            for (leaf, original_leaf) in leaves.iter_mut().zip(originals) {
+                if let (Some(leaf), Some(original_leaf)) =
+                    (leaf.as_object_mut(), original_leaf.as_object())
+                {
+                    for (field, entry) in original_leaf {
+                        leaf.entry(field.clone()).or_insert_with(|| entry.clone());
+                    }
+                }
+            }
+        }
+        value["recovery_leaves"] = leaves;
+        value["vault_address"] = serde_json::json!(vault.address.to_string());
+        value["taproot_internal_key"] =
+            serde_json::json!(hex::encode(vault.aggregate_xonly.serialize()));
+    } else {
+        if let Some(leaves) = value["recovery_leaves"].as_array_mut() {
+            for (leaf, script) in leaves.iter_mut().zip(&scripts) {
+                let rewritten = rehearsal_leaf(script, &keys, &delays);
+                if let Some(csv_blocks) = analyze_leaf_script(&rewritten).csv_blocks {
+                    leaf["timelock_blocks"] = serde_json::json!(csv_blocks);
+                }
+                leaf["script_hex"] = serde_json::json!(rewritten.to_hex_string());
+            }
+        }
+        // Control blocks are still the original's, so each leaf keeps its depth
+        let backup = parse_backup(&value.to_string())?;
+        let internal_xonly = internal_key(&backup)?;
+        let (builder, rewritten) = executor::tree_from_leaves(&backup)?;
+        let spend_info = builder
+            .finalize(&secp, internal_xonly)
+            .map_err(|_| reconstruction_error("the rehearsal tree is incomplete"))?;
+        for (leaf, script) in value["recovery_leaves"]
+            .as_array_mut()
+            .into_iter()
+            .flatten()
+            .zip(&rewritten)
+        {
+            let control_block = spend_info
+                .control_block(&(script.clone(), LeafVersion::TapScript))
+                .ok_or_else(|| reconstruction_error("a rehearsal leaf is missing from the tree"))?;
+            leaf["control_block_hex"] = serde_json::json!(hex::encode(control_block.serialize()));
+        }
+        let address = Address::p2tr_tweaked(spend_info.output_key(), Network::Signet);
+        value["vault_address"] = serde_json::json!(address.to_string());
+        value["taproot_internal_key"] = serde_json::json!(hex::encode(internal_xonly.serialize()));
+    }
+
+    // The rehearsal must verify like any backup the heir could import
+    let backup_json = value.to_string();
+    let info = import_vault_backup(backup_json.clone())?;
+    let mut secret_hex: Vec<String> = secret_keys
+        .iter()
+        .map(|sk| hex::encode(sk.secret_bytes()))
+        .collect();
+    let heir_secret_key_hex = secret_hex.remove(heir_index);
+    Ok(RehearsalVault {
+        backup_json,
+        vault_address: info.vault_address,
+        heir_label: heir.label.clone(),
+        timelock_blocks: shortened(original.timelock_blocks),
+        original_timelock_blocks: u32::from(original.timelock_blocks),
+        heir_secret_key_hex,
+        other_secret_keys_hex: secret_hex,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::executor::tests::executor_backup_json;
+    use crate::api::policy::{describe_policy, ClauseKind};
+    use crate::api::weighted::tests::weighted_backup_json;
+
+    /// Key count, threshold and CSV delay of each recovery leaf, in order.
+    fn leaf_shapes(vault_json: &str) -> Vec<(usize, u32, Option<u32>)> {
+        parse_backup(vault_json)
+            .unwrap()
+            .recovery_leaves
+            .iter()
+            .map(|leaf| analyze_leaf_script(&ScriptBuf::from_hex(&leaf.script_hex).unwrap()))
+            .map(|leaf| (leaf.keys.len(), leaf.threshold, leaf.csv_blocks))
+            .collect()
+    }
+
+    fn clause_kinds(vault_json: &str) -> Vec<ClauseKind> {
+        describe_policy(vault_json.to_string())
+            .unwrap()
+            .clauses
+            .into_iter()
+            .map(|clause| clause.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_demo_backup_is_valid_signet_vault() {
+        let (json, address, _) = build_demo_backup(1, &EntropySource::Os).unwrap();
+        let info = import_vault_backup(json).unwrap();
+        assert_eq!(info.network, "signet");
+        assert_eq!(info.vault_address, address);
+        assert!(address.starts_with("tb1p"));
+    }
+
+    #[test]
+    fn test_demo_keys_are_fresh() {
+        let (_, a, _) = build_demo_backup(2, &EntropySource::Os).unwrap();
+        let (_, b, _) = build_demo_backup(2, &EntropySource::Os).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_seeded_entropy_is_reproducible() {
+        let v = crate::api::vectors::generate_test_vectors(4).unwrap();
+        let rehearse = |seed| {
+            create_rehearsal_vault_with_entropy(
+                v.backup_json.clone(),
+                0,
+                EntropySource::Seeded { seed },
+            )
+            .unwrap()
+        };
+        let (a, b) = (rehearse(7), rehearse(7));
+        assert_eq!(a.vault_address, b.vault_address);
+        assert_eq!(a.heir_secret_key_hex, b.heir_secret_key_hex);
+        assert_ne!(rehearse(8).vault_address, a.vault_address);
+    }
+
+    #[test]
+    fn test_demo_rejects_long_timelock() {
+        let err = build_demo_backup(144, &EntropySource::Os).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_rehearsal_mirrors_heir_on_signet() {
+        let v = crate::api::vectors::generate_test_vectors(3).unwrap();
+        let rehearsal = create_rehearsal_vault(v.backup_json.clone(), 0).unwrap();
+        let info = import_vault_backup(rehearsal.backup_json.clone()).unwrap();
+        assert_eq!(info.network, "signet");
+        assert_eq!(info.heir_labels, vec![rehearsal.heir_label.clone()]);
+        assert_eq!(rehearsal.original_timelock_blocks, 144);
+        assert_ne!(rehearsal.vault_address, v.vault_address);
+
+        let err = create_rehearsal_vault(v.backup_json, 4).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::HeirIndexOutOfRange { given: 4, max: 0 }));
+    }
+
+    #[test]
+    fn test_rehearsal_keeps_every_leaf_and_the_delay_order() {
+        let json = executor_backup_json(67);
+        let rehearsal = create_rehearsal_vault(json.clone(), 0).unwrap();
+        let info = import_vault_backup(rehearsal.backup_json.clone()).unwrap();
+        assert_eq!(info.network, "signet");
+        assert_ne!(
+            rehearsal.vault_address,
+            parse_backup(&json).unwrap().vault_address
+        );
+        assert_eq!(rehearsal.timelock_blocks, 2);
+        assert_eq!(rehearsal.original_timelock_blocks, 144);
+
+        // The override still needs the executor and the heir, and still
+        // opens first
+        assert_eq!(
+            leaf_shapes(&json),
+            vec![(1, 1, Some(144)), (2, 2, Some(72))]
+        );
+        assert_eq!(
+            leaf_shapes(&rehearsal.backup_json),
+            vec![(1, 1, Some(2)), (2, 2, Some(1))]
+        );
+        let kinds = clause_kinds(&rehearsal.backup_json);
+        assert!(kinds.contains(&ClauseKind::ExecutorOverride));
+        assert_eq!(kinds, clause_kinds(&json));
+
+        // The heir's key is the one in the backup; the executor's is returned too
+        let secp = Secp256k1::new();
+        let heir_sk =
+            SecretKey::from_slice(&hex::decode(&rehearsal.heir_secret_key_hex).unwrap()).unwrap();
+        let heir_xpub = bare_xpub(heir_sk.public_key(&secp), Network::Signet).unwrap();
+        let backup = parse_backup(&rehearsal.backup_json).unwrap();
+        assert_eq!(backup.heirs[0].xpub, heir_xpub.to_string());
+        assert_eq!(rehearsal.other_secret_keys_hex.len(), 1);
+    }
+
+    #[test]
+    fn test_rehearsal_of_weighted_vault_keeps_its_signer_sets() {
+        let json = weighted_backup_json(68);
+        let rehearsal = create_rehearsal_vault(json.clone(), 1).unwrap();
+        import_vault_backup(rehearsal.backup_json.clone()).unwrap();
+        assert_eq!(rehearsal.other_secret_keys_hex.len(), 2);
+
+        let shapes = |json: &str| {
+            leaf_shapes(json)
+                .into_iter()
+                .map(|(keys, threshold, _)| (keys, threshold))
+                .collect::<Vec<_>>()
+        };
+        let original = shapes(&json);
+        assert!(original.len() > 1);
+        assert_eq!(shapes(&rehearsal.backup_json), original);
+        assert!(leaf_shapes(&rehearsal.backup_json)
+            .iter()
+            .all(|(_, _, csv_blocks)| *csv_blocks == Some(1)));
+    }
+
+    #[test]
+    fn test_demo_requires_address_placeholder() {
+        let err = create_demo_vault("https://faucet.example/claim".into(), 1).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,266 @@
+//! Output descriptor export and round-trip verification.
+//!
+//! The exported descriptor carries `[fingerprint/path]` origins for every
+//! heir key and the BIP380 checksum, so watch-only wallets and signers can
+//! match their keys and detect transcription errors.
+//!
+//! The owner-side watch config is the opposite: just enough for the owner or
+//! an executor to notice a claim, with no heir key material at all.
+
+use std::str::FromStr;
+
+use bitcoin::bip32::Xpub;
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::ScriptBuf;
+use miniscript::{Descriptor, DescriptorPublicKey, Miniscript, Tap};
+use serde::{Deserialize, Serialize};
+
+use nostring_inherit::backup::VaultBackup;
+
+use super::{parse_backup, parse_network, verified_vault_address, ErrorKind, HeirError};
+
+/// Result of [`validate_descriptor_matches_backup`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DescriptorCheck {
+    /// True if every check below passed.
+    pub matches: bool,
+    pub checksum_present: bool,
+    pub address_matches: bool,
+    pub derived_address: String,
+    /// Labels of heirs whose key is missing or lacks a matching origin.
+    pub missing_origins: Vec<String>,
+}
+
+/// Watch-only view of a vault for the owner's own monitoring tools.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnerWatchConfig {
+    pub network: String,
+    pub vault_address: String,
+    /// `addr()` descriptor with checksum, importable as watch-only.
+    pub descriptor: String,
+    pub script_pubkey_hex: String,
+    /// Blocks after funding before heirs can claim.
+    pub timelock_blocks: u32,
+    /// Number of heir recovery paths. A spend that reveals a script (more
+    /// than one witness element) used one of them; the owner's own spends
+    /// use the key path.
+    pub recovery_path_count: usize,
+}
+
+/// Export the watch-only data an owner or executor needs to be alerted when
+/// an heir claim appears. The vault is reconstructed first, so a tampered
+/// backup cannot point the watcher at the wrong address.
+pub fn export_owner_watch_config(vault_json: String) -> Result<OwnerWatchConfig, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let address = verified_vault_address(&vault_json, &backup)?;
+
+    let body = format!("addr({})", address);
+    let mut engine = miniscript::descriptor::checksum::Engine::new();
+    engine.input(&body).map_err(descriptor_error)?;
+    let descriptor = format!("{}#{}", body, engine.checksum());
+
+    Ok(OwnerWatchConfig {
+        network: backup.network.clone(),
+        vault_address: address.to_string(),
+        descriptor,
+        script_pubkey_hex: address.script_pubkey().to_hex_string(),
+        timelock_blocks: u32::from(backup.timelock_blocks),
+        recovery_path_count: backup.recovery_leaves.len(),
+    })
+}
+
+fn descriptor_error(e: impl std::fmt::Display) -> HeirError {
+    HeirError::new(
+        ErrorKind::InvalidBackup,
+        format!("Descriptor construction failed: {}", e),
+    )
+}
+
+/// `[fingerprint/path]xonly` for each heir, keyed by the bare x-only key.
+fn heir_origin_keys(backup: &VaultBackup) -> Vec<(String, XOnlyPublicKey, String)> {
+    backup
+        .heirs
+        .iter()
+        .filter_map(|heir| {
+            let xonly = Xpub::from_str(&heir.xpub).ok()?.public_key.x_only_public_key().0;
+            let path = heir.derivation_path.trim_start_matches('m').trim_start_matches('/');
+            let origin = if path.is_empty() {
+                format!("[{}]{}", heir.fingerprint, xonly)
+            } else {
+                format!("[{}/{}]{}", heir.fingerprint, path, xonly)
+            };
+            Some((heir.label.clone(), xonly, origin))
+        })
+        .collect()
+}
+
+/// Rebuild the `{a,b}` tap tree string from leaves in depth-first order.
+fn tree_string(leaves: &[(usize, String)], next: &mut usize, depth: usize) -> Option<String> {
+    let (leaf_depth, leaf) = leaves.get(*next)?;
+    if *leaf_depth == depth {
+        *next += 1;
+        return Some(leaf.clone());
+    }
+    if *leaf_depth < depth {
+        return None;
+    }
+    let left = tree_string(leaves, next, depth + 1)?;
+    let right = tree_string(leaves, next, depth + 1)?;
+    Some(format!("{{{},{}}}", left, right))
+}
+
+/// Export the vault as a `tr()` descriptor with key origins and checksum.
+pub fn export_descriptor(vault_json: String) -> Result<String, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let internal_key = backup
+        .taproot_internal_key
+        .clone()
+        .ok_or_else(|| descriptor_error("backup has no taproot internal key"))?;
+    let origins = heir_origin_keys(&backup);
+
+    let mut leaves = Vec::with_capacity(backup.recovery_leaves.len());
+    for leaf in &backup.recovery_leaves {
+        let script = ScriptBuf::from_hex(&leaf.script_hex).map_err(descriptor_error)?;
+        let ms = Miniscript::<XOnlyPublicKey, Tap>::parse_insane(&script).map_err(descriptor_error)?;
+        let mut text = ms.to_string();
+        for (_, xonly, origin) in &origins {
+            text = text.replace(&xonly.to_string(), origin);
+        }
+        // Control block is 33 bytes plus 32 per merkle level
+        let control_len = leaf.control_block_hex.len() / 2;
+        let depth = control_len.saturating_sub(33) / 32;
+        leaves.push((depth, text));
+    }
+
+    let body = if leaves.is_empty() {
+        format!("tr({})", internal_key)
+    } else {
+        let mut next = 0;
+        let tree = tree_string(&leaves, &mut next, 0)
+            .filter(|_| next == leaves.len())
+            .ok_or_else(|| descriptor_error("recovery leaf depths do not form a tree"))?;
+        format!("tr({},{})", internal_key, tree)
+    };
+
+    // Round-trip through the parser to validate and append the checksum
+    let descriptor = Descriptor::<DescriptorPublicKey>::from_str(&body).map_err(descriptor_error)?;
+    Ok(descriptor.to_string())
+}
+
+/// Check that a descriptor describes the vault in the backup: checksum
+/// present and valid, same address, and an origin for every heir key.
+pub fn validate_descriptor_matches_backup(
+    descriptor: String,
+    vault_json: String,
+) -> Result<DescriptorCheck, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let network = parse_network(&backup.network)?;
+
+    let trimmed = descriptor.trim();
+    let checksum_present = trimmed.contains('#');
+    // The parser rejects a present-but-wrong checksum
+    let parsed = Descriptor::<DescriptorPublicKey>::from_str(trimmed).map_err(|e| {
+        HeirError::new(ErrorKind::InvalidInput, format!("Invalid descriptor: {}", e))
+    })?;
+
+    let derived_address = parsed
+        .at_derivation_index(0)
+        .map_err(|e| HeirError::new(ErrorKind::InvalidInput, format!("Invalid descriptor: {}", e)))?
+        .address(network)
+        .map_err(|e| HeirError::new(ErrorKind::InvalidInput, format!("Invalid descriptor: {}", e)))?
+        .to_string();
+    let address_matches = derived_address == backup.vault_address;
+
+    let missing_origins: Vec<String> = heir_origin_keys(&backup)
+        .into_iter()
+        .filter(|(_, _, origin)| !trimmed.contains(origin.as_str()))
+        .map(|(label, _, _)| label)
+        .collect();
+
+    Ok(DescriptorCheck {
+        matches: checksum_present && address_matches && missing_origins.is_empty(),
+        checksum_present,
+        address_matches,
+        derived_address,
+        missing_origins,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::vectors::generate_test_vectors;
+
+    #[test]
+    fn test_export_has_origins_and_checksum() {
+        let v = generate_test_vectors(4).unwrap();
+        let desc = export_descriptor(v.backup_json).unwrap();
+        assert!(desc.starts_with("tr("));
+        assert!(desc.contains("[00000000/86'/1'/0']"), "{}", desc);
+        let checksum = desc.rsplit('#').next().unwrap();
+        assert_eq!(checksum.len(), 8);
+    }
+
+    #[test]
+    fn test_round_trip_matches() {
+        let v = generate_test_vectors(4).unwrap();
+        let desc = export_descriptor(v.backup_json.clone()).unwrap();
+        let check = validate_descriptor_matches_backup(desc, v.backup_json).unwrap();
+        assert!(check.matches, "{:?}", check);
+        assert_eq!(check.derived_address, v.vault_address);
+    }
+
+    #[test]
+    fn test_missing_checksum_and_origins_reported() {
+        let v = generate_test_vectors(4).unwrap();
+        let desc = export_descriptor(v.backup_json.clone()).unwrap();
+        let bare = desc
+            .split('#')
+            .next()
+            .unwrap()
+            .replace("[00000000/86'/1'/0']", "");
+        let check = validate_descriptor_matches_backup(bare, v.backup_json).unwrap();
+        assert!(!check.matches);
+        assert!(!check.checksum_present);
+        assert!(check.address_matches);
+        assert_eq!(check.missing_origins, vec!["Synthetic Heir"]);
+    }
+
+    #[test]
+    fn test_other_vault_descriptor_mismatches() {
+        let a = generate_test_vectors(4).unwrap();
+        let b = generate_test_vectors(5).unwrap();
+        let desc = export_descriptor(b.backup_json).unwrap();
+        let check = validate_descriptor_matches_backup(desc, a.backup_json).unwrap();
+        assert!(!check.address_matches);
+        assert!(!check.matches);
+    }
+
+    #[test]
+    fn test_owner_watch_config_has_no_heir_keys() {
+        let v = generate_test_vectors(4).unwrap();
+        let config = export_owner_watch_config(v.backup_json.clone()).unwrap();
+        assert_eq!(config.vault_address, v.vault_address);
+        assert_eq!(config.descriptor.split('#').next().unwrap(), format!("addr({})", v.vault_address));
+        assert_eq!(config.descriptor.rsplit('#').next().unwrap().len(), 8);
+
+        let backup = parse_backup(&v.backup_json).unwrap();
+        let exported = serde_json::to_string(&config).unwrap();
+        for heir in &backup.heirs {
+            assert!(!exported.contains(&heir.xpub));
+        }
+        for (_, xonly, _) in heir_origin_keys(&backup) {
+            assert!(!exported.contains(&xonly.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_bad_checksum_rejected() {
+        let v = generate_test_vectors(4).unwrap();
+        let desc = export_descriptor(v.backup_json.clone()).unwrap();
+        let (body, _) = desc.split_once('#').unwrap();
+        let err = validate_descriptor_matches_backup(format!("{}#qqqqqqqq", body), v.backup_json)
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,510 @@
+//! Self-test report for support requests.
+//!
+//! "Network error" on a screenshot says little about where a connection
+//! broke. [`run_diagnostics`] walks the Electrum connection one layer at a
+//! time (name resolution, TCP, TLS, the protocol handshake) before asking
+//! the backend for the tip and a fee estimate, and finishes with an offline
+//! PSBT round trip. Each step records its outcome, timing and error, so the
+//! resulting [`DiagnosticsReport`] can be attached to a support request as is.
+
+use std::time::Instant;
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use super::info::{get_library_info, LibraryInfo};
+use super::{decode_psbt, network_name, Backend, ErrorKind, HeirError};
+
+/// Fee estimate target used by the fee step.
+const FEE_TARGET_BLOCKS: u16 = 6;
+
+/// One thing [`run_diagnostics`] checks, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiagnosticStep {
+    /// Parse the server URL and resolve its hostname.
+    Dns,
+    /// Open a TCP connection to one of the resolved addresses.
+    Connect,
+    /// Complete a TLS handshake, for `ssl://` servers.
+    Tls,
+    /// Exchange `server.version` and check the server's chain.
+    ElectrumHandshake,
+    /// Fetch the chain tip through the backend.
+    Height,
+    /// Fetch a fee estimate through the backend.
+    FeeEstimate,
+    /// Decode and re-encode a claim PSBT without touching the network.
+    PsbtRoundTrip,
+}
+
+/// How a step went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CheckOutcome {
+    Passed,
+    Failed,
+    /// Not attempted, because it does not apply or an earlier step failed.
+    Skipped,
+}
+
+/// Result of one step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticCheck {
+    pub step: DiagnosticStep,
+    pub outcome: CheckOutcome,
+    pub duration_ms: u64,
+    /// What was found, or why the step was skipped.
+    pub detail: String,
+    /// Set when `outcome` is [`CheckOutcome::Failed`].
+    pub error: Option<HeirError>,
+}
+
+/// Everything [`run_diagnostics`] found, ready to serialize.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    pub library: LibraryInfo,
+    pub network: String,
+    /// Electrum server probed, if any.
+    pub server_url: Option<String>,
+    pub checks: Vec<DiagnosticCheck>,
+    /// No step failed.
+    pub all_passed: bool,
+}
+
+impl DiagnosticsReport {
+    /// Pretty-printed JSON for attaching to a support request.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("report serializes")
+    }
+}
+
+/// Collects checks as the steps run.
+struct Recorder {
+    checks: Vec<DiagnosticCheck>,
+}
+
+impl Recorder {
+    /// Run `step`, recording its detail on success or its error on failure.
+    fn run<T>(
+        &mut self,
+        step: DiagnosticStep,
+        f: impl FnOnce() -> Result<(T, String), HeirError>,
+    ) -> Option<T> {
+        let started = Instant::now();
+        let result = f();
+        let duration_ms = started.elapsed().as_millis() as u64;
+        match result {
+            Ok((value, detail)) => {
+                self.checks.push(DiagnosticCheck {
+                    step,
+                    outcome: CheckOutcome::Passed,
+                    duration_ms,
+                    detail,
+                    error: None,
+                });
+                Some(value)
+            }
+            Err(error) => {
+                self.checks.push(DiagnosticCheck {
+                    step,
+                    outcome: CheckOutcome::Failed,
+                    duration_ms,
+                    detail: error.message.clone(),
+                    error: Some(error),
+                });
+                None
+            }
+        }
+    }
+
+    fn fail(&mut self, step: DiagnosticStep, error: HeirError) {
+        self.run(step, || Err::<((), String), _>(error));
+    }
+
+    fn skip(&mut self, step: DiagnosticStep, reason: impl Into<String>) {
+        self.checks.push(DiagnosticCheck {
+            step,
+            outcome: CheckOutcome::Skipped,
+            duration_ms: 0,
+            detail: reason.into(),
+            error: None,
+        });
+    }
+
+    fn failed(&self) -> Option<DiagnosticStep> {
+        self.checks
+            .iter()
+            .find(|c| c.outcome == CheckOutcome::Failed)
+            .map(|c| c.step)
+    }
+
+    fn finish(self, network: String, server_url: Option<String>) -> DiagnosticsReport {
+        DiagnosticsReport {
+            library: get_library_info(),
+            network,
+            server_url,
+            all_passed: self
+                .checks
+                .iter()
+                .all(|c| c.outcome != CheckOutcome::Failed),
+            checks: self.checks,
+        }
+    }
+}
+
+/// Where to point [`run_diagnostics`]: the same settings the app would pass
+/// to [`Backend::electrum_with_options`].
+#[cfg(feature = "electrum")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsConfig {
+    pub electrum_url: String,
+    pub network: super::Network,
+    pub connection_options: super::socket::ConnectionOptions,
+}
+
+/// Probe the Electrum server in `config` layer by layer, then query it
+/// through a backend built from the same settings.
+///
+/// Never fails: every problem is recorded as a failed step. Once a
+/// connection step fails, the steps that need the server are skipped
+/// rather than left to time out a second time.
+#[cfg(feature = "electrum")]
+pub fn run_diagnostics(config: DiagnosticsConfig) -> DiagnosticsReport {
+    let mut recorder = Recorder { checks: Vec::new() };
+    server::probe(
+        &mut recorder,
+        &config.electrum_url,
+        config.network.into(),
+        &config.connection_options,
+    );
+
+    match recorder.failed() {
+        Some(step) => {
+            let reason = format!("Skipped because the {:?} step failed", step);
+            recorder.skip(DiagnosticStep::Height, reason.clone());
+            recorder.skip(DiagnosticStep::FeeEstimate, reason);
+        }
+        None => {
+            let backend = Backend::electrum_with_options(
+                config.electrum_url.clone(),
+                config.network,
+                config.connection_options.clone(),
+            );
+            match backend {
+                Ok(backend) => chain_checks(&mut recorder, &backend),
+                Err(e) => {
+                    recorder.fail(DiagnosticStep::Height, e);
+                    recorder.skip(DiagnosticStep::FeeEstimate, "No backend");
+                }
+            }
+        }
+    }
+    psbt_check(&mut recorder);
+    recorder.finish(
+        config.network.backup_name().to_string(),
+        Some(config.electrum_url),
+    )
+}
+
+/// Run the backend and PSBT steps against an existing backend, for
+/// backends [`run_diagnostics`] cannot probe (host transports, simulated).
+pub fn run_backend_diagnostics(backend: &Backend) -> DiagnosticsReport {
+    let mut recorder = Recorder { checks: Vec::new() };
+    for step in [
+        DiagnosticStep::Dns,
+        DiagnosticStep::Connect,
+        DiagnosticStep::Tls,
+        DiagnosticStep::ElectrumHandshake,
+    ] {
+        recorder.skip(step, "The backend manages its own connection");
+    }
+    chain_checks(&mut recorder, backend);
+    psbt_check(&mut recorder);
+    recorder.finish(network_name(backend.chain().network()).to_string(), None)
+}
+
+fn chain_checks(recorder: &mut Recorder, backend: &Backend) {
+    let chain = backend.chain();
+    recorder.run(DiagnosticStep::Height, || {
+        let height = chain.tip_height()?;
+        Ok(((), format!("Tip at height {}", height)))
+    });
+    recorder.run(DiagnosticStep::FeeEstimate, || {
+        let rate = chain.estimate_fee_rate(FEE_TARGET_BLOCKS)?;
+        Ok((
+            (),
+            format!("{:.1} sat/vB for {} blocks", rate, FEE_TARGET_BLOCKS),
+        ))
+    });
+}
+
+/// Decode a claim PSBT from the test vectors and check it re-encodes to the
+/// same bytes.
+fn psbt_check(recorder: &mut Recorder) {
+    recorder.run(DiagnosticStep::PsbtRoundTrip, || {
+        let vectors = super::vectors::generate_test_vectors(0)?;
+        let psbt = decode_psbt(&vectors.unsigned_psbt_base64)?;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+        if encoded != vectors.unsigned_psbt_base64 {
+            return Err(HeirError::new(
+                ErrorKind::Internal,
+                "PSBT changed after decoding and re-encoding",
+            ));
+        }
+        Ok((
+            (),
+            format!("{} byte PSBT round-tripped", psbt.serialize().len()),
+        ))
+    });
+}
+
+/// Layer-by-layer connection to an Electrum server.
+#[cfg(feature = "electrum")]
+mod server {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+
+    use bitcoin::Network;
+
+    use super::super::electrum::check_genesis;
+    use super::super::socket::{
+        extra_roots, parse_electrum_url, resolve, tls_config, ConnectionOptions, CONNECT_TIMEOUT,
+    };
+    use super::super::{ErrorKind, HeirError};
+    use super::{DiagnosticStep, Recorder};
+
+    fn connection_error(message: impl Into<String>) -> HeirError {
+        HeirError::new(ErrorKind::Connection, message)
+    }
+
+    pub(super) fn probe(
+        recorder: &mut Recorder,
+        url: &str,
+        network: Network,
+        options: &ConnectionOptions,
+    ) {
+        let proxied = options.socks5_proxy.is_some();
+        let Some((tls, host, addresses)) = recorder.run(DiagnosticStep::Dns, || {
+            options.validate()?;
+            let (tls, host, port) = parse_electrum_url(url)
+                .map_err(|e| HeirError::new(ErrorKind::InvalidInput, e.to_string()))?;
+            if proxied {
+                // Resolving locally would leak the lookup the proxy exists to hide
+                return Ok((
+                    (tls, host, Vec::new()),
+                    "Resolved by the SOCKS5 proxy".into(),
+                ));
+            }
+            let addresses = resolve(&host, port, options)
+                .map_err(|e| connection_error(format!("Could not resolve {}: {}", host, e)))?;
+            let detail = addresses
+                .iter()
+                .map(|a| a.ip().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            Ok(((tls, host, addresses), detail))
+        }) else {
+            skip_rest(recorder, DiagnosticStep::Connect);
+            return;
+        };
+        if proxied {
+            for step in [
+                DiagnosticStep::Connect,
+                DiagnosticStep::Tls,
+                DiagnosticStep::ElectrumHandshake,
+            ] {
+                recorder.skip(step, "Connections go through the SOCKS5 proxy");
+            }
+            return;
+        }
+
+        let Some(tcp) = recorder.run(DiagnosticStep::Connect, || {
+            let mut last_error = None;
+            for addr in &addresses {
+                match TcpStream::connect_timeout(addr, CONNECT_TIMEOUT) {
+                    Ok(stream) => {
+                        stream
+                            .set_read_timeout(Some(CONNECT_TIMEOUT))
+                            .and_then(|_| stream.set_write_timeout(Some(CONNECT_TIMEOUT)))
+                            .map_err(|e| connection_error(e.to_string()))?;
+                        return Ok((stream, format!("Connected to {}", addr)));
+                    }
+                    Err(e) => last_error = Some(format!("{}: {}", addr, e)),
+                }
+            }
+            Err(connection_error(format!(
+                "Could not connect: {}",
+                last_error.unwrap_or_default()
+            )))
+        }) else {
+            skip_rest(recorder, DiagnosticStep::Tls);
+            return;
+        };
+
+        let stream: Option<Box<dyn ReadWrite>> = if tls {
+            recorder.run(DiagnosticStep::Tls, || {
+                let stream = tls_handshake(&host, tcp, options)?;
+                let detail = format!(
+                    "{:?}",
+                    stream.conn.protocol_version().expect("handshake completed")
+                );
+                Ok((Box::new(stream) as Box<dyn ReadWrite>, detail))
+            })
+        } else {
+            recorder.skip(DiagnosticStep::Tls, "Plain TCP server (tcp://)");
+            Some(Box::new(tcp))
+        };
+        let Some(mut stream) = stream else {
+            skip_rest(recorder, DiagnosticStep::ElectrumHandshake);
+            return;
+        };
+
+        recorder.run(DiagnosticStep::ElectrumHandshake, || {
+            let version = request(&mut stream, "server.version", r#"["nostring-heir", "1.4"]"#)?;
+            let software = version[0].as_str().unwrap_or("unknown").to_string();
+            let protocol = version[1].as_str().unwrap_or("unknown").to_string();
+            let features = request(&mut stream, "server.features", "[]")?;
+            let genesis = features["genesis_hash"].as_str().ok_or_else(|| {
+                HeirError::new(
+                    ErrorKind::ServerQuery,
+                    "Server did not report its genesis block",
+                )
+            })?;
+            check_genesis(genesis, network)?;
+            Ok(((), format!("{}, protocol {}", software, protocol)))
+        });
+    }
+
+    /// Record every connection step from `first` on as skipped.
+    pub(super) fn skip_rest(recorder: &mut Recorder, first: DiagnosticStep) {
+        let steps = [
+            DiagnosticStep::Connect,
+            DiagnosticStep::Tls,
+            DiagnosticStep::ElectrumHandshake,
+        ];
+        let failed = recorder.failed().expect("a step failed");
+        for step in steps.into_iter().skip_while(|s| *s != first) {
+            recorder.skip(
+                step,
+                format!("Skipped because the {:?} step failed", failed),
+            );
+        }
+    }
+
+    trait ReadWrite: Read + Write {}
+    impl<T: Read + Write> ReadWrite for T {}
+
+    fn tls_handshake(
+        host: &str,
+        mut tcp: TcpStream,
+        options: &ConnectionOptions,
+    ) -> Result<rustls::StreamOwned<rustls::ClientConnection, TcpStream>, HeirError> {
+        let config = tls_config(extra_roots(&options.extra_root_certs_pem)?)?;
+        let name = rustls::pki_types::ServerName::try_from(host.to_string())
+            .map_err(|e| HeirError::new(ErrorKind::InvalidInput, e.to_string()))?;
+        let mut connection = rustls::ClientConnection::new(config, name)
+            .map_err(|e| connection_error(e.to_string()))?;
+        while connection.is_handshaking() {
+            connection
+                .complete_io(&mut tcp)
+                .map_err(|e| connection_error(format!("TLS handshake failed: {}", e)))?;
+        }
+        Ok(rustls::StreamOwned::new(connection, tcp))
+    }
+
+    /// Send one JSON-RPC request and return its `result`.
+    fn request(
+        stream: &mut Box<dyn ReadWrite>,
+        method: &str,
+        params: &str,
+    ) -> Result<serde_json::Value, HeirError> {
+        let line = format!(
+            "{{\"jsonrpc\":\"2.0\",\"id\":0,\"method\":\"{}\",\"params\":{}}}\n",
+            method, params
+        );
+        stream
+            .write_all(line.as_bytes())
+            .and_then(|_| stream.flush())
+            .map_err(|e| connection_error(format!("{} failed: {}", method, e)))?;
+        let mut reply = String::new();
+        BufReader::new(stream)
+            .read_line(&mut reply)
+            .map_err(|e| connection_error(format!("{} failed: {}", method, e)))?;
+        let mut reply: serde_json::Value = serde_json::from_str(&reply).map_err(|_| {
+            HeirError::new(
+                ErrorKind::ServerQuery,
+                format!("{} returned a non-JSON reply", method),
+            )
+        })?;
+        if let Some(error) = reply.get("error").filter(|e| !e.is_null()) {
+            return Err(HeirError::new(
+                ErrorKind::ServerQuery,
+                format!("{} failed: {}", method, error),
+            ));
+        }
+        Ok(reply["result"].take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::Network;
+
+    fn outcome(report: &DiagnosticsReport, step: DiagnosticStep) -> CheckOutcome {
+        report
+            .checks
+            .iter()
+            .find(|c| c.step == step)
+            .map(|c| c.outcome)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_backend_diagnostics_pass() {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(2_500_000);
+        let report = run_backend_diagnostics(&Backend::simulated(&sim));
+        assert!(report.all_passed, "{}", report.to_json());
+        assert_eq!(report.network, "testnet");
+        assert_eq!(outcome(&report, DiagnosticStep::Dns), CheckOutcome::Skipped);
+        assert_eq!(
+            outcome(&report, DiagnosticStep::Height),
+            CheckOutcome::Passed
+        );
+        assert_eq!(
+            outcome(&report, DiagnosticStep::PsbtRoundTrip),
+            CheckOutcome::Passed
+        );
+        assert!(report.checks[4].detail.contains("2500000"));
+    }
+
+    #[cfg(feature = "electrum")]
+    #[test]
+    fn test_bad_url_skips_server_steps() {
+        let report = run_diagnostics(DiagnosticsConfig {
+            electrum_url: "https://electrum.example:443".into(),
+            network: Network::Mainnet,
+            connection_options: Default::default(),
+        });
+        assert!(!report.all_passed);
+        assert_eq!(report.checks.len(), 7);
+        let dns = &report.checks[0];
+        assert_eq!(dns.outcome, CheckOutcome::Failed);
+        assert_eq!(dns.error.as_ref().unwrap().kind, ErrorKind::InvalidInput);
+        for step in [
+            DiagnosticStep::Connect,
+            DiagnosticStep::Tls,
+            DiagnosticStep::ElectrumHandshake,
+            DiagnosticStep::Height,
+            DiagnosticStep::FeeEstimate,
+        ] {
+            assert_eq!(outcome(&report, step), CheckOutcome::Skipped);
+        }
+        // The offline check still runs
+        assert_eq!(
+            outcome(&report, DiagnosticStep::PsbtRoundTrip),
+            CheckOutcome::Passed
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Comparing two versions of a claim PSBT between signing rounds.
+//!
+//! A coordinator passing a PSBT from heir to heir can show with
+//! [`diff_psbts`] that each round only added signatures: same transaction,
+//! same spent outputs, same fee. Anything else is listed so co-heirs can
+//! see exactly what moved.
+
+use std::collections::BTreeSet;
+
+use bitcoin::psbt::Input;
+use bitcoin::Psbt;
+use serde::{Deserialize, Serialize};
+
+use super::{decode_psbt, HeirError};
+
+/// A signature present in one version but not the other.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SignatureChange {
+    pub input_index: usize,
+    /// Hex public key (x-only for taproot) the signature is for.
+    pub public_key: String,
+    /// Script leaf signed, for taproot script-path signatures.
+    pub leaf_hash: Option<String>,
+}
+
+/// An output that differs between the versions. `None` means the output
+/// does not exist on that side.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputChange {
+    pub index: usize,
+    pub before_script_hex: Option<String>,
+    pub before_sat: Option<u64>,
+    pub after_script_hex: Option<String>,
+    pub after_sat: Option<u64>,
+}
+
+/// What changed from one PSBT version to the next.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PsbtDiff {
+    /// Same transaction, same spent outputs and sighash types, and no
+    /// signature taken away: the later version only adds signatures.
+    pub only_signatures_added: bool,
+    pub added_signatures: Vec<SignatureChange>,
+    /// Signatures dropped from inputs that were not finalized in between.
+    pub removed_signatures: Vec<SignatureChange>,
+    /// Inputs finalized in the later version.
+    pub finalized_inputs: Vec<usize>,
+    pub changed_outputs: Vec<OutputChange>,
+    /// `None` when a version lacks some input's witness UTXO.
+    pub fee_before_sat: Option<u64>,
+    pub fee_after_sat: Option<u64>,
+    /// Every other change that affects what is signed, in plain language.
+    pub other_changes: Vec<String>,
+}
+
+/// Every signature on `input`.
+fn signatures(index: usize, input: &Input) -> BTreeSet<SignatureChange> {
+    let mut sigs = BTreeSet::new();
+    for (xonly, leaf_hash) in input.tap_script_sigs.keys() {
+        sigs.insert(SignatureChange {
+            input_index: index,
+            public_key: xonly.to_string(),
+            leaf_hash: Some(leaf_hash.to_string()),
+        });
+    }
+    if input.tap_key_sig.is_some() {
+        if let Some(key) = input.tap_internal_key {
+            sigs.insert(SignatureChange {
+                input_index: index,
+                public_key: key.to_string(),
+                leaf_hash: None,
+            });
+        }
+    }
+    for key in input.partial_sigs.keys() {
+        sigs.insert(SignatureChange {
+            input_index: index,
+            public_key: key.to_string(),
+            leaf_hash: None,
+        });
+    }
+    sigs
+}
+
+fn fee(psbt: &Psbt) -> Option<u64> {
+    let input_sat = psbt
+        .inputs
+        .iter()
+        .map(|input| input.witness_utxo.as_ref().map(|utxo| utxo.value.to_sat()))
+        .sum::<Option<u64>>()?;
+    let output_sat: u64 = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .map(|o| o.value.to_sat())
+        .sum();
+    input_sat.checked_sub(output_sat)
+}
+
+/// Compare two versions of a PSBT, `before` from the earlier round.
+pub fn diff_psbts(before_base64: String, after_base64: String) -> Result<PsbtDiff, HeirError> {
+    let before = decode_psbt(&before_base64)?;
+    let after = decode_psbt(&after_base64)?;
+    let (a, b) = (&before.unsigned_tx, &after.unsigned_tx);
+    let mut other_changes = Vec::new();
+
+    if a.version != b.version {
+        other_changes.push(format!(
+            "Version changed from {} to {}",
+            a.version, b.version
+        ));
+    }
+    if a.lock_time != b.lock_time {
+        other_changes.push(format!(
+            "Locktime changed from {} to {}",
+            a.lock_time, b.lock_time
+        ));
+    }
+    for index in 0..a.input.len().max(b.input.len()) {
+        match (a.input.get(index), b.input.get(index)) {
+            (Some(x), Some(y)) if x.previous_output != y.previous_output => {
+                other_changes.push(format!(
+                    "Input {} now spends {} instead of {}",
+                    index, y.previous_output, x.previous_output
+                ))
+            }
+            (Some(x), Some(y)) if x.sequence != y.sequence => other_changes.push(format!(
+                "Input {} sequence changed from {} to {}",
+                index, x.sequence, y.sequence
+            )),
+            (Some(x), None) => other_changes.push(format!(
+                "Input {} spending {} was removed",
+                index, x.previous_output
+            )),
+            (None, Some(y)) => other_changes.push(format!(
+                "Input {} spending {} was added",
+                index, y.previous_output
+            )),
+            _ => {}
+        }
+    }
+
+    let changed_outputs: Vec<OutputChange> = (0..a.output.len().max(b.output.len()))
+        .filter_map(|index| {
+            let (x, y) = (a.output.get(index), b.output.get(index));
+            (x != y).then(|| OutputChange {
+                index,
+                before_script_hex: x.map(|o| o.script_pubkey.to_hex_string()),
+                before_sat: x.map(|o| o.value.to_sat()),
+                after_script_hex: y.map(|o| o.script_pubkey.to_hex_string()),
+                after_sat: y.map(|o| o.value.to_sat()),
+            })
+        })
+        .collect();
+
+    let mut added_signatures = Vec::new();
+    let mut removed_signatures = Vec::new();
+    let mut finalized_inputs = Vec::new();
+    for (index, (x, y)) in before.inputs.iter().zip(&after.inputs).enumerate() {
+        if x.witness_utxo != y.witness_utxo {
+            other_changes.push(format!("Input {} spent output details changed", index));
+        }
+        if x.sighash_type != y.sighash_type {
+            other_changes.push(format!("Input {} sighash type changed", index));
+        }
+        let finalized = x.final_script_witness.is_none() && y.final_script_witness.is_some();
+        if finalized {
+            finalized_inputs.push(index);
+        }
+        let (old, new) = (signatures(index, x), signatures(index, y));
+        added_signatures.extend(new.difference(&old).cloned());
+        // Finalizing moves signatures into the witness
+        if !finalized && y.final_script_witness.is_none() {
+            removed_signatures.extend(old.difference(&new).cloned());
+        }
+    }
+
+    Ok(PsbtDiff {
+        only_signatures_added: changed_outputs.is_empty()
+            && other_changes.is_empty()
+            && removed_signatures.is_empty(),
+        added_signatures,
+        removed_signatures,
+        finalized_inputs,
+        changed_outputs,
+        fee_before_sat: fee(&before),
+        fee_after_sat: fee(&after),
+        other_changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::psbt::tests::external_sign;
+    use crate::api::vectors::generate_test_vectors;
+    use base64::Engine;
+
+    fn encode(psbt: &Psbt) -> String {
+        base64::engine::general_purpose::STANDARD.encode(psbt.serialize())
+    }
+
+    #[test]
+    fn test_signing_round_only_adds_signatures() {
+        let v = generate_test_vectors(33).unwrap();
+        let mut psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        external_sign(&mut psbt, 0, &v.heir_secret_key_hex);
+
+        let diff = diff_psbts(v.unsigned_psbt_base64.clone(), encode(&psbt)).unwrap();
+        assert!(diff.only_signatures_added, "{:?}", diff);
+        assert_eq!(diff.added_signatures.len(), 1);
+        assert!(diff.added_signatures[0].leaf_hash.is_some());
+        assert_eq!(diff.fee_before_sat, Some(v.fee_sat));
+        assert_eq!(diff.fee_after_sat, Some(v.fee_sat));
+
+        let finalized = diff_psbts(v.unsigned_psbt_base64, v.signed_psbt_base64).unwrap();
+        assert!(finalized.only_signatures_added);
+        assert_eq!(finalized.finalized_inputs, vec![0]);
+    }
+
+    #[test]
+    fn test_reports_changed_output_and_fee() {
+        let v = generate_test_vectors(34).unwrap();
+        let mut psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        let output = &mut psbt.unsigned_tx.output[0];
+        output.value = output.value - bitcoin::Amount::from_sat(5_000);
+
+        let diff = diff_psbts(v.unsigned_psbt_base64.clone(), encode(&psbt)).unwrap();
+        assert!(!diff.only_signatures_added);
+        assert_eq!(diff.changed_outputs.len(), 1);
+        assert_eq!(diff.changed_outputs[0].index, 0);
+        assert_eq!(diff.fee_after_sat, Some(v.fee_sat + 5_000));
+
+        // Dropping a co-heir's signature is not "only adding"
+        let mut signed = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        external_sign(&mut signed, 0, &v.heir_secret_key_hex);
+        let diff = diff_psbts(encode(&signed), v.unsigned_psbt_base64).unwrap();
+        assert!(!diff.only_signatures_added);
+        assert_eq!(diff.removed_signatures.len(), 1);
+    }
+}
@@ -0,0 +1,609 @@
+//! Differences between Electrum server implementations.
+//!
+//! ElectrumX, electrs and Fulcrum speak the same protocol but disagree on
+//! error text and how large a batch they accept. The backend identifies the
+//! server from `server.features` once per connection and asks this module
+//! instead of assuming one implementation. Transactions are always fetched
+//! raw and decoded locally, since electrs does not support verbose mode.
+//!
+//! Only compiled with the `electrum` feature (on by default).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use bitcoin::{Address, Amount, Network, OutPoint, ScriptBuf, Transaction, Txid};
+use electrum_client::{ElectrumApi, ServerFeaturesRes};
+
+use super::backend::{block_height, ChainBackend, ChainHistoryEntry, ChainUtxo};
+use super::{ErrorKind, HeirError};
+use crate::trace::span;
+
+/// Open an Electrum connection (`ssl://host:port` or `tcp://host:port`),
+/// optionally through a SOCKS5 proxy that also resolves the hostname.
+pub(crate) fn connect_electrum(
+    electrum_url: &str,
+    proxy: Option<&str>,
+) -> Result<electrum_client::Client, HeirError> {
+    span!("electrum.connect");
+    let socks5 = proxy
+        .map(super::socket::proxy_address)
+        .transpose()?
+        .map(electrum_client::Socks5Config::new);
+    let config = electrum_client::ConfigBuilder::new().socks5(socks5).build();
+    electrum_client::Client::from_config(electrum_url, config).map_err(|e| {
+        HeirError::new(
+            ErrorKind::Connection,
+            format!("Electrum connection failed: {}", e),
+        )
+    })
+}
+
+/// Server software, from the `server_version` string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ServerImpl {
+    ElectrumX,
+    Electrs,
+    Fulcrum,
+    Unknown,
+}
+
+impl ServerImpl {
+    /// Identify the implementation from e.g. `"ElectrumX 1.16.0"`,
+    /// `"electrs/0.10.2"`, `"electrs-esplora 0.4.1"` or `"Fulcrum 1.9.8"`.
+    pub(crate) fn from_version(server_version: &str) -> Self {
+        let version = server_version.to_ascii_lowercase();
+        if version.starts_with("electrumx") {
+            ServerImpl::ElectrumX
+        } else if version.starts_with("electrs") {
+            ServerImpl::Electrs
+        } else if version.starts_with("fulcrum") {
+            ServerImpl::Fulcrum
+        } else {
+            ServerImpl::Unknown
+        }
+    }
+
+    /// Largest JSON-RPC batch to send in one round trip. electrs closes the
+    /// connection on oversized batches; unknown servers get a cautious size.
+    pub(crate) fn batch_limit(self) -> usize {
+        match self {
+            ServerImpl::ElectrumX | ServerImpl::Fulcrum => 100,
+            ServerImpl::Electrs => 50,
+            ServerImpl::Unknown => 20,
+        }
+    }
+}
+
+/// What the backend learned about the server when it connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ServerInfo {
+    pub implementation: ServerImpl,
+}
+
+impl ServerInfo {
+    /// Server that did not answer `server.features`; assume nothing.
+    pub(crate) const UNKNOWN: ServerInfo = ServerInfo {
+        implementation: ServerImpl::Unknown,
+    };
+
+    /// Check the server's chain and identify its implementation.
+    pub(crate) fn from_features(
+        features: &ServerFeaturesRes,
+        network: Network,
+    ) -> Result<Self, HeirError> {
+        check_genesis(&hex::encode(features.genesis_hash), network)?;
+        Ok(ServerInfo {
+            implementation: ServerImpl::from_version(&features.server_version),
+        })
+    }
+}
+
+/// Fail unless `genesis_hex` (display order) is `network`'s genesis block.
+pub(crate) fn check_genesis(genesis_hex: &str, network: Network) -> Result<(), HeirError> {
+    let expected = bitcoin::constants::genesis_block(network).block_hash().to_string();
+    if genesis_hex.eq_ignore_ascii_case(&expected) {
+        Ok(())
+    } else {
+        Err(HeirError::new(
+            ErrorKind::NetworkMismatch,
+            format!("Electrum server is not a {} server", network),
+        ))
+    }
+}
+
+/// Last status hash and UTXO set seen per script.
+///
+/// The status hash changes whenever a transaction touching the script
+/// appears, confirms or is dropped, so an unchanged status means the cached
+/// UTXOs are still exact. Generic over the status type to keep it testable.
+#[derive(Debug)]
+pub(crate) struct StatusCache<S, U> {
+    entries: HashMap<ScriptBuf, (Option<S>, Vec<U>)>,
+}
+
+impl<S: PartialEq, U: Clone> StatusCache<S, U> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Cached UTXOs for `script` if its status is still `status`.
+    pub(crate) fn get(&self, script: &ScriptBuf, status: &Option<S>) -> Option<Vec<U>> {
+        match self.entries.get(script) {
+            Some((cached, utxos)) if cached == status => Some(utxos.clone()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn store(&mut self, script: ScriptBuf, status: Option<S>, utxos: Vec<U>) {
+        self.entries.insert(script, (status, utxos));
+    }
+}
+
+/// Pull the node's rejection reason out of a broadcast error.
+///
+/// ElectrumX wraps it in prose and appends the raw transaction, electrs
+/// embeds bitcoind's JSON error or prefixes the RPC name, and Fulcrum passes
+/// it through. The classifier matches substrings, so this only has to drop
+/// the noise that would otherwise end up in the message shown to the user.
+pub(crate) fn rejection_reason(error: &serde_json::Value) -> String {
+    let message = error
+        .get("message")
+        .unwrap_or(error)
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| error.to_string());
+
+    // electrs: `sendrawtransaction RPC error: {"code":-26,"message":"..."}`
+    if let Some(start) = message.find('{') {
+        if let Ok(inner) = serde_json::from_str::<serde_json::Value>(&message[start..]) {
+            if let Some(inner) = inner.get("message").and_then(|m| m.as_str()) {
+                return inner.to_string();
+            }
+        }
+    }
+
+    // ElectrumX: `the transaction was rejected by network rules.\n\n<reason>\n[<tx hex>]`
+    if let Some((_, rest)) = message.split_once("\n\n") {
+        if let Some(reason) = rest.lines().map(str::trim).find(|l| !l.is_empty()) {
+            return reason.to_string();
+        }
+    }
+
+    // electrs: `sendrawtransaction RPC error -26: <reason>`
+    if message.starts_with("sendrawtransaction") {
+        if let Some((_, reason)) = message.split_once(": ") {
+            return reason.trim().to_string();
+        }
+    }
+
+    message.trim().to_string()
+}
+
+/// Opens the connections an [`ElectrumBackend`] runs over.
+pub(crate) trait Connector: Send + Sync + 'static {
+    type Client: ElectrumApi + Send;
+
+    fn connect(&self, url: &str) -> Result<Self::Client, HeirError>;
+}
+
+/// The electrum client's own TCP and TLS sockets.
+#[derive(Default)]
+pub(crate) struct NativeConnector {
+    pub(crate) proxy: Option<String>,
+}
+
+impl Connector for NativeConnector {
+    type Client = electrum_client::Client;
+
+    fn connect(&self, url: &str) -> Result<Self::Client, HeirError> {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        connect_electrum(url, self.proxy.as_deref())
+    }
+}
+
+/// Electrum backend. Connects on first use and reconnects after a failure.
+pub(crate) struct ElectrumBackend<C: Connector = NativeConnector> {
+    connector: C,
+    url: String,
+    network: Network,
+    session: Mutex<Option<ElectrumSession<C::Client>>>,
+    /// Outlives sessions: a fresh subscription's status is compared against it.
+    utxo_cache: Mutex<StatusCache<electrum_client::ScriptStatus, ChainUtxo>>,
+}
+
+/// A live connection and what the server said about itself.
+struct ElectrumSession<A> {
+    client: A,
+    server: ServerInfo,
+    /// Scripts subscribed on this connection, with their latest status.
+    statuses: HashMap<ScriptBuf, Option<electrum_client::ScriptStatus>>,
+}
+
+impl<A: ElectrumApi> ElectrumSession<A> {
+    /// Current status hash of `script`. The first call subscribes; later
+    /// calls cost one ping and read any queued change notifications.
+    fn script_status(
+        &mut self,
+        script: &ScriptBuf,
+    ) -> Result<Option<electrum_client::ScriptStatus>, electrum_client::Error> {
+        if let Some(status) = self.statuses.get_mut(script) {
+            self.client.ping()?;
+            while let Some(update) = self.client.script_pop(script)? {
+                *status = Some(update);
+            }
+            return Ok(*status);
+        }
+        let status = self.client.script_subscribe(script)?;
+        self.statuses.insert(script.clone(), status);
+        Ok(status)
+    }
+}
+
+impl ElectrumBackend {
+    pub(crate) fn new(url: String, network: Network) -> Self {
+        Self::with_connector(NativeConnector::default(), url, network)
+    }
+}
+
+impl<C: Connector> ElectrumBackend<C> {
+    pub(crate) fn with_connector(connector: C, url: String, network: Network) -> Self {
+        Self {
+            connector,
+            url,
+            network,
+            session: Mutex::new(None),
+            utxo_cache: Mutex::new(StatusCache::new()),
+        }
+    }
+
+    fn connect(&self) -> Result<ElectrumSession<C::Client>, HeirError> {
+        let client = self.connector.connect(&self.url)?;
+        let server = match client.server_features() {
+            Ok(features) => ServerInfo::from_features(&features, self.network)?,
+            // Not fatal: the other calls still work, just more cautiously
+            Err(_) => ServerInfo::UNKNOWN,
+        };
+        Ok(ElectrumSession {
+            client,
+            server,
+            statuses: Default::default(),
+        })
+    }
+
+    /// Run `f` against a connected session, dropping the connection on error
+    /// so the next call starts fresh.
+    fn with_session<T>(
+        &self,
+        f: impl FnOnce(&mut ElectrumSession<C::Client>) -> Result<T, HeirError>,
+    ) -> Result<T, HeirError> {
+        let mut guard = self
+            .session
+            .lock()
+            .map_err(|_| HeirError::new(ErrorKind::Internal, "Electrum client lock poisoned"))?;
+        if guard.is_none() {
+            *guard = Some(self.connect()?);
+        }
+        let result = f(guard.as_mut().expect("client connected above"));
+        if result.is_err() {
+            *guard = None;
+        }
+        result
+    }
+
+    fn with_client<T>(
+        &self,
+        f: impl FnOnce(&C::Client, ServerInfo) -> Result<T, HeirError>,
+    ) -> Result<T, HeirError> {
+        self.with_session(|session| f(&session.client, session.server))
+    }
+}
+
+impl<C: Connector> ChainBackend for ElectrumBackend<C> {
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn tip_height(&self) -> Result<u64, HeirError> {
+        span!("electrum.get_height");
+        self.with_client(|client, _| {
+            client
+                .block_headers_subscribe()
+                .map_err(|e| {
+                    HeirError::new(
+                        ErrorKind::ServerQuery,
+                        format!("Failed to get block height: {}", e),
+                    )
+                })
+                .and_then(|header| block_height(header.height).map(u64::from))
+        })
+    }
+
+    fn list_unspent(&self, address: &Address) -> Result<Vec<ChainUtxo>, HeirError> {
+        span!("electrum.get_utxos");
+        let script_pubkey = address.script_pubkey();
+        self.with_session(|session| {
+            let status = session.script_status(&script_pubkey).map_err(|e| {
+                HeirError::new(ErrorKind::ServerQuery, format!("Failed to fetch status: {}", e))
+            })?;
+            let mut cache = self
+                .utxo_cache
+                .lock()
+                .map_err(|_| HeirError::new(ErrorKind::Internal, "UTXO cache lock poisoned"))?;
+            if let Some(utxos) = cache.get(&script_pubkey, &status) {
+                return Ok(utxos);
+            }
+            // No status means no history at all
+            let utxos = if status.is_none() {
+                Vec::new()
+            } else {
+                let utxos = session.client.script_list_unspent(&script_pubkey).map_err(|e| {
+                    HeirError::new(ErrorKind::ServerQuery, format!("Failed to fetch UTXOs: {}", e))
+                })?;
+                chain_utxos(&utxos, &script_pubkey)?
+            };
+            cache.store(script_pubkey.clone(), status, utxos.clone());
+            Ok(utxos)
+        })
+    }
+
+    fn history(&self, address: &Address) -> Result<Vec<ChainHistoryEntry>, HeirError> {
+        span!("electrum.get_history");
+        self.with_client(|client, _| {
+            let history = client
+                .script_get_history(&address.script_pubkey())
+                .map_err(history_error)?;
+            Ok(history_entries(&history))
+        })
+    }
+
+    fn list_unspent_many(&self, addresses: &[Address]) -> Result<Vec<Vec<ChainUtxo>>, HeirError> {
+        span!("electrum.batch_get_utxos");
+        let scripts: Vec<ScriptBuf> = addresses.iter().map(Address::script_pubkey).collect();
+        self.with_client(|client, server| {
+            let mut all = Vec::with_capacity(scripts.len());
+            for chunk in scripts.chunks(server.implementation.batch_limit()) {
+                let batch = client
+                    .batch_script_list_unspent(chunk.iter().map(ScriptBuf::as_script))
+                    .map_err(|e| {
+                        HeirError::new(ErrorKind::ServerQuery, format!("Failed to fetch UTXOs: {}", e))
+                    })?;
+                for (utxos, script) in batch.iter().zip(chunk) {
+                    all.push(chain_utxos(utxos, script)?);
+                }
+            }
+            Ok(all)
+        })
+    }
+
+    fn histories(&self, addresses: &[Address]) -> Result<Vec<Vec<ChainHistoryEntry>>, HeirError> {
+        span!("electrum.batch_get_history");
+        let scripts: Vec<ScriptBuf> = addresses.iter().map(Address::script_pubkey).collect();
+        self.with_client(|client, server| {
+            let mut all = Vec::with_capacity(scripts.len());
+            for chunk in scripts.chunks(server.implementation.batch_limit()) {
+                let batch = client
+                    .batch_script_get_history(chunk.iter().map(ScriptBuf::as_script))
+                    .map_err(history_error)?;
+                all.extend(batch.iter().map(|history| history_entries(history)));
+            }
+            Ok(all)
+        })
+    }
+
+    fn block_time(&self, height: u32) -> Result<u64, HeirError> {
+        span!("electrum.get_header");
+        self.with_client(|client, _| {
+            client
+                .block_header(height as usize)
+                .map(|header| u64::from(header.time))
+                .map_err(|e| {
+                    HeirError::new(
+                        ErrorKind::ServerQuery,
+                        format!("Failed to fetch block header {}: {}", height, e),
+                    )
+                })
+        })
+    }
+
+    fn transaction(&self, txid: &Txid) -> Result<Transaction, HeirError> {
+        span!("electrum.get_transaction");
+        self.with_client(|client, _| {
+            client.transaction_get(txid).map_err(|e| {
+                HeirError::new(
+                    ErrorKind::ServerQuery,
+                    format!("Failed to fetch transaction {}: {}", txid, e),
+                )
+            })
+        })
+    }
+
+    fn transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>, HeirError> {
+        span!("electrum.get_transactions");
+        self.with_client(|client, server| {
+            let mut txs = Vec::with_capacity(txids.len());
+            for chunk in txids.chunks(server.implementation.batch_limit()) {
+                txs.extend(client.batch_transaction_get(chunk).map_err(|e| {
+                    HeirError::new(
+                        ErrorKind::ServerQuery,
+                        format!("Failed to fetch {} transactions: {}", chunk.len(), e),
+                    )
+                })?);
+            }
+            Ok(txs)
+        })
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, HeirError> {
+        span!("electrum.broadcast");
+        self.with_client(|client, _| {
+            client
+                .transaction_broadcast(tx)
+                .map_err(|e| match e {
+                    // The server answered: classify its rejection
+                    electrum_client::Error::Protocol(error) => {
+                        HeirError::broadcast_rejected(rejection_reason(&error))
+                    }
+                    _ => HeirError::new(ErrorKind::Connection, format!("Broadcast failed: {}", e)),
+                })
+        })
+    }
+
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<f64, HeirError> {
+        span!("electrum.estimate_fee");
+        self.with_client(|client, _| {
+            let btc_per_kvb = client.estimate_fee(usize::from(target_blocks)).map_err(|e| {
+                HeirError::new(ErrorKind::ServerQuery, format!("Failed to estimate fee: {}", e))
+            })?;
+            // Servers answer -1 when they have no estimate for the target
+            if btc_per_kvb <= 0.0 {
+                return Err(HeirError::new(
+                    ErrorKind::ServerQuery,
+                    format!("No fee estimate available for {} blocks", target_blocks),
+                ));
+            }
+            Ok(btc_per_kvb * 100_000_000.0 / 1000.0)
+        })
+    }
+
+    fn relay_fee_rate(&self) -> Result<f64, HeirError> {
+        span!("electrum.relay_fee");
+        self.with_client(|client, _| {
+            let btc_per_kvb = client.relay_fee().map_err(|e| {
+                HeirError::new(ErrorKind::ServerQuery, format!("Failed to get relay fee: {}", e))
+            })?;
+            Ok(btc_per_kvb * 100_000_000.0 / 1000.0)
+        })
+    }
+}
+
+fn chain_utxos(
+    utxos: &[electrum_client::ListUnspentRes],
+    script_pubkey: &ScriptBuf,
+) -> Result<Vec<ChainUtxo>, HeirError> {
+    utxos
+        .iter()
+        .map(|u| {
+            let vout = u32::try_from(u.tx_pos).map_err(|_| {
+                HeirError::new(
+                    ErrorKind::ServerQuery,
+                    format!("The server reported an impossible output index: {}", u.tx_pos),
+                )
+            })?;
+            Ok(ChainUtxo {
+                outpoint: OutPoint::new(u.tx_hash, vout),
+                value: Amount::from_sat(u.value),
+                script_pubkey: script_pubkey.clone(),
+                height: block_height(u.height)?,
+            })
+        })
+        .collect()
+}
+
+/// Map a history query failure, recognising servers that refuse or cut
+/// off histories above their size limit.
+fn history_error(e: electrum_client::Error) -> HeirError {
+    let text = e.to_string();
+    let lower = text.to_ascii_lowercase();
+    let too_large = [
+        "history too large",
+        "too many history entries",
+        "excessive resource usage",
+        "response too large",
+        "history is too long",
+    ]
+    .iter()
+    .any(|needle| lower.contains(needle));
+    if too_large {
+        HeirError::new(
+            ErrorKind::HistoryTooLarge,
+            format!("The server refused the address history as too large: {}", text),
+        )
+    } else {
+        HeirError::new(ErrorKind::ServerQuery, format!("Failed to fetch history: {}", text))
+    }
+}
+
+fn history_entries(history: &[electrum_client::GetHistoryRes]) -> Vec<ChainHistoryEntry> {
+    history
+        .iter()
+        .map(|h| ChainHistoryEntry {
+            txid: h.tx_hash,
+            // Mempool entries are reported as 0 or -1
+            height: u32::try_from(h.height).unwrap_or(0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::BroadcastFailure;
+    use serde_json::json;
+
+    #[test]
+    fn test_identify_server() {
+        assert_eq!(ServerImpl::from_version("ElectrumX 1.16.0"), ServerImpl::ElectrumX);
+        assert_eq!(ServerImpl::from_version("electrs/0.10.2"), ServerImpl::Electrs);
+        assert_eq!(ServerImpl::from_version("electrs-esplora 0.4.1"), ServerImpl::Electrs);
+        assert_eq!(ServerImpl::from_version("Fulcrum 1.9.8"), ServerImpl::Fulcrum);
+        assert_eq!(ServerImpl::from_version("mystery 0.1"), ServerImpl::Unknown);
+        assert!(ServerImpl::Unknown.batch_limit() <= ServerImpl::Electrs.batch_limit());
+    }
+
+    #[test]
+    fn test_status_cache() {
+        let script = ScriptBuf::from_bytes(vec![0x51]);
+        let mut cache: StatusCache<u8, u64> = StatusCache::new();
+        assert_eq!(cache.get(&script, &Some(1)), None);
+
+        cache.store(script.clone(), Some(1), vec![5_000]);
+        assert_eq!(cache.get(&script, &Some(1)), Some(vec![5_000]));
+        assert_eq!(cache.get(&script, &Some(2)), None);
+        assert_eq!(cache.get(&script, &None), None);
+    }
+
+    #[test]
+    fn test_check_genesis() {
+        let mainnet = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        assert!(check_genesis(mainnet, Network::Bitcoin).is_ok());
+        let err = check_genesis(mainnet, Network::Testnet).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::NetworkMismatch);
+    }
+
+    #[test]
+    fn test_rejection_reason_per_server() {
+        let electrumx = json!({
+            "code": 1,
+            "message": "the transaction was rejected by network rules.\n\nnon-BIP68-final\n[0200000001...]"
+        });
+        let electrs = json!({
+            "code": -32603,
+            "message": "sendrawtransaction RPC error: {\"code\":-26,\"message\":\"non-BIP68-final\"}"
+        });
+        let electrs_plain = json!({
+            "code": 2,
+            "message": "sendrawtransaction RPC error -26: non-BIP68-final"
+        });
+        let fulcrum = json!({"code": 1, "message": "non-BIP68-final"});
+
+        for error in [electrumx, electrs, electrs_plain, fulcrum] {
+            let reason = rejection_reason(&error);
+            assert_eq!(reason, "non-BIP68-final");
+            assert_eq!(BroadcastFailure::classify(&reason), BroadcastFailure::NonFinal);
+        }
+        assert_eq!(rejection_reason(&json!("bad tx")), "bad tx");
+    }
+
+    #[test]
+    fn test_history_too_large_is_recognised() {
+        let electrumx = electrum_client::Error::Protocol(json!({
+            "code": -32600,
+            "message": "history too large"
+        }));
+        assert_eq!(history_error(electrumx).kind, ErrorKind::HistoryTooLarge);
+        let other = electrum_client::Error::Message("unknown script hash".into());
+        assert_eq!(history_error(other).kind, ErrorKind::ServerQuery);
+    }
+}
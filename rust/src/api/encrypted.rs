@@ -0,0 +1,261 @@
+//! Password-protected backups.
+//!
+//! A backup holds no private keys, but its xpubs and labels reveal who the
+//! heirs are and every vault address. [`encrypt_backup`] wraps it in a
+//! `nostring:enc:v1:` envelope so the owner can hand it out more freely;
+//! [`import_encrypted_backup`] opens it, so neither the password nor the
+//! plaintext leaves Rust.
+//!
+//! Envelope, base64 after the prefix: scrypt `log_n`, `r` and `p`
+//! (1 + 4 + 4 bytes, big-endian), a 16-byte salt, a 12-byte nonce, then the
+//! AES-256-GCM ciphertext and tag of the canonical backup JSON. The prefix
+//! is authenticated as associated data.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use bitcoin::secp256k1::rand::RngCore;
+
+use super::entropy::EntropySource;
+use super::import::ENCRYPTED_BACKUP_PREFIX;
+use super::{import_vault_backup, ErrorKind, HeirError, VaultInfo};
+
+/// scrypt cost for new envelopes: 32 MiB and well under a second on a
+/// phone.
+const LOG_N: u8 = 15;
+const R: u32 = 8;
+const P: u32 = 1;
+/// Highest cost accepted when opening, so a crafted envelope cannot make
+/// the app allocate gigabytes.
+const MAX_LOG_N: u8 = 20;
+const MAX_R: u32 = 32;
+const MAX_P: u32 = 16;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = 9 + SALT_LEN + NONCE_LEN;
+
+fn derive_key(
+    password: &str,
+    salt: &[u8],
+    log_n: u8,
+    r: u32,
+    p: u32,
+) -> Result<[u8; 32], HeirError> {
+    let params = scrypt::Params::new(log_n, r, p, 32).map_err(|_| {
+        HeirError::new(
+            ErrorKind::InvalidEncoding,
+            "Invalid key derivation parameters",
+        )
+    })?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+        .map_err(|_| HeirError::new(ErrorKind::Internal, "Key derivation failed"))?;
+    Ok(key)
+}
+
+fn require_password(password: &str) -> Result<(), HeirError> {
+    if password.is_empty() {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            "A password is needed",
+        ));
+    }
+    Ok(())
+}
+
+/// Envelope for `json` at scrypt cost `log_n`, with salt and nonce drawn
+/// from `entropy`.
+pub(crate) fn seal(
+    json: &str,
+    password: &str,
+    log_n: u8,
+    entropy: &EntropySource,
+) -> Result<String, HeirError> {
+    require_password(password)?;
+    let mut rng = entropy.rng();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut salt);
+    rng.fill_bytes(&mut nonce);
+
+    let key = derive_key(password, &salt, log_n, R, P)?;
+    let ciphertext = Aes256Gcm::new(&key.into())
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: json.as_bytes(),
+                aad: ENCRYPTED_BACKUP_PREFIX.as_bytes(),
+            },
+        )
+        .map_err(|_| HeirError::new(ErrorKind::Internal, "Encryption failed"))?;
+
+    let mut envelope = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    envelope.push(log_n);
+    envelope.extend_from_slice(&R.to_be_bytes());
+    envelope.extend_from_slice(&P.to_be_bytes());
+    envelope.extend_from_slice(&salt);
+    envelope.extend_from_slice(&nonce);
+    envelope.extend_from_slice(&ciphertext);
+    Ok(format!(
+        "{}{}",
+        ENCRYPTED_BACKUP_PREFIX,
+        base64::engine::general_purpose::STANDARD.encode(envelope)
+    ))
+}
+
+/// Decrypt an envelope to the JSON inside.
+pub(crate) fn open(ciphertext: &str, password: &str) -> Result<String, HeirError> {
+    require_password(password)?;
+    let malformed = || HeirError::new(ErrorKind::InvalidEncoding, "Damaged encrypted backup");
+    let encoded = ciphertext
+        .trim()
+        .strip_prefix(ENCRYPTED_BACKUP_PREFIX)
+        .ok_or_else(|| {
+            HeirError::new(
+                ErrorKind::UnrecognizedFormat,
+                "Not a nostring encrypted backup",
+            )
+        })?;
+    let envelope = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|_| malformed())?;
+    if envelope.len() <= HEADER_LEN {
+        return Err(malformed());
+    }
+
+    let log_n = envelope[0];
+    let r = u32::from_be_bytes(envelope[1..5].try_into().expect("4 bytes"));
+    let p = u32::from_be_bytes(envelope[5..9].try_into().expect("4 bytes"));
+    if log_n > MAX_LOG_N || r > MAX_R || p > MAX_P {
+        return Err(malformed());
+    }
+    let salt = &envelope[9..9 + SALT_LEN];
+    let nonce = &envelope[9 + SALT_LEN..HEADER_LEN];
+
+    let key = derive_key(password, salt, log_n, r, p)?;
+    let plaintext = Aes256Gcm::new(&key.into())
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: &envelope[HEADER_LEN..],
+                aad: ENCRYPTED_BACKUP_PREFIX.as_bytes(),
+            },
+        )
+        .map_err(|_| {
+            HeirError::new(
+                ErrorKind::EncryptedBackup,
+                "Wrong password, or the encrypted backup is damaged",
+            )
+        })?;
+    String::from_utf8(plaintext).map_err(|_| malformed())
+}
+
+/// Verify `vault_json` and encrypt it under `password`.
+pub fn encrypt_backup(vault_json: String, password: String) -> Result<String, HeirError> {
+    encrypt_backup_with_entropy(vault_json, password, EntropySource::Os)
+}
+
+/// [`encrypt_backup`] with the salt and nonce drawn from `entropy`.
+pub fn encrypt_backup_with_entropy(
+    vault_json: String,
+    password: String,
+    entropy: EntropySource,
+) -> Result<String, HeirError> {
+    let info = import_vault_backup(vault_json)?;
+    seal(&info.canonical_json, &password, LOG_N, &entropy)
+}
+
+/// Decrypt a `nostring:enc:v1:` backup and import it as
+/// [`import_vault_backup`] would.
+pub fn import_encrypted_backup(
+    ciphertext: String,
+    password: String,
+) -> Result<VaultInfo, HeirError> {
+    import_vault_backup(open(&ciphertext, &password)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::import::{sniff_backup_encoding, BackupEncoding};
+    use crate::api::vectors::generate_test_vectors;
+
+    /// Cheap enough for debug builds.
+    const TEST_LOG_N: u8 = 8;
+
+    #[test]
+    fn test_round_trip() {
+        let v = generate_test_vectors(51).unwrap();
+        let info = import_vault_backup(v.backup_json).unwrap();
+        let sealed = seal(
+            &info.canonical_json,
+            "correct horse",
+            TEST_LOG_N,
+            &EntropySource::Os,
+        )
+        .unwrap();
+        assert_eq!(
+            sniff_backup_encoding(sealed.as_bytes()).unwrap(),
+            BackupEncoding::Encrypted
+        );
+        assert!(!sealed.contains(&info.vault_address));
+
+        let opened = import_encrypted_backup(sealed, "correct horse".into()).unwrap();
+        assert_eq!(opened.content_hash, info.content_hash);
+    }
+
+    #[test]
+    fn test_wrong_password_and_tampering() {
+        let json = generate_test_vectors(52).unwrap().backup_json;
+        let sealed = seal(&json, "correct horse", TEST_LOG_N, &EntropySource::Os).unwrap();
+
+        let err = open(&sealed, "battery staple").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::EncryptedBackup);
+        assert_eq!(err.remediation, crate::api::Remediation::EnterPassword);
+
+        let mut bytes = base64::engine::general_purpose::STANDARD
+            .decode(sealed.strip_prefix(ENCRYPTED_BACKUP_PREFIX).unwrap())
+            .unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let tampered = format!(
+            "{}{}",
+            ENCRYPTED_BACKUP_PREFIX,
+            base64::engine::general_purpose::STANDARD.encode(&bytes)
+        );
+        assert_eq!(
+            open(&tampered, "correct horse").unwrap_err().kind,
+            ErrorKind::EncryptedBackup
+        );
+
+        bytes[0] = 40;
+        let costly = format!(
+            "{}{}",
+            ENCRYPTED_BACKUP_PREFIX,
+            base64::engine::general_purpose::STANDARD.encode(&bytes)
+        );
+        assert_eq!(
+            open(&costly, "correct horse").unwrap_err().kind,
+            ErrorKind::InvalidEncoding
+        );
+        assert_eq!(open(&sealed, "").unwrap_err().kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_seeded_entropy_gives_the_same_envelope() {
+        let json = generate_test_vectors(53).unwrap().backup_json;
+        let sealed = |seed| {
+            seal(
+                &json,
+                "correct horse",
+                TEST_LOG_N,
+                &EntropySource::Seeded { seed },
+            )
+            .unwrap()
+        };
+        assert_eq!(sealed(7), sealed(7));
+        assert_ne!(sealed(7), sealed(8));
+        assert_eq!(open(&sealed(7), "correct horse").unwrap(), json);
+    }
+}
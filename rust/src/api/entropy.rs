@@ -0,0 +1,35 @@
+//! Where the library's randomness comes from.
+//!
+//! Fresh keys for demo and rehearsal vaults, the BIP340 auxiliary
+//! randomness of in-crate signatures and the salts and nonces of encrypted
+//! backups are the only random values the library produces. Each function
+//! drawing them has a `_with_entropy` variant taking an [`EntropySource`],
+//! so integration tests and reproducibility checks can pin it to a seed;
+//! the plain function uses the OS generator. Claims are deterministic
+//! already: nLockTime is zero rather than a random anti-fee-sniping height,
+//! and inputs are sorted by outpoint.
+
+use bitcoin::secp256k1::rand::rngs::StdRng;
+use bitcoin::secp256k1::rand::{thread_rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Randomness for the functions that draw random values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntropySource {
+    /// The operating system's generator. Always use this outside tests.
+    #[default]
+    Os,
+    /// A generator seeded with `seed`: the same seed gives the same keys.
+    /// Anyone who knows the seed knows the keys, so only for signet demos
+    /// and tests.
+    Seeded { seed: u64 },
+}
+
+impl EntropySource {
+    pub(crate) fn rng(&self) -> Box<dyn RngCore> {
+        match self {
+            EntropySource::Os => Box::new(thread_rng()),
+            EntropySource::Seeded { seed } => Box::new(StdRng::seed_from_u64(*seed)),
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Structured errors returned across the FFI boundary.
+//!
+//! Every fallible API function returns [`HeirError`]. The `kind` tells the app
+//! what went wrong, `remediation` tells it which help screen to show, and
+//! `message` is the human-readable text for display. The app should never
+//! need to match on `message`.
+
+use serde::{Deserialize, Serialize};
+
+/// What went wrong.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorKind {
+    /// Input was not valid JSON or not a VaultBackup.
+    InvalidBackup,
+    /// A non-backup argument was malformed.
+    InvalidInput,
+    /// The backup parsed but the vault address could not be reproduced.
+    VerificationFailed,
+    /// Unknown network name.
+    InvalidNetwork,
+    /// Address could not be parsed.
+    InvalidAddress,
+    /// Address is valid but belongs to a different network.
+    NetworkMismatch,
+    /// Could not connect to the Electrum server.
+    Connection,
+    /// Connected, but a server query failed.
+    ServerQuery,
+    /// The server refused or truncated an address history as too large.
+    HistoryTooLarge,
+    /// The server reported a block height that is negative or beyond any
+    /// chain. `reported` saturates at the `i64` range.
+    HeightOutOfRange { reported: i64 },
+    /// The requested backend is not compiled into this build.
+    BackendUnavailable,
+    /// The vault has no spendable outputs.
+    NoUtxos,
+    /// Requested fee rate is above the safety limit.
+    FeeRateTooHigh,
+    /// After fees, the claim would pay out less than the configured floor.
+    ClaimBelowMinimum { net_sat: u64, minimum_sat: u64 },
+    /// `heir_index` does not name a recovery leaf of the vault. `max` is the
+    /// highest valid index.
+    HeirIndexOutOfRange { given: usize, max: usize },
+    /// The claim PSBT could not be built.
+    PsbtConstruction,
+    /// Input was not valid base64 or hex.
+    InvalidEncoding,
+    /// Bytes decoded but are not a valid PSBT.
+    InvalidPsbt,
+    /// Bytes decoded but are not a valid transaction.
+    InvalidTransaction,
+    /// No input of the PSBT carries a signature.
+    Unsigned { unsigned_inputs: usize },
+    /// Some but not all inputs carry signatures.
+    PartiallySigned { signed_inputs: usize, total_inputs: usize },
+    /// Fewer heirs have signed than the recovery leaf needs.
+    ThresholdNotMet { signatures: usize, threshold: usize },
+    /// All inputs appear signed but the transaction could not be extracted.
+    Finalization,
+    /// A signature or requested sighash is not SIGHASH_DEFAULT or SIGHASH_ALL.
+    UnsupportedSighash { input_index: usize },
+    /// The server rejected the transaction.
+    Broadcast { reason: BroadcastFailure },
+    /// Some inputs were already spent, e.g. by a co-heir's claim.
+    InputsAlreadySpent { outpoints: Vec<String> },
+    /// QR payload could not be compressed or decompressed.
+    Compression,
+    /// QR payload has an unknown prefix.
+    UnrecognizedFormat,
+    /// The backup is password-protected.
+    EncryptedBackup,
+    /// A read-only copy was given where the full backup is needed.
+    ReadOnlyVault,
+    /// The app cancelled the call before it finished.
+    Cancelled,
+    /// Unexpected internal failure.
+    Internal,
+}
+
+/// Why a server rejected a broadcast, classified from its error text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BroadcastFailure {
+    /// A timelock (CSV or nLockTime) has not matured yet.
+    NonFinal,
+    /// Fee rate below the node's relay or mempool minimum.
+    FeeTooLow,
+    /// An input is already spent in the mempool or a block.
+    Conflict,
+    /// Rejected by standardness or script checks.
+    NonStandard,
+    /// The node already has this exact transaction.
+    AlreadyInMempool,
+    /// Anything the classifier does not recognise.
+    Other,
+}
+
+impl BroadcastFailure {
+    /// Classify a node/Electrum rejection message.
+    pub(crate) fn classify(server_message: &str) -> Self {
+        let text = server_message.to_ascii_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|n| text.contains(n));
+
+        // Checked before script failures: an unmet CSV is reported as a
+        // script-verify failure with this reason
+        if has(&["non-bip68-final", "non-final", "locktime requirement not satisfied"]) {
+            BroadcastFailure::NonFinal
+        } else if has(&["txn-already-in-mempool", "txn-already-known", "already in block chain"]) {
+            BroadcastFailure::AlreadyInMempool
+        } else if has(&["txn-mempool-conflict", "missingorspent", "missing-inputs", "missing inputs"])
+        {
+            BroadcastFailure::Conflict
+        } else if has(&["min relay fee", "mempool min fee", "insufficient fee", "fee not met"]) {
+            BroadcastFailure::FeeTooLow
+        } else if has(&["non-standard", "nonstandard", "dust", "script-verify-flag", "tx-size"]) {
+            BroadcastFailure::NonStandard
+        } else {
+            BroadcastFailure::Other
+        }
+    }
+
+    /// Plain-language explanation for display.
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            BroadcastFailure::NonFinal => "The timelock has not expired yet",
+            BroadcastFailure::FeeTooLow => "The fee is too low for the network to accept",
+            BroadcastFailure::Conflict => "The vault funds were already spent by another transaction",
+            BroadcastFailure::NonStandard => "The network refused this transaction as invalid or non-standard",
+            BroadcastFailure::AlreadyInMempool => "This transaction was already broadcast",
+            BroadcastFailure::Other => "The server rejected the transaction",
+        }
+    }
+
+    fn remediation(self) -> Remediation {
+        match self {
+            BroadcastFailure::NonFinal => Remediation::WaitForTimelock,
+            BroadcastFailure::FeeTooLow => Remediation::IncreaseFee,
+            BroadcastFailure::Conflict => Remediation::RefreshVaultStatus,
+            BroadcastFailure::NonStandard
+            | BroadcastFailure::AlreadyInMempool
+            | BroadcastFailure::Other => Remediation::None,
+        }
+    }
+}
+
+/// What the user can do about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Remediation {
+    /// Nothing specific; show the message.
+    None,
+    /// Re-scan or re-import the backup from the owner.
+    CheckBackup,
+    /// Sign the PSBT with a wallet before importing it.
+    TrySigningFirst,
+    /// Finish collecting signatures for the remaining inputs or heirs.
+    CompleteSigning,
+    /// The selected network does not match the backup or address.
+    CheckNetworkSelection,
+    /// Check the connection or pick another Electrum server.
+    CheckConnection,
+    /// Double-check the destination address.
+    CheckAddress,
+    /// Rebuild the claim with a higher fee rate.
+    IncreaseFee,
+    /// Rebuild the claim with a lower fee rate.
+    LowerFee,
+    /// The timelock has not matured yet; try again later.
+    WaitForTimelock,
+    /// The vault must receive funds before anything can be claimed.
+    FundVault,
+    /// The vault changed on-chain; reload its status before retrying.
+    RefreshVaultStatus,
+    /// Ask the user for the backup password.
+    EnterPassword,
+    /// Import the full backup from the owner; a read-only copy cannot claim.
+    UseFullBackup,
+}
+
+impl ErrorKind {
+    /// Default remediation for this kind of error.
+    pub(crate) fn remediation(&self) -> Remediation {
+        match self {
+            ErrorKind::InvalidBackup | ErrorKind::VerificationFailed => Remediation::CheckBackup,
+            ErrorKind::InvalidNetwork | ErrorKind::NetworkMismatch => {
+                Remediation::CheckNetworkSelection
+            }
+            ErrorKind::InvalidAddress => Remediation::CheckAddress,
+            ErrorKind::Connection
+            | ErrorKind::ServerQuery
+            | ErrorKind::HistoryTooLarge
+            | ErrorKind::HeightOutOfRange { .. } => Remediation::CheckConnection,
+            ErrorKind::BackendUnavailable => Remediation::None,
+            ErrorKind::NoUtxos => Remediation::FundVault,
+            ErrorKind::InputsAlreadySpent { .. } => Remediation::RefreshVaultStatus,
+            ErrorKind::Broadcast { reason } => reason.remediation(),
+            ErrorKind::FeeRateTooHigh | ErrorKind::ClaimBelowMinimum { .. } => {
+                Remediation::LowerFee
+            }
+            ErrorKind::Unsigned { .. } => Remediation::TrySigningFirst,
+            ErrorKind::PartiallySigned { .. } | ErrorKind::ThresholdNotMet { .. } => {
+                Remediation::CompleteSigning
+            }
+            ErrorKind::Compression | ErrorKind::UnrecognizedFormat => Remediation::CheckBackup,
+            ErrorKind::EncryptedBackup => Remediation::EnterPassword,
+            ErrorKind::ReadOnlyVault => Remediation::UseFullBackup,
+            ErrorKind::InvalidInput
+            | ErrorKind::HeirIndexOutOfRange { .. }
+            | ErrorKind::PsbtConstruction
+            | ErrorKind::InvalidEncoding
+            | ErrorKind::InvalidPsbt
+            | ErrorKind::InvalidTransaction
+            | ErrorKind::Finalization
+            | ErrorKind::UnsupportedSighash { .. }
+            | ErrorKind::Cancelled
+            | ErrorKind::Internal => Remediation::None,
+        }
+    }
+}
+
+/// Error returned by every fallible FFI function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeirError {
+    pub kind: ErrorKind,
+    pub remediation: Remediation,
+    pub message: String,
+}
+
+impl HeirError {
+    /// Create an error with the kind's default remediation.
+    pub(crate) fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        let remediation = kind.remediation();
+        Self {
+            kind,
+            remediation,
+            message: message.into(),
+        }
+    }
+
+    /// Classified broadcast rejection; keeps the server text for experts.
+    pub(crate) fn broadcast_rejected(server_message: impl std::fmt::Display) -> Self {
+        let raw = server_message.to_string();
+        let reason = BroadcastFailure::classify(&raw);
+        Self::new(
+            ErrorKind::Broadcast { reason },
+            format!("{} (server said: {})", reason.description(), raw),
+        )
+    }
+
+    /// Override the default remediation.
+    pub(crate) fn with_remediation(mut self, remediation: Remediation) -> Self {
+        self.remediation = remediation;
+        self
+    }
+}
+
+impl std::fmt::Display for HeirError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for HeirError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_remediation() {
+        let err = HeirError::new(ErrorKind::Unsigned { unsigned_inputs: 2 }, "unsigned");
+        assert_eq!(err.remediation, Remediation::TrySigningFirst);
+        assert_eq!(err.to_string(), "unsigned");
+    }
+
+    #[test]
+    fn test_override_remediation() {
+        let other = ErrorKind::Broadcast {
+            reason: BroadcastFailure::Other,
+        };
+        let err = HeirError::new(other.clone(), "non-final")
+            .with_remediation(Remediation::WaitForTimelock);
+        assert_eq!(err.kind, other);
+        assert_eq!(err.remediation, Remediation::WaitForTimelock);
+    }
+
+    #[test]
+    fn test_classify_broadcast_failures() {
+        let cases = [
+            ("non-BIP68-final (code 64)", BroadcastFailure::NonFinal),
+            (
+                "mandatory-script-verify-flag-failed (Locktime requirement not satisfied)",
+                BroadcastFailure::NonFinal,
+            ),
+            ("min relay fee not met, 110 < 141", BroadcastFailure::FeeTooLow),
+            ("txn-mempool-conflict (code 18)", BroadcastFailure::Conflict),
+            ("bad-txns-inputs-missingorspent", BroadcastFailure::Conflict),
+            ("dust (code 64)", BroadcastFailure::NonStandard),
+            ("txn-already-in-mempool", BroadcastFailure::AlreadyInMempool),
+            ("something new", BroadcastFailure::Other),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(BroadcastFailure::classify(message), expected, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_broadcast_rejected_message() {
+        let err = HeirError::broadcast_rejected("min relay fee not met");
+        assert_eq!(err.remediation, Remediation::IncreaseFee);
+        assert!(err.message.starts_with("The fee is too low"));
+        assert!(err.message.contains("min relay fee not met"));
+    }
+}
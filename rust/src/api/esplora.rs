@@ -0,0 +1,365 @@
+//! Esplora REST backend (Blockstream, mempool.space, self-hosted).
+//!
+//! For heirs on networks that block Electrum ports, or who would rather use
+//! a public block explorer than find an Electrum server. Everything goes
+//! over plain HTTPS requests, one per call; there is no batching, so large
+//! vault scans are slower than over Electrum.
+//!
+//! Only compiled with the `esplora` feature (on by default).
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use bitcoin::consensus::encode::{deserialize_hex, serialize_hex};
+use bitcoin::{Address, Amount, Network, OutPoint, ScriptBuf, Transaction, Txid};
+use serde::Deserialize;
+
+use super::backend::{block_height, ChainBackend, ChainHistoryEntry, ChainUtxo};
+use super::{ErrorKind, HeirError};
+use crate::trace::span;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Confirmed transactions per `/txs/chain` page, fixed by Esplora.
+const CHAIN_PAGE_SIZE: usize = 25;
+/// Pages fetched before giving up on an address history as too large.
+const MAX_HISTORY_PAGES: usize = 200;
+
+#[derive(Debug, Deserialize)]
+struct TxStatus {
+    confirmed: bool,
+    block_height: Option<i64>,
+}
+
+impl TxStatus {
+    fn height(&self) -> Result<u32, HeirError> {
+        match (self.confirmed, self.block_height) {
+            (true, Some(height)) => block_height(height),
+            _ => Ok(0),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UtxoRes {
+    txid: Txid,
+    vout: u32,
+    value: u64,
+    status: TxStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct TxRes {
+    txid: Txid,
+    status: TxStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockRes {
+    timestamp: u64,
+}
+
+fn query_error(what: &str, e: impl std::fmt::Display) -> HeirError {
+    HeirError::new(ErrorKind::ServerQuery, format!("Failed to {}: {}", what, e))
+}
+
+/// Map a failed request: no answer is a connection problem, an HTTP error
+/// status a failed query.
+fn request_error(what: &str, e: ureq::Error) -> HeirError {
+    match e {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            query_error(what, format!("HTTP {} {}", code, body.trim()))
+        }
+        ureq::Error::Transport(e) => HeirError::new(
+            ErrorKind::Connection,
+            format!("Esplora request failed: {}", e),
+        ),
+    }
+}
+
+fn chain_utxos(
+    utxos: Vec<UtxoRes>,
+    script_pubkey: &ScriptBuf,
+) -> Result<Vec<ChainUtxo>, HeirError> {
+    utxos
+        .into_iter()
+        .map(|u| {
+            Ok(ChainUtxo {
+                outpoint: OutPoint::new(u.txid, u.vout),
+                value: Amount::from_sat(u.value),
+                script_pubkey: script_pubkey.clone(),
+                height: u.status.height()?,
+            })
+        })
+        .collect()
+}
+
+/// Esplora lists newest first with the mempool on top; backends report
+/// oldest first with the mempool last.
+fn history_entries(txs: Vec<TxRes>) -> Result<Vec<ChainHistoryEntry>, HeirError> {
+    let mut entries = txs
+        .into_iter()
+        .rev()
+        .map(|tx| {
+            Ok(ChainHistoryEntry {
+                txid: tx.txid,
+                height: tx.status.height()?,
+            })
+        })
+        .collect::<Result<Vec<_>, HeirError>>()?;
+    entries.sort_by_key(|e| if e.height == 0 { u32::MAX } else { e.height });
+    Ok(entries)
+}
+
+/// Rate for `target_blocks` from `/fee-estimates`, falling back to the
+/// closest faster target, then to the slowest one given.
+fn pick_fee_rate(estimates: &BTreeMap<u16, f64>, target_blocks: u16) -> Option<f64> {
+    estimates
+        .range(..=target_blocks)
+        .next_back()
+        .or_else(|| estimates.iter().next())
+        .map(|(_, rate)| *rate)
+        .filter(|rate| *rate > 0.0)
+}
+
+/// Esplora backend rooted at e.g. `https://blockstream.info/api`.
+pub(crate) struct EsploraBackend {
+    agent: ureq::Agent,
+    base_url: String,
+    network: Network,
+}
+
+impl EsploraBackend {
+    pub(crate) fn new(base_url: String, network: Network) -> Result<Self, HeirError> {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        if !(base_url.starts_with("https://") || base_url.starts_with("http://")) {
+            return Err(HeirError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Esplora URL must start with https:// or http://: {}",
+                    base_url
+                ),
+            ));
+        }
+        Ok(Self {
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+            base_url,
+            network,
+        })
+    }
+
+    fn get_text(&self, path: &str, what: &str) -> Result<String, HeirError> {
+        self.agent
+            .get(&format!("{}{}", self.base_url, path))
+            .call()
+            .map_err(|e| request_error(what, e))?
+            .into_string()
+            .map_err(|e| query_error(what, e))
+    }
+
+    fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        what: &str,
+    ) -> Result<T, HeirError> {
+        serde_json::from_str(&self.get_text(path, what)?).map_err(|e| query_error(what, e))
+    }
+}
+
+impl ChainBackend for EsploraBackend {
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn tip_height(&self) -> Result<u64, HeirError> {
+        span!("esplora.get_height");
+        let text = self.get_text("/blocks/tip/height", "get block height")?;
+        let height: i64 = text
+            .trim()
+            .parse()
+            .map_err(|_| query_error("get block height", format!("unexpected reply {:?}", text)))?;
+        block_height(height).map(u64::from)
+    }
+
+    fn list_unspent(&self, address: &Address) -> Result<Vec<ChainUtxo>, HeirError> {
+        span!("esplora.get_utxos");
+        let utxos = self.get_json(&format!("/address/{}/utxo", address), "fetch UTXOs")?;
+        chain_utxos(utxos, &address.script_pubkey())
+    }
+
+    fn history(&self, address: &Address) -> Result<Vec<ChainHistoryEntry>, HeirError> {
+        span!("esplora.get_history");
+        // The first page holds the mempool and the newest confirmed page
+        let mut txs: Vec<TxRes> =
+            self.get_json(&format!("/address/{}/txs", address), "fetch history")?;
+        let mut last_page = txs.iter().filter(|tx| tx.status.confirmed).count();
+        let mut pages = 1;
+        while last_page == CHAIN_PAGE_SIZE {
+            if pages == MAX_HISTORY_PAGES {
+                return Err(HeirError::new(
+                    ErrorKind::HistoryTooLarge,
+                    format!(
+                        "Address history is longer than {} transactions",
+                        MAX_HISTORY_PAGES * CHAIN_PAGE_SIZE
+                    ),
+                ));
+            }
+            let last_seen = txs.last().expect("a full page was read").txid;
+            let page: Vec<TxRes> = self.get_json(
+                &format!("/address/{}/txs/chain/{}", address, last_seen),
+                "fetch history",
+            )?;
+            last_page = page.len();
+            pages += 1;
+            txs.extend(page);
+        }
+        history_entries(txs)
+    }
+
+    fn block_time(&self, height: u32) -> Result<u64, HeirError> {
+        span!("esplora.get_header");
+        let what = format!("fetch block header {}", height);
+        let hash = self.get_text(&format!("/block-height/{}", height), &what)?;
+        let block: BlockRes = self.get_json(&format!("/block/{}", hash.trim()), &what)?;
+        Ok(block.timestamp)
+    }
+
+    fn transaction(&self, txid: &Txid) -> Result<Transaction, HeirError> {
+        span!("esplora.get_transaction");
+        let what = format!("fetch transaction {}", txid);
+        let hex = self.get_text(&format!("/tx/{}/hex", txid), &what)?;
+        let tx: Transaction = deserialize_hex(hex.trim()).map_err(|e| query_error(&what, e))?;
+        if tx.compute_txid() != *txid {
+            return Err(query_error(
+                &what,
+                "server returned a different transaction",
+            ));
+        }
+        Ok(tx)
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, HeirError> {
+        span!("esplora.broadcast");
+        let response = self
+            .agent
+            .post(&format!("{}/tx", self.base_url))
+            .send_string(&serialize_hex(tx))
+            .map_err(|e| match e {
+                // The node answered: classify its rejection
+                ureq::Error::Status(_, response) => {
+                    HeirError::broadcast_rejected(response.into_string().unwrap_or_default().trim())
+                }
+                ureq::Error::Transport(e) => {
+                    HeirError::new(ErrorKind::Connection, format!("Broadcast failed: {}", e))
+                }
+            })?;
+        let text = response
+            .into_string()
+            .map_err(|e| query_error("read broadcast reply", e))?;
+        Txid::from_str(text.trim()).map_err(|_| {
+            query_error(
+                "read broadcast reply",
+                format!("unexpected reply {:?}", text),
+            )
+        })
+    }
+
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<f64, HeirError> {
+        span!("esplora.estimate_fee");
+        let estimates: BTreeMap<String, f64> = self.get_json("/fee-estimates", "estimate fee")?;
+        let estimates: BTreeMap<u16, f64> = estimates
+            .into_iter()
+            .filter_map(|(target, rate)| Some((target.parse().ok()?, rate)))
+            .collect();
+        pick_fee_rate(&estimates, target_blocks).ok_or_else(|| {
+            HeirError::new(
+                ErrorKind::ServerQuery,
+                format!("No fee estimate available for {} blocks", target_blocks),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TXID_A: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+    const TXID_B: &str = "2222222222222222222222222222222222222222222222222222222222222222";
+    const TXID_C: &str = "3333333333333333333333333333333333333333333333333333333333333333";
+
+    #[test]
+    fn test_history_is_oldest_first_with_mempool_last() {
+        let txs: Vec<TxRes> = serde_json::from_value(serde_json::json!([
+            { "txid": TXID_C, "status": { "confirmed": false } },
+            { "txid": TXID_B, "status": { "confirmed": true, "block_height": 900 } },
+            { "txid": TXID_A, "status": { "confirmed": true, "block_height": 850 } },
+        ]))
+        .unwrap();
+        let entries = history_entries(txs).unwrap();
+        let order: Vec<(String, u32)> = entries
+            .iter()
+            .map(|e| (e.txid.to_string(), e.height))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (TXID_A.to_string(), 850),
+                (TXID_B.to_string(), 900),
+                (TXID_C.to_string(), 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_utxos_reject_impossible_heights() {
+        let script = ScriptBuf::new();
+        let utxos: Vec<UtxoRes> = serde_json::from_value(serde_json::json!([
+            { "txid": TXID_A, "vout": 1, "value": 50_000,
+              "status": { "confirmed": true, "block_height": 800 } },
+            { "txid": TXID_B, "vout": 0, "value": 1_000, "status": { "confirmed": false } },
+        ]))
+        .unwrap();
+        let utxos = chain_utxos(utxos, &script).unwrap();
+        assert_eq!(utxos[0].height, 800);
+        assert_eq!(utxos[0].value, Amount::from_sat(50_000));
+        assert_eq!(utxos[1].height, 0);
+
+        let broken: Vec<UtxoRes> = serde_json::from_value(serde_json::json!([
+            { "txid": TXID_A, "vout": 0, "value": 1,
+              "status": { "confirmed": true, "block_height": -5 } },
+        ]))
+        .unwrap();
+        assert_eq!(
+            chain_utxos(broken, &script).unwrap_err().kind,
+            ErrorKind::HeightOutOfRange { reported: -5 }
+        );
+    }
+
+    #[test]
+    fn test_pick_fee_rate() {
+        let estimates: BTreeMap<u16, f64> = [(1, 40.0), (3, 20.0), (6, 12.5), (144, 1.0)].into();
+        assert_eq!(pick_fee_rate(&estimates, 6), Some(12.5));
+        // No estimate for 10: use the faster 6-block rate
+        assert_eq!(pick_fee_rate(&estimates, 10), Some(12.5));
+        let sparse: BTreeMap<u16, f64> = [(2, 30.0)].into();
+        assert_eq!(pick_fee_rate(&sparse, 1), Some(30.0));
+        assert_eq!(pick_fee_rate(&BTreeMap::new(), 6), None);
+    }
+
+    #[test]
+    fn test_url_must_be_http() {
+        assert!(
+            EsploraBackend::new("https://blockstream.info/api/".into(), Network::Bitcoin)
+                .is_ok_and(|b| b.base_url == "https://blockstream.info/api")
+        );
+        assert_eq!(
+            EsploraBackend::new("ssl://electrum.example:50002".into(), Network::Bitcoin)
+                .err()
+                .unwrap()
+                .kind,
+            ErrorKind::InvalidInput
+        );
+    }
+}
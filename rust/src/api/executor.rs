@@ -0,0 +1,397 @@
+//! Emergency override: a designated executor together with any one heir.
+//!
+//! A backup may name an `executor` whose `timelock_blocks` is shorter than
+//! the heirs'. The vault then carries one extra leaf,
+//! `and_v(v:pk(executor), and_v(v:multi_a(1, heirs), older(t)))`, so the
+//! executor and any single heir can act before the heirs alone could.
+//! Neither the field nor the leaf is known upstream, so these vaults are
+//! rebuilt here from the backup's leaves and control blocks.
+
+use std::str::FromStr;
+
+use bitcoin::bip32::{DerivationPath, Fingerprint, KeySource, Xpub};
+use bitcoin::opcodes::all::{
+    OP_CHECKSIG, OP_CHECKSIGADD, OP_CHECKSIGVERIFY, OP_CSV, OP_NUMEQUALVERIFY,
+};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{Secp256k1, XOnlyPublicKey};
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::{Address, Psbt, ScriptBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use nostring_inherit::backup::VaultBackup;
+
+use super::weighted::{heir_keys, internal_key};
+use super::{
+    compute_eligibility, parse_backup, parse_network, timelock, weighted, ErrorKind, HeirError,
+};
+use crate::trace::span;
+
+const FIELD: &str = "executor";
+
+/// The override path and whether it is open yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutorOverride {
+    /// Recovery leaf to pass as `heir_index` when building the claim.
+    pub heir_index: usize,
+    pub executor_label: String,
+    /// Blocks since the last owner activity before the override applies.
+    pub after_blocks: u32,
+    pub after_days: f64,
+    pub eligible: bool,
+    pub blocks_remaining: i64,
+}
+
+/// Executor recorded in a backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Executor {
+    pub label: String,
+    pub xpub: Xpub,
+    pub source: KeySource,
+    pub timelock_blocks: u16,
+}
+
+impl Executor {
+    pub(crate) fn key(&self) -> XOnlyPublicKey {
+        self.xpub.public_key.x_only_public_key().0
+    }
+}
+
+/// A vault with an override leaf, rebuilt from its backup.
+pub(crate) struct ExecutorVault {
+    pub address: Address,
+    pub spend_info: TaprootSpendInfo,
+    pub recovery_scripts: Vec<ScriptBuf>,
+    /// Position of the override leaf in `recovery_scripts`.
+    pub override_index: usize,
+}
+
+fn invalid(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidBackup, message)
+}
+
+/// The backup's executor, or `None` if it names none.
+pub(crate) fn load_executor(vault_json: &str) -> Result<Option<Executor>, HeirError> {
+    let value: Value =
+        serde_json::from_str(vault_json).map_err(|_| invalid("Backup is not valid JSON"))?;
+    let entry = match value.get(FIELD) {
+        None | Some(Value::Null) => return Ok(None),
+        Some(entry) => entry,
+    };
+    let text = |name: &str| {
+        entry
+            .get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid(format!("Executor has no {}", name)))
+    };
+
+    let label = text("label")?.to_string();
+    let xpub = Xpub::from_str(text("xpub")?)
+        .map_err(|e| invalid(format!("Invalid executor xpub: {}", e)))?;
+    let fingerprint = Fingerprint::from_str(text("fingerprint")?)
+        .map_err(|e| invalid(format!("Invalid executor fingerprint: {}", e)))?;
+    let path = DerivationPath::from_str(text("derivation_path")?)
+        .map_err(|e| invalid(format!("Invalid executor derivation path: {}", e)))?;
+    let timelock_blocks = entry
+        .get("timelock_blocks")
+        .and_then(Value::as_u64)
+        .and_then(|t| u16::try_from(t).ok())
+        .filter(|&t| t > 0)
+        .ok_or_else(|| invalid("Invalid executor timelock_blocks"))?;
+
+    if weighted::heir_weights(vault_json)?.is_some() {
+        return Err(invalid(
+            "An executor cannot be combined with weighted heir thresholds",
+        ));
+    }
+
+    Ok(Some(Executor {
+        label,
+        xpub,
+        source: (fingerprint, path),
+        timelock_blocks,
+    }))
+}
+
+/// Copy a validated executor into a re-serialized backup.
+pub(crate) fn add_executor(backup: &mut Value, executor: &Executor) {
+    backup[FIELD] = serde_json::json!({
+        "label": executor.label,
+        "xpub": executor.xpub.to_string(),
+        "fingerprint": executor.source.0.to_string(),
+        "derivation_path": executor.source.1.to_string(),
+        "timelock_blocks": executor.timelock_blocks,
+    });
+}
+
+/// `and_v(v:pk(executor), and_v(v:multi_a(1, heirs), older(csv_blocks)))`,
+/// with `v:pk` for a single heir.
+pub(crate) fn override_leaf_script(
+    executor: &XOnlyPublicKey,
+    heirs: &[XOnlyPublicKey],
+    csv_blocks: u16,
+) -> ScriptBuf {
+    let builder = Builder::new()
+        .push_x_only_key(executor)
+        .push_opcode(OP_CHECKSIGVERIFY);
+    let builder = match heirs {
+        [heir] => builder.push_x_only_key(heir).push_opcode(OP_CHECKSIGVERIFY),
+        _ => heirs
+            .iter()
+            .enumerate()
+            .fold(builder, |builder, (i, key)| {
+                let op = if i == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD };
+                builder.push_x_only_key(key).push_opcode(op)
+            })
+            .push_int(1)
+            .push_opcode(OP_NUMEQUALVERIFY),
+    };
+    builder
+        .push_int(i64::from(csv_blocks))
+        .push_opcode(OP_CSV)
+        .into_script()
+}
+
+/// The override leaf for `backup`.
+fn expected_override(backup: &VaultBackup, executor: &Executor) -> Result<ScriptBuf, HeirError> {
+    if executor.timelock_blocks >= backup.timelock_blocks {
+        return Err(invalid(format!(
+            "Executor timelock ({} blocks) must be shorter than the heirs' ({} blocks)",
+            executor.timelock_blocks, backup.timelock_blocks
+        )));
+    }
+    Ok(override_leaf_script(
+        &executor.key(),
+        &heir_keys(backup)?,
+        executor.timelock_blocks,
+    ))
+}
+
+/// The backup's leaf scripts, added to a tree builder at the depths their
+/// control blocks imply.
+pub(crate) fn tree_from_leaves(
+    backup: &VaultBackup,
+) -> Result<(TaprootBuilder, Vec<ScriptBuf>), HeirError> {
+    let mut builder = TaprootBuilder::new();
+    let mut recovery_scripts = Vec::with_capacity(backup.recovery_leaves.len());
+    for (index, leaf) in backup.recovery_leaves.iter().enumerate() {
+        let script = ScriptBuf::from_hex(&leaf.script_hex)
+            .map_err(|e| invalid(format!("Invalid script in leaf {}: {}", index, e)))?;
+        let control_len = leaf.control_block_hex.len() / 2;
+        if control_len < 33 || !(control_len - 33).is_multiple_of(32) {
+            return Err(invalid(format!("Invalid control block in leaf {}", index)));
+        }
+        let depth = u8::try_from((control_len - 33) / 32)
+            .map_err(|_| invalid(format!("Leaf {} is too deep", index)))?;
+        builder = builder
+            .add_leaf(depth, script.clone())
+            .map_err(|e| invalid(format!("Leaf {} does not fit the tree: {}", index, e)))?;
+        recovery_scripts.push(script);
+    }
+    Ok((builder, recovery_scripts))
+}
+
+/// Rebuild the tree from the backup's leaves, each at the depth its control
+/// block implies, and check it against the backup's address.
+pub(crate) fn verify_executor_vault(
+    backup: &VaultBackup,
+    executor: &Executor,
+) -> Result<ExecutorVault, HeirError> {
+    span!("vault.reconstruct");
+    let network = parse_network(&backup.network)?;
+    let override_script = expected_override(backup, executor)?;
+    let (builder, recovery_scripts) = tree_from_leaves(backup)?;
+
+    let mut overrides = recovery_scripts
+        .iter()
+        .enumerate()
+        .filter(|(_, script)| **script == override_script)
+        .map(|(index, _)| index);
+    let override_index = match (overrides.next(), overrides.next()) {
+        (Some(index), None) => index,
+        _ => {
+            return Err(HeirError::new(
+                ErrorKind::VerificationFailed,
+                "Vault verification failed: the backup must carry exactly one executor leaf",
+            ))
+        }
+    };
+
+    let secp = Secp256k1::verification_only();
+    let spend_info = builder
+        .finalize(&secp, internal_key(backup)?)
+        .map_err(|_| invalid("Recovery leaves do not form a complete tree"))?;
+    let address = Address::p2tr_tweaked(spend_info.output_key(), network);
+    if address.to_string() != backup.vault_address {
+        return Err(HeirError::new(
+            ErrorKind::VerificationFailed,
+            format!(
+                "Vault verification failed: leaves derive {}, not the backup's address",
+                address
+            ),
+        ));
+    }
+
+    Ok(ExecutorVault {
+        address,
+        spend_info,
+        recovery_scripts,
+        override_index,
+    })
+}
+
+/// Record the executor's key origin for the override leaf on every input,
+/// so the executor's signer finds its key next to the heirs'.
+pub(crate) fn add_executor_origin(
+    psbt: &mut Psbt,
+    executor: &Executor,
+    override_script: &ScriptBuf,
+) {
+    let leaf_hash = TapLeafHash::from_script(override_script, LeafVersion::TapScript);
+    for input in &mut psbt.inputs {
+        input
+            .tap_key_origins
+            .insert(executor.key(), (vec![leaf_hash], executor.source.clone()));
+    }
+    psbt.xpub.insert(executor.xpub, executor.source.clone());
+}
+
+/// The override leaf of a backup with an executor, and whether an output
+/// confirmed at `confirmation_height` can be spent through it yet.
+pub fn executor_override(
+    vault_json: String,
+    current_height: u64,
+    confirmation_height: u64,
+) -> Result<ExecutorOverride, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let network = parse_network(&backup.network)?;
+    let executor = load_executor(&vault_json)?
+        .ok_or_else(|| HeirError::new(ErrorKind::InvalidInput, "This backup names no executor"))?;
+    let vault = verify_executor_vault(&backup, &executor)?;
+
+    let after_blocks = u32::from(executor.timelock_blocks);
+    let eligibility =
+        compute_eligibility(after_blocks, current_height, confirmation_height, network);
+    Ok(ExecutorOverride {
+        heir_index: vault.override_index,
+        executor_label: executor.label,
+        after_blocks,
+        after_days: timelock::blocks_to_days(i64::from(after_blocks), network),
+        eligible: eligibility.eligible,
+        blocks_remaining: eligibility.blocks_remaining,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::api::policy::{analyze_leaf_script, describe_policy, ClauseKind};
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::{build_claim_psbt, decode_psbt, import_vault_backup, Backend, Network};
+    use bitcoin::Sequence;
+
+    /// Vector backup with an executor whose override opens after 72 blocks,
+    /// next to the heir's 144-block leaf.
+    pub(crate) fn executor_backup_json(seed: u32) -> String {
+        let v = generate_test_vectors(seed).unwrap();
+        let mut value: Value = serde_json::from_str(&v.backup_json).unwrap();
+        let backup: VaultBackup = serde_json::from_value(value.clone()).unwrap();
+
+        let secp = Secp256k1::new();
+        let sk = bitcoin::secp256k1::SecretKey::from_slice(&[0x31; 32]).unwrap();
+        let mut xpub = Xpub::from_str(&backup.heirs[0].xpub).unwrap();
+        xpub.public_key = sk.public_key(&secp);
+        value[FIELD] = serde_json::json!({
+            "label": "Executor",
+            "xpub": xpub.to_string(),
+            "fingerprint": "0a0b0c0d",
+            "derivation_path": "m/86'/1'/0'",
+            "timelock_blocks": 72,
+        });
+        let executor = load_executor(&value.to_string()).unwrap().unwrap();
+
+        let heir_leaf = ScriptBuf::from_hex(&backup.recovery_leaves[0].script_hex).unwrap();
+        let override_leaf = expected_override(&backup, &executor).unwrap();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, heir_leaf.clone())
+            .unwrap()
+            .add_leaf(1, override_leaf.clone())
+            .unwrap()
+            .finalize(&secp, internal_key(&backup).unwrap())
+            .unwrap();
+        value["vault_address"] = serde_json::json!(Address::p2tr_tweaked(
+            spend_info.output_key(),
+            bitcoin::Network::Testnet
+        )
+        .to_string());
+        let mut template = value["recovery_leaves"][0].clone();
+        value["recovery_leaves"] = [heir_leaf, override_leaf]
+            .iter()
+            .enumerate()
+            .map(|(index, script)| {
+                let control = spend_info
+                    .control_block(&(script.clone(), LeafVersion::TapScript))
+                    .unwrap();
+                template["leaf_index"] = serde_json::json!(index);
+                template["script_hex"] = serde_json::json!(script.to_hex_string());
+                template["control_block_hex"] = serde_json::json!(hex::encode(control.serialize()));
+                template.clone()
+            })
+            .collect();
+        value.to_string()
+    }
+
+    #[test]
+    fn test_override_leaf_needs_two_signatures() {
+        let v = generate_test_vectors(23).unwrap();
+        let backup: VaultBackup = serde_json::from_str(&v.backup_json).unwrap();
+        let heirs = heir_keys(&backup).unwrap();
+        let executor = heirs[0];
+        let analysis = analyze_leaf_script(&override_leaf_script(&executor, &heirs, 72));
+        assert_eq!(analysis.threshold, 2);
+        assert_eq!(analysis.csv_blocks, Some(72));
+    }
+
+    #[test]
+    fn test_executor_backup_imports_and_reports_override() {
+        let json = executor_backup_json(24);
+        let info = import_vault_backup(json.clone()).unwrap();
+        assert!(info.canonical_json.contains("\"executor\":{"));
+
+        let path = executor_override(json.clone(), 1_100, 1_000).unwrap();
+        assert_eq!(path.heir_index, 1);
+        assert!(path.eligible);
+        assert_eq!(path.blocks_remaining, -28);
+
+        let policy = describe_policy(json).unwrap();
+        assert_eq!(policy.clauses[2].kind, ClauseKind::ExecutorOverride);
+        assert_eq!(policy.clauses[2].required_signatures, 2);
+    }
+
+    #[test]
+    fn test_override_claim_carries_executor_origin() {
+        let json = executor_backup_json(25);
+        let v = generate_test_vectors(25).unwrap();
+        let address = serde_json::from_str::<Value>(&json).unwrap()["vault_address"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(1_100);
+        sim.add_utxo(address, "c5".repeat(32), 0, 60_000, 1_000)
+            .unwrap();
+
+        let claim = build_claim_psbt(json, &Backend::simulated(&sim), v.destination, 1, 2).unwrap();
+        let psbt = decode_psbt(&claim.psbt_base64).unwrap();
+        let input = &psbt.inputs[0];
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            Sequence::from_height(72)
+        );
+        assert_eq!(input.tap_scripts.len(), 2);
+        assert_eq!(input.tap_key_origins.len(), 2);
+        assert_eq!(psbt.xpub.len(), 2);
+    }
+}
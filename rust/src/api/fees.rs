@@ -0,0 +1,187 @@
+//! Fee rates by name instead of by number.
+//!
+//! Heirs should not have to know what a sat/vB is. [`estimate_fee_rates`]
+//! asks the server what each [`FeePreset`] costs right now, and
+//! [`build_claim_psbt_at_preset`] builds a claim at one of them.
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::ChainBackend;
+use super::{build_claim_psbt_with_options, Backend, ClaimOptions, ClaimPsbt, HeirError, Network};
+
+/// Confirmation targets of the presets, in blocks.
+pub(crate) const FAST_TARGET_BLOCKS: u16 = 2;
+const MEDIUM_TARGET_BLOCKS: u16 = 6;
+pub(crate) const SLOW_TARGET_BLOCKS: u16 = 144;
+
+/// How soon the claim should confirm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeePreset {
+    /// Within about 20 minutes (2 blocks).
+    Fast,
+    /// Within about an hour (6 blocks).
+    Medium,
+    /// Within about a day (144 blocks).
+    Slow,
+    /// The lowest rate the server's node relays; may take days.
+    Minimum,
+}
+
+/// Fee rate of every preset, in whole sat/vB as claims are built with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeEstimates {
+    pub fast: u64,
+    pub medium: u64,
+    pub slow: u64,
+    pub minimum: u64,
+}
+
+impl FeeEstimates {
+    pub fn rate(&self, preset: FeePreset) -> u64 {
+        match preset {
+            FeePreset::Fast => self.fast,
+            FeePreset::Medium => self.medium,
+            FeePreset::Slow => self.slow,
+            FeePreset::Minimum => self.minimum,
+        }
+    }
+}
+
+/// Round a sat/vB rate up to a whole rate of at least 1.
+fn whole_rate(rate: f64) -> u64 {
+    (rate.ceil() as u64).max(1)
+}
+
+/// Rates for every preset from `chain`. Servers without a long-horizon
+/// estimate fall back to the next faster one, and a slower preset never
+/// costs more than a faster one.
+pub(crate) fn fee_estimates(chain: &dyn ChainBackend) -> Result<FeeEstimates, HeirError> {
+    let minimum = whole_rate(chain.relay_fee_rate()?);
+    let fast = whole_rate(chain.estimate_fee_rate(FAST_TARGET_BLOCKS)?).max(minimum);
+    let estimate = |target_blocks, faster: u64| {
+        chain
+            .estimate_fee_rate(target_blocks)
+            .map_or(faster, |rate| whole_rate(rate).clamp(minimum, faster))
+    };
+    let medium = estimate(MEDIUM_TARGET_BLOCKS, fast);
+    let slow = estimate(SLOW_TARGET_BLOCKS, medium);
+    Ok(FeeEstimates {
+        fast,
+        medium,
+        slow,
+        minimum,
+    })
+}
+
+/// Fee rate of `preset` on `backend` right now.
+pub(crate) fn preset_fee_rate(backend: &Backend, preset: FeePreset) -> Result<u64, HeirError> {
+    let chain = backend.chain();
+    match preset {
+        FeePreset::Minimum => Ok(whole_rate(chain.relay_fee_rate()?)),
+        _ => fee_estimates(chain).map(|estimates| estimates.rate(preset)),
+    }
+}
+
+/// Current rate of every [`FeePreset`] from the Electrum server at
+/// `electrum_url`.
+pub fn estimate_fee_rates(
+    electrum_url: String,
+    network: Network,
+) -> Result<FeeEstimates, HeirError> {
+    let backend = Backend::electrum(electrum_url, network)?;
+    fee_estimates(backend.chain())
+}
+
+/// [`estimate_fee_rates`] from an existing backend.
+pub fn fetch_fee_estimates(backend: &Backend) -> Result<FeeEstimates, HeirError> {
+    fee_estimates(backend.chain())
+}
+
+/// [`super::build_claim_psbt_with_options`] at the current rate of
+/// `preset` instead of a number.
+///
+/// A separate function rather than a preset argument of
+/// [`super::build_claim_psbt`], so apps already passing a rate keep the
+/// same signature and the rate a claim was built at stays explicit.
+pub fn build_claim_psbt_at_preset(
+    vault_json: String,
+    backend: &Backend,
+    destination_address: String,
+    heir_index: usize,
+    preset: FeePreset,
+    options: ClaimOptions,
+) -> Result<ClaimPsbt, HeirError> {
+    let fee_rate_sat_vb = preset_fee_rate(backend, preset)?;
+    build_claim_psbt_with_options(
+        vault_json,
+        backend,
+        destination_address,
+        heir_index,
+        fee_rate_sat_vb,
+        options,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+
+    #[test]
+    fn test_presets_are_whole_and_ordered() {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_fee_rate(12.2);
+        let estimates = fetch_fee_estimates(&Backend::simulated(&sim)).unwrap();
+        assert_eq!(
+            estimates,
+            FeeEstimates {
+                fast: 13,
+                medium: 13,
+                slow: 13,
+                minimum: 1
+            }
+        );
+
+        sim.set_fee_rate(0.2);
+        let estimates = fetch_fee_estimates(&Backend::simulated(&sim)).unwrap();
+        assert_eq!(estimates.fast, 1);
+        assert_eq!(estimates.rate(FeePreset::Slow), 1);
+
+        sim.set_offline(true);
+        assert!(fetch_fee_estimates(&Backend::simulated(&sim)).is_err());
+    }
+
+    #[test]
+    fn test_claim_at_preset() {
+        let v = generate_test_vectors(65).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(300);
+        sim.set_fee_rate(4.5);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+        let backend = Backend::simulated(&sim);
+
+        let at_preset = |preset| {
+            build_claim_psbt_at_preset(
+                v.backup_json.clone(),
+                &backend,
+                v.destination.clone(),
+                0,
+                preset,
+                ClaimOptions::default(),
+            )
+            .unwrap()
+        };
+        let fast = at_preset(FeePreset::Fast);
+        let minimum = at_preset(FeePreset::Minimum);
+        assert_eq!(fast.fee_sat, 5 * minimum.fee_sat);
+    }
+}
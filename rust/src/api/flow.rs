@@ -0,0 +1,324 @@
+//! The claim flow as one sequence of stages, reported as events.
+//!
+//! [`prepare_claim_flow`] loads the vault, checks its status, picks a fee
+//! and builds the claim; [`complete_claim_flow`] finalizes the heir's signed
+//! PSBT and broadcasts it. Signing happens in between, outside this library.
+//! Each stage emits [`ClaimEvent`]s to a callback, so the app can drive a
+//! stepper UI and show exactly where a claim stopped from the same events,
+//! without sending anything anywhere.
+
+use flutter_rust_bridge::DartFnFuture;
+use serde::{Deserialize, Serialize};
+
+use super::fees::{preset_fee_rate, FeePreset};
+use super::{
+    broadcast_transaction, build_claim_psbt_with_options, fetch_vault_status, finalize_psbt,
+    import_vault_backup, Backend, BroadcastResult, ClaimOptions, ClaimPsbt, HeirError, VaultState,
+};
+
+/// A step of the claim flow, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlowStage {
+    LoadVault,
+    CheckStatus,
+    ChooseFee,
+    BuildClaim,
+    Finalize,
+    Broadcast,
+}
+
+/// Something only the heir can do before the flow can go on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UserAction {
+    /// Sign the claim externally, then pass it to [`complete_claim_flow`].
+    SignPsbt { psbt_base64: String },
+    /// The timelock has not expired. The claim can be signed now but will
+    /// only be accepted after this many more blocks.
+    WaitForTimelock { blocks_remaining: i64 },
+}
+
+/// Progress of the claim flow.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClaimEvent {
+    StageStarted {
+        stage: FlowStage,
+    },
+    StageCompleted {
+        stage: FlowStage,
+    },
+    /// The stage went on, but the heir should know about this.
+    Warning {
+        stage: FlowStage,
+        message: String,
+    },
+    NeedsUserAction {
+        kind: UserAction,
+    },
+    /// The stage failed and the flow stopped; the same error is returned.
+    Failed {
+        stage: FlowStage,
+        error: HeirError,
+    },
+}
+
+/// What [`prepare_claim_flow`] should build.
+#[derive(Debug, Clone)]
+pub struct ClaimFlowRequest {
+    pub vault_json: String,
+    pub destination_address: String,
+    pub heir_index: usize,
+    /// `None` asks the backend for a rate that confirms in about an hour.
+    pub fee_rate_sat_vb: Option<u64>,
+    pub options: ClaimOptions,
+}
+
+/// Run `f` as `stage`, emitting its start and then its completion or failure.
+fn run_stage<T>(
+    emit: &dyn Fn(ClaimEvent),
+    stage: FlowStage,
+    f: impl FnOnce() -> Result<T, HeirError>,
+) -> Result<T, HeirError> {
+    emit(ClaimEvent::StageStarted { stage });
+    match f() {
+        Ok(value) => {
+            emit(ClaimEvent::StageCompleted { stage });
+            Ok(value)
+        }
+        Err(error) => {
+            emit(ClaimEvent::Failed {
+                stage,
+                error: error.clone(),
+            });
+            Err(error)
+        }
+    }
+}
+
+fn warn(emit: &dyn Fn(ClaimEvent), stage: FlowStage, message: String) {
+    emit(ClaimEvent::Warning { stage, message });
+}
+
+pub(crate) fn prepare_claim(
+    request: ClaimFlowRequest,
+    backend: &Backend,
+    emit: &dyn Fn(ClaimEvent),
+) -> Result<ClaimPsbt, HeirError> {
+    let vault = run_stage(emit, FlowStage::LoadVault, || {
+        import_vault_backup(request.vault_json)
+    })?;
+
+    let status = run_stage(emit, FlowStage::CheckStatus, || {
+        let status = fetch_vault_status(vault.canonical_json.clone(), backend)?;
+        if status.state == VaultState::ClaimPending {
+            warn(
+                emit,
+                FlowStage::CheckStatus,
+                "A spend of the vault is already waiting in the mempool".into(),
+            );
+        }
+        let not_yet = status.unconfirmed_sat + status.immature_for_claim_sat;
+        if status.eligible && not_yet > 0 {
+            warn(
+                emit,
+                FlowStage::CheckStatus,
+                format!(
+                    "{} sat is unconfirmed or still locked and stays in the vault",
+                    not_yet
+                ),
+            );
+        }
+        Ok(status)
+    })?;
+    if !status.eligible {
+        emit(ClaimEvent::NeedsUserAction {
+            kind: UserAction::WaitForTimelock {
+                blocks_remaining: status.blocks_remaining,
+            },
+        });
+    }
+
+    let fee_rate_sat_vb = run_stage(emit, FlowStage::ChooseFee, || {
+        match request.fee_rate_sat_vb {
+            Some(rate) => Ok(rate),
+            None => preset_fee_rate(backend, FeePreset::Medium),
+        }
+    })?;
+
+    let claim = run_stage(emit, FlowStage::BuildClaim, || {
+        build_claim_psbt_with_options(
+            vault.canonical_json.clone(),
+            backend,
+            request.destination_address,
+            request.heir_index,
+            fee_rate_sat_vb,
+            request.options,
+        )
+    })?;
+    if claim.skipped_dust_inputs > 0 {
+        warn(
+            emit,
+            FlowStage::BuildClaim,
+            format!(
+                "{} dust output(s) worth {} sat were left out",
+                claim.skipped_dust_inputs, claim.skipped_dust_sat
+            ),
+        );
+    }
+    emit(ClaimEvent::NeedsUserAction {
+        kind: UserAction::SignPsbt {
+            psbt_base64: claim.psbt_base64.clone(),
+        },
+    });
+    Ok(claim)
+}
+
+pub(crate) fn complete_claim(
+    signed_psbt_base64: String,
+    backend: &Backend,
+    emit: &dyn Fn(ClaimEvent),
+) -> Result<BroadcastResult, HeirError> {
+    let finalized = run_stage(emit, FlowStage::Finalize, || {
+        finalize_psbt(signed_psbt_base64)
+    })?;
+    run_stage(emit, FlowStage::Broadcast, || {
+        broadcast_transaction(finalized.tx_hex, backend)
+    })
+}
+
+/// Build the claim described by `request`, reporting progress to
+/// `on_event`.
+///
+/// Ends with [`UserAction::SignPsbt`] on success. A vault whose timelock
+/// has not expired still gets a claim, after [`UserAction::WaitForTimelock`].
+pub fn prepare_claim_flow(
+    request: ClaimFlowRequest,
+    backend: &Backend,
+    on_event: impl Fn(ClaimEvent) -> DartFnFuture<()> + Send + Sync,
+) -> Result<ClaimPsbt, HeirError> {
+    // The flow runs on a worker thread, so waiting on the Dart side is fine
+    prepare_claim(request, backend, &|event| {
+        futures::executor::block_on(on_event(event))
+    })
+}
+
+/// Finalize and broadcast the heir's signed claim, reporting progress to
+/// `on_event`.
+pub fn complete_claim_flow(
+    signed_psbt_base64: String,
+    backend: &Backend,
+    on_event: impl Fn(ClaimEvent) -> DartFnFuture<()> + Send + Sync,
+) -> Result<BroadcastResult, HeirError> {
+    complete_claim(signed_psbt_base64, backend, &|event| {
+        futures::executor::block_on(on_event(event))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::Network;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::{generate_test_vectors, TestVectors};
+    use crate::api::ErrorKind;
+
+    fn funded(v: &TestVectors, height: u64) -> SimulatedBackend {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(height);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+        sim
+    }
+
+    fn request(v: &TestVectors) -> ClaimFlowRequest {
+        ClaimFlowRequest {
+            vault_json: v.backup_json.clone(),
+            destination_address: v.destination.clone(),
+            heir_index: 0,
+            fee_rate_sat_vb: None,
+            options: ClaimOptions::default(),
+        }
+    }
+
+    #[test]
+    fn test_prepare_then_complete() {
+        let v = generate_test_vectors(37).unwrap();
+        let sim = funded(&v, 300);
+        let backend = Backend::simulated(&sim);
+        let events = Mutex::new(Vec::new());
+        let emit = |event| events.lock().unwrap().push(event);
+
+        let claim = prepare_claim(request(&v), &backend, &emit).unwrap();
+        let seen = events.lock().unwrap().split_off(0);
+        let stages: Vec<FlowStage> = seen
+            .iter()
+            .filter_map(|e| match e {
+                ClaimEvent::StageCompleted { stage } => Some(*stage),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            stages,
+            [
+                FlowStage::LoadVault,
+                FlowStage::CheckStatus,
+                FlowStage::ChooseFee,
+                FlowStage::BuildClaim
+            ]
+        );
+        assert_eq!(
+            seen.last(),
+            Some(&ClaimEvent::NeedsUserAction {
+                kind: UserAction::SignPsbt {
+                    psbt_base64: claim.psbt_base64
+                }
+            })
+        );
+
+        let result = complete_claim(v.signed_psbt_base64, &backend, &emit).unwrap();
+        assert_eq!(result.txid, v.txid);
+        assert_eq!(
+            events.lock().unwrap().last(),
+            Some(&ClaimEvent::StageCompleted {
+                stage: FlowStage::Broadcast
+            })
+        );
+    }
+
+    #[test]
+    fn test_locked_vault_waits_and_failure_is_reported() {
+        let v = generate_test_vectors(38).unwrap();
+        let sim = funded(&v, 50);
+        let events = Mutex::new(Vec::new());
+        let emit = |event| events.lock().unwrap().push(event);
+
+        prepare_claim(request(&v), &Backend::simulated(&sim), &emit).unwrap();
+        assert!(events
+            .lock()
+            .unwrap()
+            .contains(&ClaimEvent::NeedsUserAction {
+                kind: UserAction::WaitForTimelock {
+                    blocks_remaining: 95
+                }
+            }));
+
+        let err =
+            complete_claim(v.unsigned_psbt_base64, &Backend::simulated(&sim), &emit).unwrap_err();
+        assert_eq!(
+            events.lock().unwrap().last(),
+            Some(&ClaimEvent::Failed {
+                stage: FlowStage::Finalize,
+                error: err.clone()
+            })
+        );
+        assert!(matches!(err.kind, ErrorKind::Unsigned { .. }));
+    }
+}
@@ -0,0 +1,398 @@
+//! Handing backups to heirs over Nostr.
+//!
+//! [`publish_backup_for_heirs`] seals the verified backup to each heir's
+//! npub as a NIP-59 gift wrap (NIP-44 encryption, signed by the owner
+//! inside, by a throwaway key outside) and sends it to the given relays.
+//! Relays and onlookers see only an encrypted event for a recipient; the
+//! heir later collects it with [`fetch_backups_for_heir`], or opens a wrap
+//! the app already holds with [`open_backup_gift_wrap`].
+//!
+//! Only compiled with the `nostr` feature (on by default).
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use nostr::nips::nip59::UnwrappedGift;
+use nostr::{Event, EventBuilder, JsonUtil, Keys, Kind, PublicKey, Tag, ToBech32};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+use super::{import_vault_backup, ErrorKind, HeirError, VaultInfo};
+
+/// NIP-59 gift wrap.
+const GIFT_WRAP_KIND: u16 = 1059;
+/// NIP-78 application data; the rumor is never published on its own.
+const BACKUP_RUMOR_KIND: u16 = 30078;
+/// `d` tag marking a rumor as a vault backup.
+const BACKUP_IDENTIFIER: &str = "nostring-heir/backup";
+const RELAY_TIMEOUT: Duration = Duration::from_secs(15);
+/// Most gift wraps read per relay when fetching.
+const FETCH_LIMIT: usize = 500;
+
+/// What one relay said about one event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayResult {
+    pub relay: String,
+    pub accepted: bool,
+    /// The relay's reason, or the connection error.
+    pub message: String,
+}
+
+/// The gift wrap sent to one heir.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishedBackup {
+    pub heir_npub: String,
+    pub event_id: String,
+    pub relays: Vec<RelayResult>,
+}
+
+/// A backup found addressed to the heir.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceivedBackup {
+    /// The verified backup; pass `info.canonical_json` to later calls.
+    pub info: VaultInfo,
+    /// Who sealed it. Compare with the owner's npub before trusting it.
+    pub sender_npub: String,
+    /// When the owner created it (unix seconds).
+    pub created_at: u64,
+    pub event_id: String,
+}
+
+fn parse_secret(nsec: &str) -> Result<Keys, HeirError> {
+    Keys::parse(nsec.trim())
+        .map_err(|_| HeirError::new(ErrorKind::InvalidInput, "Invalid Nostr secret key"))
+}
+
+fn parse_npub(npub: &str) -> Result<PublicKey, HeirError> {
+    PublicKey::parse(npub.trim()).map_err(|_| {
+        HeirError::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid Nostr public key: {}", npub),
+        )
+    })
+}
+
+fn npub(key: &PublicKey) -> String {
+    key.to_bech32().unwrap_or_else(|_| key.to_hex())
+}
+
+fn crypto_error(e: impl std::fmt::Display) -> HeirError {
+    HeirError::new(ErrorKind::Internal, format!("Gift wrap failed: {}", e))
+}
+
+/// Gift wrap `canonical_json` from `owner` to `heir`.
+fn wrap_backup(canonical_json: &str, owner: &Keys, heir: &PublicKey) -> Result<Event, HeirError> {
+    let rumor = EventBuilder::new(
+        Kind::Custom(BACKUP_RUMOR_KIND),
+        canonical_json,
+        [Tag::identifier(BACKUP_IDENTIFIER)],
+    )
+    .to_unsigned_event(owner.public_key());
+    EventBuilder::gift_wrap(owner, heir, rumor, None).map_err(crypto_error)
+}
+
+/// Open a gift wrap with `heir` and verify the backup inside.
+fn unwrap_backup(event: &Event, heir: &Keys) -> Result<ReceivedBackup, HeirError> {
+    let gift = UnwrappedGift::from_gift_wrap(heir, event).map_err(|_| {
+        HeirError::new(
+            ErrorKind::InvalidInput,
+            "This gift wrap is not addressed to this key",
+        )
+    })?;
+    let is_backup = gift.rumor.kind == Kind::Custom(BACKUP_RUMOR_KIND)
+        && gift
+            .rumor
+            .tags
+            .iter()
+            .any(|tag| tag.as_slice() == ["d", BACKUP_IDENTIFIER]);
+    if !is_backup {
+        return Err(HeirError::new(
+            ErrorKind::UnrecognizedFormat,
+            "This gift wrap does not hold a vault backup",
+        ));
+    }
+    Ok(ReceivedBackup {
+        info: import_vault_backup(gift.rumor.content.clone())?,
+        sender_npub: npub(&gift.sender),
+        created_at: gift.rumor.created_at.as_u64(),
+        event_id: event.id.to_hex(),
+    })
+}
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// Open a websocket to `relay` with connect and read timeouts.
+fn connect(relay: &str) -> io::Result<Socket> {
+    let uri: tungstenite::http::Uri = relay
+        .parse()
+        .map_err(|_| io::Error::other(format!("Invalid relay URL: {}", relay)))?;
+    let host = uri
+        .host()
+        .ok_or_else(|| io::Error::other(format!("Missing host in {}", relay)))?;
+    let port = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("ws") {
+        80
+    } else {
+        443
+    });
+    let mut last_error = io::Error::other(format!("No address for {}", host));
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, RELAY_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(RELAY_TIMEOUT))?;
+                stream.set_write_timeout(Some(RELAY_TIMEOUT))?;
+                let (socket, _) = tungstenite::client_tls(relay, stream)
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                return Ok(socket);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn send(socket: &mut Socket, message: Value) -> io::Result<()> {
+    socket
+        .send(Message::Text(message.to_string()))
+        .map_err(io::Error::other)
+}
+
+/// Next relay message as a JSON array, skipping pings and non-JSON frames.
+fn receive(socket: &mut Socket) -> io::Result<Vec<Value>> {
+    loop {
+        match socket.read().map_err(io::Error::other)? {
+            Message::Text(text) => {
+                if let Ok(Value::Array(items)) = serde_json::from_str(&text) {
+                    return Ok(items);
+                }
+            }
+            Message::Close(_) => {
+                return Err(io::Error::other("Relay closed the connection"));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Send `event` to `relay` and wait for its `OK`: whether it was accepted,
+/// and the relay's message.
+fn send_event(relay: &str, event: &Event) -> io::Result<(bool, String)> {
+    let mut socket = connect(relay)?;
+    let event_value: Value = serde_json::from_str(&event.as_json()).map_err(io::Error::other)?;
+    send(&mut socket, json!(["EVENT", event_value]))?;
+    let id = event.id.to_hex();
+    loop {
+        let reply = receive(&mut socket)?;
+        if reply.first().and_then(Value::as_str) == Some("OK")
+            && reply.get(1).and_then(Value::as_str) == Some(id.as_str())
+        {
+            let _ = socket.close(None);
+            let accepted = reply.get(2).and_then(Value::as_bool).unwrap_or(false);
+            let message = reply.get(3).and_then(Value::as_str).unwrap_or("");
+            return Ok((accepted, message.to_string()));
+        }
+    }
+}
+
+fn publish_to(relay: &str, event: &Event) -> RelayResult {
+    let (accepted, message) = send_event(relay, event).unwrap_or_else(|e| (false, e.to_string()));
+    RelayResult {
+        relay: relay.to_string(),
+        accepted,
+        message,
+    }
+}
+
+/// Gift wraps addressed to `recipient` stored on `relay`.
+fn fetch_from(relay: &str, recipient: &PublicKey) -> io::Result<Vec<Event>> {
+    let mut socket = connect(relay)?;
+    let subscription = "nostring-heir-backups";
+    send(
+        &mut socket,
+        json!([
+            "REQ",
+            subscription,
+            {
+                "kinds": [GIFT_WRAP_KIND],
+                "#p": [recipient.to_hex()],
+                "limit": FETCH_LIMIT,
+            }
+        ]),
+    )?;
+    let mut events = Vec::new();
+    loop {
+        let reply = receive(&mut socket)?;
+        match reply.first().and_then(Value::as_str) {
+            Some("EVENT") => {
+                // Malformed or forged events are skipped, not fatal
+                if let Some(event) = reply
+                    .get(2)
+                    .and_then(|e| Event::from_json(e.to_string()).ok())
+                    .filter(|e| e.verify().is_ok())
+                {
+                    events.push(event);
+                }
+            }
+            Some("EOSE") | Some("CLOSED") => break,
+            _ => {}
+        }
+    }
+    let _ = send(&mut socket, json!(["CLOSE", subscription]));
+    let _ = socket.close(None);
+    Ok(events)
+}
+
+/// Gift wrap the verified backup to every heir and publish each wrap to
+/// every relay.
+///
+/// Fails before sending anything if the backup does not verify or a key
+/// is malformed, and with [`ErrorKind::Connection`] if no relay accepted
+/// any wrap. Otherwise per-relay results are returned so the app can show
+/// which heirs are covered where.
+pub fn publish_backup_for_heirs(
+    backup_json: String,
+    owner_nsec: String,
+    heir_npubs: Vec<String>,
+    relays: Vec<String>,
+) -> Result<Vec<PublishedBackup>, HeirError> {
+    let info = import_vault_backup(backup_json)?;
+    let owner = parse_secret(&owner_nsec)?;
+    let heirs = heir_npubs
+        .iter()
+        .map(|npub| parse_npub(npub))
+        .collect::<Result<Vec<_>, _>>()?;
+    if heirs.is_empty() || relays.is_empty() {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            "At least one heir and one relay are needed",
+        ));
+    }
+
+    let mut published = Vec::with_capacity(heirs.len());
+    for heir in &heirs {
+        let event = wrap_backup(&info.canonical_json, &owner, heir)?;
+        published.push(PublishedBackup {
+            heir_npub: npub(heir),
+            event_id: event.id.to_hex(),
+            relays: relays
+                .iter()
+                .map(|relay| publish_to(relay, &event))
+                .collect(),
+        });
+    }
+    if !published.iter().flat_map(|p| &p.relays).any(|r| r.accepted) {
+        let reasons: Vec<String> = published[0]
+            .relays
+            .iter()
+            .map(|r| format!("{}: {}", r.relay, r.message))
+            .collect();
+        return Err(HeirError::new(
+            ErrorKind::Connection,
+            format!("No relay accepted the backup ({})", reasons.join("; ")),
+        ));
+    }
+    Ok(published)
+}
+
+/// Collect every backup gift wrapped to `heir_nsec` from `relays`, newest
+/// first. The same backup found on several relays, or sent several times,
+/// is listed once.
+///
+/// Fails with [`ErrorKind::Connection`] only if no relay could be read.
+pub fn fetch_backups_for_heir(
+    heir_nsec: String,
+    relays: Vec<String>,
+) -> Result<Vec<ReceivedBackup>, HeirError> {
+    let heir = parse_secret(&heir_nsec)?;
+    let mut errors = Vec::new();
+    let mut found: HashMap<String, ReceivedBackup> = HashMap::new();
+    for relay in &relays {
+        let events = match fetch_from(relay, &heir.public_key()) {
+            Ok(events) => events,
+            Err(e) => {
+                errors.push(format!("{}: {}", relay, e));
+                continue;
+            }
+        };
+        // Other gift wraps (private messages) share the kind; skip them
+        for backup in events.iter().filter_map(|e| unwrap_backup(e, &heir).ok()) {
+            let newer = found
+                .get(&backup.info.content_hash)
+                .is_none_or(|seen| backup.created_at > seen.created_at);
+            if newer {
+                found.insert(backup.info.content_hash.clone(), backup);
+            }
+        }
+    }
+    if errors.len() == relays.len() {
+        return Err(HeirError::new(
+            ErrorKind::Connection,
+            format!("No relay could be read ({})", errors.join("; ")),
+        ));
+    }
+    let mut backups: Vec<ReceivedBackup> = found.into_values().collect();
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(backups)
+}
+
+/// Open one gift wrap event (JSON) the app already fetched.
+pub fn open_backup_gift_wrap(
+    event_json: String,
+    heir_nsec: String,
+) -> Result<ReceivedBackup, HeirError> {
+    let heir = parse_secret(&heir_nsec)?;
+    let event = Event::from_json(event_json)
+        .map_err(|_| HeirError::new(ErrorKind::InvalidInput, "Not a Nostr event"))?;
+    event
+        .verify()
+        .map_err(|_| HeirError::new(ErrorKind::InvalidInput, "The event signature is invalid"))?;
+    unwrap_backup(&event, &heir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::vectors::generate_test_vectors;
+
+    const OWNER_SECRET: &str = "0202020202020202020202020202020202020202020202020202020202020202";
+    const HEIR_SECRET: &str = "0303030303030303030303030303030303030303030303030303030303030303";
+    const STRANGER_SECRET: &str =
+        "0404040404040404040404040404040404040404040404040404040404040404";
+
+    #[test]
+    fn test_gift_wrap_round_trip() {
+        let v = generate_test_vectors(39).unwrap();
+        let info = import_vault_backup(v.backup_json).unwrap();
+        let owner = parse_secret(OWNER_SECRET).unwrap();
+        let heir = parse_secret(HEIR_SECRET).unwrap();
+
+        let event = wrap_backup(&info.canonical_json, &owner, &heir.public_key()).unwrap();
+        assert_eq!(event.kind, Kind::GiftWrap);
+        // Neither the owner nor the backup is visible from outside
+        assert_ne!(event.pubkey, owner.public_key());
+        assert!(!event.content.contains(&info.vault_address));
+
+        let received = open_backup_gift_wrap(event.as_json(), HEIR_SECRET.into()).unwrap();
+        assert_eq!(received.info.content_hash, info.content_hash);
+        assert_eq!(received.sender_npub, npub(&owner.public_key()));
+
+        let err = open_backup_gift_wrap(event.as_json(), STRANGER_SECRET.into()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_publish_validates_before_sending() {
+        let v = generate_test_vectors(40).unwrap();
+        let err = publish_backup_for_heirs(
+            v.backup_json,
+            OWNER_SECRET.into(),
+            vec!["npub1notakey".into()],
+            vec!["wss://relay.example".into()],
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+}
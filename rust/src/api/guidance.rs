@@ -0,0 +1,131 @@
+//! What the heir has to do next.
+//!
+//! Derived from the vault state machine and the stage of any saved claim,
+//! so every frontend walks the heir through the same steps in the same
+//! order.
+
+use serde::{Deserialize, Serialize};
+
+use super::state::VaultState;
+use super::store::ClaimStage;
+use super::{EligibilityPhase, VaultStatus};
+
+/// One step of the claim, in the order the heir takes them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InstructionStep {
+    /// The vault has never been funded; there is nothing to claim.
+    WaitForFunding,
+    /// The timelock has not expired yet.
+    WaitForTimelock { blocks_remaining: u64, days_remaining: f64 },
+    /// Build the claim PSBT.
+    BuildClaim,
+    /// Sign the PSBT with the heir's wallet.
+    SignClaim,
+    /// Some inputs are signed; sign the rest.
+    CompleteSigning,
+    /// Finalize and broadcast the signed claim.
+    Broadcast,
+    /// The claim is in the mempool; wait for it to confirm.
+    WaitForConfirmation,
+    /// Outputs still locked can be claimed in a later transaction.
+    ClaimRemainingLater { locked_sat: u64 },
+    /// Nothing is left in the vault.
+    Complete,
+}
+
+/// Steps from `stage` (or from scratch) up to confirmation.
+fn claim_steps(stage: Option<ClaimStage>) -> Vec<InstructionStep> {
+    use InstructionStep::*;
+    let from_signing = match stage {
+        None => vec![BuildClaim, SignClaim],
+        Some(ClaimStage::Unsigned) => vec![SignClaim],
+        Some(ClaimStage::PartiallySigned) => vec![CompleteSigning],
+        Some(ClaimStage::Signed) | Some(ClaimStage::Finalized) => vec![],
+    };
+    from_signing
+        .into_iter()
+        .chain([Broadcast, WaitForConfirmation])
+        .collect()
+}
+
+/// Ordered steps the heir must take from here, given the vault's status and
+/// the stage of the claim saved for it, if any.
+pub fn next_steps(status: VaultStatus, claim_stage: Option<ClaimStage>) -> Vec<InstructionStep> {
+    use InstructionStep::*;
+    match status.state {
+        VaultState::Unfunded => vec![WaitForFunding],
+        VaultState::ClaimPending => vec![WaitForConfirmation],
+        VaultState::Swept => vec![Complete],
+        VaultState::FundedLocked | VaultState::OwnerRefreshed => {
+            let wait = match status.phase {
+                EligibilityPhase::Waiting {
+                    blocks_remaining,
+                    days_remaining,
+                } => WaitForTimelock {
+                    blocks_remaining,
+                    days_remaining,
+                },
+                // Expired on paper, but the outputs are not confirmed yet
+                EligibilityPhase::Eligible { .. } => WaitForConfirmation,
+            };
+            std::iter::once(wait).chain(claim_steps(None)).collect()
+        }
+        VaultState::FullyClaimable => claim_steps(claim_stage),
+        VaultState::PartiallyClaimable => {
+            let mut steps = claim_steps(claim_stage);
+            steps.push(ClaimRemainingLater {
+                locked_sat: status.balance_sat.saturating_sub(
+                    status
+                        .utxos
+                        .iter()
+                        .filter(|u| u.claimable)
+                        .map(|u| u.value_sat)
+                        .sum(),
+                ),
+            });
+            steps
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InstructionStep::*;
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::{fetch_vault_status, Backend, Network};
+
+    fn status_at(height: u64, funded_at: u32) -> VaultStatus {
+        let v = generate_test_vectors(7).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(height);
+        sim.add_utxo(v.vault_address, "71".repeat(32), 0, 50_000, funded_at)
+            .unwrap();
+        fetch_vault_status(v.backup_json, &Backend::simulated(&sim)).unwrap()
+    }
+
+    #[test]
+    fn test_locked_vault_starts_with_wait() {
+        let steps = next_steps(status_at(1_000, 1_000), None);
+        assert!(matches!(steps[0], WaitForTimelock { .. }));
+        assert_eq!(&steps[1..], &[BuildClaim, SignClaim, Broadcast, WaitForConfirmation]);
+    }
+
+    #[test]
+    fn test_claimable_vault_follows_claim_stage() {
+        let status = status_at(10_000, 1_000);
+        assert_eq!(
+            next_steps(status.clone(), None),
+            vec![BuildClaim, SignClaim, Broadcast, WaitForConfirmation]
+        );
+        assert_eq!(
+            next_steps(status.clone(), Some(ClaimStage::PartiallySigned)),
+            vec![CompleteSigning, Broadcast, WaitForConfirmation]
+        );
+        assert_eq!(
+            next_steps(status, Some(ClaimStage::Finalized)),
+            vec![Broadcast, WaitForConfirmation]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Transaction history of a vault, with the fee each transaction paid.
+//!
+//! Electrum's history only lists txids and heights. Fees need the previous
+//! outputs of every input, so the transactions those inputs spend are
+//! fetched in one batch alongside the history itself.
+
+use std::collections::{BTreeSet, HashMap};
+
+use bitcoin::{Amount, Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
+use super::readonly::load_watched;
+use super::state::{fetch_history_txs, outgoing_spends};
+use super::{Backend, HeirError};
+
+/// What a history transaction did to the vault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryKind {
+    /// Paid into the vault without spending from it.
+    Deposit,
+    /// Spent via the key path by the owner and co-signer, usually a
+    /// refresh that restarts the timelock.
+    OwnerSpend,
+    /// Spent via a recovery leaf.
+    HeirClaim,
+}
+
+/// One transaction touching the vault address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryItem {
+    pub txid: String,
+    /// Confirmation height, or 0 while in the mempool.
+    pub height: u64,
+    /// Block timestamp (unix seconds), if confirmed and the backend had it.
+    pub time: Option<u64>,
+    pub kind: HistoryKind,
+    /// Paid to the vault address by this transaction.
+    pub received_sat: u64,
+    /// Vault outputs this transaction spent.
+    pub spent_sat: u64,
+    /// Fee the transaction paid. `None` for coinbase transactions.
+    pub fee_sat: Option<u64>,
+    pub fee_rate_sat_vb: Option<f64>,
+}
+
+/// Fee of `tx` from its previous outputs, or `None` if one is unknown.
+fn fee_paid(tx: &Transaction, txs: &HashMap<Txid, Transaction>) -> Option<Amount> {
+    if tx.is_coinbase() {
+        return None;
+    }
+    let input = tx
+        .input
+        .iter()
+        .map(|i| {
+            txs.get(&i.previous_output.txid)
+                .and_then(|prev| prev.output.get(i.previous_output.vout as usize))
+                .map(|out| out.value)
+        })
+        .sum::<Option<Amount>>()?;
+    let output: Amount = tx.output.iter().map(|o| o.value).sum();
+    input.checked_sub(output)
+}
+
+/// Every transaction touching the vault, oldest first, with its fee.
+/// Accepts a full backup or a read-only copy.
+pub fn fetch_vault_history(
+    vault_json: String,
+    backend: &Backend,
+) -> Result<Vec<HistoryItem>, HeirError> {
+    let vault = load_watched(&vault_json)?;
+    backend.require_network(vault.network)?;
+    let chain = backend.chain();
+
+    let history = chain.history(&vault.address)?;
+    let mut txs = fetch_history_txs(chain, &history)?;
+
+    // Transactions spent by history inputs that are not history themselves
+    let missing: Vec<Txid> = txs
+        .values()
+        .filter(|tx| !tx.is_coinbase())
+        .flat_map(|tx| tx.input.iter().map(|i| i.previous_output.txid))
+        .filter(|txid| !txs.contains_key(txid))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let prevs = chain.transactions(&missing)?;
+    txs.extend(missing.into_iter().zip(prevs));
+
+    let script = vault.address.script_pubkey();
+    let outgoing = outgoing_spends(&script, &history, &txs);
+    let mut block_times = HashMap::new();
+
+    Ok(history
+        .iter()
+        .filter_map(|entry| {
+            let tx = txs.get(&entry.txid)?;
+            let received_sat = tx
+                .output
+                .iter()
+                .filter(|o| o.script_pubkey == script)
+                .map(|o| o.value.to_sat())
+                .sum();
+            let spent_sat = tx
+                .input
+                .iter()
+                .filter_map(|i| {
+                    txs.get(&i.previous_output.txid)?
+                        .output
+                        .get(i.previous_output.vout as usize)
+                })
+                .filter(|o| o.script_pubkey == script)
+                .map(|o| o.value.to_sat())
+                .sum();
+            let kind = match outgoing.iter().find(|spend| spend.txid == entry.txid) {
+                None => HistoryKind::Deposit,
+                Some(spend) if spend.key_path => HistoryKind::OwnerSpend,
+                Some(_) => HistoryKind::HeirClaim,
+            };
+            let fee = fee_paid(tx, &txs);
+            // A missing header only costs the date
+            let time = (entry.height > 0)
+                .then(|| {
+                    *block_times
+                        .entry(entry.height)
+                        .or_insert_with(|| chain.block_time(entry.height).ok())
+                })
+                .flatten();
+            Some(HistoryItem {
+                txid: entry.txid.to_string(),
+                height: u64::from(entry.height),
+                time,
+                kind,
+                received_sat,
+                spent_sat,
+                fee_sat: fee.map(Amount::to_sat),
+                fee_rate_sat_vb: fee
+                    .map(|fee| fee.to_sat() as f64 / tx.weight().to_vbytes_ceil() as f64),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::Network;
+
+    #[test]
+    fn test_history_reports_claim_fee() {
+        let v = generate_test_vectors(11).unwrap();
+        let (funding_txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(10_000);
+        sim.add_utxo(
+            v.vault_address.clone(),
+            funding_txid.to_string(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1_000,
+        )
+        .unwrap();
+        let backend = Backend::simulated(&sim);
+
+        let tx: Transaction = bitcoin::consensus::encode::deserialize_hex(&v.tx_hex).unwrap();
+        backend.chain().broadcast(&tx).unwrap();
+        sim.mine_blocks(1);
+
+        let history = fetch_vault_history(v.backup_json, &backend).unwrap();
+        assert_eq!(history.len(), 2);
+        let funding = &history[0];
+        assert_eq!(funding.kind, HistoryKind::Deposit);
+        assert_eq!(funding.received_sat, v.funding_value_sat);
+        assert_eq!(funding.fee_sat, None);
+
+        let spend = &history[1];
+        assert_eq!(spend.kind, HistoryKind::HeirClaim);
+        assert_eq!(spend.spent_sat, v.funding_value_sat);
+        assert_eq!(spend.fee_sat, Some(v.fee_sat));
+        assert_eq!(spend.height, 10_001);
+        assert!(spend.time.is_some());
+    }
+}
@@ -0,0 +1,407 @@
+//! Backup import from raw bytes.
+//!
+//! QR scanners, NFC readers and file pickers hand the app bytes in whatever
+//! encoding the owner's app produced. Sniffing happens here so the host
+//! never has to guess. Backups pasted inside longer text, such as a vault
+//! certificate, are dug out by [`extract_backup_from_text`].
+
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use super::{decompress_vault_backup, import_vault_backup, ErrorKind, HeirError, VaultInfo};
+
+/// Text prefix of a password-protected backup envelope.
+pub(crate) const ENCRYPTED_BACKUP_PREFIX: &str = "nostring:enc:v1:";
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const AGE_BINARY_HEADER: &[u8] = b"age-encryption.org/v1";
+const AGE_ARMOR_HEADER: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+
+/// How a backup's bytes are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackupEncoding {
+    /// Plain VaultBackup JSON.
+    Json,
+    /// `nostring:v1:` compressed QR payload.
+    QrPayload,
+    /// Gzip-compressed JSON.
+    Gzip,
+    /// VaultBackup as a CBOR map.
+    Cbor,
+    /// Password-protected envelope.
+    Encrypted,
+}
+
+/// Guess the encoding of `bytes` from its first bytes.
+pub fn sniff_backup_encoding(bytes: &[u8]) -> Result<BackupEncoding, HeirError> {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .map_or(&[][..], |i| &bytes[i..]);
+
+    if start.starts_with(ENCRYPTED_BACKUP_PREFIX.as_bytes())
+        || start.starts_with(AGE_BINARY_HEADER)
+        || start.starts_with(AGE_ARMOR_HEADER)
+    {
+        Ok(BackupEncoding::Encrypted)
+    } else if start.starts_with(b"nostring:v1:") {
+        Ok(BackupEncoding::QrPayload)
+    } else if start.starts_with(b"{") {
+        Ok(BackupEncoding::Json)
+    } else if start.starts_with(&GZIP_MAGIC) {
+        Ok(BackupEncoding::Gzip)
+    // CBOR major type 5 (map); never valid as the first byte of UTF-8 text
+    } else if start.first().is_some_and(|b| (0xa0..=0xbf).contains(b)) {
+        Ok(BackupEncoding::Cbor)
+    } else {
+        Err(HeirError::new(
+            ErrorKind::UnrecognizedFormat,
+            "Unrecognized backup format. Expected JSON, a nostring QR payload, gzip or CBOR.",
+        ))
+    }
+}
+
+fn utf8(bytes: Vec<u8>) -> Result<String, HeirError> {
+    String::from_utf8(bytes).map_err(|_| {
+        HeirError::new(ErrorKind::InvalidEncoding, "Backup text is not valid UTF-8")
+    })
+}
+
+/// Sniff the encoding of `bytes`, decode it, and import the backup as
+/// [`import_vault_backup`] would.
+pub fn import_vault_backup_bytes(bytes: Vec<u8>) -> Result<VaultInfo, HeirError> {
+    let json = match sniff_backup_encoding(&bytes)? {
+        BackupEncoding::Json => utf8(bytes)?,
+        BackupEncoding::QrPayload => decompress_vault_backup(utf8(bytes)?)?,
+        BackupEncoding::Gzip => {
+            let mut json = String::new();
+            flate2::read::GzDecoder::new(bytes.as_slice())
+                .read_to_string(&mut json)
+                .map_err(|e| {
+                    HeirError::new(ErrorKind::Compression, format!("Decompression failed: {}", e))
+                })?;
+            json
+        }
+        BackupEncoding::Cbor => {
+            let value: serde_json::Value = ciborium::from_reader(bytes.as_slice()).map_err(|e| {
+                HeirError::new(ErrorKind::InvalidBackup, format!("Invalid CBOR backup: {}", e))
+            })?;
+            value.to_string()
+        }
+        BackupEncoding::Encrypted => {
+            return Err(HeirError::new(
+                ErrorKind::EncryptedBackup,
+                "This backup is password-protected",
+            ))
+        }
+    };
+    import_vault_backup(json)
+}
+
+/// A backup found inside a larger text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractedBackup {
+    /// The backup as JSON, ready for [`import_vault_backup`].
+    pub backup_json: String,
+    pub info: VaultInfo,
+    /// Every form the backup appeared in, e.g. JSON and a QR payload of
+    /// the same vault on one certificate.
+    pub found_as: Vec<BackupEncoding>,
+}
+
+/// Shortest bare base64 run worth decoding; anything smaller cannot hold a
+/// backup.
+const MIN_BASE64_BLOCK: usize = 64;
+
+fn is_base64(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=')
+}
+
+/// Every balanced `{...}` in `text`, outermost first, skipping braces in
+/// JSON strings.
+fn json_objects(text: &str) -> Vec<&str> {
+    let mut objects = Vec::new();
+    let bytes = text.as_bytes();
+    let mut start = 0;
+    while let Some(offset) = text[start..].find('{') {
+        let open = start + offset;
+        let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+        let mut close = None;
+        for (i, &b) in bytes.iter().enumerate().skip(open) {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' if in_string => escaped = true,
+                b'"' => in_string = !in_string,
+                b'{' if !in_string => depth += 1,
+                b'}' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        match close {
+            Some(close) => {
+                objects.push(&text[open..=close]);
+                start = close + 1;
+            }
+            None => start = open + 1,
+        }
+    }
+    objects
+}
+
+/// Base64 runs, joined across the line breaks PDF text extraction inserts.
+/// Each run is offered longest first, then without its trailing lines, in
+/// case the words after it were swallowed.
+fn base64_runs(text: &str) -> Vec<Vec<String>> {
+    let mut runs = Vec::new();
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines().chain(std::iter::once("")) {
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && trimmed.chars().all(is_base64) {
+            lines.push(trimmed.to_string());
+            continue;
+        }
+        if !lines.is_empty() {
+            let attempts: Vec<String> = (1..=lines.len())
+                .rev()
+                .map(|n| lines[..n].concat())
+                .filter(|run| run.len() >= MIN_BASE64_BLOCK)
+                .collect();
+            if !attempts.is_empty() {
+                runs.push(attempts);
+            }
+            lines.clear();
+        }
+        // A run can also sit at the end of a line of prose
+        if let Some(word) = trimmed.rsplit(char::is_whitespace).next() {
+            if word.len() >= MIN_BASE64_BLOCK && word.chars().all(is_base64) && word != trimmed {
+                runs.push(vec![word.to_string()]);
+            }
+        }
+    }
+    runs
+}
+
+/// `nostring:v1:` payloads, each with its continuation lines.
+fn qr_payloads(text: &str) -> Vec<Vec<String>> {
+    let mut payloads = Vec::new();
+    let mut rest = text;
+    while let Some(at) = rest.find("nostring:v1:") {
+        let after = &rest[at + "nostring:v1:".len()..];
+        let mut lines: Vec<&str> = Vec::new();
+        for line in after.lines() {
+            let line = line.trim();
+            let end = line.find(|c: char| !is_base64(c)).unwrap_or(line.len());
+            if end == 0 {
+                break;
+            }
+            lines.push(&line[..end]);
+            if end < line.len() {
+                break;
+            }
+        }
+        payloads.push(
+            (1..=lines.len())
+                .rev()
+                .map(|n| format!("nostring:v1:{}", lines[..n].concat()))
+                .collect(),
+        );
+        rest = after;
+    }
+    payloads
+}
+
+/// Decode a bare base64 block holding backup JSON, plain or gzipped.
+fn decode_base64_block(block: &str) -> Option<String> {
+    use base64::Engine;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(block)
+        .ok()?;
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut json = String::new();
+        flate2::read::GzDecoder::new(bytes.as_slice())
+            .read_to_string(&mut json)
+            .ok()?;
+        Some(json)
+    } else {
+        String::from_utf8(bytes).ok()
+    }
+}
+
+/// Find the one backup embedded in `blob`: text copied out of a "vault
+/// certificate" PDF, a share-sheet payload or an email.
+///
+/// Backups may appear as JSON, a `nostring:v1:` QR payload or a bare
+/// base64 block (plain or gzipped JSON), and may be wrapped across lines.
+/// Copies of the same vault count once. No candidate, or candidates for
+/// different vaults, is an error; so is a lone candidate that fails
+/// verification.
+pub fn extract_backup_from_text(blob: String) -> Result<ExtractedBackup, HeirError> {
+    let mut candidates: Vec<(BackupEncoding, String)> = json_objects(&blob)
+        .into_iter()
+        .map(|json| (BackupEncoding::Json, json.to_string()))
+        .collect();
+    for attempts in qr_payloads(&blob) {
+        if let Some(json) = attempts
+            .into_iter()
+            .find_map(|payload| decompress_vault_backup(payload).ok())
+        {
+            candidates.push((BackupEncoding::QrPayload, json));
+        }
+    }
+    for attempts in base64_runs(&blob) {
+        if let Some(json) = attempts
+            .iter()
+            .filter_map(|run| decode_base64_block(run))
+            .find(|json| json.trim_start().starts_with('{'))
+        {
+            candidates.push((BackupEncoding::Json, json));
+        }
+    }
+
+    // JSON fragments that are not backups at all are just text
+    let mut first_error = None;
+    let mut found: Vec<ExtractedBackup> = Vec::new();
+    for (encoding, json) in candidates {
+        if !serde_json::from_str::<serde_json::Value>(&json).is_ok_and(|v| v.get("heirs").is_some())
+        {
+            continue;
+        }
+        match import_vault_backup(json.clone()) {
+            Ok(info) => match found
+                .iter_mut()
+                .find(|f| f.info.content_hash == info.content_hash)
+            {
+                Some(existing) if existing.found_as.contains(&encoding) => {}
+                Some(existing) => existing.found_as.push(encoding),
+                None => found.push(ExtractedBackup {
+                    backup_json: json,
+                    info,
+                    found_as: vec![encoding],
+                }),
+            },
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match found.len() {
+        1 => Ok(found.remove(0)),
+        0 => Err(first_error.unwrap_or_else(|| {
+            HeirError::new(
+                ErrorKind::UnrecognizedFormat,
+                "No vault backup found in the text. Copy the whole certificate, including the \
+                 backup block or QR text.",
+            )
+        })),
+        n => Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Found {} different vault backups ({}); import them one at a time",
+                n,
+                found
+                    .iter()
+                    .map(|f| f.info.vault_address.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::vectors::generate_test_vectors;
+    use std::io::Write;
+
+    #[test]
+    fn test_sniff_backup_encoding() {
+        let cases: [(&[u8], BackupEncoding); 6] = [
+            (b"  {\"version\":1}", BackupEncoding::Json),
+            (b"nostring:v1:H4sI", BackupEncoding::QrPayload),
+            (&[0x1f, 0x8b, 0x08], BackupEncoding::Gzip),
+            (&[0xa4, 0x67], BackupEncoding::Cbor),
+            (b"nostring:enc:v1:abc", BackupEncoding::Encrypted),
+            (b"age-encryption.org/v1\n", BackupEncoding::Encrypted),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(sniff_backup_encoding(bytes).unwrap(), expected);
+        }
+        let err = sniff_backup_encoding(b"hello").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnrecognizedFormat);
+    }
+
+    #[test]
+    fn test_import_every_encoding() {
+        let json = generate_test_vectors(1).unwrap().backup_json;
+        let expected = import_vault_backup(json.clone()).unwrap().vault_address;
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(json.as_bytes()).unwrap();
+        let gzip = gz.finish().unwrap();
+
+        let mut cbor = Vec::new();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        ciborium::into_writer(&value, &mut cbor).unwrap();
+
+        let qr = crate::api::compress_vault_backup(json.clone()).unwrap();
+
+        for bytes in [json.into_bytes(), gzip, cbor, qr.into_bytes()] {
+            assert_eq!(import_vault_backup_bytes(bytes).unwrap().vault_address, expected);
+        }
+    }
+
+    #[test]
+    fn test_encrypted_backup_needs_password() {
+        let err = import_vault_backup_bytes(b"nostring:enc:v1:abc".to_vec()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::EncryptedBackup);
+        assert_eq!(err.remediation, crate::api::Remediation::EnterPassword);
+    }
+
+    #[test]
+    fn test_extract_backup_from_certificate_text() {
+        let json = generate_test_vectors(37).unwrap().backup_json;
+        let qr = crate::api::compress_vault_backup(json.clone()).unwrap();
+        // Wrapped at 60 columns, as PDF text extraction does
+        let wrapped: Vec<String> = qr
+            .as_bytes()
+            .chunks(60)
+            .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+            .collect();
+        let certificate = format!(
+            "VAULT CERTIFICATE\nKeep this safe.\n\n{}\n\nBackup data:\n{}\nSigned, the owner {{not json}}",
+            wrapped.join("\n"),
+            json
+        );
+
+        let extracted = extract_backup_from_text(certificate).unwrap();
+        assert_eq!(
+            extracted.found_as,
+            vec![BackupEncoding::Json, BackupEncoding::QrPayload]
+        );
+        assert_eq!(
+            extracted.info.vault_address,
+            import_vault_backup(json).unwrap().vault_address
+        );
+    }
+
+    #[test]
+    fn test_extract_reports_none_or_several() {
+        let err = extract_backup_from_text("Dear heir, {see attached}".into()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnrecognizedFormat);
+
+        let one = generate_test_vectors(38).unwrap().backup_json;
+        let two = generate_test_vectors(39).unwrap().backup_json;
+        let err = extract_backup_from_text(format!("{}\n{}", one, two)).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+        assert!(err.message.starts_with("Found 2 different"));
+    }
+}
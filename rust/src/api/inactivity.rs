@@ -0,0 +1,158 @@
+//! Periodic check-in summary for heirs.
+//!
+//! Heirs who just want to know "how close is it?" get the time since the
+//! owner last refreshed the vault, the share of the timelock that has run,
+//! and a projected date, without having to reason about block heights.
+
+use serde::{Deserialize, Serialize};
+
+use super::readonly::load_watched;
+use super::state::VaultState;
+use super::timelock::{block_interval_secs, blocks_to_days};
+use super::{fetch_vault_status, Backend, HeirError};
+
+/// Share of the timelock after which the report starts warning.
+const APPROACHING_PERCENT: f64 = 75.0;
+const IMMINENT_PERCENT: f64 = 90.0;
+
+/// How close the vault is to becoming claimable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InactivityLevel {
+    /// Nothing to watch: the vault is unfunded or already swept.
+    NotApplicable,
+    /// Less than 75% of the timelock has run.
+    OwnerActive,
+    /// At least 75% has run.
+    Approaching,
+    /// At least 90% has run.
+    Imminent,
+    /// The timelock has expired.
+    Eligible,
+}
+
+/// Plain summary of owner inactivity for one vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InactivityReport {
+    pub level: InactivityLevel,
+    /// Height at which the current timelock started: the earliest
+    /// confirmation among the vault's outputs.
+    pub last_refresh_height: Option<u64>,
+    /// Block timestamp (unix seconds) of that height, if the backend had it.
+    pub last_refresh_time: Option<u64>,
+    pub days_since_refresh: f64,
+    pub timelock_days: f64,
+    /// Share of the timelock that has run, 0 to 100.
+    pub percent_elapsed: f64,
+    /// Projected unix time the timelock expires, from the tip's timestamp
+    /// and the average block interval. Past for eligible vaults.
+    pub projected_eligible_time: Option<u64>,
+}
+
+/// Summarize how long the owner has been inactive and when the vault is
+/// expected to become claimable. Accepts a full backup or a read-only copy.
+pub fn inactivity_report(
+    vault_json: String,
+    backend: &Backend,
+) -> Result<InactivityReport, HeirError> {
+    let vault = load_watched(&vault_json)?;
+    let status = fetch_vault_status(vault_json, backend)?;
+    let chain = backend.chain();
+    let timelock_days = blocks_to_days(i64::from(vault.timelock_blocks), vault.network);
+
+    let confirmed = status.utxos.iter().any(|u| u.confirmations > 0);
+    if !confirmed || matches!(status.state, VaultState::Unfunded | VaultState::Swept) {
+        return Ok(InactivityReport {
+            level: InactivityLevel::NotApplicable,
+            last_refresh_height: None,
+            last_refresh_time: None,
+            days_since_refresh: 0.0,
+            timelock_days,
+            percent_elapsed: 0.0,
+            projected_eligible_time: None,
+        });
+    }
+
+    let elapsed_blocks = status
+        .current_height
+        .saturating_sub(status.confirmation_height);
+    let percent_elapsed = if vault.timelock_blocks == 0 {
+        100.0
+    } else {
+        (elapsed_blocks as f64 * 100.0 / f64::from(vault.timelock_blocks)).min(100.0)
+    };
+    let level = if status.eligible {
+        InactivityLevel::Eligible
+    } else if percent_elapsed >= IMMINENT_PERCENT {
+        InactivityLevel::Imminent
+    } else if percent_elapsed >= APPROACHING_PERCENT {
+        InactivityLevel::Approaching
+    } else {
+        InactivityLevel::OwnerActive
+    };
+
+    // A missing header only costs the dates
+    let block_time = |height: u64| {
+        u32::try_from(height)
+            .ok()
+            .and_then(|h| chain.block_time(h).ok())
+    };
+    let projected_eligible_time = block_time(status.current_height).map(|tip_time| {
+        let offset = status.blocks_remaining.unsigned_abs() * block_interval_secs(vault.network);
+        if status.blocks_remaining >= 0 {
+            tip_time.saturating_add(offset)
+        } else {
+            tip_time.saturating_sub(offset)
+        }
+    });
+
+    Ok(InactivityReport {
+        level,
+        last_refresh_height: Some(status.confirmation_height),
+        last_refresh_time: block_time(status.confirmation_height),
+        days_since_refresh: blocks_to_days(elapsed_blocks as i64, vault.network),
+        timelock_days,
+        percent_elapsed,
+        projected_eligible_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::Network;
+
+    fn report_at(height: u64) -> InactivityReport {
+        // Test vectors use a 144-block timelock
+        let v = generate_test_vectors(13).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(height);
+        sim.add_utxo(v.vault_address, "d1".repeat(32), 0, 50_000, 1_000)
+            .unwrap();
+        inactivity_report(v.backup_json, &Backend::simulated(&sim)).unwrap()
+    }
+
+    #[test]
+    fn test_inactivity_levels() {
+        let fresh = report_at(1_036);
+        assert_eq!(fresh.level, InactivityLevel::OwnerActive);
+        assert_eq!(fresh.percent_elapsed, 25.0);
+        assert_eq!(fresh.last_refresh_height, Some(1_000));
+
+        assert_eq!(report_at(1_110).level, InactivityLevel::Approaching);
+        assert_eq!(report_at(1_135).level, InactivityLevel::Imminent);
+
+        let expired = report_at(2_000);
+        assert_eq!(expired.level, InactivityLevel::Eligible);
+        assert_eq!(expired.percent_elapsed, 100.0);
+    }
+
+    #[test]
+    fn test_projected_date_follows_block_interval() {
+        let report = report_at(1_036);
+        let tip_time = report.last_refresh_time.unwrap() + 36 * 600;
+        let expected = tip_time + 108 * block_interval_secs(bitcoin::Network::Testnet);
+        assert_eq!(report.projected_eligible_time, Some(expected));
+    }
+}
@@ -0,0 +1,269 @@
+//! A second pair of eyes before broadcast.
+//!
+//! A claim's input values and scripts come from the server the heir's app
+//! talks to. A compromised Electrum server could feed false witness UTXOs
+//! and have the heir sign away more in fees than they see. Taproot
+//! signatures commit to every spent output, so
+//! [`verify_against_independent_source`] fetches the prevouts from a
+//! separate Esplora instance and checks that the signatures in the final
+//! transaction verify against them. If the two sources disagree, they do
+//! not.
+
+use std::collections::BTreeMap;
+
+use bitcoin::hashes::Hash;
+use bitcoin::key::XOnlyPublicKey;
+use bitcoin::secp256k1::{Message, Secp256k1, VerifyOnly};
+use bitcoin::sighash::{Prevouts, SighashCache};
+use bitcoin::taproot::{ControlBlock, LeafVersion, Signature, TapLeafHash, TAPROOT_ANNEX_PREFIX};
+use bitcoin::{Amount, ScriptBuf, Transaction, TxOut, Txid};
+use serde::{Deserialize, Serialize};
+
+use super::backend::ChainBackend;
+use super::policy::analyze_leaf_script;
+use super::{decode_tx, Backend, ErrorKind, HeirError, Network};
+
+/// One input as the independent source sees it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndependentInputCheck {
+    pub outpoint: String,
+    pub value_sat: u64,
+    pub script_pubkey_hex: String,
+    /// Whether the input's signatures verify against the prevouts from the
+    /// independent source; `None` for inputs that are not taproot spends.
+    pub signatures_commit: Option<bool>,
+}
+
+/// Result of [`verify_against_independent_source`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndependentCheck {
+    pub txid: String,
+    pub inputs: Vec<IndependentInputCheck>,
+    /// Fee by the independent source's input values.
+    pub fee_sat: Option<u64>,
+    pub fee_rate_sat_vb: Option<f64>,
+    /// False if any signature failed or the outputs exceed the inputs.
+    pub passed: bool,
+    pub problems: Vec<String>,
+}
+
+/// Output key of a P2TR script, or `None` for other scripts.
+fn taproot_output_key(script: &ScriptBuf) -> Option<XOnlyPublicKey> {
+    if !script.is_p2tr() {
+        return None;
+    }
+    XOnlyPublicKey::from_slice(&script.as_bytes()[2..]).ok()
+}
+
+/// Whether the taproot signatures on input `index` verify against
+/// `prevouts`. Key-path spends are checked against the output key,
+/// script-path spends against the leaf keys after the control block is
+/// checked against the output key.
+fn signatures_commit(
+    secp: &Secp256k1<VerifyOnly>,
+    tx: &Transaction,
+    index: usize,
+    prevouts: &[TxOut],
+) -> Option<bool> {
+    let output_key = taproot_output_key(&prevouts[index].script_pubkey)?;
+    let mut witness: Vec<&[u8]> = tx.input[index].witness.iter().collect();
+    if witness.len() >= 2 && witness.last().and_then(|e| e.first()) == Some(&TAPROOT_ANNEX_PREFIX) {
+        witness.pop();
+    }
+    let mut cache = SighashCache::new(tx);
+    let prevouts = Prevouts::All(prevouts);
+
+    if let [sig] = witness.as_slice() {
+        let Ok(sig) = Signature::from_slice(sig) else {
+            return Some(false);
+        };
+        let valid = cache
+            .taproot_key_spend_signature_hash(index, &prevouts, sig.sighash_type)
+            .is_ok_and(|sighash| {
+                let msg = Message::from_digest(sighash.to_byte_array());
+                secp.verify_schnorr(&sig.signature, &msg, &output_key)
+                    .is_ok()
+            });
+        return Some(valid);
+    }
+
+    let [sigs @ .., script, control] = &witness[..] else {
+        return Some(false);
+    };
+    let script = ScriptBuf::from_bytes(script.to_vec());
+    let Ok(control) = ControlBlock::decode(control) else {
+        return Some(false);
+    };
+    if !control.verify_taproot_commitment(secp, output_key, &script) {
+        return Some(false);
+    }
+    let keys = analyze_leaf_script(&script).keys;
+    let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+    // Absent signatures in a threshold leaf are empty elements
+    let sigs: Vec<&[u8]> = sigs.iter().copied().filter(|s| !s.is_empty()).collect();
+    if sigs.is_empty() {
+        return Some(false);
+    }
+    let valid = sigs.into_iter().all(|sig| {
+        let Ok(sig) = Signature::from_slice(sig) else {
+            return false;
+        };
+        let Ok(sighash) = cache.taproot_script_spend_signature_hash(
+            index,
+            &prevouts,
+            leaf_hash,
+            sig.sighash_type,
+        ) else {
+            return false;
+        };
+        let msg = Message::from_digest(sighash.to_byte_array());
+        keys.iter()
+            .any(|key| secp.verify_schnorr(&sig.signature, &msg, key).is_ok())
+    });
+    Some(valid)
+}
+
+/// Check `tx` against the prevouts `source` reports.
+pub(crate) fn cross_check(
+    tx: &Transaction,
+    source: &dyn ChainBackend,
+) -> Result<IndependentCheck, HeirError> {
+    let mut txids: Vec<Txid> = tx.input.iter().map(|i| i.previous_output.txid).collect();
+    txids.sort();
+    txids.dedup();
+    let parents: BTreeMap<Txid, Transaction> = txids
+        .iter()
+        .copied()
+        .zip(source.transactions(&txids)?)
+        .collect();
+
+    let prevouts = tx
+        .input
+        .iter()
+        .map(|input| {
+            let outpoint = input.previous_output;
+            parents[&outpoint.txid]
+                .output
+                .get(outpoint.vout as usize)
+                .cloned()
+                .ok_or_else(|| {
+                    HeirError::new(
+                        ErrorKind::ServerQuery,
+                        format!("The independent source has no output {}", outpoint),
+                    )
+                })
+        })
+        .collect::<Result<Vec<TxOut>, HeirError>>()?;
+
+    let secp = Secp256k1::verification_only();
+    let mut problems = Vec::new();
+    let inputs: Vec<IndependentInputCheck> = tx
+        .input
+        .iter()
+        .zip(&prevouts)
+        .enumerate()
+        .map(|(index, (input, prevout))| {
+            let commits = signatures_commit(&secp, tx, index, &prevouts);
+            match commits {
+                Some(false) => problems.push(format!(
+                    "Input {} is signed for a different amount or script than the \
+                     independent source reports",
+                    index
+                )),
+                None => problems.push(format!(
+                    "Input {} is not a taproot spend and could not be checked",
+                    index
+                )),
+                Some(true) => {}
+            }
+            IndependentInputCheck {
+                outpoint: input.previous_output.to_string(),
+                value_sat: prevout.value.to_sat(),
+                script_pubkey_hex: prevout.script_pubkey.to_hex_string(),
+                signatures_commit: commits,
+            }
+        })
+        .collect();
+
+    let input_sat: Amount = prevouts.iter().map(|p| p.value).sum();
+    let output_sat: Amount = tx.output.iter().map(|o| o.value).sum();
+    let fee_sat = input_sat.checked_sub(output_sat).map(Amount::to_sat);
+    if fee_sat.is_none() {
+        problems.push(format!(
+            "Outputs ({} sat) exceed the inputs the independent source reports ({} sat)",
+            output_sat.to_sat(),
+            input_sat.to_sat()
+        ));
+    }
+    let passed = fee_sat.is_some()
+        && inputs
+            .iter()
+            .all(|input| input.signatures_commit == Some(true));
+    Ok(IndependentCheck {
+        txid: tx.compute_txid().to_string(),
+        inputs,
+        fee_sat,
+        fee_rate_sat_vb: fee_sat.map(|fee| fee as f64 / tx.vsize() as f64),
+        passed,
+        problems,
+    })
+}
+
+/// Cross-check a final claim transaction against the Esplora instance at
+/// `esplora_url` before broadcasting it through another server.
+pub fn verify_against_independent_source(
+    tx_hex: String,
+    esplora_url: String,
+    network: Network,
+) -> Result<IndependentCheck, HeirError> {
+    let tx = decode_tx(&tx_hex)?;
+    let source = Backend::esplora(esplora_url, network)?;
+    cross_check(&tx, source.chain())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+
+    fn source(seed: u32, value_sat: u64) -> (Transaction, SimulatedBackend) {
+        let v = generate_test_vectors(seed).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address,
+            txid.into(),
+            vout.parse().unwrap(),
+            value_sat,
+            1,
+        )
+        .unwrap();
+        (decode_tx(&v.tx_hex).unwrap(), sim)
+    }
+
+    #[test]
+    fn test_honest_source_agrees() {
+        let value = generate_test_vectors(58).unwrap().funding_value_sat;
+        let (tx, sim) = source(58, value);
+        let check = cross_check(&tx, Backend::simulated(&sim).chain()).unwrap();
+        assert!(check.passed, "{:?}", check.problems);
+        assert_eq!(check.inputs[0].signatures_commit, Some(true));
+        assert_eq!(check.inputs[0].value_sat, value);
+        assert!(check.fee_sat.unwrap() > 0);
+    }
+
+    #[test]
+    fn test_disagreeing_source_fails() {
+        let value = generate_test_vectors(59).unwrap().funding_value_sat;
+        let (tx, sim) = source(59, value + 50_000);
+        let check = cross_check(&tx, Backend::simulated(&sim).chain()).unwrap();
+        assert!(!check.passed);
+        assert_eq!(check.inputs[0].signatures_commit, Some(false));
+        assert_eq!(check.problems.len(), 1);
+
+        sim.set_offline(true);
+        let err = cross_check(&tx, Backend::simulated(&sim).chain()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Connection);
+    }
+}
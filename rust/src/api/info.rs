@@ -0,0 +1,117 @@
+//! What this build of the core supports.
+//!
+//! The app gates UI on these answers and attaches them to bug reports, so
+//! they describe the compiled library, not the app around it.
+
+use serde::{Deserialize, Serialize};
+
+use super::schema::CURRENT_BACKUP_VERSION;
+
+/// Version and capabilities of the compiled library.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryInfo {
+    pub version: String,
+    /// Commit the library was built from; set via `NOSTRING_HEIR_GIT_HASH`
+    /// at build time, otherwise "unknown".
+    pub git_hash: String,
+    pub supported_backup_versions: Vec<u32>,
+    /// Names accepted wherever a network is passed.
+    pub supported_networks: Vec<String>,
+    /// Optional Cargo features compiled in.
+    pub features: Vec<String>,
+    /// Chain backends compiled in. Offer only these in the UI.
+    pub backends: Vec<BackendKind>,
+}
+
+/// A kind of chain data source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackendKind {
+    Electrum,
+    Esplora,
+    /// [`SimulatedBackend`](super::simulated::SimulatedBackend), always present.
+    Simulated,
+}
+
+/// Backends this build can construct.
+pub fn compiled_backends() -> Vec<BackendKind> {
+    let mut backends = Vec::new();
+    if cfg!(feature = "electrum") {
+        backends.push(BackendKind::Electrum);
+    }
+    if cfg!(feature = "esplora") {
+        backends.push(BackendKind::Esplora);
+    }
+    backends.push(BackendKind::Simulated);
+    backends
+}
+
+/// Backup format versions [`import_vault_backup`](super::import_vault_backup) reads:
+/// every version up to the one [`super::schema`] writes.
+pub(crate) fn supported_backup_versions() -> Vec<u32> {
+    (1..=CURRENT_BACKUP_VERSION).collect()
+}
+
+/// Describe this build.
+pub fn get_library_info() -> LibraryInfo {
+    let mut features = Vec::new();
+    if cfg!(feature = "electrum") {
+        features.push("electrum".to_string());
+    }
+    if cfg!(feature = "esplora") {
+        features.push("esplora".to_string());
+    }
+    if cfg!(feature = "nostr") {
+        features.push("nostr".to_string());
+    }
+    if cfg!(feature = "tracing") {
+        features.push("tracing".to_string());
+    }
+
+    LibraryInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: option_env!("NOSTRING_HEIR_GIT_HASH")
+            .unwrap_or("unknown")
+            .to_string(),
+        supported_backup_versions: supported_backup_versions(),
+        supported_networks: ["bitcoin", "testnet", "testnet4", "signet", "regtest"]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        features,
+        backends: compiled_backends(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{parse_network, Network};
+
+    #[test]
+    fn test_library_info() {
+        let info = get_library_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_hash.is_empty());
+        assert!(info.supported_backup_versions.contains(&1));
+        assert!(info
+            .supported_backup_versions
+            .contains(&CURRENT_BACKUP_VERSION));
+        for network in &info.supported_networks {
+            assert!(parse_network(network).is_ok(), "{}", network);
+        }
+        assert_eq!(info.features.contains(&"tracing".to_string()), cfg!(feature = "tracing"));
+    }
+
+    #[test]
+    fn test_compiled_backends_match_constructors() {
+        let backends = compiled_backends();
+        assert!(backends.contains(&BackendKind::Simulated));
+        let electrum = crate::api::Backend::electrum("tcp://localhost:1".into(), Network::Mainnet);
+        assert_eq!(backends.contains(&BackendKind::Electrum), electrum.is_ok());
+        if let Err(err) = electrum {
+            assert_eq!(err.kind, crate::api::ErrorKind::BackendUnavailable);
+        }
+        let esplora = crate::api::Backend::esplora("https://localhost/api".into(), Network::Mainnet);
+        assert_eq!(backends.contains(&BackendKind::Esplora), esplora.is_ok());
+    }
+}
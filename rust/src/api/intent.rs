@@ -0,0 +1,294 @@
+//! Claim-intent files for co-heir review.
+//!
+//! The heir proposing a claim exports one JSON file holding the PSBT, what
+//! it pays and costs, and the policy it spends under. A co-heir reviews the
+//! file against their own copy of the backup rather than trusting the email
+//! it arrived in: the seal, the vault, the amounts and every signature
+//! already on the PSBT are checked again.
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{Address, Network, Psbt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::canonical::to_canonical_string;
+use super::invariants::{verify_claim_invariants, CheckStatus, InvariantReport};
+use super::policy::describe_policy;
+use super::psbt::{psbt_fingerprint, verify_partial_sig};
+use super::store::now;
+use super::{
+    decode_psbt, import_vault_backup, parse_backup, parse_network, ClaimOutput, ErrorKind,
+    HeirError,
+};
+
+/// Format tag of a claim-intent file.
+pub const CLAIM_INTENT_FORMAT: &str = "nostring-claim-intent-v1";
+
+/// A proposed claim, as sent to co-heirs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClaimIntent {
+    pub format: String,
+    pub network: String,
+    pub vault_address: String,
+    /// `content_hash` of the backup the claim was built from.
+    pub vault_content_hash: String,
+    pub psbt_base64: String,
+    /// [`psbt_fingerprint`] of the PSBT, for reading aloud.
+    pub psbt_fingerprint: String,
+    pub outputs: Vec<ClaimOutput>,
+    pub fee_sat: u64,
+    pub policy_summary: String,
+    /// Heirs whose valid signatures the PSBT carries on every input.
+    pub signed_by: Vec<String>,
+    /// Unix seconds.
+    pub created_at: u64,
+    /// SHA-256 of the canonical JSON of every other field.
+    pub seal: String,
+}
+
+/// Outcome of [`review_claim_intent`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntentReview {
+    /// The seal matches the file's contents.
+    pub intact: bool,
+    /// The claim was built from the same backup as the reviewer's.
+    pub same_vault: bool,
+    /// Outputs, fee and fingerprint match what the PSBT really does.
+    pub matches_psbt: bool,
+    pub invariants: InvariantReport,
+    /// Heirs whose signatures verify on every input.
+    pub signed_by: Vec<String>,
+    /// Everything that disagreed, in plain language.
+    pub problems: Vec<String>,
+    /// True if nothing above disagreed: safe to add a signature.
+    pub approved: bool,
+}
+
+fn invalid(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidInput, message)
+}
+
+/// Seal over every field but the seal itself.
+fn seal_of(intent: &ClaimIntent) -> Result<String, HeirError> {
+    let mut value = serde_json::to_value(intent)
+        .map_err(|e| HeirError::new(ErrorKind::Internal, format!("Serialization failed: {}", e)))?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("seal");
+    }
+    Ok(sha256::Hash::hash(to_canonical_string(&value).as_bytes()).to_string())
+}
+
+/// Outputs and fee the PSBT actually pays.
+fn psbt_amounts(psbt: &Psbt, network: Network) -> Result<(Vec<ClaimOutput>, u64), HeirError> {
+    let outputs = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .map(|txout| {
+            let address = Address::from_script(&txout.script_pubkey, network)
+                .map(|a| a.to_string())
+                .unwrap_or_else(|_| txout.script_pubkey.to_hex_string());
+            ClaimOutput {
+                address,
+                amount_sat: txout.value.to_sat(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let input_sat = psbt
+        .inputs
+        .iter()
+        .map(|input| input.witness_utxo.as_ref().map(|utxo| utxo.value.to_sat()))
+        .sum::<Option<u64>>()
+        .ok_or_else(|| invalid("Every PSBT input needs its witness UTXO"))?;
+    let output_sat: u64 = outputs.iter().map(|o| o.amount_sat).sum();
+    Ok((outputs, input_sat.saturating_sub(output_sat)))
+}
+
+/// Labels of heirs with a valid signature on every input.
+fn verified_signers(psbt_base64: &str, vault_json: &str) -> Result<Vec<String>, HeirError> {
+    let backup = parse_backup(vault_json)?;
+    let inputs = decode_psbt(psbt_base64)?.inputs.len();
+    let mut signers = Vec::new();
+    for heir in &backup.heirs {
+        let mut signed = inputs > 0;
+        for index in 0..inputs {
+            let check = verify_partial_sig(
+                psbt_base64.to_string(),
+                index,
+                heir.fingerprint.clone(),
+                vault_json.to_string(),
+            )?;
+            signed &= check.valid;
+        }
+        if signed {
+            signers.push(heir.label.clone());
+        }
+    }
+    Ok(signers)
+}
+
+/// Bundle a claim PSBT with what it pays, its fee and the vault policy into
+/// a sealed file for co-heirs.
+pub fn export_claim_intent(psbt_base64: String, vault_json: String) -> Result<String, HeirError> {
+    let info = import_vault_backup(vault_json.clone())?;
+    let network = parse_network(&info.network)?;
+    let psbt = decode_psbt(&psbt_base64)?;
+    let (outputs, fee_sat) = psbt_amounts(&psbt, network)?;
+
+    let mut intent = ClaimIntent {
+        format: CLAIM_INTENT_FORMAT.to_string(),
+        network: info.network.clone(),
+        vault_address: info.vault_address.clone(),
+        vault_content_hash: info.content_hash.clone(),
+        psbt_fingerprint: psbt_fingerprint(psbt_base64.clone())?,
+        outputs,
+        fee_sat,
+        policy_summary: describe_policy(vault_json.clone())?.summary,
+        signed_by: verified_signers(&psbt_base64, &vault_json)?,
+        psbt_base64,
+        created_at: now(),
+        seal: String::new(),
+    };
+    intent.seal = seal_of(&intent)?;
+    serde_json::to_string_pretty(&intent)
+        .map_err(|e| HeirError::new(ErrorKind::Internal, format!("Serialization failed: {}", e)))
+}
+
+/// Check a claim-intent file against the reviewer's own copy of the backup.
+///
+/// Files that cannot be read are errors; disagreements are reported in the
+/// review.
+pub fn review_claim_intent(bundle: String, vault_json: String) -> Result<IntentReview, HeirError> {
+    let value: Value = serde_json::from_str(&bundle)
+        .map_err(|_| HeirError::new(ErrorKind::UnrecognizedFormat, "Not a claim-intent file"))?;
+    if value.get("format").and_then(Value::as_str) != Some(CLAIM_INTENT_FORMAT) {
+        return Err(HeirError::new(
+            ErrorKind::UnrecognizedFormat,
+            "Not a claim-intent file",
+        ));
+    }
+    let intent: ClaimIntent = serde_json::from_value(value).map_err(|e| {
+        HeirError::new(
+            ErrorKind::InvalidEncoding,
+            format!("Invalid claim intent: {}", e),
+        )
+    })?;
+
+    let info = import_vault_backup(vault_json.clone())?;
+    let network = parse_network(&info.network)?;
+    let psbt = decode_psbt(&intent.psbt_base64)?;
+    let mut problems = Vec::new();
+
+    let intact = seal_of(&intent)? == intent.seal;
+    if !intact {
+        problems.push("The file was changed after it was exported".to_string());
+    }
+    let same_vault = intent.vault_content_hash == info.content_hash
+        && intent.vault_address == info.vault_address;
+    if !same_vault {
+        problems.push("The claim was built from a different backup than yours".to_string());
+    }
+
+    let (outputs, fee_sat) = psbt_amounts(&psbt, network)?;
+    let mut matches_psbt = true;
+    if outputs != intent.outputs {
+        matches_psbt = false;
+        problems.push("The stated payouts differ from what the PSBT pays".to_string());
+    }
+    if fee_sat != intent.fee_sat {
+        matches_psbt = false;
+        problems.push(format!(
+            "The stated fee is {} sats but the PSBT pays {} sats",
+            intent.fee_sat, fee_sat
+        ));
+    }
+    if psbt_fingerprint(intent.psbt_base64.clone())? != intent.psbt_fingerprint {
+        matches_psbt = false;
+        problems.push("The stated fingerprint differs from the PSBT's".to_string());
+    }
+
+    let approved_destinations = outputs.iter().map(|o| o.address.clone()).collect();
+    let invariants = verify_claim_invariants(
+        vault_json.clone(),
+        intent.psbt_base64.clone(),
+        approved_destinations,
+    )?;
+    problems.extend(
+        invariants
+            .checks
+            .iter()
+            .filter(|c| c.status == CheckStatus::Failed)
+            .map(|c| c.detail.clone()),
+    );
+
+    let signed_by = verified_signers(&intent.psbt_base64, &vault_json)?;
+    if let Some(claimed) = intent
+        .signed_by
+        .iter()
+        .find(|label| !signed_by.contains(label))
+    {
+        problems.push(format!(
+            "{} is listed as a signer but has no valid signature",
+            claimed
+        ));
+    }
+
+    Ok(IntentReview {
+        intact,
+        same_vault,
+        matches_psbt,
+        approved: problems.is_empty(),
+        invariants,
+        signed_by,
+        problems,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::psbt::tests::external_sign;
+    use crate::api::vectors::generate_test_vectors;
+    use base64::Engine;
+
+    #[test]
+    fn test_intent_round_trip_approves() {
+        let v = generate_test_vectors(26).unwrap();
+        let mut psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        external_sign(&mut psbt, 0, &v.heir_secret_key_hex);
+        let signed = base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+
+        let bundle = export_claim_intent(signed, v.backup_json.clone()).unwrap();
+        let intent: ClaimIntent = serde_json::from_str(&bundle).unwrap();
+        assert_eq!(intent.signed_by, vec!["Synthetic Heir"]);
+        assert_eq!(intent.fee_sat, v.fee_sat);
+
+        let review = review_claim_intent(bundle, v.backup_json).unwrap();
+        assert!(review.approved, "{:?}", review.problems);
+        assert_eq!(review.signed_by, vec!["Synthetic Heir"]);
+    }
+
+    #[test]
+    fn test_review_catches_edits_and_other_vaults() {
+        let v = generate_test_vectors(27).unwrap();
+        let bundle = export_claim_intent(v.unsigned_psbt_base64, v.backup_json.clone()).unwrap();
+
+        // Lower the stated fee without resealing
+        let mut value: Value = serde_json::from_str(&bundle).unwrap();
+        value["fee_sat"] = serde_json::json!(1);
+        let review = review_claim_intent(value.to_string(), v.backup_json.clone()).unwrap();
+        assert!(!review.intact && !review.matches_psbt && !review.approved);
+
+        let other = generate_test_vectors(28).unwrap();
+        let review = review_claim_intent(bundle, other.backup_json).unwrap();
+        assert!(!review.same_vault);
+        assert!(!review.approved);
+
+        assert_eq!(
+            review_claim_intent("{}".into(), v.backup_json)
+                .unwrap_err()
+                .kind,
+            ErrorKind::UnrecognizedFormat
+        );
+    }
+}
@@ -0,0 +1,606 @@
+//! Crate-level safety rules for claim transactions, checked in one place.
+//!
+//! [`verify_claim_invariants`] is what the app's final confirmation screen
+//! runs before broadcast, and what an auditor can run on any claim artifact.
+
+use std::str::FromStr;
+
+use bitcoin::consensus::Decodable;
+use bitcoin::psbt::Input;
+use bitcoin::relative::LockTime as RelativeLockTime;
+use bitcoin::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::{Psbt, Script, ScriptBuf, Transaction, TxIn, TxOut};
+use serde::{Deserialize, Serialize};
+
+use super::allocation::{allocation_outputs, heir_allocations};
+use super::limits::SafetyLimits;
+use super::policy::analyze_leaf_script;
+use super::sponsor::{is_sponsor_change, is_sponsor_input};
+use super::{
+    decode_psbt, parse_backup, parse_network, recovery_tree_depth, split_amounts, verified_vault,
+    ErrorKind, HeirError, PSBT_HEX_MAGIC,
+};
+
+/// Safety rule checked by [`verify_claim_invariants`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClaimInvariant {
+    /// Every output pays one of the approved destinations, except a fee
+    /// sponsor's change when the whole vault reaches them.
+    ApprovedDestinations,
+    /// The claim has outputs, and no more than one per approved destination
+    /// besides a fee sponsor's change.
+    NoExtraOutputs,
+    /// Every input but a fee sponsor's spends an output of this vault.
+    InputsFromVault,
+    /// Every vault input's nSequence encodes at least the CSV delay of the
+    /// recovery leaf it spends.
+    SequencesEncodeTimelock,
+    /// The fee is positive and within the fee-rate safety limit.
+    FeeWithinCap,
+    /// Each heir's payout address receives the share recorded in the backup.
+    AllocationsHonored,
+}
+
+/// Result of one rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// Not checkable from this artifact (e.g. a raw transaction has no prevouts).
+    Skipped,
+}
+
+/// Outcome of a single rule with a human-readable explanation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvariantCheck {
+    pub invariant: ClaimInvariant,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+/// Outcome of all rules. `passed` is false if any rule failed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvariantReport {
+    pub passed: bool,
+    pub checks: Vec<InvariantCheck>,
+}
+
+/// A claim as a PSBT (prevouts known) or a bare transaction.
+enum ClaimArtifact {
+    Psbt(Psbt),
+    Tx(Transaction),
+}
+
+impl ClaimArtifact {
+    fn parse(input: &str) -> Result<Self, HeirError> {
+        let trimmed = input.trim();
+        let is_psbt_hex = trimmed
+            .get(..PSBT_HEX_MAGIC.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(PSBT_HEX_MAGIC));
+        if !is_psbt_hex && trimmed.chars().all(|c| c.is_ascii_hexdigit()) {
+            let bytes = hex::decode(trimmed).map_err(|e| {
+                HeirError::new(ErrorKind::InvalidEncoding, format!("Invalid hex: {}", e))
+            })?;
+            let tx = Transaction::consensus_decode(&mut bytes.as_slice()).map_err(|e| {
+                HeirError::new(
+                    ErrorKind::InvalidTransaction,
+                    format!("Invalid transaction: {}", e),
+                )
+            })?;
+            return Ok(ClaimArtifact::Tx(tx));
+        }
+        decode_psbt(trimmed).map(ClaimArtifact::Psbt)
+    }
+
+    fn tx(&self) -> &Transaction {
+        match self {
+            ClaimArtifact::Psbt(psbt) => &psbt.unsigned_tx,
+            ClaimArtifact::Tx(tx) => tx,
+        }
+    }
+
+    /// Spent outputs, if every input declares one.
+    fn prevouts(&self) -> Option<Vec<TxOut>> {
+        match self {
+            ClaimArtifact::Psbt(psbt) => psbt
+                .inputs
+                .iter()
+                .map(|input| input.witness_utxo.clone())
+                .collect(),
+            ClaimArtifact::Tx(_) => None,
+        }
+    }
+
+    /// Inputs recorded as the fee sponsor's that do not spend
+    /// `vault_script`. Only a PSBT records a sponsor.
+    fn sponsor_inputs(&self, vault_script: &Script) -> Vec<usize> {
+        match self {
+            ClaimArtifact::Psbt(psbt) => (0..psbt.inputs.len())
+                .filter(|&index| {
+                    is_sponsor_input(psbt, index)
+                        && psbt.inputs[index]
+                            .witness_utxo
+                            .as_ref()
+                            .is_some_and(|utxo| utxo.script_pubkey.as_script() != vault_script)
+                })
+                .collect(),
+            ClaimArtifact::Tx(_) => Vec::new(),
+        }
+    }
+
+    /// Outputs recorded as the fee sponsor's change.
+    fn sponsor_change(&self) -> Vec<usize> {
+        match self {
+            ClaimArtifact::Psbt(psbt) => (0..psbt.outputs.len())
+                .filter(|&index| is_sponsor_change(psbt, index))
+                .collect(),
+            ClaimArtifact::Tx(_) => Vec::new(),
+        }
+    }
+}
+
+fn check(invariant: ClaimInvariant, passed: bool, detail: String) -> InvariantCheck {
+    InvariantCheck {
+        invariant,
+        status: if passed {
+            CheckStatus::Passed
+        } else {
+            CheckStatus::Failed
+        },
+        detail,
+    }
+}
+
+fn skipped(invariant: ClaimInvariant, detail: &str) -> InvariantCheck {
+    InvariantCheck {
+        invariant,
+        status: CheckStatus::Skipped,
+        detail: detail.to_string(),
+    }
+}
+
+/// Shortest CSV delay among the vault leaves `txin` may spend: the leaf in
+/// its witness once signed, the leaves its signatures are on, or else any
+/// of `recovery_scripts`. `None` if none of them has a block delay.
+fn required_csv(
+    txin: &TxIn,
+    psbt_input: Option<&Input>,
+    recovery_scripts: &[ScriptBuf],
+) -> Option<u32> {
+    let witness = psbt_input
+        .and_then(|input| input.final_script_witness.as_ref())
+        .unwrap_or(&txin.witness);
+    let signed_leaves: Vec<TapLeafHash> = psbt_input
+        .map(|input| {
+            input
+                .tap_script_sigs
+                .keys()
+                .map(|(_, leaf)| *leaf)
+                .collect()
+        })
+        .unwrap_or_default();
+    let candidates: Vec<&Script> = match witness.tapscript() {
+        Some(script) => vec![script],
+        None => recovery_scripts
+            .iter()
+            .filter(|script| {
+                signed_leaves.is_empty()
+                    || signed_leaves
+                        .contains(&TapLeafHash::from_script(script, LeafVersion::TapScript))
+            })
+            .map(ScriptBuf::as_script)
+            .collect(),
+    };
+    candidates
+        .into_iter()
+        .filter_map(|script| analyze_leaf_script(script).csv_blocks)
+        .min()
+}
+
+/// Check a claim (base64 PSBT or raw transaction hex) against the vault's
+/// safety rules.
+///
+/// Structural problems (bad backup, undecodable artifact, invalid approved
+/// address) are errors; rule violations are reported in the result. Only a
+/// PSBT shows which output is a fee sponsor's change; for a raw sponsored
+/// transaction, approve the sponsor's change address too.
+pub fn verify_claim_invariants(
+    vault_json: String,
+    psbt_or_tx: String,
+    approved_destinations: Vec<String>,
+) -> Result<InvariantReport, HeirError> {
+    verify_claim_invariants_with_limits(vault_json, psbt_or_tx, approved_destinations, None)
+}
+
+/// [`verify_claim_invariants`] against the fee ceiling the claim was built
+/// with, for claims built under an acknowledged override.
+pub fn verify_claim_invariants_with_limits(
+    vault_json: String,
+    psbt_or_tx: String,
+    approved_destinations: Vec<String>,
+    safety_limits: Option<SafetyLimits>,
+) -> Result<InvariantReport, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let (vault_address, recovery_scripts) = verified_vault(&vault_json, &backup)?;
+    let network = parse_network(&backup.network)?;
+    let max_fee_rate = SafetyLimits::resolve(safety_limits.as_ref(), network)?.max_fee_rate_sat_vb;
+
+    let approved: Vec<ScriptBuf> = approved_destinations
+        .iter()
+        .map(|addr| {
+            bitcoin::Address::from_str(addr)
+                .map_err(|e| {
+                    HeirError::new(
+                        ErrorKind::InvalidAddress,
+                        format!("Invalid approved destination {}: {}", addr, e),
+                    )
+                })?
+                .require_network(network)
+                .map(|a| a.script_pubkey())
+                .map_err(|e| {
+                    HeirError::new(
+                        ErrorKind::NetworkMismatch,
+                        format!("Approved destination {} is for another network: {}", addr, e),
+                    )
+                })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let artifact = ClaimArtifact::parse(&psbt_or_tx)?;
+    let tx = artifact.tx();
+    let prevouts = artifact.prevouts();
+    let vault_script = vault_address.script_pubkey();
+    let sponsor_inputs = artifact.sponsor_inputs(&vault_script);
+    let mut checks = Vec::new();
+
+    // The sponsor's change may go anywhere, as long as every sat of the
+    // vault inputs reaches the other outputs
+    let sponsor_change = match (&prevouts, artifact.sponsor_change()) {
+        (Some(prevouts), change) if !sponsor_inputs.is_empty() && change.len() == 1 => {
+            let vault_sat: u64 = prevouts
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !sponsor_inputs.contains(index))
+                .map(|(_, p)| p.value.to_sat())
+                .sum();
+            let paid_sat: u64 = tx
+                .output
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !change.contains(index))
+                .map(|(_, o)| o.value.to_sat())
+                .sum();
+            if paid_sat >= vault_sat {
+                change
+            } else {
+                Vec::new()
+            }
+        }
+        _ => Vec::new(),
+    };
+    let payouts: Vec<&TxOut> = tx
+        .output
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !sponsor_change.contains(index))
+        .map(|(_, o)| o)
+        .collect();
+
+    // Destinations
+    let unapproved = payouts
+        .iter()
+        .filter(|o| !approved.contains(&o.script_pubkey))
+        .count();
+    checks.push(check(
+        ClaimInvariant::ApprovedDestinations,
+        unapproved == 0,
+        format!("{} of {} output(s) pay an unapproved script", unapproved, payouts.len()),
+    ));
+
+    checks.push(check(
+        ClaimInvariant::NoExtraOutputs,
+        !payouts.is_empty() && payouts.len() <= approved.len(),
+        format!(
+            "{} output(s) for {} approved destination(s)",
+            payouts.len(),
+            approved.len()
+        ),
+    ));
+
+    // Inputs
+    match &prevouts {
+        Some(prevouts) => {
+            let foreign = prevouts
+                .iter()
+                .enumerate()
+                .filter(|(index, p)| {
+                    !sponsor_inputs.contains(index) && p.script_pubkey != vault_script
+                })
+                .count();
+            let vault_inputs = prevouts.len() - sponsor_inputs.len();
+            checks.push(check(
+                ClaimInvariant::InputsFromVault,
+                foreign == 0 && vault_inputs > 0,
+                format!("{} of {} input(s) spend a non-vault script", foreign, vault_inputs),
+            ));
+        }
+        None => checks.push(skipped(
+            ClaimInvariant::InputsFromVault,
+            "Spent outputs are unknown for a raw transaction",
+        )),
+    }
+
+    let short = tx
+        .input
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !sponsor_inputs.contains(index))
+        .filter(|&(index, txin)| {
+            let psbt_input = match &artifact {
+                ClaimArtifact::Psbt(psbt) => psbt.inputs.get(index),
+                ClaimArtifact::Tx(_) => None,
+            };
+            let required = required_csv(txin, psbt_input, &recovery_scripts);
+            match (txin.sequence.to_relative_lock_time(), required) {
+                (Some(RelativeLockTime::Blocks(height)), Some(required)) => {
+                    u32::from(height.value()) < required
+                }
+                _ => true,
+            }
+        })
+        .count();
+    checks.push(check(
+        ClaimInvariant::SequencesEncodeTimelock,
+        short == 0 && tx.version.0 >= 2,
+        format!(
+            "{} of {} input(s) do not encode the CSV delay of their leaf (tx version {})",
+            short,
+            tx.input.len() - sponsor_inputs.len(),
+            tx.version.0
+        ),
+    ));
+
+    // Fee
+    match &prevouts {
+        Some(prevouts) => {
+            let input_sat: u64 = prevouts.iter().map(|p| p.value.to_sat()).sum();
+            let output_sat: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
+            let vbytes = match &artifact {
+                ClaimArtifact::Psbt(psbt)
+                    if psbt.inputs.iter().all(|i| i.final_script_witness.is_some()) =>
+                {
+                    psbt.clone()
+                        .extract_tx_unchecked_fee_rate()
+                        .vsize() as u64
+                }
+                _ => nostring_inherit::taproot::estimate_heir_claim_vbytes(
+                    tx.input.len(),
+                    tx.output.len(),
+                    recovery_tree_depth(&backup),
+                ) as u64,
+            };
+            match input_sat.checked_sub(output_sat) {
+                Some(fee) if fee > 0 => {
+                    let rate = fee as f64 / vbytes.max(1) as f64;
+                    checks.push(check(
+                        ClaimInvariant::FeeWithinCap,
+                        rate <= max_fee_rate as f64,
+                        format!(
+                            "Fee {} sat ≈ {:.1} sat/vB (cap {} sat/vB)",
+                            fee, rate, max_fee_rate
+                        ),
+                    ));
+                }
+                _ => checks.push(check(
+                    ClaimInvariant::FeeWithinCap,
+                    false,
+                    format!("Outputs ({} sat) exceed inputs ({} sat)", output_sat, input_sat),
+                )),
+            }
+        }
+        None => checks.push(skipped(
+            ClaimInvariant::FeeWithinCap,
+            "Input values are unknown for a raw transaction",
+        )),
+    }
+
+    // Allocations
+    if heir_allocations(&vault_json)?.is_some() {
+        let outputs = allocation_outputs(&vault_json)?;
+        let total_out: u64 = payouts.iter().map(|o| o.value.to_sat()).sum();
+        let shares: Vec<f64> = outputs.iter().map(|(_, share)| *share).collect();
+        let expected = split_amounts(total_out, &shares);
+        // Other builders may round each share differently by a sat
+        let off = outputs
+            .iter()
+            .zip(&expected)
+            .filter(|((address, _), &want)| {
+                let script = address.script_pubkey();
+                let paid: u64 = payouts
+                    .iter()
+                    .filter(|o| o.script_pubkey == script)
+                    .map(|o| o.value.to_sat())
+                    .sum();
+                paid.abs_diff(want) > outputs.len() as u64
+            })
+            .count();
+        checks.push(check(
+            ClaimInvariant::AllocationsHonored,
+            off == 0,
+            format!("{} of {} heir(s) not paid their recorded share", off, outputs.len()),
+        ));
+    } else {
+        checks.push(skipped(
+            ClaimInvariant::AllocationsHonored,
+            "The backup records no heir allocations",
+        ));
+    }
+
+    let passed = checks.iter().all(|c| c.status != CheckStatus::Failed);
+    Ok(InvariantReport { passed, checks })
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use bitcoin::Sequence;
+
+    use super::*;
+    use crate::api::executor::tests::executor_backup_json;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::sponsor::tests::sponsor;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::{
+        build_claim_psbt, build_claim_psbt_with_options, Backend, ClaimOptions, Network,
+    };
+
+    fn status(report: &InvariantReport, invariant: ClaimInvariant) -> CheckStatus {
+        report
+            .checks
+            .iter()
+            .find(|c| c.invariant == invariant)
+            .unwrap()
+            .status
+    }
+
+    #[test]
+    fn test_vector_claim_passes() {
+        let v = generate_test_vectors(5).unwrap();
+        let report = verify_claim_invariants(
+            v.backup_json.clone(),
+            v.unsigned_psbt_base64.clone(),
+            vec![v.destination.clone()],
+        )
+        .unwrap();
+        assert!(report.passed, "{:?}", report.checks);
+
+        let signed = verify_claim_invariants(
+            v.backup_json,
+            v.signed_psbt_base64,
+            vec![v.destination],
+        )
+        .unwrap();
+        assert!(signed.passed, "{:?}", signed.checks);
+    }
+
+    #[test]
+    fn test_unapproved_destination_fails() {
+        let v = generate_test_vectors(5).unwrap();
+        let other = generate_test_vectors(6).unwrap().destination;
+        let report =
+            verify_claim_invariants(v.backup_json, v.unsigned_psbt_base64, vec![other]).unwrap();
+        assert!(!report.passed);
+        assert_eq!(status(&report, ClaimInvariant::ApprovedDestinations), CheckStatus::Failed);
+    }
+
+    #[test]
+    fn test_raw_tx_skips_prevout_checks() {
+        let v = generate_test_vectors(5).unwrap();
+        let report =
+            verify_claim_invariants(v.backup_json, v.tx_hex, vec![v.destination]).unwrap();
+        assert!(report.passed);
+        assert_eq!(status(&report, ClaimInvariant::FeeWithinCap), CheckStatus::Skipped);
+        assert_eq!(status(&report, ClaimInvariant::InputsFromVault), CheckStatus::Skipped);
+        assert_eq!(
+            status(&report, ClaimInvariant::SequencesEncodeTimelock),
+            CheckStatus::Passed
+        );
+    }
+
+    #[test]
+    fn test_claim_from_other_vault_fails() {
+        let v = generate_test_vectors(5).unwrap();
+        let other = generate_test_vectors(6).unwrap();
+        let report = verify_claim_invariants(
+            v.backup_json,
+            other.unsigned_psbt_base64,
+            vec![other.destination],
+        )
+        .unwrap();
+        assert_eq!(status(&report, ClaimInvariant::InputsFromVault), CheckStatus::Failed);
+    }
+
+    #[test]
+    fn test_override_claim_checks_its_own_leaf() {
+        let json = executor_backup_json(26);
+        let v = generate_test_vectors(26).unwrap();
+        let address = serde_json::from_str::<serde_json::Value>(&json).unwrap()["vault_address"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(1_100);
+        sim.add_utxo(address, "c6".repeat(32), 0, 60_000, 1_000)
+            .unwrap();
+        let claim = build_claim_psbt(
+            json.clone(),
+            &Backend::simulated(&sim),
+            v.destination.clone(),
+            1,
+            2,
+        )
+        .unwrap();
+
+        // The override leaf's delay is shorter than the backup's
+        let report = verify_claim_invariants(
+            json.clone(),
+            claim.psbt_base64.clone(),
+            vec![v.destination.clone()],
+        )
+        .unwrap();
+        assert!(report.passed, "{:?}", report.checks);
+
+        let mut psbt = decode_psbt(&claim.psbt_base64).unwrap();
+        psbt.unsigned_tx.input[0].sequence = Sequence::from_height(71);
+        let short = base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+        let report = verify_claim_invariants(json, short, vec![v.destination]).unwrap();
+        assert_eq!(
+            status(&report, ClaimInvariant::SequencesEncodeTimelock),
+            CheckStatus::Failed
+        );
+    }
+
+    #[test]
+    fn test_sponsored_claim_passes_while_the_vault_is_paid_out() {
+        let v = generate_test_vectors(66).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(400);
+        sim.add_utxo(v.vault_address.clone(), "d9".repeat(32), 0, 3_000, 1)
+            .unwrap();
+        let options = ClaimOptions {
+            sponsor: Some(sponsor(50_000)),
+            ..Default::default()
+        };
+        let claim = build_claim_psbt_with_options(
+            v.backup_json.clone(),
+            &Backend::simulated(&sim),
+            v.destination.clone(),
+            0,
+            10,
+            options,
+        )
+        .unwrap();
+        let report = verify_claim_invariants(
+            v.backup_json.clone(),
+            claim.psbt_base64.clone(),
+            vec![v.destination.clone()],
+        )
+        .unwrap();
+        assert!(report.passed, "{:?}", report.checks);
+
+        // Change that takes some of the vault is not the sponsor's
+        let mut psbt = decode_psbt(&claim.psbt_base64).unwrap();
+        psbt.unsigned_tx.output[0].value -= bitcoin::Amount::from_sat(1_000);
+        psbt.unsigned_tx.output[1].value += bitcoin::Amount::from_sat(1_000);
+        let skimmed = base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+        let report = verify_claim_invariants(v.backup_json, skimmed, vec![v.destination]).unwrap();
+        assert_eq!(
+            status(&report, ClaimInvariant::ApprovedDestinations),
+            CheckStatus::Failed
+        );
+        assert_eq!(
+            status(&report, ClaimInvariant::InputsFromVault),
+            CheckStatus::Passed
+        );
+    }
+}
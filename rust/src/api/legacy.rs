@@ -0,0 +1,247 @@
+//! Vaults whose recovery leaves use an older script template.
+//!
+//! Upstream only rebuilds vaults in the template it writes today. Earlier
+//! releases wrote the same policy (heirs' signatures after the timelock)
+//! with the clauses in another order or shape, which gives different
+//! scripts and so a different address. When upstream reconstruction fails,
+//! each historical template is tried against the backup's leaves; a vault
+//! is accepted only if every leaf is exactly that template over the
+//! backup's heir keys and timelock, and the tree rebuilds the backup's
+//! address. [`super::VaultInfo::script_template`] reports which one matched.
+//!
+//! Every template here is valid miniscript, so claims on legacy vaults are
+//! built, signed and finalized like any rebuilt tree.
+
+use bitcoin::opcodes::all::{
+    OP_CHECKSIG, OP_CHECKSIGADD, OP_CHECKSIGVERIFY, OP_CSV, OP_NUMEQUAL, OP_NUMEQUALVERIFY,
+    OP_VERIFY,
+};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{Secp256k1, XOnlyPublicKey};
+use bitcoin::taproot::TaprootSpendInfo;
+use bitcoin::{Address, ScriptBuf};
+use serde::{Deserialize, Serialize};
+
+use nostring_inherit::backup::VaultBackup;
+
+use super::executor::tree_from_leaves;
+use super::policy::analyze_leaf_script;
+use super::weighted::{heir_keys, internal_key};
+use super::{parse_network, ErrorKind, HeirError};
+use crate::trace::span;
+
+/// Script template of a vault's recovery leaves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LeafTemplate {
+    /// What upstream writes today: `and_v(v:pk(heir), older(n))`, or
+    /// `and_v(v:multi_a(k, heirs), older(n))` for several heirs.
+    #[default]
+    Current,
+    /// Timelock checked first: `and_v(v:older(n), pk(heir))`, or
+    /// `and_v(v:older(n), multi_a(k, heirs))`.
+    TimelockFirst,
+    /// A single heir written as a one-of-one threshold:
+    /// `and_v(v:multi_a(1, heir), older(n))`.
+    SingleKeyMultiA,
+}
+
+/// Historical templates, tried in this order.
+const LEGACY_TEMPLATES: [LeafTemplate; 2] =
+    [LeafTemplate::TimelockFirst, LeafTemplate::SingleKeyMultiA];
+
+impl LeafTemplate {
+    /// The leaf this template writes for `keys` with `threshold` of them
+    /// signing after `csv_blocks`.
+    pub(crate) fn leaf_script(
+        self,
+        keys: &[XOnlyPublicKey],
+        threshold: u32,
+        csv_blocks: u32,
+    ) -> ScriptBuf {
+        let multi_a = |builder: Builder, verify: bool| {
+            let builder = keys
+                .iter()
+                .enumerate()
+                .fold(builder, |builder, (i, key)| {
+                    let op = if i == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD };
+                    builder.push_x_only_key(key).push_opcode(op)
+                })
+                .push_int(i64::from(threshold));
+            builder.push_opcode(if verify {
+                OP_NUMEQUALVERIFY
+            } else {
+                OP_NUMEQUAL
+            })
+        };
+        let older = |builder: Builder| builder.push_int(i64::from(csv_blocks)).push_opcode(OP_CSV);
+
+        let builder = match (self, keys) {
+            (LeafTemplate::Current, [key]) => older(
+                Builder::new()
+                    .push_x_only_key(key)
+                    .push_opcode(OP_CHECKSIGVERIFY),
+            ),
+            (LeafTemplate::Current | LeafTemplate::SingleKeyMultiA, _) => {
+                older(multi_a(Builder::new(), true))
+            }
+            (LeafTemplate::TimelockFirst, [key]) => older(Builder::new())
+                .push_opcode(OP_VERIFY)
+                .push_x_only_key(key)
+                .push_opcode(OP_CHECKSIG),
+            (LeafTemplate::TimelockFirst, _) => {
+                multi_a(older(Builder::new()).push_opcode(OP_VERIFY), false)
+            }
+        };
+        builder.into_script()
+    }
+}
+
+/// A legacy vault rebuilt from its backup.
+pub(crate) struct LegacyVault {
+    pub template: LeafTemplate,
+    pub address: Address,
+    pub spend_info: TaprootSpendInfo,
+    pub recovery_scripts: Vec<ScriptBuf>,
+}
+
+/// Whether `script` is exactly `template` over heir keys and the backup's
+/// timelock.
+fn matches_template(
+    script: &ScriptBuf,
+    template: LeafTemplate,
+    heirs: &[XOnlyPublicKey],
+    timelock_blocks: u32,
+) -> bool {
+    let analysis = analyze_leaf_script(script);
+    // Over several keys it is the current template, not a legacy one
+    if template == LeafTemplate::SingleKeyMultiA && analysis.keys.len() != 1 {
+        return false;
+    }
+    !analysis.keys.is_empty()
+        && analysis.keys.iter().all(|key| heirs.contains(key))
+        && analysis.csv_blocks == Some(timelock_blocks)
+        && template.leaf_script(&analysis.keys, analysis.threshold, timelock_blocks) == *script
+}
+
+/// Rebuild a vault whose leaves all follow one historical template and
+/// check it against the backup's address.
+pub(crate) fn verify_legacy_vault(backup: &VaultBackup) -> Result<LegacyVault, HeirError> {
+    span!("vault.reconstruct");
+    let network = parse_network(&backup.network)?;
+    let heirs = heir_keys(backup)?;
+    let timelock_blocks = u32::from(backup.timelock_blocks);
+    let (builder, recovery_scripts) = tree_from_leaves(backup)?;
+    let failed = |message: &str| HeirError::new(ErrorKind::VerificationFailed, message);
+
+    let template = LEGACY_TEMPLATES
+        .into_iter()
+        .find(|&template| {
+            !recovery_scripts.is_empty()
+                && recovery_scripts
+                    .iter()
+                    .all(|script| matches_template(script, template, &heirs, timelock_blocks))
+        })
+        .ok_or_else(|| failed("Vault verification failed: no known leaf template matches"))?;
+
+    let secp = Secp256k1::verification_only();
+    let spend_info = builder
+        .finalize(&secp, internal_key(backup)?)
+        .map_err(|_| failed("Vault verification failed: leaves do not form a complete tree"))?;
+    let address = Address::p2tr_tweaked(spend_info.output_key(), network);
+    if address.to_string() != backup.vault_address {
+        return Err(failed(
+            "Vault verification failed: legacy leaves do not derive the backup's address",
+        ));
+    }
+    Ok(LegacyVault {
+        template,
+        address,
+        spend_info,
+        recovery_scripts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::taproot::{LeafVersion, TaprootBuilder};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::{build_claim_psbt, fetch_vault_status, import_vault_backup, Backend, Network};
+
+    /// The vector backup with its single leaf rewritten in `template`.
+    fn legacy_backup_json(seed: u32, template: LeafTemplate) -> String {
+        let v = generate_test_vectors(seed).unwrap();
+        let mut value: Value = serde_json::from_str(&v.backup_json).unwrap();
+        let backup: VaultBackup = serde_json::from_value(value.clone()).unwrap();
+        let heirs = heir_keys(&backup).unwrap();
+        let leaf = template.leaf_script(&heirs, 1, u32::from(backup.timelock_blocks));
+
+        let secp = Secp256k1::new();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, leaf.clone())
+            .unwrap()
+            .finalize(&secp, internal_key(&backup).unwrap())
+            .unwrap();
+        let control = spend_info
+            .control_block(&(leaf.clone(), LeafVersion::TapScript))
+            .unwrap();
+        value["vault_address"] = serde_json::json!(Address::p2tr_tweaked(
+            spend_info.output_key(),
+            bitcoin::Network::Testnet
+        )
+        .to_string());
+        value["recovery_leaves"][0]["script_hex"] = serde_json::json!(leaf.to_hex_string());
+        value["recovery_leaves"][0]["control_block_hex"] =
+            serde_json::json!(hex::encode(control.serialize()));
+        value.to_string()
+    }
+
+    #[test]
+    fn test_current_template_matches_upstream() {
+        let v = generate_test_vectors(45).unwrap();
+        let backup: VaultBackup = serde_json::from_str(&v.backup_json).unwrap();
+        let leaf = LeafTemplate::Current.leaf_script(
+            &heir_keys(&backup).unwrap(),
+            1,
+            u32::from(backup.timelock_blocks),
+        );
+        assert_eq!(leaf.to_hex_string(), backup.recovery_leaves[0].script_hex);
+        assert_eq!(
+            import_vault_backup(v.backup_json).unwrap().script_template,
+            LeafTemplate::Current
+        );
+    }
+
+    #[test]
+    fn test_legacy_vaults_import_and_claim() {
+        for template in LEGACY_TEMPLATES {
+            let json = legacy_backup_json(46, template);
+            let info = import_vault_backup(json.clone()).unwrap();
+            assert_eq!(info.script_template, template);
+
+            let sim = SimulatedBackend::new(Network::Testnet);
+            sim.set_height(400);
+            sim.add_utxo(info.vault_address.clone(), "d6".repeat(32), 0, 50_000, 1)
+                .unwrap();
+            let backend = Backend::simulated(&sim);
+            assert!(fetch_vault_status(json.clone(), &backend).unwrap().eligible);
+            let destination = generate_test_vectors(46).unwrap().destination;
+            let claim = build_claim_psbt(json, &backend, destination, 0, 2).unwrap();
+            assert_eq!(claim.num_inputs, 1);
+        }
+    }
+
+    #[test]
+    fn test_unknown_template_still_fails() {
+        let json = legacy_backup_json(47, LeafTemplate::TimelockFirst);
+        let mut value: Value = serde_json::from_str(&json).unwrap();
+        // Same leaf, different vault address
+        let v = generate_test_vectors(48).unwrap();
+        value["vault_address"] = serde_json::json!(v.vault_address);
+        let err = import_vault_backup(value.to_string()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::VerificationFailed);
+    }
+}
@@ -0,0 +1,138 @@
+//! Safety ceilings on what a claim may pay in fees.
+//!
+//! Every path that picks or accepts a fee rate (building a claim, suggesting
+//! a rebuild after a fee-too-low rejection, the invariant check) reads its
+//! ceiling from [`SafetyLimits`]. Each network has a default; raising it
+//! needs `acknowledge_override`, so a typo in a settings screen cannot
+//! quietly allow a fee that eats the inheritance.
+
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
+
+use super::{ErrorKind, HeirError};
+
+/// Default ceiling on mainnet and signet.
+pub const DEFAULT_MAX_FEE_RATE_SAT_VB: u64 = 500;
+/// Default ceiling on testnet, whose fee market spikes far above mainnet's.
+pub const TESTNET_MAX_FEE_RATE_SAT_VB: u64 = 1_000;
+/// Default ceiling on regtest, where fees are whatever the demo wants.
+pub const REGTEST_MAX_FEE_RATE_SAT_VB: u64 = 10_000;
+
+/// Fee safety configuration for one network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafetyLimits {
+    /// Highest fee rate a claim may pay.
+    pub max_fee_rate_sat_vb: u64,
+    /// Must be set for `max_fee_rate_sat_vb` to exceed the network default.
+    pub acknowledge_override: bool,
+}
+
+impl SafetyLimits {
+    /// Default limits for `network`.
+    pub(crate) fn defaults(network: Network) -> SafetyLimits {
+        let max_fee_rate_sat_vb = match network {
+            Network::Testnet | Network::Testnet4 => TESTNET_MAX_FEE_RATE_SAT_VB,
+            Network::Regtest => REGTEST_MAX_FEE_RATE_SAT_VB,
+            _ => DEFAULT_MAX_FEE_RATE_SAT_VB,
+        };
+        SafetyLimits {
+            max_fee_rate_sat_vb,
+            acknowledge_override: false,
+        }
+    }
+
+    /// `limits`, or the network defaults if none were given, after checking
+    /// that a raised ceiling was acknowledged.
+    pub(crate) fn resolve(
+        limits: Option<&SafetyLimits>,
+        network: Network,
+    ) -> Result<SafetyLimits, HeirError> {
+        let defaults = SafetyLimits::defaults(network);
+        let Some(limits) = limits else {
+            return Ok(defaults);
+        };
+        if limits.max_fee_rate_sat_vb == 0 {
+            return Err(HeirError::new(
+                ErrorKind::InvalidInput,
+                "The fee-rate ceiling must be at least 1 sat/vB",
+            ));
+        }
+        if limits.max_fee_rate_sat_vb > defaults.max_fee_rate_sat_vb && !limits.acknowledge_override
+        {
+            return Err(HeirError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "A fee-rate ceiling of {} sat/vB is above the {} default of {} sat/vB; \
+                     confirm the override to use it",
+                    limits.max_fee_rate_sat_vb, network, defaults.max_fee_rate_sat_vb
+                ),
+            ));
+        }
+        Ok(limits.clone())
+    }
+
+    /// Reject a fee rate above the ceiling.
+    pub(crate) fn check_fee_rate(&self, fee_rate_sat_vb: u64) -> Result<(), HeirError> {
+        if fee_rate_sat_vb > self.max_fee_rate_sat_vb {
+            return Err(HeirError::new(
+                ErrorKind::FeeRateTooHigh,
+                format!(
+                    "Fee rate exceeds {} sat/vB safety limit",
+                    self.max_fee_rate_sat_vb
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Default safety limits for `network`, for a settings screen to start from.
+pub fn default_safety_limits(network: super::Network) -> SafetyLimits {
+    SafetyLimits::defaults(network.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_per_network() {
+        assert_eq!(
+            default_safety_limits(crate::api::Network::Mainnet).max_fee_rate_sat_vb,
+            500
+        );
+        assert_eq!(
+            default_safety_limits(crate::api::Network::Testnet).max_fee_rate_sat_vb,
+            1_000
+        );
+        assert_eq!(
+            default_safety_limits(crate::api::Network::Testnet4).max_fee_rate_sat_vb,
+            1_000
+        );
+    }
+
+    #[test]
+    fn test_raised_ceiling_needs_acknowledgement() {
+        let mut raised = SafetyLimits {
+            max_fee_rate_sat_vb: 900,
+            acknowledge_override: false,
+        };
+        let err = SafetyLimits::resolve(Some(&raised), Network::Bitcoin).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+
+        raised.acknowledge_override = true;
+        let limits = SafetyLimits::resolve(Some(&raised), Network::Bitcoin).unwrap();
+        assert!(limits.check_fee_rate(900).is_ok());
+        assert_eq!(
+            limits.check_fee_rate(901).unwrap_err().kind,
+            ErrorKind::FeeRateTooHigh
+        );
+
+        // Lowering the ceiling needs no acknowledgement
+        let lowered = SafetyLimits {
+            max_fee_rate_sat_vb: 50,
+            acknowledge_override: false,
+        };
+        assert!(SafetyLimits::resolve(Some(&lowered), Network::Bitcoin).is_ok());
+    }
+}
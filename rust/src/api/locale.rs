@@ -0,0 +1,160 @@
+//! Locale-aware numerals and dates for user-facing text.
+//!
+//! Executors often need documents in their own locale. This covers the
+//! parts that can be done without translation tables: digit grouping,
+//! decimal marks and date order. Wording stays English; localized apps
+//! build their own sentences from the structured fields instead.
+
+use serde::{Deserialize, Serialize};
+
+/// Order of the fields in a short date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateOrder {
+    DayMonthYear,
+    MonthDayYear,
+    YearMonthDay,
+}
+
+/// Number and date conventions for one locale.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocaleFormat {
+    /// The locale tag as understood, e.g. "de-ch".
+    pub locale: String,
+    pub decimal_separator: String,
+    pub group_separator: String,
+    pub date_order: DateOrder,
+    pub date_separator: String,
+}
+
+/// Conventions for a BCP 47 locale hint such as "de", "fr-CA" or "en_GB".
+/// Unknown or empty hints get ISO dates and English numerals.
+pub fn locale_format(locale: String) -> LocaleFormat {
+    let tag = locale.trim().replace('_', "-").to_ascii_lowercase();
+    let mut parts = tag.split('-');
+    let language = parts.next().unwrap_or_default();
+    let region = parts.next().unwrap_or_default();
+
+    let (decimal, group) = match (language, region) {
+        ("de", "ch") | ("it", "ch") => (".", "'"),
+        ("de" | "es" | "it" | "nl" | "pt" | "da" | "tr" | "el" | "id", _) => (",", "."),
+        // No-break space, so amounts never wrap mid-number
+        ("fr" | "ru" | "uk" | "pl" | "cs" | "sv" | "fi" | "nb" | "no", _) => (",", "\u{a0}"),
+        _ => (".", ","),
+    };
+    let (order, separator) = match (language, region) {
+        ("en", "" | "us") => (DateOrder::MonthDayYear, "/"),
+        ("en", _) | ("fr" | "es" | "it" | "pt" | "el", _) => (DateOrder::DayMonthYear, "/"),
+        ("de" | "ru" | "uk" | "pl" | "cs" | "fi" | "nb" | "no" | "da" | "tr", _) => {
+            (DateOrder::DayMonthYear, ".")
+        }
+        ("nl", _) => (DateOrder::DayMonthYear, "-"),
+        ("ja" | "zh", _) => (DateOrder::YearMonthDay, "/"),
+        _ => (DateOrder::YearMonthDay, "-"),
+    };
+
+    LocaleFormat {
+        locale: tag,
+        decimal_separator: decimal.to_string(),
+        group_separator: group.to_string(),
+        date_order: order,
+        date_separator: separator.to_string(),
+    }
+}
+
+impl LocaleFormat {
+    /// `value` with digit grouping, e.g. "1.234.567" in German.
+    pub(crate) fn integer(&self, value: u64) -> String {
+        let digits = value.to_string();
+        let mut out = String::with_capacity(digits.len() * 2);
+        for (i, ch) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                out.push_str(&self.group_separator);
+            }
+            out.push(ch);
+        }
+        out
+    }
+
+    /// `sats` as BTC with all eight decimals.
+    pub(crate) fn btc(&self, sats: u64) -> String {
+        format!(
+            "{}{}{:08} BTC",
+            self.integer(sats / 100_000_000),
+            self.decimal_separator,
+            sats % 100_000_000
+        )
+    }
+
+    /// Short calendar date (UTC) for a unix timestamp.
+    pub(crate) fn date(&self, unix_secs: u64) -> String {
+        let (year, month, day) = civil_from_days((unix_secs / 86_400) as i64);
+        let sep = &self.date_separator;
+        match self.date_order {
+            DateOrder::DayMonthYear => format!("{:02}{sep}{:02}{sep}{}", day, month, year),
+            DateOrder::MonthDayYear => format!("{:02}{sep}{:02}{sep}{}", month, day, year),
+            DateOrder::YearMonthDay => format!("{}{sep}{:02}{sep}{:02}", year, month, day),
+        }
+    }
+}
+
+/// Gregorian (year, month, day) for days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Amount in sats with the locale's digit grouping, e.g. "1,250,000 sats".
+pub fn format_sats(value_sat: u64, locale: String) -> String {
+    format!("{} sats", locale_format(locale).integer(value_sat))
+}
+
+/// Amount in BTC with the locale's decimal mark, e.g. "0,01250000 BTC".
+pub fn format_btc(value_sat: u64, locale: String) -> String {
+    locale_format(locale).btc(value_sat)
+}
+
+/// Calendar date (UTC) of a unix timestamp in the locale's short form.
+pub fn format_date(unix_secs: u64, locale: String) -> String {
+    locale_format(locale).date(unix_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_formats() {
+        assert_eq!(format_sats(1_250_000, "en".into()), "1,250,000 sats");
+        assert_eq!(format_sats(1_250_000, "de-DE".into()), "1.250.000 sats");
+        assert_eq!(
+            format_sats(1_250_000, "fr".into()),
+            "1\u{a0}250\u{a0}000 sats"
+        );
+        assert_eq!(format_sats(999, "de_CH".into()), "999 sats");
+        assert_eq!(
+            format_btc(123_456_789_012, "de".into()),
+            "1.234,56789012 BTC"
+        );
+        assert_eq!(format_btc(1_250_000, "en".into()), "0.01250000 BTC");
+    }
+
+    #[test]
+    fn test_date_formats() {
+        // 2024-03-05 12:00 UTC
+        let ts = 1_709_640_000;
+        assert_eq!(format_date(ts, "en-US".into()), "03/05/2024");
+        assert_eq!(format_date(ts, "en-GB".into()), "05/03/2024");
+        assert_eq!(format_date(ts, "de".into()), "05.03.2024");
+        assert_eq!(format_date(ts, "ja".into()), "2024/03/05");
+        assert_eq!(format_date(ts, "".into()), "2024-03-05");
+        assert_eq!(format_date(0, "xx".into()), "1970-01-01");
+    }
+}
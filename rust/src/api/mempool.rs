@@ -0,0 +1,361 @@
+//! Mempool inspection for claims that did not go through cleanly.
+//!
+//! [`find_conflicts`] finds other transactions spending a claim's inputs,
+//! the starting point for any replace-by-fee or CPFP decision.
+//! [`analyze_stuck_tx`] combines that with fee and relay checks into one
+//! diagnosis for a claim that is not confirming.
+
+use std::collections::{BTreeMap, HashMap};
+
+use bitcoin::{Address, OutPoint, Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
+use super::backend::ChainBackend;
+use super::{decode_tx, Backend, ErrorKind, HeirError};
+
+/// Another transaction spending some of the same inputs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictingTx {
+    pub txid: String,
+    /// Confirmation height, or 0 while in the mempool.
+    pub height: u32,
+    /// Inputs shared with the checked transaction (`txid:vout`).
+    pub shared_outpoints: Vec<String>,
+    pub fee_sat: u64,
+    pub vsize: u64,
+    pub fee_rate_sat_vb: f64,
+}
+
+/// Fee paid by `tx`, looking up each spent output.
+pub(crate) fn tx_fee(chain: &dyn ChainBackend, tx: &Transaction) -> Result<u64, HeirError> {
+    let mut input_sat = 0u64;
+    for input in &tx.input {
+        let prev = chain.transaction(&input.previous_output.txid)?;
+        let out = prev
+            .output
+            .get(input.previous_output.vout as usize)
+            .ok_or_else(|| {
+                HeirError::new(
+                    ErrorKind::InvalidTransaction,
+                    format!("Input {} does not exist", input.previous_output),
+                )
+            })?;
+        input_sat += out.value.to_sat();
+    }
+    let output_sat: u64 = tx.output.iter().map(|o| o.value.to_sat()).sum();
+    Ok(input_sat.saturating_sub(output_sat))
+}
+
+/// Transactions other than `tx` that spend any of its inputs.
+pub(crate) fn conflicts_for(
+    chain: &dyn ChainBackend,
+    tx: &Transaction,
+) -> Result<Vec<ConflictingTx>, HeirError> {
+    let own_txid = tx.compute_txid();
+    let inputs: Vec<OutPoint> = tx.input.iter().map(|i| i.previous_output).collect();
+
+    // Spenders of an output show up in the history of the address it paid
+    let mut addresses = Vec::new();
+    for outpoint in &inputs {
+        let prev = chain.transaction(&outpoint.txid)?;
+        if let Some(out) = prev.output.get(outpoint.vout as usize) {
+            if let Ok(address) = Address::from_script(&out.script_pubkey, chain.network()) {
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+    }
+
+    let mut candidates: BTreeMap<Txid, u32> = BTreeMap::new();
+    for address in &addresses {
+        for entry in chain.history(address)? {
+            if entry.txid != own_txid {
+                candidates.insert(entry.txid, entry.height);
+            }
+        }
+    }
+
+    let mut fetched: HashMap<Txid, Transaction> = HashMap::new();
+    let mut conflicts = Vec::new();
+    for (txid, height) in candidates {
+        let other = match fetched.get(&txid) {
+            Some(other) => other.clone(),
+            None => {
+                let other = chain.transaction(&txid)?;
+                fetched.insert(txid, other.clone());
+                other
+            }
+        };
+        let shared: Vec<String> = other
+            .input
+            .iter()
+            .filter(|i| inputs.contains(&i.previous_output))
+            .map(|i| i.previous_output.to_string())
+            .collect();
+        if shared.is_empty() {
+            continue;
+        }
+        let fee_sat = tx_fee(chain, &other)?;
+        let vsize = other.vsize() as u64;
+        conflicts.push(ConflictingTx {
+            txid: txid.to_string(),
+            height,
+            shared_outpoints: shared,
+            fee_sat,
+            vsize,
+            fee_rate_sat_vb: fee_sat as f64 / vsize.max(1) as f64,
+        });
+    }
+
+    Ok(conflicts)
+}
+
+/// Find mempool and confirmed transactions that spend the same inputs as
+/// `tx_hex`, with their fee rates.
+pub fn find_conflicts(tx_hex: String, backend: &Backend) -> Result<Vec<ConflictingTx>, HeirError> {
+    let tx = decode_tx(&tx_hex)?;
+    conflicts_for(backend.chain(), &tx)
+}
+
+/// Confirmation target used as "the current market" when judging fees.
+const STUCK_TX_TARGET_BLOCKS: u16 = 6;
+
+/// Why a transaction is (or is not) confirming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StuckReason {
+    /// It already confirmed; nothing is stuck.
+    Confirmed,
+    /// The server does not know the transaction.
+    NotRelayed,
+    /// A different spend of the same inputs confirmed instead.
+    ConflictConfirmed,
+    /// A different spend of the same inputs is competing in the mempool.
+    ConflictInMempool,
+    /// Its fee rate is below what the market currently needs.
+    FeeBelowMarket,
+    /// In the mempool at a competitive fee; it should confirm soon.
+    Pending,
+}
+
+/// What to do about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StuckRemedy {
+    None,
+    /// Broadcast the same transaction again.
+    Rebroadcast,
+    /// Rebuild the claim with a higher fee (it signals replace-by-fee).
+    RbfRebuild,
+    /// Spend its output with a high fee so both confirm together.
+    Cpfp,
+    /// Keep waiting.
+    Wait,
+}
+
+/// Diagnosis from [`analyze_stuck_tx`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StuckTxAnalysis {
+    pub txid: String,
+    pub reason: StuckReason,
+    pub remedy: StuckRemedy,
+    pub fee_rate_sat_vb: Option<f64>,
+    /// Rate expected to confirm within a few blocks, if the backend had one.
+    pub market_fee_rate_sat_vb: Option<f64>,
+    pub conflicts: Vec<ConflictingTx>,
+    pub detail: String,
+}
+
+/// Height of `txid` in the history of the addresses its inputs spent from.
+fn own_height(
+    chain: &dyn ChainBackend,
+    tx: &Transaction,
+    txid: &Txid,
+) -> Result<Option<u32>, HeirError> {
+    for input in &tx.input {
+        let prev = chain.transaction(&input.previous_output.txid)?;
+        let Some(out) = prev.output.get(input.previous_output.vout as usize) else {
+            continue;
+        };
+        let Ok(address) = Address::from_script(&out.script_pubkey, chain.network()) else {
+            continue;
+        };
+        if let Some(entry) = chain.history(&address)?.into_iter().find(|e| e.txid == *txid) {
+            return Ok(Some(entry.height));
+        }
+    }
+    Ok(None)
+}
+
+/// Explain why a claim is not confirming and which remedy applies.
+pub fn analyze_stuck_tx(txid: String, backend: &Backend) -> Result<StuckTxAnalysis, HeirError> {
+    let chain = backend.chain();
+    let parsed: Txid = txid
+        .trim()
+        .parse()
+        .map_err(|e| HeirError::new(ErrorKind::InvalidInput, format!("Invalid txid: {}", e)))?;
+
+    let analysis = |reason, remedy, detail: String| StuckTxAnalysis {
+        txid: parsed.to_string(),
+        reason,
+        remedy,
+        fee_rate_sat_vb: None,
+        market_fee_rate_sat_vb: None,
+        conflicts: Vec::new(),
+        detail,
+    };
+
+    let tx = match chain.transaction(&parsed) {
+        Ok(tx) => tx,
+        Err(e) if e.kind == ErrorKind::ServerQuery => {
+            return Ok(analysis(
+                StuckReason::NotRelayed,
+                StuckRemedy::Rebroadcast,
+                "The server has never seen this transaction; broadcast it again".into(),
+            ))
+        }
+        Err(e) => return Err(e),
+    };
+
+    let fee_sat = tx_fee(chain, &tx)?;
+    let fee_rate = fee_sat as f64 / (tx.vsize() as u64).max(1) as f64;
+    let market = chain.estimate_fee_rate(STUCK_TX_TARGET_BLOCKS).ok();
+    let conflicts = conflicts_for(chain, &tx)?;
+    let height = own_height(chain, &tx, &parsed)?;
+
+    let (reason, remedy, detail) = if height.is_some_and(|h| h > 0) {
+        (StuckReason::Confirmed, StuckRemedy::None, "Confirmed".to_string())
+    } else if let Some(c) = conflicts.iter().find(|c| c.height > 0) {
+        (
+            StuckReason::ConflictConfirmed,
+            StuckRemedy::None,
+            format!("Transaction {} spent the same funds and confirmed", c.txid),
+        )
+    } else if let Some(c) = conflicts.first() {
+        (
+            StuckReason::ConflictInMempool,
+            StuckRemedy::RbfRebuild,
+            format!(
+                "Transaction {} spends the same funds at {:.1} sat/vB; rebuild above that",
+                c.txid, c.fee_rate_sat_vb
+            ),
+        )
+    } else if height.is_none() {
+        (
+            StuckReason::NotRelayed,
+            StuckRemedy::Rebroadcast,
+            "Not in the mempool (possibly evicted); broadcast it again".to_string(),
+        )
+    } else if let Some(market_rate) = market.filter(|m| fee_rate < *m) {
+        // Claims spend CSV inputs, whose sequence always signals replaceability
+        let remedy = if tx.is_explicitly_rbf() {
+            StuckRemedy::RbfRebuild
+        } else {
+            StuckRemedy::Cpfp
+        };
+        (
+            StuckReason::FeeBelowMarket,
+            remedy,
+            format!(
+                "Pays {:.1} sat/vB; about {:.1} sat/vB confirms within {} blocks",
+                fee_rate, market_rate, STUCK_TX_TARGET_BLOCKS
+            ),
+        )
+    } else {
+        (
+            StuckReason::Pending,
+            StuckRemedy::Wait,
+            format!("In the mempool at {:.1} sat/vB", fee_rate),
+        )
+    };
+
+    Ok(StuckTxAnalysis {
+        fee_rate_sat_vb: Some(fee_rate),
+        market_fee_rate_sat_vb: market,
+        conflicts,
+        ..analysis(reason, remedy, detail)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::{generate_test_vectors, TestVectors};
+    use crate::api::Network;
+
+    fn funded(v: &TestVectors) -> SimulatedBackend {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+        sim
+    }
+
+    #[test]
+    fn test_no_conflicts_before_broadcast() {
+        let v = generate_test_vectors(3).unwrap();
+        let sim = funded(&v);
+        let conflicts = find_conflicts(v.tx_hex, &Backend::simulated(&sim)).unwrap();
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_detects_competing_spend() {
+        let v = generate_test_vectors(3).unwrap();
+        let sim = funded(&v);
+        let backend = Backend::simulated(&sim);
+
+        // A different spend of the same output reaches the mempool first
+        let mut rival = decode_tx(&v.tx_hex).unwrap();
+        rival.output[0].value = rival.output[0].value - bitcoin::Amount::from_sat(1_000);
+        backend.chain().broadcast(&rival).unwrap();
+
+        let conflicts = find_conflicts(v.tx_hex, &backend).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        let conflict = &conflicts[0];
+        assert_eq!(conflict.txid, rival.compute_txid().to_string());
+        assert_eq!(conflict.height, 0);
+        assert_eq!(conflict.shared_outpoints, vec![v.funding_outpoint]);
+        assert_eq!(conflict.fee_sat, v.fee_sat + 1_000);
+        assert!(conflict.fee_rate_sat_vb > 0.0);
+
+        let stuck = analyze_stuck_tx(v.txid, &backend).unwrap();
+        assert_eq!(stuck.reason, StuckReason::NotRelayed);
+    }
+
+    #[test]
+    fn test_stuck_tx_fee_and_confirmation() {
+        let v = generate_test_vectors(3).unwrap();
+        let sim = funded(&v);
+        let backend = Backend::simulated(&sim);
+        backend.chain().broadcast(&decode_tx(&v.tx_hex).unwrap()).unwrap();
+
+        let pending = analyze_stuck_tx(v.txid.clone(), &backend).unwrap();
+        assert_eq!(pending.reason, StuckReason::Pending);
+        assert_eq!(pending.remedy, StuckRemedy::Wait);
+
+        sim.set_fee_rate(50.0);
+        let low = analyze_stuck_tx(v.txid.clone(), &backend).unwrap();
+        assert_eq!(low.reason, StuckReason::FeeBelowMarket);
+        assert_eq!(low.remedy, StuckRemedy::RbfRebuild);
+
+        sim.mine_blocks(1);
+        let done = analyze_stuck_tx(v.txid, &backend).unwrap();
+        assert_eq!(done.reason, StuckReason::Confirmed);
+    }
+
+    #[test]
+    fn test_unknown_tx_not_relayed() {
+        let v = generate_test_vectors(3).unwrap();
+        let sim = funded(&v);
+        let stuck = analyze_stuck_tx("11".repeat(32), &Backend::simulated(&sim)).unwrap();
+        assert_eq!(stuck.reason, StuckReason::NotRelayed);
+        assert_eq!(stuck.remedy, StuckRemedy::Rebroadcast);
+    }
+}
@@ -0,0 +1,83 @@
+//! The network a call is meant for.
+//!
+//! Calls from the app take a [`Network`], so a misspelled network cannot
+//! cross the bindings. Backups still record it as text; those names are
+//! read with `parse_network` and converted for the app with
+//! [`network_from_name`].
+
+use serde::{Deserialize, Serialize};
+
+use super::{network_name, parse_network, HeirError};
+
+/// Bitcoin network of a backend, address or timelock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Testnet4,
+    Signet,
+    Regtest,
+}
+
+impl From<Network> for bitcoin::Network {
+    fn from(network: Network) -> bitcoin::Network {
+        match network {
+            Network::Mainnet => bitcoin::Network::Bitcoin,
+            Network::Testnet => bitcoin::Network::Testnet,
+            Network::Testnet4 => bitcoin::Network::Testnet4,
+            Network::Signet => bitcoin::Network::Signet,
+            Network::Regtest => bitcoin::Network::Regtest,
+        }
+    }
+}
+
+impl From<bitcoin::Network> for Network {
+    fn from(network: bitcoin::Network) -> Network {
+        match network {
+            bitcoin::Network::Testnet => Network::Testnet,
+            bitcoin::Network::Testnet4 => Network::Testnet4,
+            bitcoin::Network::Signet => Network::Signet,
+            bitcoin::Network::Regtest => Network::Regtest,
+            _ => Network::Mainnet,
+        }
+    }
+}
+
+impl Network {
+    /// Name backups record for this network.
+    pub(crate) fn backup_name(self) -> &'static str {
+        network_name(self.into())
+    }
+}
+
+/// The network a backup's `network` field names, e.g. from
+/// [`super::VaultInfo::network`].
+pub fn network_from_name(name: String) -> Result<Network, HeirError> {
+    parse_network(name.trim()).map(Network::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for network in [
+            Network::Mainnet,
+            Network::Testnet,
+            Network::Testnet4,
+            Network::Signet,
+            Network::Regtest,
+        ] {
+            assert_eq!(
+                network_from_name(network.backup_name().into()).unwrap(),
+                network
+            );
+        }
+        assert_eq!(
+            network_from_name("mainnet".into()).unwrap(),
+            Network::Mainnet
+        );
+        assert!(network_from_name("moonnet".into()).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! Reminder times for the host app's local notifications.
+//!
+//! Block heights are turned into wall-clock times here, once, so the app can
+//! register notifications without repeating the block-interval math.
+
+use serde::{Deserialize, Serialize};
+
+use super::readonly::load_watched;
+use super::state::VaultState;
+use super::store::now;
+use super::timelock::block_interval_secs;
+use super::{HeirError, VaultStatus};
+
+const DAY_SECS: u64 = 86_400;
+
+/// Which reminder a notice is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoticeKind {
+    ThirtyDaysBefore,
+    SevenDaysBefore,
+    OneDayBefore,
+    /// The timelock is expected to expire.
+    Eligible,
+}
+
+/// A reminder the app should register.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledNotice {
+    pub kind: NoticeKind,
+    /// Suggested delivery time (unix seconds).
+    pub at_unix: u64,
+    pub days_before_eligibility: u32,
+}
+
+/// Reminders ahead of eligibility for `status`, skipping any whose time has
+/// passed. Empty once the vault is eligible, or if it is unfunded, swept or
+/// has a claim pending.
+pub fn notification_schedule(
+    vault_json: String,
+    status: VaultStatus,
+) -> Result<Vec<ScheduledNotice>, HeirError> {
+    let network = load_watched(&vault_json)?.network;
+    Ok(schedule_from(&status, block_interval_secs(network), now()))
+}
+
+fn schedule_from(status: &VaultStatus, block_interval: u64, now: u64) -> Vec<ScheduledNotice> {
+    let watching = matches!(
+        status.state,
+        VaultState::FundedLocked | VaultState::OwnerRefreshed | VaultState::PartiallyClaimable
+    );
+    if !watching || status.eligible || status.blocks_remaining <= 0 {
+        return Vec::new();
+    }
+    let eligible_at = now + status.blocks_remaining as u64 * block_interval;
+
+    [
+        (NoticeKind::ThirtyDaysBefore, 30),
+        (NoticeKind::SevenDaysBefore, 7),
+        (NoticeKind::OneDayBefore, 1),
+        (NoticeKind::Eligible, 0),
+    ]
+    .into_iter()
+    .filter_map(|(kind, days)| {
+        let at_unix = eligible_at.checked_sub(u64::from(days) * DAY_SECS)?;
+        (at_unix > now).then_some(ScheduledNotice {
+            kind,
+            at_unix,
+            days_before_eligibility: days,
+        })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::{fetch_vault_status, Backend, Network};
+
+    fn locked_status(blocks_remaining: i64) -> VaultStatus {
+        let v = generate_test_vectors(16).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(1_000);
+        sim.add_utxo(v.vault_address, "e1".repeat(32), 0, 50_000, 1_000)
+            .unwrap();
+        let mut status = fetch_vault_status(v.backup_json, &Backend::simulated(&sim)).unwrap();
+        status.blocks_remaining = blocks_remaining;
+        status
+    }
+
+    #[test]
+    fn test_schedule_skips_past_reminders() {
+        let now = 1_700_000_000;
+        // Ten days out at ten-minute blocks: the 30-day reminder has passed
+        let notices = schedule_from(&locked_status(1_440), 600, now);
+        let kinds: Vec<_> = notices.iter().map(|n| n.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                NoticeKind::SevenDaysBefore,
+                NoticeKind::OneDayBefore,
+                NoticeKind::Eligible
+            ]
+        );
+        assert_eq!(notices[2].at_unix, now + 1_440 * 600);
+        assert_eq!(notices[1].at_unix, now + 9 * DAY_SECS);
+    }
+
+    #[test]
+    fn test_no_schedule_once_eligible() {
+        let mut status = locked_status(0);
+        status.eligible = true;
+        assert!(schedule_from(&status, 600, 1_700_000_000).is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+//! A dry run for heirs, long before the timelock matures.
+//!
+//! Most claims fail for reasons that could have been found on day one: a
+//! backup that does not verify, a seed that is not the one the owner
+//! registered, a server the phone cannot reach. [`onboarding_check`] runs
+//! those checks up front and returns a checklist the app can show item by
+//! item.
+
+use std::str::FromStr;
+
+use bitcoin::bip32::Xpub;
+use bitcoin::secp256k1::Secp256k1;
+use serde::{Deserialize, Serialize};
+
+use super::diagnostics::CheckOutcome;
+use super::psbt::heir_key_sources;
+use super::signer::master_key;
+use super::{
+    fetch_vault_status, import_vault_backup, parse_backup, parse_network, Backend, ErrorKind,
+    HeirError, VaultState,
+};
+
+/// What the heir holds to prove they are one of the vault's heirs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum HeirKeyMaterial {
+    /// The account xpub their wallet shows.
+    Xpub { xpub: String },
+    /// The seed phrase itself; it never leaves Rust and is not stored.
+    Mnemonic {
+        mnemonic: String,
+        passphrase: String,
+    },
+}
+
+/// One item of the checklist, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnboardingItem {
+    /// The backup rebuilds its vault address.
+    BackupVerifies,
+    /// The heir's key is one of the backup's heir keys.
+    KeyMatchesHeir,
+    /// The backend answers, on the vault's network.
+    ServerReachable,
+    /// The vault holds funds to inherit.
+    VaultFunded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnboardingCheck {
+    pub item: OnboardingItem,
+    pub outcome: CheckOutcome,
+    /// What was found, or why the item was skipped.
+    pub detail: String,
+    /// Set when `outcome` is [`CheckOutcome::Failed`].
+    pub error: Option<HeirError>,
+}
+
+/// Result of [`onboarding_check`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnboardingChecklist {
+    pub checks: Vec<OnboardingCheck>,
+    /// Label of the heir the key belongs to, if it matched.
+    pub heir_label: Option<String>,
+    pub all_passed: bool,
+}
+
+fn check(item: OnboardingItem, result: Result<String, HeirError>) -> OnboardingCheck {
+    match result {
+        Ok(detail) => OnboardingCheck {
+            item,
+            outcome: CheckOutcome::Passed,
+            detail,
+            error: None,
+        },
+        Err(error) => OnboardingCheck {
+            item,
+            outcome: CheckOutcome::Failed,
+            detail: error.message.clone(),
+            error: Some(error),
+        },
+    }
+}
+
+fn skipped(item: OnboardingItem, detail: &str) -> OnboardingCheck {
+    OnboardingCheck {
+        item,
+        outcome: CheckOutcome::Skipped,
+        detail: detail.to_string(),
+        error: None,
+    }
+}
+
+/// Label of the heir whose key `material` holds.
+fn matching_heir(vault_json: &str, material: &HeirKeyMaterial) -> Result<String, HeirError> {
+    let backup = parse_backup(vault_json)?;
+    let sources = heir_key_sources(&backup)?;
+    let secp = Secp256k1::new();
+    let index = match material {
+        HeirKeyMaterial::Xpub { xpub } => {
+            let given = Xpub::from_str(xpub.trim()).map_err(|e| {
+                HeirError::new(ErrorKind::InvalidInput, format!("Invalid xpub: {}", e))
+            })?;
+            sources
+                .iter()
+                .position(|(heir, _)| heir.public_key == given.public_key)
+        }
+        HeirKeyMaterial::Mnemonic {
+            mnemonic,
+            passphrase,
+        } => {
+            let master = master_key(mnemonic, passphrase)?;
+            // Backups may carry placeholder fingerprints, so compare keys
+            let mut found = None;
+            for (index, (heir, (_, path))) in sources.iter().enumerate() {
+                let derived = master.derive_priv(&secp, path).map_err(|e| {
+                    HeirError::new(ErrorKind::Internal, format!("Key derivation failed: {}", e))
+                })?;
+                if derived.private_key.public_key(&secp) == heir.public_key {
+                    found = Some(index);
+                    break;
+                }
+            }
+            found
+        }
+    };
+    index
+        .map(|index| backup.heirs[index].label.clone())
+        .ok_or_else(|| {
+            HeirError::new(
+                ErrorKind::InvalidInput,
+                "This key is not one of the vault's heir keys",
+            )
+        })
+}
+
+/// Check, without spending anything, that the heir could claim `vault_json`
+/// with `heir_key_material` once the timelock matures.
+pub fn onboarding_check(
+    vault_json: String,
+    heir_key_material: HeirKeyMaterial,
+    backend: &Backend,
+) -> OnboardingChecklist {
+    let mut checks = Vec::new();
+    let mut heir_label = None;
+
+    let verified = import_vault_backup(vault_json.clone());
+    let canonical_json = verified
+        .as_ref()
+        .ok()
+        .map(|info| info.canonical_json.clone());
+    checks.push(check(
+        OnboardingItem::BackupVerifies,
+        verified.map(|info| format!("Backup rebuilds {}", info.vault_address)),
+    ));
+
+    match &canonical_json {
+        Some(json) => {
+            let matched = matching_heir(json, &heir_key_material);
+            heir_label = matched.as_ref().ok().cloned();
+            checks.push(check(
+                OnboardingItem::KeyMatchesHeir,
+                matched.map(|label| format!("This key belongs to {}", label)),
+            ));
+        }
+        None => checks.push(skipped(
+            OnboardingItem::KeyMatchesHeir,
+            "The backup did not verify",
+        )),
+    }
+
+    let reachable = (|| {
+        if let Some(json) = &canonical_json {
+            backend.require_network(parse_network(&parse_backup(json)?.network)?)?;
+        }
+        let height = backend.chain().tip_height()?;
+        Ok(format!("Server is at block {}", height))
+    })();
+    let reachable_ok = reachable.is_ok();
+    checks.push(check(OnboardingItem::ServerReachable, reachable));
+
+    match &canonical_json {
+        Some(json) if reachable_ok => {
+            let funded =
+                fetch_vault_status(json.clone(), backend).and_then(|status| match status.state {
+                    VaultState::Unfunded => Err(HeirError::new(
+                        ErrorKind::NoUtxos,
+                        "Nothing has been sent to the vault yet",
+                    )),
+                    VaultState::Swept => Err(HeirError::new(
+                        ErrorKind::NoUtxos,
+                        "The vault has been emptied",
+                    )),
+                    _ => Ok(format!("The vault holds {} sat", status.balance_sat)),
+                });
+            checks.push(check(OnboardingItem::VaultFunded, funded));
+        }
+        Some(_) => checks.push(skipped(
+            OnboardingItem::VaultFunded,
+            "The server could not be reached",
+        )),
+        None => checks.push(skipped(
+            OnboardingItem::VaultFunded,
+            "The backup did not verify",
+        )),
+    }
+
+    let all_passed = checks
+        .iter()
+        .all(|check| check.outcome == CheckOutcome::Passed);
+    OnboardingChecklist {
+        checks,
+        heir_label,
+        all_passed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::Network;
+
+    fn outcomes(checklist: &OnboardingChecklist) -> Vec<CheckOutcome> {
+        checklist.checks.iter().map(|c| c.outcome).collect()
+    }
+
+    #[test]
+    fn test_ready_heir_passes_everything() {
+        let v = generate_test_vectors(53).unwrap();
+        let backup = parse_backup(&v.backup_json).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(20);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+
+        let checklist = onboarding_check(
+            v.backup_json,
+            HeirKeyMaterial::Xpub {
+                xpub: backup.heirs[0].xpub.clone(),
+            },
+            &Backend::simulated(&sim),
+        );
+        assert!(checklist.all_passed, "{:?}", checklist.checks);
+        assert_eq!(checklist.heir_label, Some(backup.heirs[0].label.clone()));
+    }
+
+    #[test]
+    fn test_failures_are_itemized() {
+        let v = generate_test_vectors(54).unwrap();
+        let other = generate_test_vectors(55).unwrap();
+        let other_xpub = parse_backup(&other.backup_json).unwrap().heirs[0]
+            .xpub
+            .clone();
+        let sim = SimulatedBackend::new(Network::Testnet);
+
+        let checklist = onboarding_check(
+            v.backup_json.clone(),
+            HeirKeyMaterial::Xpub { xpub: other_xpub },
+            &Backend::simulated(&sim),
+        );
+        assert_eq!(
+            outcomes(&checklist),
+            [
+                CheckOutcome::Passed,
+                CheckOutcome::Failed,
+                CheckOutcome::Passed,
+                CheckOutcome::Failed
+            ]
+        );
+
+        sim.set_offline(true);
+        let checklist = onboarding_check(
+            v.backup_json,
+            HeirKeyMaterial::Mnemonic {
+                mnemonic: "not a seed phrase".into(),
+                passphrase: String::new(),
+            },
+            &Backend::simulated(&sim),
+        );
+        assert_eq!(
+            outcomes(&checklist),
+            [
+                CheckOutcome::Passed,
+                CheckOutcome::Failed,
+                CheckOutcome::Failed,
+                CheckOutcome::Skipped
+            ]
+        );
+        assert!(!checklist.all_passed);
+    }
+}
@@ -0,0 +1,364 @@
+//! Child-pays-for-parent rescue of low-fee claims.
+//!
+//! A claim's fee is fixed by the heirs' signatures. If it is too low, the
+//! heir can spend their own payout in a child transaction that pays for
+//! both. [`build_cpfp_child_psbt`] builds that child for the heir's wallet
+//! to sign; [`broadcast_package`] submits parent and child together where
+//! the backend relays packages, and one after the other elsewhere.
+
+use std::str::FromStr;
+
+use base64::Engine;
+use bitcoin::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::{Address, Amount, OutPoint, Script, Sequence, Transaction, TxIn, TxOut, Witness};
+use serde::{Deserialize, Serialize};
+
+use super::limits::SafetyLimits;
+use super::mempool::tx_fee;
+use super::{
+    decode_tx, Backend, BroadcastFailure, ErrorKind, HeirError, DEFAULT_DUST_THRESHOLD_SAT,
+};
+
+/// Unsigned child transaction paying for a claim.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpfpChild {
+    pub psbt_base64: String,
+    pub child_fee_sat: u64,
+    /// Estimated size once the heir's wallet has signed it.
+    pub child_vsize: u64,
+    pub parent_fee_sat: u64,
+    pub parent_vsize: u64,
+    /// Fee rate of parent and child taken together.
+    pub package_fee_rate_sat_vb: f64,
+}
+
+/// Outcome of [`broadcast_package`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageBroadcast {
+    pub parent_txid: String,
+    pub child_txid: String,
+    /// False if the backend cannot relay packages and the two were sent
+    /// one after the other.
+    pub submitted_as_package: bool,
+    pub package_fee_sat: u64,
+    pub package_vsize: u64,
+    pub package_fee_rate_sat_vb: f64,
+}
+
+fn invalid(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidInput, message)
+}
+
+/// Virtual size of a signed input spending `script_pubkey`, for the
+/// single-key output types wallets pay claims to.
+fn spend_vbytes(script_pubkey: &Script) -> Option<u64> {
+    if script_pubkey.is_p2wpkh() {
+        Some(68)
+    } else if script_pubkey.is_p2tr() {
+        Some(58)
+    } else if script_pubkey.is_p2sh() {
+        // Assumed to wrap P2WPKH, as wallets' P2SH receive addresses do
+        Some(91)
+    } else if script_pubkey.is_p2pkh() {
+        Some(148)
+    } else {
+        None
+    }
+}
+
+/// Fee of `child`, looking up inputs from `parent` locally and the rest on
+/// the backend.
+fn child_fee(
+    backend: &Backend,
+    parent: &Transaction,
+    child: &Transaction,
+) -> Result<u64, HeirError> {
+    let parent_txid = parent.compute_txid();
+    let mut input_sat = 0u64;
+    for input in &child.input {
+        let value = if input.previous_output.txid == parent_txid {
+            parent
+                .output
+                .get(input.previous_output.vout as usize)
+                .map(|out| out.value)
+        } else {
+            let prev = backend.chain().transaction(&input.previous_output.txid)?;
+            prev.output
+                .get(input.previous_output.vout as usize)
+                .map(|out| out.value)
+        };
+        input_sat += value
+            .ok_or_else(|| invalid(format!("Input {} does not exist", input.previous_output)))?
+            .to_sat();
+    }
+    let output_sat: u64 = child.output.iter().map(|o| o.value.to_sat()).sum();
+    Ok(input_sat.saturating_sub(output_sat))
+}
+
+/// Build an unsigned child spending output `output_index` of the claim to
+/// `destination_address`, paying enough that parent and child together
+/// reach `package_fee_rate_sat_vb`.
+pub fn build_cpfp_child_psbt(
+    parent_tx_hex: String,
+    output_index: u32,
+    destination_address: String,
+    package_fee_rate_sat_vb: u64,
+    safety_limits: Option<SafetyLimits>,
+    backend: &Backend,
+) -> Result<CpfpChild, HeirError> {
+    let chain = backend.chain();
+    let network = chain.network();
+    SafetyLimits::resolve(safety_limits.as_ref(), network)?
+        .check_fee_rate(package_fee_rate_sat_vb)?;
+
+    let parent = decode_tx(&parent_tx_hex)?;
+    let spent = parent
+        .output
+        .get(output_index as usize)
+        .cloned()
+        .ok_or_else(|| {
+            invalid(format!(
+                "The claim has no output {}; it has {}",
+                output_index,
+                parent.output.len()
+            ))
+        })?;
+    let input_vbytes = spend_vbytes(&spent.script_pubkey).ok_or_else(|| {
+        invalid("Only single-key outputs (P2WPKH, P2TR, P2SH-P2WPKH, P2PKH) can pay for a claim")
+    })?;
+    let destination = Address::from_str(&destination_address)
+        .map_err(|e| {
+            HeirError::new(
+                ErrorKind::InvalidAddress,
+                format!("Invalid destination address: {}", e),
+            )
+        })?
+        .require_network(network)
+        .map_err(|e| {
+            HeirError::new(
+                ErrorKind::NetworkMismatch,
+                format!("Address network mismatch: {}", e),
+            )
+        })?;
+
+    let parent_fee_sat = tx_fee(chain, &parent)?;
+    let parent_vsize = parent.vsize() as u64;
+    if parent_fee_sat as f64 >= package_fee_rate_sat_vb as f64 * parent_vsize as f64 {
+        return Err(invalid(format!(
+            "The claim already pays {:.1} sat/vB; it needs no child",
+            parent_fee_sat as f64 / parent_vsize.max(1) as f64
+        )));
+    }
+
+    // Version, locktime, counts and segwit marker, then one input and output
+    let output_vbytes = 9 + destination.script_pubkey().len() as u64;
+    let child_vsize = 11 + input_vbytes + output_vbytes;
+    let package_vsize = parent_vsize + child_vsize;
+    let package_target = package_fee_rate_sat_vb * package_vsize;
+    // The child must still meet the 1 sat/vB relay minimum on its own
+    let child_fee_sat = package_target
+        .saturating_sub(parent_fee_sat)
+        .max(child_vsize);
+
+    let net_sat = spent.value.to_sat().saturating_sub(child_fee_sat);
+    if net_sat < DEFAULT_DUST_THRESHOLD_SAT {
+        return Err(HeirError::new(
+            ErrorKind::ClaimBelowMinimum {
+                net_sat,
+                minimum_sat: DEFAULT_DUST_THRESHOLD_SAT,
+            },
+            format!(
+                "Paying {} sats for the child leaves only {} sats of the {} sat output",
+                child_fee_sat,
+                net_sat,
+                spent.value.to_sat()
+            ),
+        ));
+    }
+
+    let child = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(parent.compute_txid(), output_index),
+            script_sig: Default::default(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(net_sat),
+            script_pubkey: destination.script_pubkey(),
+        }],
+    };
+    let mut psbt = bitcoin::Psbt::from_unsigned_tx(child).map_err(|e| {
+        HeirError::new(
+            ErrorKind::PsbtConstruction,
+            format!("PSBT creation failed: {}", e),
+        )
+    })?;
+    if !spent.script_pubkey.is_witness_program() {
+        // Legacy and wrapped spends need the whole parent to sign against
+        psbt.inputs[0].non_witness_utxo = Some(parent.clone());
+    }
+    psbt.inputs[0].witness_utxo = Some(spent);
+
+    Ok(CpfpChild {
+        psbt_base64: base64::engine::general_purpose::STANDARD.encode(psbt.serialize()),
+        child_fee_sat,
+        child_vsize,
+        parent_fee_sat,
+        parent_vsize,
+        package_fee_rate_sat_vb: (parent_fee_sat + child_fee_sat) as f64 / package_vsize as f64,
+    })
+}
+
+/// Submit a claim and the signed child paying for it.
+///
+/// A parent already in the mempool is not an error. Where the backend
+/// cannot relay packages, a parent below the mempool minimum is rejected
+/// on its own before the child is sent.
+pub fn broadcast_package(
+    parent_tx_hex: String,
+    child_tx_hex: String,
+    backend: &Backend,
+) -> Result<PackageBroadcast, HeirError> {
+    let parent = decode_tx(&parent_tx_hex)?;
+    let child = decode_tx(&child_tx_hex)?;
+    let parent_txid = parent.compute_txid();
+    if !child
+        .input
+        .iter()
+        .any(|input| input.previous_output.txid == parent_txid)
+    {
+        return Err(invalid("The child does not spend the claim"));
+    }
+
+    let chain = backend.chain();
+    let package_fee_sat = tx_fee(chain, &parent)? + child_fee(backend, &parent, &child)?;
+    let package_vsize = (parent.vsize() + child.vsize()) as u64;
+    let submitted_as_package = chain.relays_packages();
+
+    let txids = chain
+        .broadcast_package(&[parent, child])
+        .map_err(|e| match e.kind {
+            ErrorKind::Broadcast {
+                reason: BroadcastFailure::FeeTooLow,
+            } if !submitted_as_package => HeirError::new(
+                e.kind,
+                format!(
+                    "{}. This server cannot take the claim and child together, so the claim \
+                     must meet the mempool minimum alone; use a server with package relay",
+                    e.message
+                ),
+            ),
+            _ => e,
+        })?;
+
+    Ok(PackageBroadcast {
+        parent_txid: txids[0].to_string(),
+        child_txid: txids[1].to_string(),
+        submitted_as_package,
+        package_fee_sat,
+        package_vsize,
+        package_fee_rate_sat_vb: package_fee_sat as f64 / package_vsize.max(1) as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::decode_psbt;
+    use crate::api::simulated::{SimulatedBackend, SimulatedOutcome};
+    use crate::api::vectors::{generate_test_vectors, TestVectors};
+    use crate::api::Network;
+    use bitcoin::consensus::encode::serialize_hex;
+
+    fn funded(v: &TestVectors) -> SimulatedBackend {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+        sim
+    }
+
+    #[test]
+    fn test_child_reaches_package_rate() {
+        let v = generate_test_vectors(30).unwrap();
+        let sim = funded(&v);
+        let backend = Backend::simulated(&sim);
+
+        let child = build_cpfp_child_psbt(
+            v.tx_hex.clone(),
+            0,
+            v.destination.clone(),
+            20,
+            None,
+            &backend,
+        )
+        .unwrap();
+        assert_eq!(child.parent_fee_sat, v.fee_sat);
+        assert!(child.package_fee_rate_sat_vb >= 20.0);
+        assert!(child.package_fee_rate_sat_vb < 21.0);
+
+        let psbt = decode_psbt(&child.psbt_base64).unwrap();
+        assert_eq!(
+            psbt.unsigned_tx.input[0].previous_output.txid.to_string(),
+            v.txid
+        );
+        assert!(psbt.inputs[0].witness_utxo.is_some());
+
+        let err =
+            build_cpfp_child_psbt(v.tx_hex, 0, v.destination, 5_000, None, &backend).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::FeeRateTooHigh);
+    }
+
+    #[test]
+    fn test_sequential_broadcast_tolerates_parent_in_mempool() {
+        let v = generate_test_vectors(31).unwrap();
+        let sim = funded(&v);
+        let backend = Backend::simulated(&sim);
+        let child = build_cpfp_child_psbt(
+            v.tx_hex.clone(),
+            0,
+            v.destination.clone(),
+            20,
+            None,
+            &backend,
+        )
+        .unwrap();
+        let child_hex = serialize_hex(&decode_psbt(&child.psbt_base64).unwrap().unsigned_tx);
+
+        // The stuck claim is already in the mempool
+        backend
+            .chain()
+            .broadcast(&decode_tx(&v.tx_hex).unwrap())
+            .unwrap();
+        sim.push_broadcast_outcome(SimulatedOutcome::Reject {
+            message: "txn-already-in-mempool".into(),
+        });
+        let result = broadcast_package(v.tx_hex, child_hex, &backend).unwrap();
+        assert_eq!(result.parent_txid, v.txid);
+        assert!(!result.submitted_as_package);
+        assert_eq!(
+            result.package_fee_sat,
+            child.parent_fee_sat + child.child_fee_sat
+        );
+        assert_eq!(sim.broadcast_count(), 2);
+    }
+
+    #[test]
+    fn test_child_must_spend_claim() {
+        let v = generate_test_vectors(32).unwrap();
+        let sim = funded(&v);
+        let err =
+            broadcast_package(v.tx_hex.clone(), v.tx_hex, &Backend::simulated(&sim)).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+}
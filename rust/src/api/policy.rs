@@ -0,0 +1,451 @@
+//! Plain-language description of a vault's spending policy.
+//!
+//! Derived from the actual recovery leaf scripts in the backup, so the app
+//! never shows hand-written copy that could drift from what the vault
+//! really enforces.
+
+use std::str::FromStr;
+
+use bitcoin::bip32::Xpub;
+use bitcoin::opcodes::all::{
+    OP_CHECKSIG, OP_CHECKSIGADD, OP_CHECKSIGVERIFY, OP_CSV, OP_NUMEQUAL, OP_NUMEQUALVERIFY,
+    OP_PUSHNUM_1, OP_PUSHNUM_16,
+};
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::{Script, ScriptBuf};
+use serde::{Deserialize, Serialize};
+
+use nostring_inherit::backup::VaultBackup;
+
+use super::locale::{locale_format, LocaleFormat};
+use super::{
+    executor, parse_backup, parse_network, readonly, timelock, weighted, ErrorKind, HeirError,
+};
+
+/// Who a clause grants spending rights to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClauseKind {
+    /// Owner and co-signer, via the taproot key path. No delay.
+    OwnerKeyPath,
+    /// Heirs, via a timelocked recovery leaf.
+    HeirRecovery,
+    /// The executor together with one heir, via a shorter-timelocked leaf.
+    ExecutorOverride,
+}
+
+/// One way the vault can be spent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyClause {
+    pub kind: ClauseKind,
+    /// Blocks since the last owner activity before this clause applies.
+    pub after_blocks: u32,
+    pub after_days: f64,
+    pub required_signatures: u32,
+    pub total_keys: u32,
+    /// Labels of the heirs whose keys appear in this clause.
+    pub heir_labels: Vec<String>,
+    /// Sum of the clause's heir weights, for a weighted-threshold backup.
+    #[serde(default)]
+    pub combined_weight: Option<u32>,
+    pub sentence: String,
+}
+
+/// Structured and sentence form of the whole policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyDescription {
+    pub clauses: Vec<PolicyClause>,
+    /// Combined heir weight needed to claim, for a weighted-threshold backup.
+    #[serde(default)]
+    pub weight_threshold: Option<u32>,
+    /// All clause sentences joined, e.g. "Owner can spend anytime; after
+    /// 182 days of inactivity, 2 of 3 heirs can claim."
+    pub summary: String,
+}
+
+/// Keys, threshold, and CSV delay read from a recovery leaf script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LeafAnalysis {
+    pub keys: Vec<XOnlyPublicKey>,
+    pub threshold: u32,
+    pub csv_blocks: Option<u32>,
+}
+
+/// Decode a minimally-encoded script number or small-integer opcode.
+pub(crate) fn instruction_int(instruction: &Instruction<'_>) -> Option<i64> {
+    match instruction {
+        Instruction::PushBytes(bytes) => {
+            let bytes = bytes.as_bytes();
+            if bytes.len() > 4 {
+                return None;
+            }
+            let mut value: i64 = 0;
+            for (i, b) in bytes.iter().enumerate() {
+                value |= i64::from(*b) << (8 * i);
+            }
+            // Sign bit lives in the top bit of the last byte
+            if let Some(last) = bytes.last() {
+                if last & 0x80 != 0 {
+                    value &= !(0x80_i64 << (8 * (bytes.len() - 1)));
+                    value = -value;
+                }
+            }
+            Some(value)
+        }
+        Instruction::Op(op) => {
+            let code = op.to_u8();
+            if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&code) {
+                Some(i64::from(code - OP_PUSHNUM_1.to_u8() + 1))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Read keys, threshold, and CSV delay from a tapscript.
+pub(crate) fn analyze_leaf_script(script: &Script) -> LeafAnalysis {
+    let instructions: Vec<Instruction<'_>> = script.instructions().filter_map(Result::ok).collect();
+
+    let mut keys = Vec::new();
+    let mut checksigs = 0u32;
+    let mut verifies = 0u32;
+    let mut threshold = None;
+    let mut csv_blocks = None;
+
+    for (i, instruction) in instructions.iter().enumerate() {
+        let previous = i.checked_sub(1).map(|p| &instructions[p]);
+        match instruction {
+            Instruction::PushBytes(bytes) if bytes.len() == 32 => {
+                if let Ok(key) = XOnlyPublicKey::from_slice(bytes.as_bytes()) {
+                    keys.push(key);
+                }
+            }
+            Instruction::Op(op) if *op == OP_CHECKSIG || *op == OP_CHECKSIGVERIFY => {
+                checksigs += 1;
+                if *op == OP_CHECKSIGVERIFY {
+                    verifies += 1;
+                }
+            }
+            Instruction::Op(op) if *op == OP_NUMEQUAL || *op == OP_NUMEQUALVERIFY => {
+                threshold = previous.and_then(instruction_int).map(|n| n as u32);
+            }
+            Instruction::Op(op) if *op == OP_CSV => {
+                csv_blocks = previous.and_then(instruction_int).map(|n| n as u32);
+            }
+            _ => {}
+        }
+    }
+
+    let uses_checksigadd = instructions
+        .iter()
+        .any(|i| matches!(i, Instruction::Op(op) if *op == OP_CHECKSIGADD));
+    // A CHECKSIGVERIFY ahead of a threshold (an executor key) always signs
+    let threshold = match threshold {
+        Some(k) if uses_checksigadd => k + verifies,
+        _ => checksigs.max(1),
+    };
+
+    LeafAnalysis {
+        keys,
+        threshold,
+        csv_blocks,
+    }
+}
+
+/// Labels of heirs whose x-only key appears in `keys`.
+pub(crate) fn heir_labels_for_keys(backup: &VaultBackup, keys: &[XOnlyPublicKey]) -> Vec<String> {
+    backup
+        .heirs
+        .iter()
+        .filter(|heir| {
+            Xpub::from_str(&heir.xpub)
+                .map(|x| keys.contains(&x.public_key.x_only_public_key().0))
+                .unwrap_or(false)
+        })
+        .map(|heir| heir.label.clone())
+        .collect()
+}
+
+fn heir_sentence(
+    after_days: f64,
+    required: u32,
+    total: u32,
+    labels: &[String],
+    format: &LocaleFormat,
+) -> String {
+    let who = match (required, total, labels) {
+        (1, 1, [label]) => label.clone(),
+        (1, 1, _) => "the heir".to_string(),
+        (k, n, _) if k == n => format!("all {} heirs together", n),
+        (1, n, _) => format!("any 1 of {} heirs", n),
+        (k, n, _) => format!("{} of {} heirs", k, n),
+    };
+    claim_sentence(after_days, &who, format)
+}
+
+/// Sentence for one qualifying group of a weighted-threshold vault.
+fn weighted_sentence(
+    after_days: f64,
+    labels: &[String],
+    combined_weight: u32,
+    threshold: u32,
+    format: &LocaleFormat,
+) -> String {
+    let who = match labels {
+        [label] => format!("{} (weight {})", label, combined_weight),
+        [rest @ .., last] => format!(
+            "{} and {} together (weight {} of {} needed)",
+            rest.join(", "),
+            last,
+            combined_weight,
+            threshold
+        ),
+        [] => "the heirs".to_string(),
+    };
+    claim_sentence(after_days, &who, format)
+}
+
+fn claim_sentence(after_days: f64, who: &str, format: &LocaleFormat) -> String {
+    let days = after_days.round();
+    let unit = if days == 1.0 { "day" } else { "days" };
+    format!(
+        "after {} {} of inactivity, {} can claim",
+        format.integer(days as u64),
+        unit,
+        who
+    )
+}
+
+/// Describe the vault's spending policy in structured sentences.
+pub fn describe_policy(vault_json: String) -> Result<PolicyDescription, HeirError> {
+    describe_policy_localized(vault_json, "en".to_string())
+}
+
+/// [`describe_policy`] with numbers in the sentences formatted for `locale`
+/// (e.g. "de", "fr-CA"). The wording stays English.
+///
+/// A read-only copy returns the policy recorded when it was exported.
+pub fn describe_policy_localized(
+    vault_json: String,
+    locale: String,
+) -> Result<PolicyDescription, HeirError> {
+    if readonly::is_read_only(&vault_json) {
+        return Ok(readonly::import_read_only_vault(vault_json)?.policy);
+    }
+    let format = locale_format(locale);
+    let backup = parse_backup(&vault_json)?;
+    let network = parse_network(&backup.network)?;
+    let weights = weighted::heir_weights(&vault_json)?;
+    let executor = executor::load_executor(&vault_json)?;
+
+    let mut clauses = vec![PolicyClause {
+        kind: ClauseKind::OwnerKeyPath,
+        after_blocks: 0,
+        after_days: 0.0,
+        required_signatures: 2,
+        total_keys: 2,
+        heir_labels: Vec::new(),
+        combined_weight: None,
+        sentence: "Owner can spend anytime (with the co-signer)".to_string(),
+    }];
+
+    for leaf in &backup.recovery_leaves {
+        let script = ScriptBuf::from_hex(&leaf.script_hex).map_err(|e| {
+            HeirError::new(
+                ErrorKind::InvalidBackup,
+                format!("Invalid recovery leaf script: {}", e),
+            )
+        })?;
+        let analysis = analyze_leaf_script(&script);
+        let after_blocks = analysis
+            .csv_blocks
+            .unwrap_or_else(|| u32::from(leaf.timelock_blocks));
+        let after_days = timelock::blocks_to_days(i64::from(after_blocks), network);
+        let total_keys = analysis.keys.len() as u32;
+        let heir_labels = heir_labels_for_keys(&backup, &analysis.keys);
+        let combined_weight = weights.as_ref().map(|w| {
+            backup
+                .heirs
+                .iter()
+                .zip(&w.weights)
+                .filter(|(heir, _)| heir_labels.contains(&heir.label))
+                .map(|(_, weight)| weight)
+                .sum()
+        });
+        if let Some(executor) = executor
+            .as_ref()
+            .filter(|e| analysis.keys.contains(&e.key()))
+        {
+            let with = match heir_labels.as_slice() {
+                [label] => label.clone(),
+                _ => "any 1 heir".to_string(),
+            };
+            let who = format!("{} with {}", executor.label, with);
+            clauses.push(PolicyClause {
+                kind: ClauseKind::ExecutorOverride,
+                after_blocks,
+                after_days,
+                required_signatures: analysis.threshold,
+                total_keys,
+                heir_labels,
+                combined_weight: None,
+                sentence: claim_sentence(after_days, &who, &format),
+            });
+            continue;
+        }
+        let sentence = match (&weights, combined_weight) {
+            (Some(w), Some(combined)) => {
+                weighted_sentence(after_days, &heir_labels, combined, w.threshold, &format)
+            }
+            _ => heir_sentence(
+                after_days,
+                analysis.threshold,
+                total_keys,
+                &heir_labels,
+                &format,
+            ),
+        };
+
+        clauses.push(PolicyClause {
+            kind: ClauseKind::HeirRecovery,
+            after_blocks,
+            after_days,
+            required_signatures: analysis.threshold,
+            total_keys,
+            heir_labels,
+            combined_weight,
+            sentence,
+        });
+    }
+
+    let summary = format!(
+        "{}.",
+        clauses
+            .iter()
+            .map(|c| c.sentence.as_str())
+            .collect::<Vec<_>>()
+            .join("; ")
+    );
+
+    Ok(PolicyDescription {
+        clauses,
+        weight_threshold: weights.map(|w| w.threshold),
+        summary,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::vectors::generate_test_vectors;
+    use bitcoin::script::Builder;
+
+    fn key(byte: u8) -> XOnlyPublicKey {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let sk = bitcoin::secp256k1::SecretKey::from_slice(&[byte; 32]).unwrap();
+        sk.x_only_public_key(&secp).0
+    }
+
+    #[test]
+    fn test_analyze_single_key_leaf() {
+        let script = Builder::new()
+            .push_x_only_key(&key(1))
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_int(26280)
+            .push_opcode(OP_CSV)
+            .into_script();
+        let analysis = analyze_leaf_script(&script);
+        assert_eq!(analysis.keys, vec![key(1)]);
+        assert_eq!(analysis.threshold, 1);
+        assert_eq!(analysis.csv_blocks, Some(26280));
+    }
+
+    #[test]
+    fn test_analyze_threshold_leaf() {
+        let script = Builder::new()
+            .push_x_only_key(&key(1))
+            .push_opcode(OP_CHECKSIG)
+            .push_x_only_key(&key(2))
+            .push_opcode(OP_CHECKSIGADD)
+            .push_x_only_key(&key(3))
+            .push_opcode(OP_CHECKSIGADD)
+            .push_int(2)
+            .push_opcode(OP_NUMEQUALVERIFY)
+            .push_int(144)
+            .push_opcode(OP_CSV)
+            .into_script();
+        let analysis = analyze_leaf_script(&script);
+        assert_eq!(analysis.keys.len(), 3);
+        assert_eq!(analysis.threshold, 2);
+        assert_eq!(analysis.csv_blocks, Some(144));
+    }
+
+    #[test]
+    fn test_analyze_executor_leaf() {
+        let script = Builder::new()
+            .push_x_only_key(&key(9))
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_x_only_key(&key(1))
+            .push_opcode(OP_CHECKSIG)
+            .push_x_only_key(&key(2))
+            .push_opcode(OP_CHECKSIGADD)
+            .push_int(1)
+            .push_opcode(OP_NUMEQUALVERIFY)
+            .push_int(72)
+            .push_opcode(OP_CSV)
+            .into_script();
+        let analysis = analyze_leaf_script(&script);
+        assert_eq!(analysis.keys.len(), 3);
+        assert_eq!(analysis.threshold, 2);
+    }
+
+    #[test]
+    fn test_heir_sentences() {
+        let en = locale_format("en".into());
+        assert_eq!(
+            heir_sentence(182.5, 2, 3, &[], &en),
+            "after 183 days of inactivity, 2 of 3 heirs can claim"
+        );
+        assert_eq!(
+            heir_sentence(1.0, 1, 1, &["Alice".to_string()], &en),
+            "after 1 day of inactivity, Alice can claim"
+        );
+        assert_eq!(
+            heir_sentence(1095.0, 1, 2, &[], &locale_format("de".into())),
+            "after 1.095 days of inactivity, any 1 of 2 heirs can claim"
+        );
+    }
+
+    #[test]
+    fn test_weighted_sentences() {
+        let en = locale_format("en".into());
+        assert_eq!(
+            weighted_sentence(
+                182.5,
+                &["Spouse".to_string(), "Alice".to_string()],
+                3,
+                3,
+                &en
+            ),
+            "after 183 days of inactivity, Spouse and Alice together (weight 3 of 3 needed) can claim"
+        );
+        assert_eq!(
+            weighted_sentence(1.0, &["Spouse".to_string()], 3, 3, &en),
+            "after 1 day of inactivity, Spouse (weight 3) can claim"
+        );
+    }
+
+    #[test]
+    fn test_describe_vector_policy() {
+        let v = generate_test_vectors(2).unwrap();
+        let policy = describe_policy(v.backup_json).unwrap();
+        assert_eq!(policy.clauses.len(), 2);
+        assert_eq!(policy.clauses[0].kind, ClauseKind::OwnerKeyPath);
+        let heir = &policy.clauses[1];
+        assert_eq!(heir.kind, ClauseKind::HeirRecovery);
+        assert_eq!(heir.after_blocks, 144);
+        assert_eq!(heir.heir_labels, vec!["Synthetic Heir"]);
+        assert!(policy.summary.starts_with("Owner can spend anytime"));
+    }
+}
@@ -0,0 +1,356 @@
+//! Several Electrum servers behind one backend.
+//!
+//! Public servers go down, lag behind the chain or time out halfway through
+//! a claim. [`probe_servers`] checks a list of them at once, and
+//! [`Backend::electrum_pool`] turns the list into a single [`Backend`] that
+//! starts with the healthiest server and moves to the next one whenever a
+//! call fails for connection reasons. Errors that say something about the
+//! request itself, such as a rejected broadcast, are returned as they are.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use bitcoin::{Address, Network, Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
+use super::backend::{ChainBackend, ChainHistoryEntry, ChainUtxo};
+use super::{Backend, ErrorKind, HeirError};
+
+/// Servers more than this many blocks from the median height disagree with
+/// the rest of the pool.
+const MAX_HEIGHT_SPREAD: u64 = 1;
+
+/// How one server answered the probe.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerHealth {
+    pub url: String,
+    /// Round trip of the height query, including connecting.
+    pub latency_ms: Option<u64>,
+    pub height: Option<u64>,
+    /// Reachable and within a block of the median height of the pool.
+    pub in_agreement: bool,
+    pub error: Option<HeirError>,
+}
+
+/// Query the tip height of every server in `members` concurrently.
+/// Results are in the order given.
+pub(crate) fn probe(members: &[(String, Backend)]) -> Vec<ServerHealth> {
+    let mut health: Vec<ServerHealth> = std::thread::scope(|scope| {
+        let probes: Vec<_> = members
+            .iter()
+            .map(|(url, backend)| {
+                scope.spawn(move || {
+                    let started = Instant::now();
+                    let result = backend.chain().tip_height();
+                    let latency_ms = started.elapsed().as_millis() as u64;
+                    match result {
+                        Ok(height) => ServerHealth {
+                            url: url.clone(),
+                            latency_ms: Some(latency_ms),
+                            height: Some(height),
+                            in_agreement: false,
+                            error: None,
+                        },
+                        Err(error) => ServerHealth {
+                            url: url.clone(),
+                            latency_ms: None,
+                            height: None,
+                            in_agreement: false,
+                            error: Some(error),
+                        },
+                    }
+                })
+            })
+            .collect();
+        probes
+            .into_iter()
+            .map(|probe| probe.join().expect("probe thread panicked"))
+            .collect()
+    });
+
+    let mut heights: Vec<u64> = health.iter().filter_map(|h| h.height).collect();
+    heights.sort_unstable();
+    if let Some(&median) = heights.get(heights.len() / 2) {
+        for server in &mut health {
+            server.in_agreement = server
+                .height
+                .is_some_and(|height| height.abs_diff(median) <= MAX_HEIGHT_SPREAD);
+        }
+    }
+    health
+}
+
+/// Electrum backend for `url`, through the SOCKS5 `proxy` if given.
+fn electrum_member(
+    url: String,
+    network: super::Network,
+    proxy: Option<String>,
+) -> Result<Backend, HeirError> {
+    #[cfg(feature = "electrum")]
+    {
+        let options = super::socket::ConnectionOptions {
+            socks5_proxy: proxy,
+            ..Default::default()
+        };
+        Backend::electrum_with_options(url, network, options)
+    }
+    #[cfg(not(feature = "electrum"))]
+    {
+        let _ = proxy;
+        Backend::electrum(url, network)
+    }
+}
+
+fn electrum_members(
+    urls: Vec<String>,
+    network: super::Network,
+    proxy: Option<String>,
+) -> Result<Vec<(String, Backend)>, HeirError> {
+    if urls.is_empty() {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            "At least one server URL is needed",
+        ));
+    }
+    urls.into_iter()
+        .map(|url| electrum_member(url.clone(), network, proxy.clone()).map(|b| (url, b)))
+        .collect()
+}
+
+/// Check every Electrum server in `urls` at once: whether it answers, how
+/// fast, and whether its chain tip agrees with the others. With a SOCKS5
+/// `proxy` (`socks5://host:port`, e.g. Orbot's), every probe goes through
+/// it.
+pub fn probe_servers(
+    urls: Vec<String>,
+    network: super::Network,
+    proxy: Option<String>,
+) -> Result<Vec<ServerHealth>, HeirError> {
+    Ok(probe(&electrum_members(urls, network, proxy)?))
+}
+
+/// Probe the Electrum servers in `urls` and pool them, healthiest first.
+pub(crate) fn electrum_pool(
+    urls: Vec<String>,
+    network: super::Network,
+    proxy: Option<String>,
+) -> Result<ServerPool, HeirError> {
+    let members = electrum_members(urls, network, proxy)?;
+    let health = probe(&members);
+    ServerPool::new(members, &health)
+}
+
+/// Failover over several backends of the same network.
+pub(crate) struct ServerPool {
+    network: Network,
+    /// Healthiest first.
+    members: Vec<(String, Backend)>,
+    /// The member that answered last; calls start there.
+    active: AtomicUsize,
+}
+
+impl ServerPool {
+    /// Pool `members`, ordered by their probe results: servers in agreement
+    /// by latency, then reachable ones that disagree, then the unreachable.
+    pub(crate) fn new(
+        members: Vec<(String, Backend)>,
+        health: &[ServerHealth],
+    ) -> Result<ServerPool, HeirError> {
+        let network = match members.first() {
+            Some((_, backend)) => backend.chain().network(),
+            None => {
+                return Err(HeirError::new(
+                    ErrorKind::InvalidInput,
+                    "At least one server is needed",
+                ))
+            }
+        };
+        let mut ranked: Vec<_> = members.into_iter().zip(health).collect();
+        ranked.sort_by_key(|(_, h)| (!h.in_agreement, h.error.is_some(), h.latency_ms));
+        Ok(ServerPool {
+            network,
+            members: ranked.into_iter().map(|(member, _)| member).collect(),
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    /// Run `f` on the active member, then on each other member in turn
+    /// while it fails for connection reasons. Returns the first failure if
+    /// every member fails.
+    fn call<T>(
+        &self,
+        f: impl Fn(&dyn ChainBackend) -> Result<T, HeirError>,
+    ) -> Result<T, HeirError> {
+        let start = self.active.load(Ordering::Relaxed);
+        let mut first_error = None;
+        for offset in 0..self.members.len() {
+            let index = (start + offset) % self.members.len();
+            match f(self.members[index].1.chain()) {
+                Ok(value) => {
+                    self.active.store(index, Ordering::Relaxed);
+                    return Ok(value);
+                }
+                Err(error) if fails_over(&error) => {
+                    first_error.get_or_insert(error);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Err(first_error.expect("pool has at least one member"))
+    }
+}
+
+/// Whether another server might answer where this one failed.
+fn fails_over(error: &HeirError) -> bool {
+    matches!(
+        error.kind,
+        ErrorKind::Connection
+            | ErrorKind::ServerQuery
+            | ErrorKind::HistoryTooLarge
+            | ErrorKind::HeightOutOfRange { .. }
+    )
+}
+
+impl ChainBackend for ServerPool {
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    fn tip_height(&self) -> Result<u64, HeirError> {
+        self.call(|chain| chain.tip_height())
+    }
+
+    fn list_unspent(&self, address: &Address) -> Result<Vec<ChainUtxo>, HeirError> {
+        self.call(|chain| chain.list_unspent(address))
+    }
+
+    fn history(&self, address: &Address) -> Result<Vec<ChainHistoryEntry>, HeirError> {
+        self.call(|chain| chain.history(address))
+    }
+
+    fn list_unspent_many(&self, addresses: &[Address]) -> Result<Vec<Vec<ChainUtxo>>, HeirError> {
+        self.call(|chain| chain.list_unspent_many(addresses))
+    }
+
+    fn histories(&self, addresses: &[Address]) -> Result<Vec<Vec<ChainHistoryEntry>>, HeirError> {
+        self.call(|chain| chain.histories(addresses))
+    }
+
+    fn block_time(&self, height: u32) -> Result<u64, HeirError> {
+        self.call(|chain| chain.block_time(height))
+    }
+
+    fn transaction(&self, txid: &Txid) -> Result<Transaction, HeirError> {
+        self.call(|chain| chain.transaction(txid))
+    }
+
+    fn transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>, HeirError> {
+        self.call(|chain| chain.transactions(txids))
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, HeirError> {
+        self.call(|chain| chain.broadcast(tx))
+    }
+
+    fn relays_packages(&self) -> bool {
+        self.members[self.active.load(Ordering::Relaxed)]
+            .1
+            .chain()
+            .relays_packages()
+    }
+
+    fn broadcast_package(&self, txs: &[Transaction]) -> Result<Vec<Txid>, HeirError> {
+        self.call(|chain| chain.broadcast_package(txs))
+    }
+
+    fn estimate_fee_rate(&self, target_blocks: u16) -> Result<f64, HeirError> {
+        self.call(|chain| chain.estimate_fee_rate(target_blocks))
+    }
+
+    fn relay_fee_rate(&self) -> Result<f64, HeirError> {
+        self.call(|chain| chain.relay_fee_rate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+
+    fn active_url(pool: &ServerPool) -> &str {
+        &pool.members[pool.active.load(Ordering::Relaxed)].0
+    }
+
+    fn member(name: &str, height: u64) -> (String, SimulatedBackend) {
+        let sim = SimulatedBackend::new(crate::api::Network::Testnet);
+        sim.set_height(height);
+        (name.to_string(), sim)
+    }
+
+    #[test]
+    fn test_probe_flags_lagging_and_offline_servers() {
+        let servers = [
+            member("a", 800),
+            member("b", 801),
+            member("c", 700),
+            member("d", 800),
+        ];
+        servers[3].1.set_offline(true);
+        let members: Vec<_> = servers
+            .iter()
+            .map(|(url, sim)| (url.clone(), Backend::simulated(sim)))
+            .collect();
+
+        let health = probe(&members);
+        let agreeing: Vec<bool> = health.iter().map(|h| h.in_agreement).collect();
+        assert_eq!(agreeing, [true, true, false, false]);
+        assert_eq!(health[2].height, Some(700));
+        assert_eq!(
+            health[3].error.as_ref().unwrap().kind,
+            ErrorKind::Connection
+        );
+
+        let pool = ServerPool::new(members, &health).unwrap();
+        assert!(["a", "b"].contains(&active_url(&pool)));
+        assert_eq!(pool.members[2].0, "c");
+        assert_eq!(pool.members[3].0, "d");
+    }
+
+    #[test]
+    fn test_pool_fails_over_and_stays() {
+        let primary = member("primary", 900);
+        let backup = member("backup", 900);
+        let members = vec![
+            (primary.0.clone(), Backend::simulated(&primary.1)),
+            (backup.0.clone(), Backend::simulated(&backup.1)),
+        ];
+        let health = probe(&members);
+        let pool = ServerPool::new(members, &health).unwrap();
+        let first = active_url(&pool).to_string();
+        let (down, up) = if first == "primary" {
+            (&primary.1, &backup.1)
+        } else {
+            (&backup.1, &primary.1)
+        };
+
+        down.set_offline(true);
+        up.set_height(901);
+        assert_eq!(pool.tip_height().unwrap(), 901);
+        assert_ne!(active_url(&pool), first);
+
+        // The recovered server is not switched back to mid-flow
+        down.set_offline(false);
+        assert_eq!(pool.tip_height().unwrap(), 901);
+        assert_ne!(active_url(&pool), first);
+
+        up.set_offline(true);
+        down.set_offline(true);
+        assert_eq!(pool.tip_height().unwrap_err().kind, ErrorKind::Connection);
+    }
+
+    #[test]
+    fn test_empty_pool_is_rejected() {
+        let err = probe_servers(Vec::new(), crate::api::Network::Testnet, None).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,76 @@
+//! Export of timing spans to the app (requires the `tracing` feature).
+//!
+//! The app registers a callback once with [`set_span_callback`] and
+//! receives a [`SpanTiming`] each time an instrumented section (network
+//! call, vault reconstruction, PSBT construction) finishes. Timings are
+//! queued and handed to Dart from one exporter thread, so a span never
+//! waits on the app and the measured sections are not slowed down.
+
+use std::sync::mpsc::{channel, Sender};
+use std::time::Instant;
+
+use flutter_rust_bridge::DartFnFuture;
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::api::{ErrorKind, HeirError};
+
+/// Duration of one completed span.
+#[derive(Debug, Clone)]
+pub struct SpanTiming {
+    pub name: String,
+    pub duration_ms: f64,
+}
+
+/// Queues span timings for the exporter thread.
+struct CallbackLayer {
+    timings: Sender<SpanTiming>,
+}
+
+impl<S> Layer<S> for CallbackLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Instant::now());
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(started) = span.extensions().get::<Instant>().copied() else {
+            return;
+        };
+        let timing = SpanTiming {
+            name: span.name().to_string(),
+            duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        };
+        // Only fails once the exporter thread is gone; nothing is listening
+        let _ = self.timings.send(timing);
+    }
+}
+
+/// Send every span timing to `on_span`. Can only be called once per process.
+pub fn set_span_callback(
+    on_span: impl Fn(SpanTiming) -> DartFnFuture<()> + Send + Sync + 'static,
+) -> Result<(), HeirError> {
+    let (timings, queued) = channel();
+    let subscriber = tracing_subscriber::registry().with(CallbackLayer { timings });
+    tracing::subscriber::set_global_default(subscriber).map_err(|e| {
+        HeirError::new(
+            ErrorKind::Internal,
+            format!("Span exporter already installed: {}", e),
+        )
+    })?;
+    std::thread::spawn(move || {
+        for timing in queued {
+            futures::executor::block_on(on_span(timing));
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,607 @@
+//! Signer-facing metadata on claim PSBTs.
+//!
+//! Hardware wallets and Sparrow need key origins and leaf hashes to find the
+//! heir's key and show the spending path instead of a blind hash.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use bitcoin::bip32::{DerivationPath, Fingerprint, KeySource, Xpub};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::psbt::PsbtSighashType;
+use bitcoin::sighash::TapSighashType;
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TaprootSpendInfo};
+use bitcoin::absolute::LockTime;
+use bitcoin::transaction::Version;
+use bitcoin::{
+    Address, Amount, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use serde::{Deserialize, Serialize};
+
+use nostring_inherit::backup::VaultBackup;
+
+use super::policy::analyze_leaf_script;
+use super::sponsor::{finalize_key_spend, is_sponsor_input};
+use super::{decode_psbt, ErrorKind, HeirError};
+
+/// Sighash type claim signatures commit to. Both commit to every input and
+/// output; other types would let a signature be reused on a modified claim.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClaimSighash {
+    /// SIGHASH_DEFAULT (64-byte signatures). Left implicit in the PSBT.
+    #[default]
+    Default,
+    /// SIGHASH_ALL (65-byte signatures), written explicitly to the PSBT for
+    /// signers that refuse the implicit default.
+    All,
+}
+
+impl ClaimSighash {
+    pub(crate) fn tap_sighash_type(self) -> TapSighashType {
+        match self {
+            ClaimSighash::Default => TapSighashType::Default,
+            ClaimSighash::All => TapSighashType::All,
+        }
+    }
+
+    fn psbt_field(self) -> Option<PsbtSighashType> {
+        match self {
+            ClaimSighash::Default => None,
+            ClaimSighash::All => Some(TapSighashType::All.into()),
+        }
+    }
+}
+
+fn unsupported_sighash(input_index: usize, what: impl std::fmt::Display) -> HeirError {
+    HeirError::new(
+        ErrorKind::UnsupportedSighash { input_index },
+        format!(
+            "Input {} uses sighash {}; only SIGHASH_DEFAULT and SIGHASH_ALL are accepted",
+            input_index, what
+        ),
+    )
+}
+
+/// Set the sighash type on every input.
+pub(crate) fn apply_sighash(psbt: &mut Psbt, sighash: ClaimSighash) {
+    for input in &mut psbt.inputs {
+        input.sighash_type = sighash.psbt_field();
+    }
+}
+
+/// Reject any requested sighash or signature other than DEFAULT/ALL.
+pub(crate) fn check_sighash_types(psbt: &Psbt) -> Result<(), HeirError> {
+    let allowed = |t: TapSighashType| matches!(t, TapSighashType::Default | TapSighashType::All);
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        if let Some(requested) = input.sighash_type {
+            match requested.taproot_hash_ty() {
+                Ok(t) if allowed(t) => {}
+                _ => return Err(unsupported_sighash(index, requested)),
+            }
+        }
+        let sigs = input.tap_key_sig.iter().chain(input.tap_script_sigs.values());
+        if let Some(sig) = sigs.into_iter().find(|sig| !allowed(sig.sighash_type)) {
+            return Err(unsupported_sighash(index, sig.sighash_type));
+        }
+        // A 65-byte first witness element is a signature with explicit sighash
+        if let Some(first) = input.final_script_witness.as_ref().and_then(|w| w.nth(0)) {
+            if first.len() == 65 && first[64] != TapSighashType::All as u8 {
+                return Err(unsupported_sighash(index, format!("0x{:02x}", first[64])));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Set the sighash type on a single input of a claim PSBT.
+pub fn set_input_sighash(
+    psbt_base64: String,
+    input_index: usize,
+    sighash: ClaimSighash,
+) -> Result<String, HeirError> {
+    use base64::Engine;
+
+    let mut psbt = decode_psbt(&psbt_base64)?;
+    let input = psbt.inputs.get_mut(input_index).ok_or_else(|| {
+        HeirError::new(
+            ErrorKind::InvalidInput,
+            format!("Input index {} out of range", input_index),
+        )
+    })?;
+    input.sighash_type = sighash.psbt_field();
+    Ok(base64::engine::general_purpose::STANDARD.encode(psbt.serialize()))
+}
+
+/// Build final witnesses for inputs that carry signatures but were not
+/// finalized by their signer, so PSBTs assembled from several devices
+/// (some finalizing, some only adding `tap_script_sigs`) extract cleanly.
+/// A fee sponsor's key-path or P2WPKH input is completed directly.
+///
+/// Returns `(input_index, reason)` for inputs that could not be completed.
+pub(crate) fn finalize_signed_inputs(psbt: &mut Psbt) -> Vec<(usize, String)> {
+    use miniscript::psbt::PsbtExt;
+
+    let secp = bitcoin::secp256k1::Secp256k1::verification_only();
+    let mut failures = Vec::new();
+    for index in 0..psbt.inputs.len() {
+        let input = &psbt.inputs[index];
+        let has_sigs = input.tap_key_sig.is_some()
+            || !input.tap_script_sigs.is_empty()
+            || !input.partial_sigs.is_empty();
+        if input.final_script_witness.is_some() || input.final_script_sig.is_some() || !has_sigs {
+            continue;
+        }
+        // A fee sponsor's input has no leaves to build a descriptor from
+        if is_sponsor_input(psbt, index) && finalize_key_spend(&mut psbt.inputs[index]) {
+            continue;
+        }
+        if let Err(e) = psbt.finalize_inp_mut(&secp, index) {
+            failures.push((index, e.to_string()));
+        }
+    }
+    failures
+}
+
+/// Result of [`verify_partial_sig`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialSigCheck {
+    pub signature_present: bool,
+    pub valid: bool,
+    pub detail: String,
+}
+
+/// Check one heir's script-path signature on one input against the leaf
+/// sighash, so a bad co-signature is caught before the other heirs sign.
+pub fn verify_partial_sig(
+    psbt_base64: String,
+    input_index: usize,
+    heir_fingerprint: String,
+    vault_json: String,
+) -> Result<PartialSigCheck, HeirError> {
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Message, Secp256k1};
+    use bitcoin::sighash::{Prevouts, SighashCache};
+
+    let backup = super::parse_backup(&vault_json)?;
+    let psbt = decode_psbt(&psbt_base64)?;
+
+    let (xpub, _) = heir_key_sources(&backup)?
+        .into_iter()
+        .find(|(_, (fingerprint, _))| {
+            fingerprint.to_string().eq_ignore_ascii_case(heir_fingerprint.trim())
+        })
+        .ok_or_else(|| {
+            HeirError::new(
+                ErrorKind::InvalidInput,
+                format!("No heir with fingerprint {} in this vault", heir_fingerprint),
+            )
+        })?;
+    let heir_key = xpub.public_key.x_only_public_key().0;
+
+    let input = psbt.inputs.get(input_index).ok_or_else(|| {
+        HeirError::new(
+            ErrorKind::InvalidInput,
+            format!("Input index {} out of range", input_index),
+        )
+    })?;
+
+    let result = |present: bool, valid: bool, detail: String| PartialSigCheck {
+        signature_present: present,
+        valid,
+        detail,
+    };
+
+    let Some(((_, leaf_hash), sig)) = input
+        .tap_script_sigs
+        .iter()
+        .find(|((key, _), _)| *key == heir_key)
+    else {
+        return Ok(result(false, false, "No signature from this heir on the input".into()));
+    };
+
+    let vault_leaves: Vec<TapLeafHash> = backup
+        .recovery_leaves
+        .iter()
+        .filter_map(|leaf| ScriptBuf::from_hex(&leaf.script_hex).ok())
+        .map(|script| TapLeafHash::from_script(&script, LeafVersion::TapScript))
+        .collect();
+    if !vault_leaves.contains(leaf_hash) {
+        return Ok(result(true, false, "Signature commits to a leaf outside this vault".into()));
+    }
+
+    let prevouts: Option<Vec<_>> = psbt.inputs.iter().map(|i| i.witness_utxo.clone()).collect();
+    let Some(prevouts) = prevouts else {
+        return Err(HeirError::new(
+            ErrorKind::InvalidPsbt,
+            "Every input needs its witness UTXO to compute the sighash",
+        ));
+    };
+    let sighash = SighashCache::new(&psbt.unsigned_tx)
+        .taproot_script_spend_signature_hash(
+            input_index,
+            &Prevouts::All(&prevouts),
+            *leaf_hash,
+            sig.sighash_type,
+        )
+        .map_err(|e| HeirError::new(ErrorKind::InvalidPsbt, format!("Sighash failed: {}", e)))?;
+
+    let secp = Secp256k1::verification_only();
+    let msg = Message::from_digest(sighash.to_byte_array());
+    Ok(match secp.verify_schnorr(&sig.signature, &msg, &heir_key) {
+        Ok(()) => result(true, true, "Signature is valid".into()),
+        Err(e) => result(true, false, format!("Signature does not verify: {}", e)),
+    })
+}
+
+/// Short digest of what a claim PSBT's signatures commit to: the unsigned
+/// transaction and every spent output. Signing and annotations don't change
+/// it, so co-heirs can read it aloud to confirm they hold the same claim.
+pub fn psbt_fingerprint(psbt_base64: String) -> Result<String, HeirError> {
+    use bitcoin::consensus::Encodable;
+    use bitcoin::hashes::{sha256, Hash, HashEngine};
+
+    let psbt = decode_psbt(&psbt_base64)?;
+    let mut engine = sha256::Hash::engine();
+    let encode_err =
+        |e: std::io::Error| HeirError::new(ErrorKind::InvalidPsbt, format!("Encoding failed: {}", e));
+    psbt.unsigned_tx.consensus_encode(&mut engine).map_err(encode_err)?;
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        let utxo = input.witness_utxo.as_ref().ok_or_else(|| {
+            HeirError::new(
+                ErrorKind::InvalidPsbt,
+                format!("Input {} is missing its witness UTXO", index),
+            )
+        })?;
+        utxo.consensus_encode(&mut engine).map_err(encode_err)?;
+    }
+    let digest = sha256::Hash::from_engine(engine);
+
+    // 8 bytes, grouped for reading over the phone
+    let hex = hex::encode(&digest.as_byte_array()[..8]);
+    Ok(hex
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("-"))
+}
+
+/// Each heir's xpub and its origin, as recorded in the backup.
+pub(crate) fn heir_key_sources(backup: &VaultBackup) -> Result<Vec<(Xpub, KeySource)>, HeirError> {
+    backup
+        .heirs
+        .iter()
+        .map(|heir| {
+            let invalid = |what: &str, e: &dyn std::fmt::Display| {
+                HeirError::new(
+                    ErrorKind::InvalidBackup,
+                    format!("Invalid {} for heir {}: {}", what, heir.label, e),
+                )
+            };
+            let xpub = Xpub::from_str(&heir.xpub).map_err(|e| invalid("xpub", &e))?;
+            let fingerprint =
+                Fingerprint::from_str(&heir.fingerprint).map_err(|e| invalid("fingerprint", &e))?;
+            let path = DerivationPath::from_str(&heir.derivation_path)
+                .map_err(|e| invalid("derivation path", &e))?;
+            Ok((xpub, (fingerprint, path)))
+        })
+        .collect()
+}
+
+/// Add taproot key origins, leaf scripts, and control blocks to every input
+/// of a claim PSBT.
+///
+/// Each heir key found in a recovery leaf gets a `tap_bip32_derivation`
+/// entry listing the leaves it can sign, so any heir's signer can locate
+/// its key. Carrying every leaf with its control block lets third-party
+/// signers compute the script-path sighash and finalize on their own.
+fn add_taproot_signing_info(
+    psbt: &mut Psbt,
+    backup: &VaultBackup,
+    spend_info: &TaprootSpendInfo,
+    recovery_scripts: &[ScriptBuf],
+) -> Result<(), HeirError> {
+    let sources = heir_key_sources(backup)?;
+
+    let mut derivations: BTreeMap<XOnlyPublicKey, (Vec<TapLeafHash>, KeySource)> = BTreeMap::new();
+    let mut tap_scripts = BTreeMap::new();
+    for script in recovery_scripts {
+        let leaf = (script.clone(), LeafVersion::TapScript);
+        let control_block = spend_info.control_block(&leaf).ok_or_else(|| {
+            HeirError::new(
+                ErrorKind::PsbtConstruction,
+                "Recovery leaf is not part of the vault's taproot tree",
+            )
+        })?;
+        tap_scripts.insert(control_block, leaf);
+
+        let leaf_hash = TapLeafHash::from_script(script, LeafVersion::TapScript);
+        let keys = analyze_leaf_script(script).keys;
+        for (xpub, source) in &sources {
+            let key = xpub.public_key.x_only_public_key().0;
+            if !keys.contains(&key) {
+                continue;
+            }
+            derivations
+                .entry(key)
+                .or_insert_with(|| (Vec::new(), source.clone()))
+                .0
+                .push(leaf_hash);
+        }
+    }
+
+    for input in &mut psbt.inputs {
+        input.tap_internal_key = Some(spend_info.internal_key());
+        input.tap_merkle_root = spend_info.merkle_root();
+        input.tap_scripts.extend(tap_scripts.clone());
+        input.tap_key_origins.extend(derivations.clone());
+    }
+    Ok(())
+}
+
+/// Record every heir xpub with its origin in the PSBT's global xpub map,
+/// so multisig-aware signers can check the quorum and match their key.
+fn add_global_xpubs(psbt: &mut Psbt, backup: &VaultBackup) -> Result<(), HeirError> {
+    psbt.xpub.extend(heir_key_sources(backup)?);
+    Ok(())
+}
+
+/// Unsigned claim spending every UTXO through a recovery leaf with a
+/// `csv_blocks` delay to `destination`, shaped like upstream's single-heir
+/// claim. Used for trees this crate rebuilds itself.
+pub(crate) fn script_path_claim_psbt(
+    csv_blocks: u16,
+    utxos: &[(OutPoint, TxOut)],
+    destination: &Address,
+    fee: Amount,
+) -> Result<Psbt, HeirError> {
+    let construction = |message: String| HeirError::new(ErrorKind::PsbtConstruction, message);
+    let total: Amount = utxos.iter().map(|(_, txout)| txout.value).sum();
+    let value = total
+        .checked_sub(fee)
+        .ok_or_else(|| construction(format!("Fee {} exceeds the vault balance {}", fee, total)))?;
+
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: utxos
+            .iter()
+            .map(|(outpoint, _)| TxIn {
+                previous_output: *outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::from_height(csv_blocks),
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: vec![TxOut {
+            value,
+            script_pubkey: destination.script_pubkey(),
+        }],
+    };
+    let mut psbt = Psbt::from_unsigned_tx(tx)
+        .map_err(|e| construction(format!("PSBT construction failed: {}", e)))?;
+    for (input, (_, txout)) in psbt.inputs.iter_mut().zip(utxos) {
+        input.witness_utxo = Some(txout.clone());
+    }
+    Ok(psbt)
+}
+
+/// Attach all signer-facing metadata to a freshly built claim PSBT.
+pub(crate) fn annotate_claim_psbt(
+    psbt: &mut Psbt,
+    backup: &VaultBackup,
+    spend_info: &TaprootSpendInfo,
+    recovery_scripts: &[ScriptBuf],
+) -> Result<(), HeirError> {
+    add_taproot_signing_info(psbt, backup, spend_info, recovery_scripts)?;
+    add_global_xpubs(psbt, backup)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::api::decode_psbt;
+    use crate::api::vectors::generate_test_vectors;
+
+    #[test]
+    fn test_vector_psbt_has_heir_origin() {
+        let v = generate_test_vectors(8).unwrap();
+        let psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        let input = &psbt.inputs[0];
+        assert!(input.tap_internal_key.is_some());
+        assert_eq!(input.tap_key_origins.len(), 1);
+
+        let (leaves, (fingerprint, path)) = input.tap_key_origins.values().next().unwrap();
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(*fingerprint, Fingerprint::from_str("00000000").unwrap());
+        assert_eq!(*path, DerivationPath::from_str("m/86'/1'/0'").unwrap());
+
+        assert_eq!(input.tap_scripts.len(), 1);
+        assert!(input.tap_merkle_root.is_some());
+    }
+
+    #[test]
+    fn test_vector_psbt_has_global_xpubs() {
+        let v = generate_test_vectors(8).unwrap();
+        let psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        let backup: VaultBackup = serde_json::from_str(&v.backup_json).unwrap();
+        assert_eq!(psbt.xpub.len(), backup.heirs.len());
+        let xpub = Xpub::from_str(&backup.heirs[0].xpub).unwrap();
+        let (fingerprint, _) = &psbt.xpub[&xpub];
+        assert_eq!(fingerprint.to_string(), backup.heirs[0].fingerprint);
+    }
+
+    #[test]
+    fn test_set_input_sighash() {
+        let v = generate_test_vectors(8).unwrap();
+        let updated = set_input_sighash(v.unsigned_psbt_base64.clone(), 0, ClaimSighash::All).unwrap();
+        let psbt = decode_psbt(&updated).unwrap();
+        assert_eq!(psbt.inputs[0].sighash_type, Some(TapSighashType::All.into()));
+        assert!(check_sighash_types(&psbt).is_ok());
+
+        let err = set_input_sighash(v.unsigned_psbt_base64, 5, ClaimSighash::All).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_rejects_other_sighash_types() {
+        let v = generate_test_vectors(8).unwrap();
+        let mut psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        psbt.inputs[0].sighash_type = Some(TapSighashType::SinglePlusAnyoneCanPay.into());
+        let err = check_sighash_types(&psbt).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnsupportedSighash { input_index: 0 });
+
+        let mut psbt = decode_psbt(&v.signed_psbt_base64).unwrap();
+        let mut witness = psbt.inputs[0].final_script_witness.clone().unwrap().to_vec();
+        witness[0].push(TapSighashType::None as u8);
+        psbt.inputs[0].final_script_witness = Some(bitcoin::Witness::from_slice(&witness));
+        assert!(check_sighash_types(&psbt).is_err());
+    }
+
+    /// Sign `index` the way an external signer would: from PSBT fields alone.
+    pub(crate) fn external_sign(psbt: &mut Psbt, index: usize, heir_secret_key_hex: &str) {
+        use bitcoin::hashes::Hash;
+        use bitcoin::secp256k1::{Keypair, Message, Secp256k1, SecretKey};
+        use bitcoin::sighash::{Prevouts, SighashCache};
+
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&hex::decode(heir_secret_key_hex).unwrap()).unwrap();
+        let keypair = Keypair::from_secret_key(&secp, &sk);
+
+        let (_, (script, version)) = psbt.inputs[index].tap_scripts.iter().next().unwrap();
+        let leaf_hash = TapLeafHash::from_script(script, *version);
+        let prevouts: Vec<_> = psbt
+            .inputs
+            .iter()
+            .map(|i| i.witness_utxo.clone().unwrap())
+            .collect();
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .taproot_script_spend_signature_hash(
+                index,
+                &Prevouts::All(&prevouts),
+                leaf_hash,
+                TapSighashType::Default,
+            )
+            .unwrap();
+        let sig = secp.sign_schnorr_no_aux_rand(
+            &Message::from_digest(sighash.to_byte_array()),
+            &keypair,
+        );
+        psbt.inputs[index].tap_script_sigs.insert(
+            (keypair.x_only_public_key().0, leaf_hash),
+            bitcoin::taproot::Signature {
+                signature: sig,
+                sighash_type: TapSighashType::Default,
+            },
+        );
+    }
+
+    #[test]
+    fn test_generic_finalizer_uses_psbt_fields_only() {
+        use miniscript::psbt::PsbtExt;
+
+        let v = generate_test_vectors(8).unwrap();
+        let mut psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        external_sign(&mut psbt, 0, &v.heir_secret_key_hex);
+
+        psbt.finalize_mut(&bitcoin::secp256k1::Secp256k1::new()).unwrap();
+        let tx = psbt.extract_tx_unchecked_fee_rate();
+        assert_eq!(tx.compute_txid().to_string(), v.txid);
+    }
+
+    #[test]
+    fn test_psbt_fingerprint_ignores_signatures() {
+        use base64::Engine;
+
+        let v = generate_test_vectors(9).unwrap();
+        let unsigned = psbt_fingerprint(v.unsigned_psbt_base64.clone()).unwrap();
+        assert_eq!(unsigned.len(), 19);
+        assert_eq!(psbt_fingerprint(v.signed_psbt_base64.clone()).unwrap(), unsigned);
+
+        let mut psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        psbt.unsigned_tx.output[0].value = bitcoin::Amount::from_sat(1);
+        let altered = base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+        assert_ne!(psbt_fingerprint(altered).unwrap(), unsigned);
+    }
+
+    #[test]
+    fn test_verify_partial_sig() {
+        use base64::Engine;
+
+        let v = generate_test_vectors(8).unwrap();
+        let mut psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        let encode = |psbt: &Psbt| base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+
+        let missing =
+            verify_partial_sig(encode(&psbt), 0, "00000000".into(), v.backup_json.clone()).unwrap();
+        assert!(!missing.signature_present);
+
+        external_sign(&mut psbt, 0, &v.heir_secret_key_hex);
+        let good =
+            verify_partial_sig(encode(&psbt), 0, "00000000".into(), v.backup_json.clone()).unwrap();
+        assert!(good.valid, "{}", good.detail);
+
+        // Corrupt the signature
+        let sig = psbt.inputs[0].tap_script_sigs.values_mut().next().unwrap();
+        let mut bytes = sig.signature.serialize();
+        bytes[0] ^= 1;
+        sig.signature = bitcoin::secp256k1::schnorr::Signature::from_slice(&bytes).unwrap();
+        let bad =
+            verify_partial_sig(encode(&psbt), 0, "00000000".into(), v.backup_json.clone()).unwrap();
+        assert!(bad.signature_present);
+        assert!(!bad.valid);
+
+        let err = verify_partial_sig(encode(&psbt), 0, "deadbeef".into(), v.backup_json).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_finalize_psbt_accepts_script_sigs() {
+        use base64::Engine;
+
+        let v = generate_test_vectors(8).unwrap();
+        let mut psbt = decode_psbt(&v.unsigned_psbt_base64).unwrap();
+        external_sign(&mut psbt, 0, &v.heir_secret_key_hex);
+        let encoded = base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+
+        let finalized = crate::api::finalize_psbt(encoded).unwrap();
+        assert_eq!(finalized.txid, v.txid);
+    }
+
+    #[test]
+    fn test_finalize_psbt_mixed_signers() {
+        use crate::api::simulated::SimulatedBackend;
+        use crate::api::{build_claim_psbt, finalize_psbt, Backend, Network};
+        use base64::Engine;
+        use miniscript::psbt::PsbtExt;
+
+        let v = generate_test_vectors(8).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(1_000);
+        for (byte, value) in [("51", 60_000), ("52", 70_000)] {
+            sim.add_utxo(v.vault_address.clone(), byte.repeat(32), 0, value, 10)
+                .unwrap();
+        }
+        let built = build_claim_psbt(
+            v.backup_json.clone(),
+            &Backend::simulated(&sim),
+            v.destination.clone(),
+            0,
+            2,
+        )
+        .unwrap();
+        let mut psbt = decode_psbt(&built.psbt_base64).unwrap();
+        assert_eq!(psbt.inputs.len(), 2);
+
+        // Device A already finalized its input; device B only added a signature
+        external_sign(&mut psbt, 0, &v.heir_secret_key_hex);
+        external_sign(&mut psbt, 1, &v.heir_secret_key_hex);
+        psbt.finalize_inp_mut(&bitcoin::secp256k1::Secp256k1::new(), 0).unwrap();
+        assert!(psbt.inputs[0].final_script_witness.is_some());
+        assert!(psbt.inputs[1].final_script_witness.is_none());
+
+        let encoded = base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+        let finalized = finalize_psbt(encoded).unwrap();
+        assert_eq!(finalized.num_inputs, 2);
+    }
+}
@@ -0,0 +1,341 @@
+//! Read-only copies of a vault for executors and estate lawyers.
+//!
+//! A read-only copy carries the vault address, timelock and policy, but no
+//! xpubs or chain code. Status, history and the policy description accept
+//! it in place of a full backup; anything that builds or signs a claim
+//! rejects it with [`ErrorKind::ReadOnlyVault`].
+//!
+//! A co-heir with nothing but the vault address or its descriptor can still
+//! watch it through [`fetch_address_status`], which says what it could not
+//! work out without the backup.
+
+use std::str::FromStr;
+
+use bitcoin::{Address, Network};
+use miniscript::{Descriptor, DescriptorPublicKey};
+use serde::{Deserialize, Serialize};
+
+use super::policy::{analyze_leaf_script, describe_policy, PolicyDescription};
+use super::{
+    history_unless_too_large, parse_backup, parse_network, recovery_tree_depth, status_from_chain,
+    verified_vault_address, Backend, EligibilityPhase, ErrorKind, HeirError, VaultStatus,
+};
+
+/// `format` tag of a read-only copy.
+pub(crate) const READ_ONLY_FORMAT: &str = "nostring-read-only-v1";
+
+/// Address and policy of a vault, without key material.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadOnlyVault {
+    pub format: String,
+    pub network: String,
+    pub vault_address: String,
+    pub timelock_blocks: u32,
+    /// Depth of the taproot script tree, for claim size estimates.
+    pub recovery_tree_depth: u32,
+    pub policy: PolicyDescription,
+}
+
+/// What status and history queries need to know about a vault.
+pub(crate) struct WatchedVault {
+    pub network: Network,
+    pub address: Address,
+    pub timelock_blocks: u32,
+    pub tree_depth: usize,
+}
+
+/// Whether `json` is a read-only copy rather than a full backup.
+pub(crate) fn is_read_only(json: &str) -> bool {
+    #[derive(Deserialize)]
+    struct Tagged {
+        format: Option<String>,
+    }
+    serde_json::from_str::<Tagged>(json)
+        .is_ok_and(|tagged| tagged.format.as_deref() == Some(READ_ONLY_FORMAT))
+}
+
+/// Error for operations that need the full backup.
+pub(crate) fn read_only_error() -> HeirError {
+    HeirError::new(
+        ErrorKind::ReadOnlyVault,
+        "This is a read-only copy of the vault. Building or signing a claim needs the full backup",
+    )
+}
+
+/// Strip the key material from a full backup, after checking that it
+/// reproduces its vault address.
+pub fn export_read_only_vault(vault_json: String) -> Result<String, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let address = verified_vault_address(&vault_json, &backup)?;
+    let copy = ReadOnlyVault {
+        format: READ_ONLY_FORMAT.to_string(),
+        network: backup.network.clone(),
+        vault_address: address.to_string(),
+        timelock_blocks: u32::from(backup.timelock_blocks),
+        recovery_tree_depth: recovery_tree_depth(&backup) as u32,
+        policy: describe_policy(vault_json)?,
+    };
+    serde_json::to_string_pretty(&copy)
+        .map_err(|e| HeirError::new(ErrorKind::Internal, format!("Serialization failed: {}", e)))
+}
+
+/// Parse and check a read-only copy.
+pub fn import_read_only_vault(json: String) -> Result<ReadOnlyVault, HeirError> {
+    let copy: ReadOnlyVault = serde_json::from_str(&json).map_err(|e| {
+        HeirError::new(
+            ErrorKind::InvalidBackup,
+            format!("Invalid read-only vault: {}", e),
+        )
+    })?;
+    if copy.format != READ_ONLY_FORMAT {
+        return Err(HeirError::new(
+            ErrorKind::UnrecognizedFormat,
+            format!("Unknown read-only vault format '{}'", copy.format),
+        ));
+    }
+    parse_address(&copy)?;
+    Ok(copy)
+}
+
+fn parse_address(copy: &ReadOnlyVault) -> Result<(Network, Address), HeirError> {
+    let network = parse_network(&copy.network)?;
+    let address = Address::from_str(&copy.vault_address)
+        .map_err(|e| {
+            HeirError::new(
+                ErrorKind::InvalidAddress,
+                format!("Invalid vault address: {}", e),
+            )
+        })?
+        .require_network(network)
+        .map_err(|e| {
+            HeirError::new(
+                ErrorKind::NetworkMismatch,
+                format!("Vault address network mismatch: {}", e),
+            )
+        })?;
+    Ok((network, address))
+}
+
+/// Load either a full backup or a read-only copy for watching.
+pub(crate) fn load_watched(json: &str) -> Result<WatchedVault, HeirError> {
+    if is_read_only(json) {
+        let copy = import_read_only_vault(json.to_string())?;
+        let (network, address) = parse_address(&copy)?;
+        return Ok(WatchedVault {
+            network,
+            address,
+            timelock_blocks: copy.timelock_blocks,
+            tree_depth: copy.recovery_tree_depth as usize,
+        });
+    }
+    let backup = parse_backup(json)?;
+    Ok(WatchedVault {
+        network: parse_network(&backup.network)?,
+        address: verified_vault_address(json, &backup)?,
+        timelock_blocks: u32::from(backup.timelock_blocks),
+        tree_depth: recovery_tree_depth(&backup),
+    })
+}
+
+/// Status of a vault watched from its address or descriptor alone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressStatus {
+    pub vault_address: String,
+    /// Shortest recovery timelock, if a `tr()` descriptor revealed one.
+    pub timelock_blocks: Option<u32>,
+    /// Balance and UTXOs. Without a timelock, nothing is reported as
+    /// eligible or claimable.
+    pub status: VaultStatus,
+    /// What this view cannot do that the full backup could, in plain
+    /// language.
+    pub limitations: Vec<String>,
+}
+
+fn input_error(e: impl std::fmt::Display) -> HeirError {
+    HeirError::new(
+        ErrorKind::InvalidInput,
+        format!("Invalid descriptor: {}", e),
+    )
+}
+
+/// Address and shortest recovery timelock of a descriptor.
+fn parse_descriptor(
+    descriptor: &str,
+    network: Network,
+) -> Result<(Address, Option<u32>), HeirError> {
+    let parsed = Descriptor::<DescriptorPublicKey>::from_str(descriptor)
+        .map_err(input_error)?
+        .at_derivation_index(0)
+        .map_err(input_error)?;
+    let address = parsed.address(network).map_err(input_error)?;
+    let timelock_blocks = match &parsed {
+        Descriptor::Tr(tr) => tr
+            .iter_scripts()
+            .filter_map(|(_, ms)| analyze_leaf_script(&ms.encode()).csv_blocks)
+            .min(),
+        _ => None,
+    };
+    Ok((address, timelock_blocks))
+}
+
+/// Without a timelock, eligibility cannot be estimated; say nothing rather
+/// than "claimable now".
+fn clear_eligibility(status: &mut VaultStatus) {
+    status.eligible = false;
+    status.blocks_remaining = 0;
+    status.days_remaining = 0.0;
+    status.phase = EligibilityPhase::Waiting {
+        blocks_remaining: 0,
+        days_remaining: 0.0,
+    };
+    status.immature_for_claim_sat = 0;
+    for utxo in &mut status.utxos {
+        utxo.claimable = false;
+        utxo.claimable_at_height = None;
+    }
+}
+
+/// Balance and, where possible, eligibility for a vault known only by its
+/// address, an `addr()` descriptor or a `tr()` descriptor.
+///
+/// Nothing here can be checked against the heirs' keys, and no claim can be
+/// built from it; `limitations` lists what is missing.
+pub fn fetch_address_status(
+    address_or_descriptor: String,
+    backend: &Backend,
+) -> Result<AddressStatus, HeirError> {
+    let chain = backend.chain();
+    let network = chain.network();
+    let input = address_or_descriptor.trim();
+    let (address, timelock_blocks) = if input.contains('(') {
+        parse_descriptor(input, network)?
+    } else {
+        let address = Address::from_str(input)
+            .map_err(|e| {
+                HeirError::new(ErrorKind::InvalidAddress, format!("Invalid address: {}", e))
+            })?
+            .require_network(network)
+            .map_err(|e| {
+                HeirError::new(
+                    ErrorKind::NetworkMismatch,
+                    format!("Address is not for the backend's network: {}", e),
+                )
+            })?;
+        (address, None)
+    };
+
+    let current_height = chain.tip_height()?;
+    let utxos = chain.list_unspent(&address)?;
+    let history = history_unless_too_large(chain.history(&address))?;
+    let mut status = status_from_chain(
+        timelock_blocks.unwrap_or(0),
+        &address,
+        chain,
+        current_height,
+        utxos,
+        history.as_deref(),
+    )?;
+
+    let mut limitations = vec![
+        "The address cannot be checked against the heirs' keys".to_string(),
+        "Building or signing a claim needs the full backup".to_string(),
+    ];
+    if timelock_blocks.is_none() {
+        clear_eligibility(&mut status);
+        limitations.push(
+            "The timelock is unknown, so eligibility and claimable amounts are not estimated"
+                .to_string(),
+        );
+    }
+
+    Ok(AddressStatus {
+        vault_address: address.to_string(),
+        timelock_blocks,
+        status,
+        limitations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::descriptor::export_descriptor;
+    use crate::api::history::fetch_vault_history;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::{build_claim_psbt, fetch_vault_status, Network};
+
+    #[test]
+    fn test_read_only_copy_has_no_key_material() {
+        let v = generate_test_vectors(12).unwrap();
+        let json = export_read_only_vault(v.backup_json.clone()).unwrap();
+        assert!(is_read_only(&json));
+        assert!(!json.contains("xpub") && !json.contains("chain_code"));
+
+        let copy = import_read_only_vault(json).unwrap();
+        assert_eq!(copy.vault_address, v.vault_address);
+        assert_eq!(copy.timelock_blocks, 144);
+        assert!(!is_read_only(&v.backup_json));
+    }
+
+    #[test]
+    fn test_read_only_copy_watches_but_cannot_claim() {
+        let v = generate_test_vectors(12).unwrap();
+        let copy = export_read_only_vault(v.backup_json.clone()).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(10_000);
+        sim.add_utxo(v.vault_address.clone(), "c1".repeat(32), 0, 50_000, 1_000)
+            .unwrap();
+        let backend = Backend::simulated(&sim);
+
+        let full = fetch_vault_status(v.backup_json, &backend).unwrap();
+        let watched = fetch_vault_status(copy.clone(), &backend).unwrap();
+        assert_eq!(watched.balance_sat, full.balance_sat);
+        assert_eq!(watched.eligible, full.eligible);
+        assert_eq!(
+            fetch_vault_history(copy.clone(), &backend).unwrap().len(),
+            1
+        );
+
+        let err = build_claim_psbt(copy, &backend, v.destination, 0, 2).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ReadOnlyVault);
+    }
+
+    #[test]
+    fn test_address_status_reports_reduced_view() {
+        let v = generate_test_vectors(13).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(10_000);
+        sim.add_utxo(v.vault_address.clone(), "c2".repeat(32), 0, 50_000, 1_000)
+            .unwrap();
+        let backend = Backend::simulated(&sim);
+        let full = fetch_vault_status(v.backup_json.clone(), &backend).unwrap();
+
+        let bare = fetch_address_status(v.vault_address.clone(), &backend).unwrap();
+        assert_eq!(bare.status.balance_sat, full.balance_sat);
+        assert_eq!(bare.timelock_blocks, None);
+        assert!(!bare.status.eligible);
+        assert!(!bare.status.utxos[0].claimable);
+        assert_eq!(bare.limitations.len(), 3);
+
+        let descriptor = export_descriptor(v.backup_json).unwrap();
+        let described = fetch_address_status(descriptor, &backend).unwrap();
+        assert_eq!(described.vault_address, v.vault_address);
+        assert_eq!(described.timelock_blocks, Some(144));
+        assert_eq!(described.status.eligible, full.eligible);
+        assert_eq!(described.status.blocks_remaining, full.blocks_remaining);
+        assert_eq!(described.limitations.len(), 2);
+    }
+
+    #[test]
+    fn test_address_status_rejects_other_network() {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        let backend = Backend::simulated(&sim);
+        let err = fetch_address_status(
+            "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".into(),
+            &backend,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::NetworkMismatch);
+    }
+}
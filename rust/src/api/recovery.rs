@@ -0,0 +1,198 @@
+//! Broadcasting with automatic handling of recoverable failures.
+//!
+//! Transient connection failures are retried with exponential backoff. A
+//! fee-too-low rejection cannot be fixed without a new signature, so the
+//! result tells the app which fee rate to rebuild at before asking the
+//! heir's signer again.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::limits::SafetyLimits;
+use super::mempool::tx_fee;
+use super::{broadcast_transaction, decode_tx, Backend, BroadcastFailure, ErrorKind, HeirError};
+
+/// Confirmation target used to pick the rebuild fee rate.
+const REBUILD_TARGET_BLOCKS: u16 = 2;
+
+/// Retry knobs for [`broadcast_with_recovery`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryPolicy {
+    /// Total broadcast attempts on connection failures, including the first.
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled after each further failure.
+    pub initial_backoff_ms: u64,
+    /// Ceiling for the suggested rebuild fee rate. `None` uses the
+    /// network's default.
+    pub safety_limits: Option<SafetyLimits>,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 1_000,
+            safety_limits: None,
+        }
+    }
+}
+
+/// What [`broadcast_with_recovery`] ended up doing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecoveryOutcome {
+    /// The network accepted the transaction.
+    Broadcast { txid: String, attempts: u32 },
+    /// The fee was too low. Rebuild the claim at `suggested_fee_rate_sat_vb`
+    /// and have it signed again.
+    RebuildAtHigherFee {
+        current_fee_rate_sat_vb: f64,
+        suggested_fee_rate_sat_vb: u64,
+    },
+}
+
+/// Next fee rate to try after a fee-too-low rejection: the market rate or a
+/// 50% bump, whichever is higher, within the safety cap.
+fn next_fee_rate(current: f64, market: Option<f64>, ceiling: u64) -> Option<u64> {
+    let bumped = (current * 1.5).ceil().max(current.floor() + 1.0);
+    let target = market.map_or(bumped, |m| m.ceil().max(bumped));
+    let capped = target.min(ceiling as f64) as u64;
+    (capped as f64 > current).then_some(capped)
+}
+
+/// [`broadcast_transaction`], retrying connection failures and turning a
+/// fee-too-low rejection into a rebuild suggestion. Other errors are
+/// returned unchanged.
+pub fn broadcast_with_recovery(
+    tx_hex: String,
+    backend: &Backend,
+    policy: RecoveryPolicy,
+) -> Result<RecoveryOutcome, HeirError> {
+    let tx = decode_tx(&tx_hex)?;
+    let limits = SafetyLimits::resolve(policy.safety_limits.as_ref(), backend.chain().network())?;
+    let mut backoff = Duration::from_millis(policy.initial_backoff_ms);
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        let err = match broadcast_transaction(tx_hex.clone(), backend) {
+            Ok(result) => {
+                return Ok(RecoveryOutcome::Broadcast {
+                    txid: result.txid,
+                    attempts,
+                })
+            }
+            Err(err) => err,
+        };
+
+        match err.kind {
+            ErrorKind::Connection if attempts < policy.max_attempts.max(1) => {
+                std::thread::sleep(backoff);
+                backoff = backoff.saturating_mul(2);
+            }
+            ErrorKind::Broadcast {
+                reason: BroadcastFailure::FeeTooLow,
+            } => {
+                let chain = backend.chain();
+                let current = tx_fee(chain, &tx)? as f64 / tx.vsize() as f64;
+                let market = chain.estimate_fee_rate(REBUILD_TARGET_BLOCKS).ok();
+                // Already at the cap: nothing to rebuild with
+                let Some(suggested) = next_fee_rate(current, market, limits.max_fee_rate_sat_vb)
+                else {
+                    return Err(err);
+                };
+                return Ok(RecoveryOutcome::RebuildAtHigherFee {
+                    current_fee_rate_sat_vb: current,
+                    suggested_fee_rate_sat_vb: suggested,
+                });
+            }
+            _ => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::{SimulatedBackend, SimulatedOutcome};
+    use crate::api::vectors::{generate_test_vectors, TestVectors};
+    use crate::api::Network;
+
+    fn funded(v: &TestVectors) -> SimulatedBackend {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+        sim
+    }
+
+    fn no_wait() -> RecoveryPolicy {
+        RecoveryPolicy {
+            max_attempts: 3,
+            initial_backoff_ms: 0,
+            safety_limits: None,
+        }
+    }
+
+    #[test]
+    fn test_next_fee_rate() {
+        assert_eq!(next_fee_rate(2.0, None, 500), Some(3));
+        assert_eq!(next_fee_rate(2.0, Some(10.2), 500), Some(11));
+        assert_eq!(next_fee_rate(500.0, Some(900.0), 500), None);
+        assert_eq!(next_fee_rate(500.0, Some(900.0), 1_000), Some(900));
+    }
+
+    #[test]
+    fn test_retries_connection_failures() {
+        let v = generate_test_vectors(3).unwrap();
+        let sim = funded(&v);
+        sim.push_broadcast_outcome(SimulatedOutcome::ConnectionFailure);
+        sim.push_broadcast_outcome(SimulatedOutcome::Accept);
+        let outcome = broadcast_with_recovery(v.tx_hex, &Backend::simulated(&sim), no_wait()).unwrap();
+        assert_eq!(
+            outcome,
+            RecoveryOutcome::Broadcast {
+                txid: v.txid,
+                attempts: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_gives_up_after_max_attempts() {
+        let v = generate_test_vectors(3).unwrap();
+        let sim = funded(&v);
+        for _ in 0..3 {
+            sim.push_broadcast_outcome(SimulatedOutcome::ConnectionFailure);
+        }
+        let err = broadcast_with_recovery(v.tx_hex, &Backend::simulated(&sim), no_wait()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Connection);
+        assert_eq!(sim.broadcast_count(), 0);
+    }
+
+    #[test]
+    fn test_fee_too_low_suggests_rebuild() {
+        let v = generate_test_vectors(3).unwrap();
+        let sim = funded(&v);
+        sim.set_fee_rate(40.0);
+        sim.push_broadcast_outcome(SimulatedOutcome::Reject {
+            message: "min relay fee not met".into(),
+        });
+        match broadcast_with_recovery(v.tx_hex, &Backend::simulated(&sim), no_wait()).unwrap() {
+            RecoveryOutcome::RebuildAtHigherFee {
+                current_fee_rate_sat_vb,
+                suggested_fee_rate_sat_vb,
+            } => {
+                assert!(current_fee_rate_sat_vb > 0.0);
+                assert_eq!(suggested_fee_rate_sat_vb, 40);
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+    }
+}
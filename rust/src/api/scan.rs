@@ -0,0 +1,117 @@
+//! Activity across several vault addresses.
+//!
+//! An owner who rotates to a fresh address index gets one backup per index.
+//! Scanning them queries every address in batched round trips instead of
+//! one request per address, which matters over Tor.
+
+use serde::{Deserialize, Serialize};
+
+use super::{parse_backup, parse_network, verified_vault_address, Backend, ErrorKind, HeirError};
+
+/// What the chain shows for one vault address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressActivity {
+    pub address_index: u32,
+    pub vault_address: String,
+    pub tx_count: usize,
+    pub balance_sat: u64,
+    /// Highest confirmed height touching the address.
+    pub last_height: Option<u64>,
+    /// A transaction touching the address is waiting in the mempool.
+    pub pending: bool,
+}
+
+/// Activity for every backup in `vault_jsons`, in the same order.
+///
+/// All backups must be for the backend's network. UTXOs are only fetched for
+/// addresses that have any history.
+pub fn scan_vault_addresses(
+    vault_jsons: Vec<String>,
+    backend: &Backend,
+) -> Result<Vec<AddressActivity>, HeirError> {
+    let mut vaults = Vec::with_capacity(vault_jsons.len());
+    for json in &vault_jsons {
+        let backup = parse_backup(json)?;
+        backend.require_network(parse_network(&backup.network)?)?;
+        let address = verified_vault_address(json, &backup)?;
+        vaults.push((backup.address_index, address));
+    }
+    if vaults.is_empty() {
+        return Err(HeirError::new(ErrorKind::InvalidInput, "No vault backups to scan"));
+    }
+
+    let chain = backend.chain();
+    let addresses: Vec<_> = vaults.iter().map(|(_, address)| address.clone()).collect();
+    let histories = chain.histories(&addresses)?;
+
+    let used: Vec<_> = addresses
+        .iter()
+        .zip(&histories)
+        .filter(|(_, history)| !history.is_empty())
+        .map(|(address, _)| address.clone())
+        .collect();
+    let mut unspent = chain.list_unspent_many(&used)?.into_iter();
+
+    Ok(vaults
+        .into_iter()
+        .zip(histories)
+        .map(|((address_index, address), history)| {
+            let balance_sat = if history.is_empty() {
+                0
+            } else {
+                unspent
+                    .next()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|u| u.value.to_sat())
+                    .sum()
+            };
+            AddressActivity {
+                address_index,
+                vault_address: address.to_string(),
+                tx_count: history.len(),
+                balance_sat,
+                last_height: history.iter().map(|h| u64::from(h.height)).filter(|&h| h > 0).max(),
+                pending: history.iter().any(|h| h.height == 0),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::Network;
+
+    #[test]
+    fn test_scan_vault_addresses() {
+        let used = generate_test_vectors(1).unwrap();
+        let fresh = generate_test_vectors(2).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(500);
+        sim.add_utxo(used.vault_address.clone(), "61".repeat(32), 0, 25_000, 400)
+            .unwrap();
+
+        let activity = scan_vault_addresses(
+            vec![fresh.backup_json, used.backup_json],
+            &Backend::simulated(&sim),
+        )
+        .unwrap();
+        assert_eq!(activity.len(), 2);
+        assert_eq!(activity[0].tx_count, 0);
+        assert_eq!(activity[0].balance_sat, 0);
+        assert_eq!(activity[1].vault_address, used.vault_address);
+        assert_eq!(activity[1].balance_sat, 25_000);
+        assert_eq!(activity[1].last_height, Some(400));
+        assert!(!activity[1].pending);
+    }
+
+    #[test]
+    fn test_scan_rejects_empty_list() {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        let err = scan_vault_addresses(vec![], &Backend::simulated(&sim)).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+}
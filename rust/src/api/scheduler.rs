@@ -0,0 +1,182 @@
+//! Broadcasting a signed claim as soon as its timelock allows.
+//!
+//! An heir who collected every signature a little early hands the finished
+//! transaction to [`schedule_broadcast`], which watches the tip and
+//! broadcasts at `not_before_height`. A node that still reports the claim
+//! as non-final (the height was a block early) is asked again on the next
+//! poll rather than treated as a failure.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    already_spent_inputs, broadcast_transaction, decode_tx, Backend, BroadcastFailure, ErrorKind,
+    HeirError,
+};
+
+/// Polling knobs for [`schedule_broadcast`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleOptions {
+    /// Wait between tip checks.
+    pub poll_interval_ms: u64,
+    /// Give up waiting after this long and return
+    /// [`ScheduleOutcome::Waiting`]. `None` waits until broadcast.
+    pub max_wait_ms: Option<u64>,
+}
+
+impl Default for ScheduleOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: 60_000,
+            max_wait_ms: None,
+        }
+    }
+}
+
+/// Where a scheduled broadcast stands when [`schedule_broadcast`] returns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ScheduleOutcome {
+    /// The wait ran out first; call again to keep watching.
+    Waiting {
+        current_height: u64,
+        blocks_remaining: u64,
+    },
+    /// The network accepted the claim at `height`.
+    Broadcast { txid: String, height: u64 },
+}
+
+/// Watch the tip and broadcast the signed `tx_hex` once the chain reaches
+/// `not_before_height`.
+///
+/// Inputs already spent (a co-heir claimed first) end the wait with
+/// [`ErrorKind::InputsAlreadySpent`]; other broadcast errors are returned
+/// unchanged.
+pub fn schedule_broadcast(
+    tx_hex: String,
+    not_before_height: u64,
+    backend: &Backend,
+    options: ScheduleOptions,
+) -> Result<ScheduleOutcome, HeirError> {
+    let tx = decode_tx(&tx_hex)?;
+    let chain = backend.chain();
+    let started = Instant::now();
+    let deadline = options.max_wait_ms.map(Duration::from_millis);
+
+    loop {
+        let current_height = chain.tip_height()?;
+        if current_height >= not_before_height {
+            match broadcast_transaction(tx_hex.clone(), backend) {
+                Ok(result) => {
+                    return Ok(ScheduleOutcome::Broadcast {
+                        txid: result.txid,
+                        height: current_height,
+                    })
+                }
+                Err(HeirError {
+                    kind:
+                        ErrorKind::Broadcast {
+                            reason: BroadcastFailure::NonFinal,
+                        },
+                    ..
+                }) => {}
+                Err(e) => return Err(e),
+            }
+        } else {
+            // Nothing to wait for if a co-heir's claim already took the funds
+            let spent = already_spent_inputs(chain, &tx)?;
+            if !spent.is_empty() {
+                return Err(HeirError::new(
+                    ErrorKind::InputsAlreadySpent {
+                        outpoints: spent.clone(),
+                    },
+                    format!("Inputs already spent: {}", spent.join(", ")),
+                ));
+            }
+        }
+
+        if deadline.is_some_and(|limit| started.elapsed() >= limit) {
+            return Ok(ScheduleOutcome::Waiting {
+                current_height,
+                // A non-final rejection at the height means one more block
+                blocks_remaining: not_before_height.saturating_sub(current_height).max(1),
+            });
+        }
+        std::thread::sleep(Duration::from_millis(options.poll_interval_ms));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::{SimulatedBackend, SimulatedOutcome};
+    use crate::api::vectors::{generate_test_vectors, TestVectors};
+    use crate::api::Network;
+
+    fn funded(v: &TestVectors, height: u64) -> SimulatedBackend {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(height);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+        sim
+    }
+
+    fn once() -> ScheduleOptions {
+        ScheduleOptions {
+            poll_interval_ms: 0,
+            max_wait_ms: Some(0),
+        }
+    }
+
+    #[test]
+    fn test_waits_until_height() {
+        let v = generate_test_vectors(35).unwrap();
+        let sim = funded(&v, 100);
+        let backend = Backend::simulated(&sim);
+
+        let outcome = schedule_broadcast(v.tx_hex.clone(), 145, &backend, once()).unwrap();
+        assert_eq!(
+            outcome,
+            ScheduleOutcome::Waiting {
+                current_height: 100,
+                blocks_remaining: 45
+            }
+        );
+        assert_eq!(sim.broadcast_count(), 0);
+
+        sim.mine_blocks(45);
+        let outcome = schedule_broadcast(v.tx_hex, 145, &backend, once()).unwrap();
+        assert_eq!(
+            outcome,
+            ScheduleOutcome::Broadcast {
+                txid: v.txid,
+                height: 145
+            }
+        );
+        assert_eq!(sim.broadcast_count(), 1);
+    }
+
+    #[test]
+    fn test_non_final_keeps_waiting() {
+        let v = generate_test_vectors(36).unwrap();
+        let sim = funded(&v, 200);
+        sim.push_broadcast_outcome(SimulatedOutcome::Reject {
+            message: "non-BIP68-final".into(),
+        });
+        let outcome = schedule_broadcast(v.tx_hex, 200, &Backend::simulated(&sim), once()).unwrap();
+        assert_eq!(
+            outcome,
+            ScheduleOutcome::Waiting {
+                current_height: 200,
+                blocks_remaining: 1
+            }
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Backup schema versions and the migrations between them.
+//!
+//! The upstream backup model only reads version 1. Newer backups are
+//! migrated step by step to the current schema, then lowered to the layout
+//! upstream parses; fields upstream does not know are read from the raw
+//! JSON by their own modules, as before.
+//!
+//! - Version 1: the upstream layout. Releases before versioning also wrote
+//!   the version 2 extensions under it.
+//! - Version 2: declares the nostring-heir extensions: `allocation_percent`,
+//!   heir weights, the executor and tranche allowances.
+//!
+//! Importing a backup keeps the version it was written in, so its content
+//! hash does not change under anyone holding it. [`upgrade_backup`]
+//! rewrites it at the current version when the app asks the owner to.
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::canonical::to_canonical_string;
+use super::{import_vault_backup, readonly, ErrorKind, HeirError};
+
+/// Version new backups are written in.
+pub const CURRENT_BACKUP_VERSION: u32 = 2;
+/// Version the upstream backup model parses.
+const UPSTREAM_VERSION: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), HeirError>;
+
+/// `MIGRATIONS[i]` takes a backup from version `i + 1` to `i + 2`.
+const MIGRATIONS: [Migration; (CURRENT_BACKUP_VERSION - 1) as usize] = [v1_to_v2];
+
+/// Schema version of a backup, as reported by [`backup_schema_version`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupSchemaVersion {
+    pub version: u32,
+    pub current_version: u32,
+    /// Older than the current version; [`upgrade_backup`] rewrites it.
+    pub needs_upgrade: bool,
+}
+
+fn invalid(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidBackup, message)
+}
+
+/// The extensions were already optional in version 1, so only the version
+/// changes.
+fn v1_to_v2(_backup: &mut Map<String, Value>) -> Result<(), HeirError> {
+    Ok(())
+}
+
+/// Declared `version` of `backup`, checked to be one this build reads.
+fn supported_version(backup: &Map<String, Value>) -> Result<u32, HeirError> {
+    let version = backup
+        .get("version")
+        .ok_or_else(|| invalid("Backup has no version"))?;
+    match version.as_u64().and_then(|v| u32::try_from(v).ok()) {
+        Some(v @ 1..=CURRENT_BACKUP_VERSION) => Ok(v),
+        Some(v) if v > CURRENT_BACKUP_VERSION => Err(invalid(format!(
+            "Backup version {} is newer than this app reads; update the app",
+            v
+        ))),
+        _ => Err(invalid(format!("Invalid backup version: {}", version))),
+    }
+}
+
+/// Migrate `backup` in place from its declared version to the current one.
+fn migrate(backup: &mut Map<String, Value>) -> Result<(), HeirError> {
+    let from = supported_version(backup)?;
+    for (step, migration) in MIGRATIONS.iter().enumerate().skip(from as usize - 1) {
+        migration(backup)?;
+        backup.insert("version".into(), Value::from(step as u32 + 2));
+    }
+    Ok(())
+}
+
+/// Declared version of a backup, or an error if this build cannot read it.
+pub(crate) fn declared_version(vault_json: &str) -> Result<u32, HeirError> {
+    let value: Value =
+        serde_json::from_str(vault_json).map_err(|_| invalid("Backup is not valid JSON"))?;
+    let backup = value
+        .as_object()
+        .ok_or_else(|| invalid("Backup is not a JSON object"))?;
+    supported_version(backup)
+}
+
+/// `vault_json` in the layout the upstream model parses.
+///
+/// Input that is not a versioned backup object is returned as is, so the
+/// upstream parser reports what is wrong with it.
+pub(crate) fn upstream_json(vault_json: &str) -> Result<Cow<'_, str>, HeirError> {
+    let Ok(Value::Object(mut backup)) = serde_json::from_str::<Value>(vault_json) else {
+        return Ok(Cow::Borrowed(vault_json));
+    };
+    if readonly::is_read_only(vault_json)
+        || !backup.contains_key("version")
+        || supported_version(&backup)? == UPSTREAM_VERSION
+    {
+        return Ok(Cow::Borrowed(vault_json));
+    }
+    migrate(&mut backup)?;
+    backup.insert("version".into(), Value::from(UPSTREAM_VERSION));
+    Ok(Cow::Owned(Value::Object(backup).to_string()))
+}
+
+/// Which schema version `vault_json` was written in, and whether it should
+/// be upgraded.
+pub fn backup_schema_version(vault_json: String) -> Result<BackupSchemaVersion, HeirError> {
+    let version = declared_version(&vault_json)?;
+    Ok(BackupSchemaVersion {
+        version,
+        current_version: CURRENT_BACKUP_VERSION,
+        needs_upgrade: version < CURRENT_BACKUP_VERSION,
+    })
+}
+
+/// Verify `vault_json` and rewrite it, canonically, at the current schema
+/// version. The upgraded backup has a new content hash.
+pub fn upgrade_backup(vault_json: String) -> Result<String, HeirError> {
+    let info = import_vault_backup(vault_json)?;
+    let Ok(Value::Object(mut backup)) = serde_json::from_str(&info.canonical_json) else {
+        return Err(HeirError::new(
+            ErrorKind::Internal,
+            "Canonical backup is not an object",
+        ));
+    };
+    migrate(&mut backup)?;
+    Ok(to_canonical_string(&Value::Object(backup)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::vectors::generate_test_vectors;
+
+    #[test]
+    fn test_upgrade_v1_backup() {
+        let v = generate_test_vectors(56).unwrap();
+        let before = backup_schema_version(v.backup_json.clone()).unwrap();
+        assert_eq!(before.version, 1);
+        assert!(before.needs_upgrade);
+
+        let original = import_vault_backup(v.backup_json.clone()).unwrap();
+        assert_eq!(declared_version(&original.canonical_json).unwrap(), 1);
+
+        let upgraded = upgrade_backup(v.backup_json).unwrap();
+        let after = backup_schema_version(upgraded.clone()).unwrap();
+        assert_eq!(after.version, CURRENT_BACKUP_VERSION);
+        assert!(!after.needs_upgrade);
+
+        let info = import_vault_backup(upgraded.clone()).unwrap();
+        assert_eq!(info.vault_address, original.vault_address);
+        assert_eq!(info.canonical_json, upgraded);
+        assert_ne!(info.content_hash, original.content_hash);
+    }
+
+    #[test]
+    fn test_unreadable_versions() {
+        let json = generate_test_vectors(57).unwrap().backup_json;
+        let mut value: Value = serde_json::from_str(&json).unwrap();
+
+        value["version"] = serde_json::json!(CURRENT_BACKUP_VERSION + 1);
+        let err = import_vault_backup(value.to_string()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidBackup);
+        assert!(err.message.contains("update the app"));
+
+        value["version"] = serde_json::json!(0);
+        assert!(backup_schema_version(value.to_string()).is_err());
+        value.as_object_mut().unwrap().remove("version");
+        assert!(backup_schema_version(value.to_string()).is_err());
+    }
+}
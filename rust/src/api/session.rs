@@ -0,0 +1,406 @@
+//! A claim as one object that knows where it is.
+//!
+//! The free functions leave the app to carry the vault, the claim and the
+//! signed PSBT from screen to screen. [`ClaimSession`] holds them and only
+//! allows the next step of the claim:
+//!
+//! import → check status → build → sign or import signatures → finalize →
+//! broadcast.
+//!
+//! [`ClaimSession::export_session`] serializes everything it holds, so the
+//! app can save it and [`ClaimSession::resume`] where the heir left off
+//! after a restart. No secrets are kept: the seed phrase passed to
+//! [`ClaimSession::sign_with_mnemonic`] is used and dropped.
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use super::combine::merge_signed_psbts;
+use super::signer::{sign_claim_psbt, SignedClaim};
+use super::store::{stage_of, ClaimStage};
+use super::{
+    broadcast_transaction, build_claim_psbt_with_options, decode_psbt, fetch_vault_status,
+    finalize_psbt, import_vault_backup, Backend, BroadcastResult, ClaimOptions, ClaimPsbt,
+    ErrorKind, FinalizedTx, HeirError, VaultInfo, VaultStatus,
+};
+
+/// Version of the layout [`ClaimSession::export_session`] writes.
+const SESSION_VERSION: u32 = 1;
+
+/// The step a claim session is waiting for, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionStep {
+    ImportVault,
+    CheckStatus,
+    BuildClaim,
+    /// The claim needs heir signatures, from this device or imported.
+    Sign,
+    Finalize,
+    Broadcast,
+    /// The claim was broadcast.
+    Done,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SessionState {
+    version: u32,
+    step: SessionStep,
+    /// Canonical backup of the imported vault.
+    vault_json: Option<String>,
+    status: Option<VaultStatus>,
+    claim: Option<ClaimPsbt>,
+    /// The claim with every signature collected so far.
+    psbt_base64: Option<String>,
+    finalized: Option<FinalizedTx>,
+    broadcast: Option<BroadcastResult>,
+}
+
+impl SessionState {
+    /// Fail unless the session is at one of `steps`.
+    fn require(&self, steps: &[SessionStep], action: &str) -> Result<(), HeirError> {
+        if steps.contains(&self.step) {
+            return Ok(());
+        }
+        Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Cannot {} while the claim is at step {:?}",
+                action, self.step
+            ),
+        ))
+    }
+
+    fn missing(what: &str) -> HeirError {
+        HeirError::new(
+            ErrorKind::Internal,
+            format!("The claim session has no {}", what),
+        )
+    }
+
+    fn vault_json(&self) -> Result<String, HeirError> {
+        self.vault_json
+            .clone()
+            .ok_or_else(|| Self::missing("vault"))
+    }
+
+    fn psbt_base64(&self) -> Result<String, HeirError> {
+        self.psbt_base64
+            .clone()
+            .ok_or_else(|| Self::missing("claim"))
+    }
+
+    /// Keep `psbt_base64` and move on to finalizing once it is signed.
+    fn record_signatures(&mut self, psbt_base64: String, ready: bool) -> Result<(), HeirError> {
+        let signed = matches!(
+            stage_of(&decode_psbt(&psbt_base64)?),
+            ClaimStage::Signed | ClaimStage::Finalized
+        );
+        self.psbt_base64 = Some(psbt_base64);
+        self.finalized = None;
+        self.step = if signed && ready {
+            SessionStep::Finalize
+        } else {
+            SessionStep::Sign
+        };
+        Ok(())
+    }
+}
+
+/// A claim in progress. See the module docs for its steps.
+pub struct ClaimSession {
+    state: Mutex<SessionState>,
+}
+
+impl ClaimSession {
+    /// A session waiting for a vault backup.
+    pub fn new() -> ClaimSession {
+        ClaimSession {
+            state: Mutex::new(SessionState {
+                version: SESSION_VERSION,
+                step: SessionStep::ImportVault,
+                vault_json: None,
+                status: None,
+                claim: None,
+                psbt_base64: None,
+                finalized: None,
+                broadcast: None,
+            }),
+        }
+    }
+
+    /// Pick up a session saved by [`ClaimSession::export_session`].
+    pub fn resume(session_json: String) -> Result<ClaimSession, HeirError> {
+        let state: SessionState = serde_json::from_str(&session_json).map_err(|e| {
+            HeirError::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid claim session: {}", e),
+            )
+        })?;
+        if state.version != SESSION_VERSION {
+            return Err(HeirError::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Claim session version {} is not one this app reads",
+                    state.version
+                ),
+            ));
+        }
+        Ok(ClaimSession {
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Everything the session holds, as JSON to save across restarts.
+    pub fn export_session(&self) -> Result<String, HeirError> {
+        serde_json::to_string(&*self.state.lock().unwrap()).map_err(|e| {
+            HeirError::new(
+                ErrorKind::Internal,
+                format!("Failed to serialize claim session: {}", e),
+            )
+        })
+    }
+
+    pub fn current_step(&self) -> SessionStep {
+        self.state.lock().unwrap().step
+    }
+
+    /// The claim as built, once it has been.
+    pub fn claim(&self) -> Option<ClaimPsbt> {
+        self.state.lock().unwrap().claim.clone()
+    }
+
+    /// The claim with every signature collected so far.
+    pub fn current_psbt(&self) -> Option<String> {
+        self.state.lock().unwrap().psbt_base64.clone()
+    }
+
+    /// Verify and keep the vault backup. A different vault can be imported
+    /// until a claim is built.
+    pub fn import_vault(&self, vault_json: String) -> Result<VaultInfo, HeirError> {
+        let mut state = self.state.lock().unwrap();
+        state.require(
+            &[
+                SessionStep::ImportVault,
+                SessionStep::CheckStatus,
+                SessionStep::BuildClaim,
+            ],
+            "import a vault",
+        )?;
+        let info = import_vault_backup(vault_json)?;
+        state.vault_json = Some(info.canonical_json.clone());
+        state.status = None;
+        state.step = SessionStep::CheckStatus;
+        Ok(info)
+    }
+
+    /// Fetch the vault's status. It can be refreshed until a claim is built;
+    /// a vault still under its timelock can be claimed, to be broadcast
+    /// once it matures.
+    pub fn check_status(&self, backend: &Backend) -> Result<VaultStatus, HeirError> {
+        let mut state = self.state.lock().unwrap();
+        state.require(
+            &[SessionStep::CheckStatus, SessionStep::BuildClaim],
+            "check the vault's status",
+        )?;
+        let status = fetch_vault_status(state.vault_json()?, backend)?;
+        state.status = Some(status.clone());
+        state.step = SessionStep::BuildClaim;
+        Ok(status)
+    }
+
+    /// Build the claim. Until someone signs it, it can be rebuilt, e.g. at
+    /// another fee rate.
+    pub fn build_claim(
+        &self,
+        backend: &Backend,
+        destination_address: String,
+        heir_index: usize,
+        fee_rate_sat_vb: u64,
+        options: ClaimOptions,
+    ) -> Result<ClaimPsbt, HeirError> {
+        let mut state = self.state.lock().unwrap();
+        let rebuild = state.step == SessionStep::Sign
+            && stage_of(&decode_psbt(&state.psbt_base64()?)?) == ClaimStage::Unsigned;
+        if !rebuild {
+            state.require(&[SessionStep::BuildClaim], "build the claim")?;
+        }
+        let claim = build_claim_psbt_with_options(
+            state.vault_json()?,
+            backend,
+            destination_address,
+            heir_index,
+            fee_rate_sat_vb,
+            options,
+        )?;
+        state.psbt_base64 = Some(claim.psbt_base64.clone());
+        state.claim = Some(claim.clone());
+        state.step = SessionStep::Sign;
+        Ok(claim)
+    }
+
+    /// Sign the claim with the heir's seed phrase on this device.
+    pub fn sign_with_mnemonic(
+        &self,
+        mnemonic: String,
+        passphrase: String,
+        derivation_path: Option<String>,
+    ) -> Result<SignedClaim, HeirError> {
+        let mut state = self.state.lock().unwrap();
+        state.require(&[SessionStep::Sign], "sign the claim")?;
+        let signed = sign_claim_psbt(state.psbt_base64()?, mnemonic, passphrase, derivation_path)?;
+        let ready = merge_signed_psbts(vec![signed.psbt_base64.clone()])?.ready_to_finalize;
+        state.record_signatures(signed.psbt_base64.clone(), ready)?;
+        Ok(signed)
+    }
+
+    /// Add the signatures from a copy of the claim signed elsewhere: a
+    /// hardware wallet, another heir, or a fee sponsor.
+    pub fn import_signed_psbt(&self, psbt_base64: String) -> Result<SessionStep, HeirError> {
+        let mut state = self.state.lock().unwrap();
+        state.require(
+            &[SessionStep::Sign, SessionStep::Finalize],
+            "import signatures",
+        )?;
+        let merged = merge_signed_psbts(vec![state.psbt_base64()?, psbt_base64])?;
+        state.record_signatures(merged.psbt_base64, merged.ready_to_finalize)?;
+        Ok(state.step)
+    }
+
+    /// Finalize the signed claim into a transaction ready to broadcast.
+    pub fn finalize(&self) -> Result<FinalizedTx, HeirError> {
+        let mut state = self.state.lock().unwrap();
+        state.require(&[SessionStep::Finalize], "finalize the claim")?;
+        let finalized = finalize_psbt(state.psbt_base64()?)?;
+        state.finalized = Some(finalized.clone());
+        state.step = SessionStep::Broadcast;
+        Ok(finalized)
+    }
+
+    /// Broadcast the finalized claim. A failed broadcast can be retried.
+    pub fn broadcast(&self, backend: &Backend) -> Result<BroadcastResult, HeirError> {
+        let mut state = self.state.lock().unwrap();
+        state.require(&[SessionStep::Broadcast], "broadcast the claim")?;
+        let tx_hex = state
+            .finalized
+            .as_ref()
+            .map(|finalized| finalized.tx_hex.clone())
+            .ok_or_else(|| SessionState::missing("finalized transaction"))?;
+        let result = broadcast_transaction(tx_hex, backend)?;
+        state.broadcast = Some(result.clone());
+        state.step = SessionStep::Done;
+        Ok(result)
+    }
+}
+
+impl Default for ClaimSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::{generate_test_vectors, TestVectors};
+    use crate::api::Network;
+
+    fn funded(v: &TestVectors) -> SimulatedBackend {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(300);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+        sim
+    }
+
+    #[test]
+    fn test_session_walks_every_step_across_a_restart() {
+        let v = generate_test_vectors(63).unwrap();
+        let sim = funded(&v);
+        let backend = Backend::simulated(&sim);
+
+        let session = ClaimSession::new();
+        session.import_vault(v.backup_json.clone()).unwrap();
+        assert_eq!(session.current_step(), SessionStep::CheckStatus);
+        assert!(session.check_status(&backend).unwrap().eligible);
+        let claim = session
+            .build_claim(
+                &backend,
+                v.destination.clone(),
+                0,
+                2,
+                ClaimOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(session.current_step(), SessionStep::Sign);
+
+        // The app restarts while the heir signs on a hardware wallet
+        let saved = session.export_session().unwrap();
+        let session = ClaimSession::resume(saved).unwrap();
+        assert_eq!(session.current_step(), SessionStep::Sign);
+        assert_eq!(session.claim().unwrap().txid_preview, claim.txid_preview);
+
+        let mut psbt = decode_psbt(&claim.psbt_base64).unwrap();
+        crate::api::psbt::tests::external_sign(&mut psbt, 0, &v.heir_secret_key_hex);
+        let signed = base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+        assert_eq!(
+            session.import_signed_psbt(signed).unwrap(),
+            SessionStep::Finalize
+        );
+        let finalized = session.finalize().unwrap();
+        let result = session.broadcast(&backend).unwrap();
+        assert_eq!(result.txid, finalized.txid);
+        assert_eq!(session.current_step(), SessionStep::Done);
+    }
+
+    #[test]
+    fn test_steps_cannot_be_skipped() {
+        let v = generate_test_vectors(64).unwrap();
+        let sim = funded(&v);
+        let backend = Backend::simulated(&sim);
+
+        let session = ClaimSession::new();
+        let err = session.check_status(&backend).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+        assert!(session.finalize().is_err());
+
+        session.import_vault(v.backup_json).unwrap();
+        assert!(session
+            .build_claim(
+                &backend,
+                v.destination.clone(),
+                0,
+                2,
+                ClaimOptions::default()
+            )
+            .is_err());
+        session.check_status(&backend).unwrap();
+        session
+            .build_claim(
+                &backend,
+                v.destination.clone(),
+                0,
+                2,
+                ClaimOptions::default(),
+            )
+            .unwrap();
+        // Unsigned, so it can still be rebuilt at another fee rate
+        session
+            .build_claim(&backend, v.destination, 0, 3, ClaimOptions::default())
+            .unwrap();
+        assert!(session.broadcast(&backend).is_err());
+        assert_eq!(session.current_step(), SessionStep::Sign);
+
+        assert!(ClaimSession::resume("{}".into()).is_err());
+    }
+}
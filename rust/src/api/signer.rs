@@ -0,0 +1,319 @@
+//! Signing a claim with the heir's seed phrase.
+//!
+//! For heirs without a hardware wallet or Sparrow: the BIP39 mnemonic goes
+//! in, the heir key is derived along the origins the claim PSBT records for
+//! its recovery leaves, and only signatures come back out. The seed and
+//! keys are dropped before [`sign_claim_psbt`] returns.
+
+use std::str::FromStr;
+
+use base64::Engine;
+use bitcoin::bip32::{DerivationPath, Xpriv};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::rand::RngCore;
+use bitcoin::secp256k1::{Keypair, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{Signature, TapLeafHash};
+use bitcoin::{NetworkKind, Psbt, TxOut};
+use serde::{Deserialize, Serialize};
+
+use super::entropy::EntropySource;
+use super::psbt::{check_sighash_types, finalize_signed_inputs};
+use super::{decode_psbt, ErrorKind, HeirError};
+
+/// Result of [`sign_claim_psbt`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedClaim {
+    pub psbt_base64: String,
+    /// Script-path signatures added, over all inputs and leaves.
+    pub signatures_added: usize,
+    /// Every input had enough signatures and was finalized, so the PSBT can
+    /// go straight to [`finalize_psbt`](super::finalize_psbt). False while
+    /// co-heirs still need to sign.
+    pub finalized: bool,
+}
+
+fn invalid(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidInput, message)
+}
+
+/// Sign every recovery leaf of `psbt_base64` that a key from `mnemonic`
+/// (with the BIP39 `passphrase`, empty for none) can sign.
+///
+/// Keys are derived at the paths recorded in the PSBT's taproot key
+/// origins, or at `derivation_path` when given (for backups whose origins
+/// were written with a placeholder fingerprint or path). Fails if the seed
+/// holds none of the claim's heir keys, which usually means a mistyped
+/// word or passphrase.
+pub fn sign_claim_psbt(
+    psbt_base64: String,
+    mnemonic: String,
+    passphrase: String,
+    derivation_path: Option<String>,
+) -> Result<SignedClaim, HeirError> {
+    sign_claim_psbt_with_entropy(
+        psbt_base64,
+        mnemonic,
+        passphrase,
+        derivation_path,
+        EntropySource::Os,
+    )
+}
+
+/// [`sign_claim_psbt`] with the BIP340 auxiliary randomness of each
+/// signature drawn from `entropy`.
+pub fn sign_claim_psbt_with_entropy(
+    psbt_base64: String,
+    mnemonic: String,
+    passphrase: String,
+    derivation_path: Option<String>,
+    entropy: EntropySource,
+) -> Result<SignedClaim, HeirError> {
+    let mut psbt = decode_psbt(&psbt_base64)?;
+    check_sighash_types(&psbt)?;
+    let override_path = derivation_path
+        .map(|path| {
+            DerivationPath::from_str(path.trim())
+                .map_err(|e| invalid(format!("Invalid derivation path: {}", e)))
+        })
+        .transpose()?;
+
+    let master = master_key(&mnemonic, &passphrase)?;
+    let secp = Secp256k1::new();
+
+    let signatures_added = sign_with(
+        &mut psbt,
+        &secp,
+        &master,
+        override_path.as_ref(),
+        &mut *entropy.rng(),
+    )?;
+    if signatures_added == 0 {
+        return Err(invalid(
+            "This seed phrase and passphrase hold none of the heir keys in this claim",
+        ));
+    }
+
+    let mut finalized_psbt = psbt.clone();
+    let finalized = finalize_signed_inputs(&mut finalized_psbt).is_empty()
+        && finalized_psbt
+            .inputs
+            .iter()
+            .all(|input| input.final_script_witness.is_some());
+    if finalized {
+        psbt = finalized_psbt;
+    }
+    Ok(SignedClaim {
+        psbt_base64: base64::engine::general_purpose::STANDARD.encode(psbt.serialize()),
+        signatures_added,
+        finalized,
+    })
+}
+
+/// BIP32 master key of a BIP39 `mnemonic` with `passphrase`.
+pub(crate) fn master_key(mnemonic: &str, passphrase: &str) -> Result<Xpriv, HeirError> {
+    // Never echo the words back, even in an error
+    let mnemonic = bip39::Mnemonic::parse(mnemonic.trim())
+        .map_err(|_| invalid("The seed phrase is not a valid BIP39 mnemonic"))?;
+    Xpriv::new_master(NetworkKind::Main, &mnemonic.to_seed(passphrase))
+        .map_err(|e| HeirError::new(ErrorKind::Internal, format!("Key derivation failed: {}", e)))
+}
+
+/// Add a script-path signature for every (input, key, leaf) `master` can
+/// sign. Returns how many were added.
+fn sign_with(
+    psbt: &mut Psbt,
+    secp: &Secp256k1<bitcoin::secp256k1::All>,
+    master: &Xpriv,
+    override_path: Option<&DerivationPath>,
+    rng: &mut dyn RngCore,
+) -> Result<usize, HeirError> {
+    let prevouts = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            input.witness_utxo.clone().ok_or_else(|| {
+                HeirError::new(
+                    ErrorKind::InvalidPsbt,
+                    format!("Input {} is missing its witness UTXO", index),
+                )
+            })
+        })
+        .collect::<Result<Vec<TxOut>, HeirError>>()?;
+
+    let mut signatures = Vec::new();
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        if input.final_script_witness.is_some() {
+            continue;
+        }
+        let sighash_type = match input.sighash_type {
+            Some(requested) => requested
+                .taproot_hash_ty()
+                .map_err(|_| invalid(format!("Input {} has an invalid sighash type", index)))?,
+            None => TapSighashType::Default,
+        };
+        let leaves_in_psbt: Vec<TapLeafHash> = input
+            .tap_scripts
+            .values()
+            .map(|(script, version)| TapLeafHash::from_script(script, *version))
+            .collect();
+
+        for (xonly, (leaves, (_, path))) in &input.tap_key_origins {
+            let path = override_path.unwrap_or(path);
+            let Some(keypair) = derive_keypair(secp, master, path, xonly)? else {
+                continue;
+            };
+            for leaf_hash in leaves.iter().filter(|leaf| leaves_in_psbt.contains(leaf)) {
+                if input.tap_script_sigs.contains_key(&(*xonly, *leaf_hash)) {
+                    continue;
+                }
+                let sighash = cache
+                    .taproot_script_spend_signature_hash(
+                        index,
+                        &Prevouts::All(&prevouts),
+                        *leaf_hash,
+                        sighash_type,
+                    )
+                    .map_err(|e| {
+                        HeirError::new(
+                            ErrorKind::InvalidPsbt,
+                            format!("Cannot compute the sighash of input {}: {}", index, e),
+                        )
+                    })?;
+                let mut aux_rand = [0u8; 32];
+                rng.fill_bytes(&mut aux_rand);
+                let signature = secp.sign_schnorr_with_aux_rand(
+                    &Message::from_digest(sighash.to_byte_array()),
+                    &keypair,
+                    &aux_rand,
+                );
+                signatures.push((
+                    index,
+                    (*xonly, *leaf_hash),
+                    Signature {
+                        signature,
+                        sighash_type,
+                    },
+                ));
+            }
+        }
+    }
+
+    let added = signatures.len();
+    for (index, key, signature) in signatures {
+        psbt.inputs[index].tap_script_sigs.insert(key, signature);
+    }
+    Ok(added)
+}
+
+/// The key at `path` under `master`, if it is `expected`.
+fn derive_keypair(
+    secp: &Secp256k1<bitcoin::secp256k1::All>,
+    master: &Xpriv,
+    path: &DerivationPath,
+    expected: &XOnlyPublicKey,
+) -> Result<Option<Keypair>, HeirError> {
+    let derived = master.derive_priv(secp, path).map_err(|e| {
+        HeirError::new(ErrorKind::Internal, format!("Key derivation failed: {}", e))
+    })?;
+    let keypair = derived.to_keypair(secp);
+    Ok((keypair.x_only_public_key().0 == *expected).then_some(keypair))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::{synthetic_backup, SyntheticKeys};
+    use crate::api::{build_claim_psbt, finalize_psbt, import_vault_backup, Backend, Network};
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon \
+                            abandon abandon abandon about";
+    const HEIR_PATH: &str = "m/86'/1'/0'";
+
+    /// Claim PSBT for a testnet vault whose heir key is the account key of
+    /// `MNEMONIC` at `HEIR_PATH`.
+    fn claim_psbt() -> String {
+        let secp = Secp256k1::new();
+        let seed = bip39::Mnemonic::parse(MNEMONIC).unwrap().to_seed("");
+        let heir = Xpriv::new_master(NetworkKind::Test, &seed)
+            .unwrap()
+            .derive_priv(&secp, &DerivationPath::from_str(HEIR_PATH).unwrap())
+            .unwrap()
+            .private_key
+            .public_key(&secp);
+        let other = |byte| {
+            bitcoin::secp256k1::SecretKey::from_slice(&[byte; 32])
+                .unwrap()
+                .public_key(&secp)
+        };
+        let keys = SyntheticKeys {
+            owner: other(1),
+            cosigner: other(2),
+            heir,
+            chain_code: [3; 32],
+        };
+        let backup = synthetic_backup(&keys, 144, bitcoin::Network::Testnet).unwrap();
+        let info = import_vault_backup(serde_json::to_string(&backup).unwrap()).unwrap();
+
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(500);
+        let funding = "44".repeat(32);
+        sim.add_utxo(info.vault_address.clone(), funding, 0, 80_000, 1)
+            .unwrap();
+        build_claim_psbt(
+            info.canonical_json,
+            &Backend::simulated(&sim),
+            info.vault_address,
+            0,
+            2,
+        )
+        .unwrap()
+        .psbt_base64
+    }
+
+    #[test]
+    fn test_mnemonic_signs_and_finalizes() {
+        let signed = sign_claim_psbt(claim_psbt(), MNEMONIC.into(), String::new(), None).unwrap();
+        assert_eq!(signed.signatures_added, 1);
+        assert!(signed.finalized);
+        assert!(finalize_psbt(signed.psbt_base64).is_ok());
+    }
+
+    #[test]
+    fn test_wrong_passphrase_signs_nothing() {
+        let err = sign_claim_psbt(
+            claim_psbt(),
+            MNEMONIC.into(),
+            "wrong".into(),
+            Some(HEIR_PATH.into()),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+        assert!(!err.message.contains("abandon"));
+
+        let err =
+            sign_claim_psbt(claim_psbt(), "abandon about".into(), String::new(), None).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_seeded_entropy_gives_the_same_signature() {
+        let psbt = claim_psbt();
+        let sign = |seed| {
+            sign_claim_psbt_with_entropy(
+                psbt.clone(),
+                MNEMONIC.into(),
+                String::new(),
+                None,
+                EntropySource::Seeded { seed },
+            )
+            .unwrap()
+            .psbt_base64
+        };
+        assert_eq!(sign(7), sign(7));
+        assert_ne!(sign(7), sign(8));
+    }
+}
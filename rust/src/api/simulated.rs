@@ -0,0 +1,394 @@
+//! Offline backend with scriptable chain state.
+//!
+//! Lets app developers build every screen (waiting, claimable, broadcast
+//! failures) without a network. Wrap it with [`Backend::simulated`] and keep
+//! the [`SimulatedBackend`] handle around to change the state mid-session.
+//!
+//! [`Backend::simulated`]: super::backend::Backend::simulated
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bitcoin::{Address, Amount, Network, OutPoint, ScriptBuf, Transaction, TxOut, Txid};
+
+use super::backend::{block_height, ChainBackend, ChainHistoryEntry, ChainUtxo};
+use super::{ErrorKind, HeirError};
+
+/// Simulated block `n` is timestamped `n * 600` seconds after this.
+pub const SIMULATED_GENESIS_TIME: u64 = 1_231_006_505;
+
+/// Scripted result of the next broadcast.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimulatedOutcome {
+    /// Accept the transaction and spend its inputs.
+    Accept,
+    /// Reject with the given server message.
+    Reject { message: String },
+    /// Fail as if the server were unreachable.
+    ConnectionFailure,
+}
+
+/// A transaction in the simulated history and the scripts it touches.
+///
+/// Outputs added with [`SimulatedBackend::add_utxo`] get a synthetic
+/// funding transaction whose real txid differs from the scripted one.
+struct SimTx {
+    txid: Txid,
+    height: u32,
+    scripts: Vec<ScriptBuf>,
+    tx: Transaction,
+}
+
+struct SimState {
+    network: Network,
+    height: u64,
+    utxos: Vec<ChainUtxo>,
+    history: Vec<SimTx>,
+    outcomes: VecDeque<SimulatedOutcome>,
+    broadcasts: Vec<Transaction>,
+    fee_rate_sat_vb: f64,
+    offline: bool,
+    history_limit: Option<usize>,
+}
+
+/// Scriptable in-memory chain. Cloning shares the same state.
+#[derive(Clone)]
+pub struct SimulatedBackend {
+    state: Arc<Mutex<SimState>>,
+}
+
+impl SimulatedBackend {
+    /// Empty chain at height 0 on `network`.
+    pub fn new(network: super::Network) -> SimulatedBackend {
+        SimulatedBackend {
+            state: Arc::new(Mutex::new(SimState {
+                network: network.into(),
+                height: 0,
+                utxos: Vec::new(),
+                history: Vec::new(),
+                outcomes: VecDeque::new(),
+                broadcasts: Vec::new(),
+                fee_rate_sat_vb: 1.0,
+                offline: false,
+                history_limit: None,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SimState> {
+        // State is plain data; a panic elsewhere cannot leave it inconsistent
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_height(&self, height: u64) {
+        self.lock().height = height;
+    }
+
+    /// Advance the tip, confirming any unconfirmed outputs and mempool
+    /// transactions in the first block.
+    pub fn mine_blocks(&self, count: u64) {
+        let mut state = self.lock();
+        if count == 0 {
+            return;
+        }
+        let first = u32::try_from(state.height + 1).unwrap_or(u32::MAX);
+        for utxo in state.utxos.iter_mut().filter(|u| u.height == 0) {
+            utxo.height = first;
+        }
+        for tx in state.history.iter_mut().filter(|t| t.height == 0) {
+            tx.height = first;
+        }
+        state.height += count;
+    }
+
+    /// Add an output paying `address`. A `height` of 0 means unconfirmed.
+    pub fn add_utxo(
+        &self,
+        address: String,
+        txid: String,
+        vout: u32,
+        value_sat: u64,
+        height: u32,
+    ) -> Result<(), HeirError> {
+        let mut state = self.lock();
+        let script_pubkey = Address::from_str(&address)
+            .map_err(|e| HeirError::new(ErrorKind::InvalidAddress, format!("Invalid address: {}", e)))?
+            .require_network(state.network)
+            .map_err(|e| {
+                HeirError::new(ErrorKind::NetworkMismatch, format!("Address network mismatch: {}", e))
+            })?
+            .script_pubkey();
+        let txid = Txid::from_str(&txid)
+            .map_err(|e| HeirError::new(ErrorKind::InvalidInput, format!("Invalid txid: {}", e)))?;
+
+        let index = match state.history.iter().position(|t| t.txid == txid) {
+            Some(index) => index,
+            None => {
+                state.history.push(SimTx {
+                    txid,
+                    height,
+                    scripts: Vec::new(),
+                    tx: Transaction {
+                        version: bitcoin::transaction::Version::TWO,
+                        lock_time: bitcoin::absolute::LockTime::ZERO,
+                        input: vec![bitcoin::TxIn::default()],
+                        output: Vec::new(),
+                    },
+                });
+                state.history.len() - 1
+            }
+        };
+        let funding = &mut state.history[index];
+        funding.scripts.push(script_pubkey.clone());
+        let outputs = &mut funding.tx.output;
+        if outputs.len() <= vout as usize {
+            outputs.resize(
+                vout as usize + 1,
+                TxOut {
+                    value: Amount::ZERO,
+                    script_pubkey: ScriptBuf::new(),
+                },
+            );
+        }
+        outputs[vout as usize] = TxOut {
+            value: Amount::from_sat(value_sat),
+            script_pubkey: script_pubkey.clone(),
+        };
+        state.utxos.push(ChainUtxo {
+            outpoint: OutPoint::new(txid, vout),
+            value: Amount::from_sat(value_sat),
+            script_pubkey,
+            height,
+        });
+        Ok(())
+    }
+
+    /// Remove every output, as if spent elsewhere. History is kept.
+    pub fn clear_utxos(&self) {
+        self.lock().utxos.clear();
+    }
+
+    /// Queue the outcome of the next broadcast. With an empty queue,
+    /// broadcasts are accepted.
+    pub fn push_broadcast_outcome(&self, outcome: SimulatedOutcome) {
+        self.lock().outcomes.push_back(outcome);
+    }
+
+    /// Fee rate returned for every confirmation target. Defaults to 1 sat/vB.
+    pub fn set_fee_rate(&self, sat_per_vb: f64) {
+        self.lock().fee_rate_sat_vb = sat_per_vb;
+    }
+
+    /// Make every query fail with a connection error.
+    pub fn set_offline(&self, offline: bool) {
+        self.lock().offline = offline;
+    }
+
+    /// Refuse histories longer than `limit` entries, like public servers
+    /// that cap response sizes. `None` serves every history.
+    pub fn set_history_limit(&self, limit: Option<usize>) {
+        self.lock().history_limit = limit;
+    }
+
+    /// Number of accepted broadcasts so far.
+    pub fn broadcast_count(&self) -> usize {
+        self.lock().broadcasts.len()
+    }
+}
+
+fn offline_error() -> HeirError {
+    HeirError::new(ErrorKind::Connection, "Simulated backend is offline")
+}
+
+impl ChainBackend for SimulatedBackend {
+    fn network(&self) -> Network {
+        self.lock().network
+    }
+
+    fn tip_height(&self) -> Result<u64, HeirError> {
+        let state = self.lock();
+        if state.offline {
+            return Err(offline_error());
+        }
+        block_height(state.height).map(u64::from)
+    }
+
+    fn list_unspent(&self, address: &Address) -> Result<Vec<ChainUtxo>, HeirError> {
+        let state = self.lock();
+        if state.offline {
+            return Err(offline_error());
+        }
+        let script = address.script_pubkey();
+        Ok(state
+            .utxos
+            .iter()
+            .filter(|u| u.script_pubkey == script)
+            .cloned()
+            .collect())
+    }
+
+    fn history(&self, address: &Address) -> Result<Vec<ChainHistoryEntry>, HeirError> {
+        let state = self.lock();
+        if state.offline {
+            return Err(offline_error());
+        }
+        let script = address.script_pubkey();
+        let history: Vec<_> = state
+            .history
+            .iter()
+            .filter(|t| t.scripts.contains(&script))
+            .map(|t| ChainHistoryEntry {
+                txid: t.txid,
+                height: t.height,
+            })
+            .collect();
+        if state.history_limit.is_some_and(|limit| history.len() > limit) {
+            return Err(HeirError::new(
+                ErrorKind::HistoryTooLarge,
+                "Simulated server refuses histories this large",
+            ));
+        }
+        Ok(history)
+    }
+
+    fn block_time(&self, height: u32) -> Result<u64, HeirError> {
+        let state = self.lock();
+        if state.offline {
+            return Err(offline_error());
+        }
+        Ok(SIMULATED_GENESIS_TIME + u64::from(height) * 600)
+    }
+
+    fn transaction(&self, txid: &Txid) -> Result<Transaction, HeirError> {
+        let state = self.lock();
+        if state.offline {
+            return Err(offline_error());
+        }
+        state
+            .history
+            .iter()
+            .find(|t| t.txid == *txid)
+            .map(|t| t.tx.clone())
+            .ok_or_else(|| {
+                HeirError::new(ErrorKind::ServerQuery, format!("Unknown transaction {}", txid))
+            })
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Txid, HeirError> {
+        let mut state = self.lock();
+        if state.offline {
+            return Err(offline_error());
+        }
+        match state.outcomes.pop_front().unwrap_or(SimulatedOutcome::Accept) {
+            SimulatedOutcome::Accept => {
+                let spent: Vec<OutPoint> = tx.input.iter().map(|i| i.previous_output).collect();
+                let mut scripts: Vec<ScriptBuf> =
+                    tx.output.iter().map(|o| o.script_pubkey.clone()).collect();
+                scripts.extend(
+                    state
+                        .utxos
+                        .iter()
+                        .filter(|u| spent.contains(&u.outpoint))
+                        .map(|u| u.script_pubkey.clone()),
+                );
+                state.utxos.retain(|u| !spent.contains(&u.outpoint));
+                state.history.push(SimTx {
+                    txid: tx.compute_txid(),
+                    height: 0,
+                    scripts,
+                    tx: tx.clone(),
+                });
+                state.broadcasts.push(tx.clone());
+                Ok(tx.compute_txid())
+            }
+            SimulatedOutcome::Reject { message } => Err(HeirError::broadcast_rejected(message)),
+            SimulatedOutcome::ConnectionFailure => Err(offline_error()),
+        }
+    }
+
+    fn estimate_fee_rate(&self, _target_blocks: u16) -> Result<f64, HeirError> {
+        let state = self.lock();
+        if state.offline {
+            return Err(offline_error());
+        }
+        Ok(state.fee_rate_sat_vb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Network;
+
+    const ADDR: &str = "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx";
+    const TXID: &str = "4242424242424242424242424242424242424242424242424242424242424242";
+
+    #[test]
+    fn test_height_beyond_u32_is_an_error() {
+        let sim = SimulatedBackend::new(Network::Regtest);
+        sim.set_height(u64::from(u32::MAX) + 1);
+        let err = sim.tip_height().unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::HeightOutOfRange {
+                reported: i64::from(u32::MAX) + 1
+            }
+        );
+        assert_eq!(
+            block_height(-1i32).unwrap_err().kind,
+            ErrorKind::HeightOutOfRange { reported: -1 }
+        );
+    }
+
+    #[test]
+    fn test_scripted_height_and_utxos() {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(100);
+        sim.add_utxo(ADDR.into(), TXID.into(), 0, 50_000, 0).unwrap();
+        sim.mine_blocks(3);
+
+        assert_eq!(sim.tip_height().unwrap(), 103);
+        let addr = Address::from_str(ADDR).unwrap().assume_checked();
+        let utxos = sim.list_unspent(&addr).unwrap();
+        assert_eq!(utxos.len(), 1);
+        assert_eq!(utxos[0].height, 101);
+
+        sim.clear_utxos();
+        let history = sim.history(&addr).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].height, 101);
+    }
+
+    #[test]
+    fn test_offline_mode() {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_offline(true);
+        assert_eq!(sim.tip_height().unwrap_err().kind, ErrorKind::Connection);
+    }
+
+    #[test]
+    fn test_scripted_broadcast_outcomes() {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.push_broadcast_outcome(SimulatedOutcome::Reject {
+            message: "min relay fee not met".into(),
+        });
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        let err = sim.broadcast(&tx).unwrap_err();
+        assert!(err.message.contains("min relay fee"));
+        assert!(sim.broadcast(&tx).is_ok());
+        assert_eq!(sim.broadcast_count(), 1);
+    }
+
+    #[test]
+    fn test_rejects_wrong_network_address() {
+        let sim = SimulatedBackend::new(Network::Mainnet);
+        let err = sim.add_utxo(ADDR.into(), TXID.into(), 0, 1, 1).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::NetworkMismatch);
+    }
+}
@@ -0,0 +1,383 @@
+//! Electrum connections with control over name resolution.
+//!
+//! Several mobile carriers break resolution of Electrum hostnames, or hand
+//! out IPv6 addresses they cannot route, and the heir just sees
+//! "Connection failed". [`ConnectionOptions`] lets the app force an address
+//! family, pin known servers to explicit IPs, or leave resolution to a SOCKS5
+//! proxy. Self-hosted servers signed by a private CA can add its root
+//! certificate. Connections that need any of this are opened here and run
+//! over the same stream adapter as host transports.
+//!
+//! Only compiled with the `electrum` feature (on by default).
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use serde::{Deserialize, Serialize};
+
+use super::transport::Transport;
+use super::{ErrorKind, HeirError};
+
+pub(crate) const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Which address family to connect over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IpVersion {
+    #[default]
+    Any,
+    V4Only,
+    V6Only,
+}
+
+/// Explicit IPs to use for `host` instead of asking DNS.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostOverride {
+    pub host: String,
+    pub addresses: Vec<String>,
+}
+
+/// How Electrum connections are opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionOptions {
+    pub ip_version: IpVersion,
+    pub host_overrides: Vec<HostOverride>,
+    /// `socks5://host:port`. Hostnames are then resolved by the proxy, never
+    /// locally, so the other options must be left at their defaults.
+    pub socks5_proxy: Option<String>,
+    /// PEM root certificates trusted in addition to the built-in roots.
+    pub extra_root_certs_pem: Vec<String>,
+}
+
+impl ConnectionOptions {
+    /// True when the electrum client's own connection logic can be used.
+    pub(crate) fn uses_native_client(&self) -> bool {
+        self.ip_version == IpVersion::Any
+            && self.host_overrides.is_empty()
+            && self.extra_root_certs_pem.is_empty()
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), HeirError> {
+        if self.socks5_proxy.is_some() && !self.uses_native_client() {
+            return Err(HeirError::new(
+                ErrorKind::InvalidInput,
+                "A SOCKS5 proxy cannot be combined with IP overrides or extra root certificates",
+            ));
+        }
+        if let Some(proxy) = &self.socks5_proxy {
+            proxy_address(proxy)?;
+        }
+        extra_roots(&self.extra_root_certs_pem)?;
+        for entry in &self.host_overrides {
+            for address in &entry.addresses {
+                address.parse::<IpAddr>().map_err(|_| {
+                    HeirError::new(
+                        ErrorKind::InvalidInput,
+                        format!("Invalid IP address for {}: {}", entry.host, address),
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Certificates in `pems`; each entry must hold at least one.
+pub(crate) fn extra_roots(pems: &[String]) -> Result<Vec<CertificateDer<'static>>, HeirError> {
+    let mut certs = Vec::new();
+    for (i, pem) in pems.iter().enumerate() {
+        let before = certs.len();
+        for cert in CertificateDer::pem_slice_iter(pem.as_bytes()) {
+            certs.push(cert.map_err(|e| {
+                HeirError::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid root certificate {}: {}", i + 1, e),
+                )
+            })?);
+        }
+        if certs.len() == before {
+            return Err(HeirError::new(
+                ErrorKind::InvalidInput,
+                format!("Root certificate {} contains no PEM certificate", i + 1),
+            ));
+        }
+    }
+    Ok(certs)
+}
+
+/// Built-in web roots plus `extra`.
+pub(crate) fn tls_config(extra: Vec<CertificateDer<'static>>) -> Result<Arc<rustls::ClientConfig>, HeirError> {
+    let mut roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    for cert in extra {
+        roots.add(cert).map_err(|e| {
+            HeirError::new(
+                ErrorKind::InvalidInput,
+                format!("Root certificate rejected: {}", e),
+            )
+        })?;
+    }
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    Ok(Arc::new(
+        rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    ))
+}
+
+/// Host address of a SOCKS5 proxy URL, for the electrum client config.
+pub(crate) fn proxy_address(proxy: &str) -> Result<String, HeirError> {
+    let address = proxy
+        .strip_prefix("socks5h://")
+        .or_else(|| proxy.strip_prefix("socks5://"))
+        .unwrap_or(proxy);
+    let valid = !address.contains("://")
+        && address
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+    if !valid {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid SOCKS5 proxy: {}", proxy),
+        ));
+    }
+    Ok(address.to_string())
+}
+
+/// Split `ssl://host:port` or `tcp://host:port` into (tls, host, port).
+pub(crate) fn parse_electrum_url(url: &str) -> io::Result<(bool, String, u16)> {
+    let (tls, rest) = match url.split_once("://") {
+        Some(("ssl", rest)) => (true, rest),
+        Some(("tcp", rest)) => (false, rest),
+        None => (false, url),
+        Some((scheme, _)) => {
+            return Err(io::Error::other(format!("Unsupported scheme: {}", scheme)))
+        }
+    };
+    let (host, port) = rest
+        .rsplit_once(':')
+        .ok_or_else(|| io::Error::other(format!("Missing port in {}", url)))?;
+    let port = port
+        .parse()
+        .map_err(|_| io::Error::other(format!("Invalid port in {}", url)))?;
+    // IPv6 literals are written in brackets
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Ok((tls, host.to_string(), port))
+}
+
+/// Addresses to try for `host`, in order, after overrides and filtering.
+pub(crate) fn resolve(host: &str, port: u16, options: &ConnectionOptions) -> io::Result<Vec<SocketAddr>> {
+    let candidates: Vec<SocketAddr> = match options
+        .host_overrides
+        .iter()
+        .find(|o| o.host.eq_ignore_ascii_case(host))
+    {
+        Some(entry) => entry
+            .addresses
+            .iter()
+            .filter_map(|a| a.parse::<IpAddr>().ok())
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect(),
+        None => (host, port).to_socket_addrs()?.collect(),
+    };
+    let addresses: Vec<SocketAddr> = candidates
+        .into_iter()
+        .filter(|addr| match options.ip_version {
+            IpVersion::Any => true,
+            IpVersion::V4Only => addr.is_ipv4(),
+            IpVersion::V6Only => addr.is_ipv6(),
+        })
+        .collect();
+    if addresses.is_empty() {
+        return Err(io::Error::other(format!(
+            "No usable {} address for {}",
+            match options.ip_version {
+                IpVersion::Any => "IP",
+                IpVersion::V4Only => "IPv4",
+                IpVersion::V6Only => "IPv6",
+            },
+            host
+        )));
+    }
+    Ok(addresses)
+}
+
+trait Stream: Read + Write + Send {}
+impl<T: Read + Write + Send> Stream for T {}
+
+/// Rust sockets, resolved according to [`ConnectionOptions`].
+pub(crate) struct SocketTransport {
+    options: ConnectionOptions,
+    tls: Arc<rustls::ClientConfig>,
+    stream: Option<Box<dyn Stream>>,
+}
+
+impl SocketTransport {
+    pub(crate) fn new(options: ConnectionOptions) -> Result<Self, HeirError> {
+        let tls = tls_config(extra_roots(&options.extra_root_certs_pem)?)?;
+        Ok(Self {
+            options,
+            tls,
+            stream: None,
+        })
+    }
+
+    fn stream(&mut self) -> io::Result<&mut Box<dyn Stream>> {
+        self.stream
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))
+    }
+}
+
+impl Transport for SocketTransport {
+    fn connect(&mut self, url: &str) -> io::Result<()> {
+        self.close();
+        let (tls, host, port) = parse_electrum_url(url)?;
+        let mut last_error = None;
+        let mut tcp = None;
+        for addr in resolve(&host, port, &self.options)? {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    tcp = Some(stream);
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let tcp = tcp.ok_or_else(|| last_error.unwrap_or_else(|| io::Error::other("No address")))?;
+
+        self.stream = Some(if tls {
+            let name = rustls::pki_types::ServerName::try_from(host)
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            let connection = rustls::ClientConnection::new(self.tls.clone(), name)
+                .map_err(io::Error::other)?;
+            Box::new(rustls::StreamOwned::new(connection, tcp))
+        } else {
+            Box::new(tcp)
+        });
+        Ok(())
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let stream = self.stream()?;
+        stream.write_all(data)?;
+        stream.flush()
+    }
+
+    fn receive(&mut self, max_len: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; max_len];
+        let n = self.stream()?.read(&mut buf)?;
+        buf.truncate(n);
+        Ok(buf)
+    }
+
+    fn close(&mut self) {
+        self.stream = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pinned(ip_version: IpVersion) -> ConnectionOptions {
+        ConnectionOptions {
+            ip_version,
+            host_overrides: vec![HostOverride {
+                host: "electrum.example".into(),
+                addresses: vec!["192.0.2.7".into(), "2001:db8::7".into()],
+            }],
+            socks5_proxy: None,
+            extra_root_certs_pem: vec![],
+        }
+    }
+
+    const PRIVATE_CA: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBjjCCATOgAwIBAgIUMPmNg+bAArPbF1tXB2zysnksVIUwCgYIKoZIzj0EAwIw
+GzEZMBcGA1UEAwwQVGVzdCBFbGVjdHJ1bSBDQTAgFw0yNjEwMTYwODIxNTVaGA8y
+MTI2MDkyMjA4MjE1NVowGzEZMBcGA1UEAwwQVGVzdCBFbGVjdHJ1bSBDQTBZMBMG
+ByqGSM49AgEGCCqGSM49AwEHA0IABMk6Vm3uNW8NqUTMaFtv+UATaBj6t73rhV59
+Tf/fAXnHEvY6f1/uetK3+nkMrx4BFzRgovCIfSvZ0VBMrnNgHY+jUzBRMB0GA1Ud
+DgQWBBRjrElw73gZB8Ss9bE4FgFOb/818TAfBgNVHSMEGDAWgBRjrElw73gZB8Ss
+9bE4FgFOb/818TAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQCI
+Rp2bWqGEbtZlivuSyfSZuUxvYJjYDSI5bTfOBYGKPAIhAJ9lHqERuZumOCL0TrbP
+OxsORofzRSL+kjiImjGHU44j
+-----END CERTIFICATE-----";
+
+    #[test]
+    fn test_parse_electrum_url() {
+        assert_eq!(
+            parse_electrum_url("ssl://electrum.example:50002").unwrap(),
+            (true, "electrum.example".into(), 50002)
+        );
+        assert_eq!(
+            parse_electrum_url("tcp://[2001:db8::7]:50001").unwrap(),
+            (false, "2001:db8::7".into(), 50001)
+        );
+        assert!(parse_electrum_url("http://electrum.example:80").is_err());
+        assert!(parse_electrum_url("ssl://electrum.example").is_err());
+    }
+
+    #[test]
+    fn test_resolve_uses_overrides_and_family() {
+        let any = resolve("Electrum.Example", 50002, &pinned(IpVersion::Any)).unwrap();
+        assert_eq!(any.len(), 2);
+        let v6 = resolve("electrum.example", 50002, &pinned(IpVersion::V6Only)).unwrap();
+        assert_eq!(v6, vec!["[2001:db8::7]:50002".parse().unwrap()]);
+
+        let v4_only = ConnectionOptions {
+            ip_version: IpVersion::V4Only,
+            ..Default::default()
+        };
+        let err = resolve("2001:db8::7", 50002, &v4_only).unwrap_err();
+        assert!(err.to_string().contains("IPv4"));
+    }
+
+    #[test]
+    fn test_validate_options() {
+        assert!(pinned(IpVersion::Any).validate().is_ok());
+        let mut bad_ip = pinned(IpVersion::Any);
+        bad_ip.host_overrides[0].addresses.push("not-an-ip".into());
+        assert_eq!(bad_ip.validate().unwrap_err().kind, ErrorKind::InvalidInput);
+
+        let mut proxied = pinned(IpVersion::Any);
+        proxied.socks5_proxy = Some("socks5://127.0.0.1:9050".into());
+        assert!(proxied.validate().is_err());
+        assert_eq!(proxy_address("socks5://127.0.0.1:9050").unwrap(), "127.0.0.1:9050");
+        assert!(proxy_address("http://127.0.0.1:8080").is_err());
+        let bad_proxy = ConnectionOptions {
+            socks5_proxy: Some("socks5://127.0.0.1".into()),
+            ..Default::default()
+        };
+        assert_eq!(bad_proxy.validate().unwrap_err().kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_extra_root_certificates() {
+        let options = ConnectionOptions {
+            extra_root_certs_pem: vec![PRIVATE_CA.into()],
+            ..Default::default()
+        };
+        assert!(options.validate().is_ok());
+        assert!(!options.uses_native_client());
+        assert!(SocketTransport::new(options).is_ok());
+
+        let garbage = ConnectionOptions {
+            extra_root_certs_pem: vec!["not a certificate".into()],
+            ..Default::default()
+        };
+        assert_eq!(garbage.validate().unwrap_err().kind, ErrorKind::InvalidInput);
+
+        let proxied = ConnectionOptions {
+            extra_root_certs_pem: vec![PRIVATE_CA.into()],
+            socks5_proxy: Some("socks5://127.0.0.1:9050".into()),
+            ..Default::default()
+        };
+        assert!(proxied.validate().is_err());
+    }
+}
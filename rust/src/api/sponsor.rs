@@ -0,0 +1,325 @@
+//! Claim fees paid by a third party.
+//!
+//! A vault holding one small UTXO can lose most of it to fees. A solvent
+//! third party, such as the executor, can add one of their own UTXOs to the
+//! claim instead: the heirs receive the vault's whole balance, and the fee
+//! comes out of the sponsor's input, less the change returned to them. The
+//! sponsor signs their input with their own wallet, the heirs sign the vault
+//! inputs, and [`super::finalize_psbt`] completes both.
+//!
+//! The claim PSBT records the sponsor's outpoint and change script under
+//! proprietary global keys, so the sponsor's input and change are known by
+//! what they spend and pay rather than by what a signer left in the PSBT.
+
+use std::str::FromStr;
+
+use bitcoin::consensus::serialize;
+use bitcoin::psbt::raw::ProprietaryKey;
+use bitcoin::psbt::Input;
+use bitcoin::{Address, Amount, OutPoint, Psbt, ScriptBuf, Sequence, TxIn, TxOut, Witness};
+use serde::{Deserialize, Serialize};
+
+use super::{ErrorKind, HeirError};
+
+/// Virtual size of a P2TR key-path input with a 64-byte signature.
+const KEY_PATH_INPUT_VBYTES: u64 = 58;
+/// Virtual size of a P2WPKH input.
+const P2WPKH_INPUT_VBYTES: u64 = 68;
+
+/// Prefix of the PSBT proprietary keys recording a claim's sponsor.
+const PROPRIETARY_PREFIX: &[u8] = b"nostring-heir";
+/// Subtype whose key is the consensus-encoded outpoint of a sponsor input.
+const SPONSOR_OUTPOINT_SUBTYPE: u8 = 0x00;
+/// Subtype whose key is the scriptPubKey of the sponsor's change.
+const SPONSOR_CHANGE_SUBTYPE: u8 = 0x01;
+
+/// A UTXO of the sponsor's, added to a claim to pay its fee.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeSponsor {
+    /// `txid:vout` of the UTXO.
+    pub outpoint: String,
+    pub value_sat: u64,
+    /// Its scriptPubKey as hex. Must be P2TR or P2WPKH.
+    pub script_pubkey_hex: String,
+    /// Where what is left of the UTXO after the fee goes.
+    pub change_address: String,
+}
+
+/// A [`FeeSponsor`] checked against the claim's network.
+pub(crate) struct SponsorInput {
+    pub outpoint: OutPoint,
+    txout: TxOut,
+    change_script: ScriptBuf,
+}
+
+fn invalid(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidInput, message)
+}
+
+impl FeeSponsor {
+    pub(crate) fn parse(&self, network: bitcoin::Network) -> Result<SponsorInput, HeirError> {
+        let outpoint = OutPoint::from_str(self.outpoint.trim())
+            .map_err(|e| invalid(format!("Invalid sponsor outpoint: {}", e)))?;
+        let script_pubkey = ScriptBuf::from_hex(self.script_pubkey_hex.trim())
+            .map_err(|e| invalid(format!("Invalid sponsor script: {}", e)))?;
+        if !script_pubkey.is_p2tr() && !script_pubkey.is_p2wpkh() {
+            return Err(invalid("The sponsor's UTXO must be P2TR or P2WPKH"));
+        }
+        let change_script = Address::from_str(self.change_address.trim())
+            .map_err(|e| {
+                HeirError::new(
+                    ErrorKind::InvalidAddress,
+                    format!("Invalid sponsor change address: {}", e),
+                )
+            })?
+            .require_network(network)
+            .map_err(|e| {
+                HeirError::new(
+                    ErrorKind::NetworkMismatch,
+                    format!("Sponsor change address network mismatch: {}", e),
+                )
+            })?
+            .script_pubkey();
+        Ok(SponsorInput {
+            outpoint,
+            txout: TxOut {
+                value: Amount::from_sat(self.value_sat),
+                script_pubkey,
+            },
+            change_script,
+        })
+    }
+}
+
+impl SponsorInput {
+    /// Virtual size the sponsor's input and change output add to a claim.
+    pub(crate) fn added_vbytes(&self) -> u64 {
+        let input = if self.txout.script_pubkey.is_p2tr() {
+            KEY_PATH_INPUT_VBYTES
+        } else {
+            P2WPKH_INPUT_VBYTES
+        };
+        let change = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: self.change_script.clone(),
+        };
+        input + change.size() as u64
+    }
+
+    /// What the sponsor gets back after paying `fee_sat`.
+    pub(crate) fn change_sat(&self, fee_sat: u64, dust_limit_sat: u64) -> Result<u64, HeirError> {
+        let value_sat = self.txout.value.to_sat();
+        match value_sat.checked_sub(fee_sat) {
+            Some(change) if change >= dust_limit_sat => Ok(change),
+            _ => Err(invalid(format!(
+                "The sponsor's {} sat UTXO does not cover the {} sat fee plus relayable change",
+                value_sat, fee_sat
+            ))),
+        }
+    }
+
+    /// Append the sponsor's input and `change_sat` change output to `psbt`.
+    pub(crate) fn add_to(&self, psbt: &mut Psbt, change_sat: u64) {
+        psbt.unsigned_tx.input.push(TxIn {
+            previous_output: self.outpoint,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            witness: Witness::new(),
+        });
+        psbt.inputs.push(Input {
+            witness_utxo: Some(self.txout.clone()),
+            ..Default::default()
+        });
+        psbt.unsigned_tx.output.push(TxOut {
+            value: Amount::from_sat(change_sat),
+            script_pubkey: self.change_script.clone(),
+        });
+        psbt.outputs.push(Default::default());
+        psbt.proprietary.insert(
+            proprietary_key(SPONSOR_OUTPOINT_SUBTYPE, serialize(&self.outpoint)),
+            Vec::new(),
+        );
+        psbt.proprietary.insert(
+            proprietary_key(SPONSOR_CHANGE_SUBTYPE, self.change_script.to_bytes()),
+            Vec::new(),
+        );
+    }
+}
+
+fn proprietary_key(subtype: u8, key: Vec<u8>) -> ProprietaryKey {
+    ProprietaryKey {
+        prefix: PROPRIETARY_PREFIX.to_vec(),
+        subtype,
+        key,
+    }
+}
+
+/// Whether input `index` of `psbt` spends the outpoint its sponsor was
+/// recorded with.
+pub(crate) fn is_sponsor_input(psbt: &Psbt, index: usize) -> bool {
+    psbt.unsigned_tx.input.get(index).is_some_and(|txin| {
+        psbt.proprietary.contains_key(&proprietary_key(
+            SPONSOR_OUTPOINT_SUBTYPE,
+            serialize(&txin.previous_output),
+        ))
+    })
+}
+
+/// Whether output `index` of `psbt` pays the change script its sponsor was
+/// recorded with.
+pub(crate) fn is_sponsor_change(psbt: &Psbt, index: usize) -> bool {
+    psbt.unsigned_tx.output.get(index).is_some_and(|txout| {
+        psbt.proprietary.contains_key(&proprietary_key(
+            SPONSOR_CHANGE_SUBTYPE,
+            txout.script_pubkey.to_bytes(),
+        ))
+    })
+}
+
+/// Complete a signed sponsor input without a descriptor: a P2TR key-path
+/// signature, or a P2WPKH signature by the key the script commits to.
+/// Returns false if `input` is not one of those.
+pub(crate) fn finalize_key_spend(input: &mut Input) -> bool {
+    let Some(script) = input.witness_utxo.as_ref().map(|u| u.script_pubkey.clone()) else {
+        return false;
+    };
+    let witness = if script.is_p2tr() {
+        input
+            .tap_key_sig
+            .map(|sig| Witness::from_slice(&[sig.to_vec()]))
+    } else if script.is_p2wpkh() {
+        input.partial_sigs.iter().find_map(|(key, sig)| {
+            let compressed = bitcoin::CompressedPublicKey::try_from(*key).ok()?;
+            (ScriptBuf::new_p2wpkh(&compressed.wpubkey_hash()) == script)
+                .then(|| Witness::p2wpkh(sig, &compressed.0))
+        })
+    } else {
+        None
+    };
+    match witness {
+        Some(witness) => {
+            input.final_script_witness = Some(witness);
+            input.tap_key_sig = None;
+            input.partial_sigs.clear();
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use base64::Engine;
+    use bitcoin::hashes::Hash;
+    use bitcoin::key::TapTweak;
+    use bitcoin::secp256k1::{Keypair, Message, Secp256k1, SecretKey};
+    use bitcoin::sighash::{Prevouts, SighashCache};
+    use bitcoin::TapSighashType;
+
+    use super::*;
+    use crate::api::psbt::tests::external_sign;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::{
+        build_claim_psbt_with_options, decode_psbt, finalize_psbt, Backend, ClaimOptions, Network,
+    };
+
+    const SPONSOR_KEY: [u8; 32] = [0x41; 32];
+
+    pub(crate) fn sponsor(value_sat: u64) -> FeeSponsor {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&SPONSOR_KEY).unwrap();
+        let (xonly, _) = sk.public_key(&secp).x_only_public_key();
+        let address = Address::p2tr(&secp, xonly, None, bitcoin::Network::Testnet);
+        FeeSponsor {
+            outpoint: format!("{}:1", "e4".repeat(32)),
+            value_sat,
+            script_pubkey_hex: address.script_pubkey().to_hex_string(),
+            change_address: address.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_sponsor_pays_and_keeps_change() {
+        let input = sponsor(20_000).parse(bitcoin::Network::Testnet).unwrap();
+        // Key-path input plus a P2TR change output
+        assert_eq!(input.added_vbytes(), 58 + 43);
+        assert_eq!(input.change_sat(1_000, 546).unwrap(), 19_000);
+        assert!(input.change_sat(19_600, 546).is_err());
+        assert!(input.change_sat(30_000, 546).is_err());
+    }
+
+    #[test]
+    fn test_invalid_sponsors_are_refused() {
+        let mut bad = sponsor(20_000);
+        bad.script_pubkey_hex = "6a".into();
+        assert!(bad.parse(bitcoin::Network::Testnet).is_err());
+
+        let err = sponsor(20_000)
+            .parse(bitcoin::Network::Bitcoin)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind, ErrorKind::NetworkMismatch);
+    }
+
+    #[test]
+    fn test_sponsored_claim_pays_out_the_whole_vault() {
+        let v = generate_test_vectors(62).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(400);
+        sim.add_utxo(v.vault_address.clone(), "d8".repeat(32), 0, 3_000, 1)
+            .unwrap();
+        let options = ClaimOptions {
+            sponsor: Some(sponsor(50_000)),
+            ..Default::default()
+        };
+        let claim = build_claim_psbt_with_options(
+            v.backup_json,
+            &Backend::simulated(&sim),
+            v.destination,
+            0,
+            10,
+            options,
+        )
+        .unwrap();
+        assert_eq!(claim.output_sat, 3_000);
+        assert_eq!(claim.sponsor_change_sat, Some(50_000 - claim.fee_sat));
+
+        let mut psbt = decode_psbt(&claim.psbt_base64).unwrap();
+        assert_eq!(psbt.inputs.len(), 2);
+        assert!(is_sponsor_input(&psbt, 1));
+        assert!(is_sponsor_change(&psbt, 1));
+        // A vault input stripped of its leaves is still the vault's
+        let mut stripped = psbt.clone();
+        stripped.inputs[0].tap_scripts.clear();
+        assert!(!is_sponsor_input(&stripped, 0));
+        external_sign(&mut psbt, 0, &v.heir_secret_key_hex);
+        let encode =
+            |psbt: &Psbt| base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+        let err = finalize_psbt(encode(&psbt)).unwrap_err();
+        assert!(err.message.contains("fee sponsor"), "{}", err.message);
+
+        // The sponsor signs their input with a key-path signature
+        let secp = Secp256k1::new();
+        let keypair =
+            Keypair::from_secret_key(&secp, &SecretKey::from_slice(&SPONSOR_KEY).unwrap())
+                .tap_tweak(&secp, None)
+                .to_inner();
+        let prevouts: Vec<_> = psbt
+            .inputs
+            .iter()
+            .map(|i| i.witness_utxo.clone().unwrap())
+            .collect();
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .taproot_key_spend_signature_hash(1, &Prevouts::All(&prevouts), TapSighashType::Default)
+            .unwrap();
+        psbt.inputs[1].tap_key_sig = Some(bitcoin::taproot::Signature {
+            signature: secp
+                .sign_schnorr_no_aux_rand(&Message::from_digest(sighash.to_byte_array()), &keypair),
+            sighash_type: TapSighashType::Default,
+        });
+        let finalized = finalize_psbt(encode(&psbt)).unwrap();
+        assert_eq!(finalized.num_inputs, 2);
+        assert_eq!(finalized.total_output_sat, 3_000 + 50_000 - claim.fee_sat);
+    }
+}
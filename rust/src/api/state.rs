@@ -0,0 +1,175 @@
+//! Vault lifecycle state machine.
+//!
+//! Derived in one place from UTXOs, mempool, and history so every frontend
+//! shows the same state for the same chain data.
+
+use std::collections::HashMap;
+
+use bitcoin::{Script, Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
+use super::backend::{ChainBackend, ChainHistoryEntry};
+use super::{HeirError, UtxoStatus};
+
+/// Lifecycle state of a vault, derived from its on-chain activity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VaultState {
+    /// No transaction has ever paid the vault address.
+    Unfunded,
+    /// Funded, and no output's timelock has expired yet.
+    FundedLocked,
+    /// Some outputs are claimable; others are unconfirmed or still locked.
+    PartiallyClaimable,
+    /// Every output is claimable.
+    FullyClaimable,
+    /// A spend of vault outputs is waiting in the mempool.
+    ClaimPending,
+    /// Everything was spent; the vault is empty.
+    Swept,
+    /// The owner recently spent via the key path and re-funded the vault,
+    /// restarting the timelock.
+    OwnerRefreshed,
+}
+
+/// A confirmed or pending transaction spending vault outputs.
+#[derive(Debug, Clone)]
+pub(crate) struct OutgoingSpend {
+    pub txid: Txid,
+    /// 0 while in the mempool.
+    pub height: u32,
+    /// Spent via the taproot key path (owner + co-signer), not a recovery leaf.
+    pub key_path: bool,
+    pub tx: Transaction,
+}
+
+/// Fetch every transaction in `history`.
+pub(crate) fn fetch_history_txs(
+    chain: &dyn ChainBackend,
+    history: &[ChainHistoryEntry],
+) -> Result<HashMap<Txid, Transaction>, HeirError> {
+    let txids: Vec<Txid> = history.iter().map(|entry| entry.txid).collect();
+    let txs = chain.transactions(&txids)?;
+    Ok(txids.into_iter().zip(txs).collect())
+}
+
+/// Transactions in `history` that spend an output paying `vault_script`,
+/// oldest first.
+pub(crate) fn outgoing_spends(
+    vault_script: &Script,
+    history: &[ChainHistoryEntry],
+    txs: &HashMap<Txid, Transaction>,
+) -> Vec<OutgoingSpend> {
+    let pays_vault = |txid: &Txid, vout: u32| {
+        txs.get(txid)
+            .and_then(|tx| tx.output.get(vout as usize))
+            .is_some_and(|out| out.script_pubkey.as_script() == vault_script)
+    };
+
+    history
+        .iter()
+        .filter_map(|entry| {
+            let tx = txs.get(&entry.txid)?;
+            let vault_inputs: Vec<_> = tx
+                .input
+                .iter()
+                .filter(|i| pays_vault(&i.previous_output.txid, i.previous_output.vout))
+                .collect();
+            if vault_inputs.is_empty() {
+                return None;
+            }
+            Some(OutgoingSpend {
+                txid: entry.txid,
+                height: entry.height,
+                // Key-path spends carry a lone signature; script paths add
+                // the leaf script and control block
+                key_path: vault_inputs.iter().all(|i| i.witness.len() == 1),
+                tx: tx.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Derive the lifecycle state.
+pub(crate) fn classify_vault(
+    utxos: &[UtxoStatus],
+    has_history: bool,
+    outgoing: &[OutgoingSpend],
+) -> VaultState {
+    if utxos.is_empty() && !has_history {
+        return VaultState::Unfunded;
+    }
+    if outgoing.iter().any(|spend| spend.height == 0) {
+        return VaultState::ClaimPending;
+    }
+    if utxos.is_empty() {
+        return if outgoing.is_empty() {
+            VaultState::Unfunded
+        } else {
+            VaultState::Swept
+        };
+    }
+
+    let claimable = utxos.iter().filter(|u| u.claimable).count();
+    if claimable == utxos.len() {
+        VaultState::FullyClaimable
+    } else if claimable > 0 {
+        VaultState::PartiallyClaimable
+    } else if outgoing.last().is_some_and(|spend| spend.key_path) {
+        VaultState::OwnerRefreshed
+    } else {
+        VaultState::FundedLocked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    fn utxo(claimable: bool) -> UtxoStatus {
+        UtxoStatus {
+            outpoint: String::new(),
+            value_sat: 1_000,
+            confirmations: 1,
+            claimable,
+            claimable_at_height: Some(0),
+            dust: false,
+            frozen: false,
+            label: None,
+        }
+    }
+
+    fn spend(height: u32, key_path: bool) -> OutgoingSpend {
+        OutgoingSpend {
+            txid: Txid::from_byte_array([7; 32]),
+            height,
+            key_path,
+            tx: Transaction {
+                version: bitcoin::transaction::Version::TWO,
+                lock_time: bitcoin::absolute::LockTime::ZERO,
+                input: vec![],
+                output: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_classify_states() {
+        assert_eq!(classify_vault(&[], false, &[]), VaultState::Unfunded);
+        assert_eq!(classify_vault(&[], true, &[spend(10, false)]), VaultState::Swept);
+        assert_eq!(
+            classify_vault(&[utxo(true)], true, &[spend(0, false)]),
+            VaultState::ClaimPending
+        );
+        assert_eq!(classify_vault(&[utxo(false)], true, &[]), VaultState::FundedLocked);
+        assert_eq!(
+            classify_vault(&[utxo(true), utxo(false)], true, &[]),
+            VaultState::PartiallyClaimable
+        );
+        assert_eq!(classify_vault(&[utxo(true)], true, &[]), VaultState::FullyClaimable);
+        assert_eq!(
+            classify_vault(&[utxo(false)], true, &[spend(10, true)]),
+            VaultState::OwnerRefreshed
+        );
+    }
+}
@@ -0,0 +1,641 @@
+//! On-disk stores for in-progress claims and per-vault settings.
+//!
+//! A claim can take days to collect signatures. Each PSBT is saved as one
+//! JSON file in an app-provided directory so a restart mid-claim resumes
+//! where the heir left off. Claims are keyed by the unsigned txid, which
+//! signing does not change.
+//!
+//! [`VaultStore`] keeps the heir's imported vaults and their own notes about
+//! each, such as which UTXOs to leave untouched and what each one is, keyed
+//! by vault address.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use bitcoin::Psbt;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    decode_psbt, fetch_vault_status, history_unless_too_large, import_vault_backup, parse_backup,
+    status_from_chain, verified_vault_address, Backend, ClaimOptions, ErrorKind, HeirError,
+    VaultInfo, VaultStatus,
+};
+
+/// How far along a stored claim is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClaimStage {
+    Unsigned,
+    PartiallySigned,
+    /// Every input has a signature; not yet finalized.
+    Signed,
+    /// Every input has its final witness; ready to extract and broadcast.
+    Finalized,
+}
+
+/// A claim saved by [`ClaimStore`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingClaim {
+    /// Unsigned txid of the claim.
+    pub id: String,
+    pub stage: ClaimStage,
+    pub psbt_base64: String,
+    pub destination: String,
+    pub fee_sat: u64,
+    /// Unix seconds.
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+pub(crate) fn stage_of(psbt: &Psbt) -> ClaimStage {
+    let finalized = psbt
+        .inputs
+        .iter()
+        .filter(|i| i.final_script_witness.is_some())
+        .count();
+    let signed = psbt
+        .inputs
+        .iter()
+        .filter(|i| {
+            i.final_script_witness.is_some()
+                || i.tap_key_sig.is_some()
+                || !i.tap_script_sigs.is_empty()
+        })
+        .count();
+    let total = psbt.inputs.len();
+    if finalized == total {
+        ClaimStage::Finalized
+    } else if signed == total {
+        ClaimStage::Signed
+    } else if signed > 0 {
+        ClaimStage::PartiallySigned
+    } else {
+        ClaimStage::Unsigned
+    }
+}
+
+fn fee_of(psbt: &Psbt) -> u64 {
+    let input_sat: u64 = psbt
+        .inputs
+        .iter()
+        .filter_map(|i| i.witness_utxo.as_ref())
+        .map(|utxo| utxo.value.to_sat())
+        .sum();
+    let output_sat: u64 = psbt.unsigned_tx.output.iter().map(|o| o.value.to_sat()).sum();
+    input_sat.saturating_sub(output_sat)
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn io_error(action: &str, e: impl std::fmt::Display) -> HeirError {
+    HeirError::new(ErrorKind::Internal, format!("Failed to {}: {}", action, e))
+}
+
+/// Directory of saved claims. The app passes its private documents path.
+pub struct ClaimStore {
+    dir: PathBuf,
+}
+
+impl ClaimStore {
+    /// Open (creating if needed) the store in `dir`.
+    pub fn open(dir: String) -> Result<ClaimStore, HeirError> {
+        let dir = PathBuf::from(dir);
+        std::fs::create_dir_all(&dir).map_err(|e| io_error("create claim store", e))?;
+        Ok(ClaimStore { dir })
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn write(&self, claim: &PendingClaim) -> Result<(), HeirError> {
+        let json = serde_json::to_vec_pretty(claim).map_err(|e| io_error("serialize claim", e))?;
+        // Write then rename, so a crash never leaves a half-written claim
+        let tmp = self.dir.join(format!("{}.json.tmp", claim.id));
+        std::fs::write(&tmp, json).map_err(|e| io_error("save claim", e))?;
+        std::fs::rename(&tmp, self.path(&claim.id)).map_err(|e| io_error("save claim", e))
+    }
+
+    /// Save a PSBT, or update the stored claim for the same transaction.
+    /// `destination` is kept from the first save.
+    pub fn save_claim(&self, psbt_base64: String, destination: String) -> Result<PendingClaim, HeirError> {
+        let psbt = decode_psbt(&psbt_base64)?;
+        let id = psbt.unsigned_tx.compute_txid().to_string();
+        let now = now();
+        let existing = self.resume_claim(id.clone()).ok();
+        let claim = PendingClaim {
+            stage: stage_of(&psbt),
+            fee_sat: fee_of(&psbt),
+            // Stored as standard base64 whatever encoding was passed in
+            psbt_base64: base64::engine::general_purpose::STANDARD.encode(psbt.serialize()),
+            destination: existing
+                .as_ref()
+                .map_or(destination, |c| c.destination.clone()),
+            created_at: existing.as_ref().map_or(now, |c| c.created_at),
+            updated_at: now,
+            id,
+        };
+        self.write(&claim)?;
+        Ok(claim)
+    }
+
+    /// Every saved claim, oldest first.
+    pub fn list_pending_claims(&self) -> Result<Vec<PendingClaim>, HeirError> {
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| io_error("read claim store", e))?;
+        let mut claims = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| io_error("read claim store", e))?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let bytes = std::fs::read(&path).map_err(|e| io_error("read claim", e))?;
+                // Skip files this version cannot read rather than failing the list
+                if let Ok(claim) = serde_json::from_slice::<PendingClaim>(&bytes) {
+                    claims.push(claim);
+                }
+            }
+        }
+        claims.sort_by_key(|c| c.created_at);
+        Ok(claims)
+    }
+
+    /// Load one saved claim.
+    pub fn resume_claim(&self, id: String) -> Result<PendingClaim, HeirError> {
+        let bytes = std::fs::read(self.path(&id)).map_err(|_| {
+            HeirError::new(ErrorKind::InvalidInput, format!("No saved claim {}", id))
+        })?;
+        serde_json::from_slice(&bytes).map_err(|e| io_error("read claim", e))
+    }
+
+    /// Forget a claim, e.g. once it has confirmed.
+    pub fn remove_claim(&self, id: String) -> Result<(), HeirError> {
+        std::fs::remove_file(self.path(&id)).map_err(|_| {
+            HeirError::new(ErrorKind::InvalidInput, format!("No saved claim {}", id))
+        })
+    }
+}
+
+/// Settings saved for one vault.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct VaultRecord {
+    /// `txid:vout`, kept sorted.
+    #[serde(default)]
+    frozen: Vec<String>,
+    /// `txid:vout` to label.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    /// Canonical backup, once the vault was added with [`VaultStore::add_vault`].
+    #[serde(default, skip_serializing_if = Option::is_none)]
+    backup_json: Option<String>,
+}
+
+impl VaultRecord {
+    /// Fill in frozen flags and labels.
+    fn annotate(&self, status: &mut VaultStatus) {
+        for utxo in &mut status.utxos {
+            utxo.frozen = self.frozen.contains(&utxo.outpoint);
+            utxo.label = self.labels.get(&utxo.outpoint).cloned();
+        }
+        status.frozen_sat = status
+            .utxos
+            .iter()
+            .filter(|u| u.frozen)
+            .map(|u| u.value_sat)
+            .sum();
+    }
+}
+
+/// One vault of a [`PortfolioStatus`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioEntry {
+    pub vault_address: String,
+    pub status: VaultStatus,
+}
+
+/// Every stored vault on the backend's network, with totals for a home
+/// screen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortfolioStatus {
+    pub vaults: Vec<PortfolioEntry>,
+    pub total_balance_sat: u64,
+    /// Claimable now and not frozen.
+    pub total_claimable_sat: u64,
+    pub total_unconfirmed_sat: u64,
+    /// Vaults with at least one claimable output.
+    pub claimable_vault_count: usize,
+}
+
+/// Normalized `txid:vout`.
+fn parse_outpoint(outpoint: &str) -> Result<String, HeirError> {
+    bitcoin::OutPoint::from_str(outpoint.trim())
+        .map(|o| o.to_string())
+        .map_err(|e| HeirError::new(ErrorKind::InvalidInput, format!("Invalid outpoint: {}", e)))
+}
+
+/// Directory of per-vault settings. May share a directory with
+/// [`ClaimStore`].
+pub struct VaultStore {
+    dir: PathBuf,
+}
+
+impl VaultStore {
+    /// Open (creating if needed) the store in `dir`.
+    pub fn open(dir: String) -> Result<VaultStore, HeirError> {
+        let dir = PathBuf::from(dir);
+        std::fs::create_dir_all(&dir).map_err(|e| io_error("create vault store", e))?;
+        Ok(VaultStore { dir })
+    }
+
+    fn path(&self, vault_address: &str) -> PathBuf {
+        self.dir.join(format!("vault-{}.json", vault_address))
+    }
+
+    fn load(&self, vault_address: &str) -> Result<VaultRecord, HeirError> {
+        match std::fs::read(self.path(vault_address)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| io_error("read vault settings", e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(VaultRecord::default()),
+            Err(e) => Err(io_error("read vault settings", e)),
+        }
+    }
+
+    fn save(&self, vault_address: &str, record: &VaultRecord) -> Result<(), HeirError> {
+        let json =
+            serde_json::to_vec_pretty(record).map_err(|e| io_error("serialize vault settings", e))?;
+        let path = self.path(vault_address);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|e| io_error("save vault settings", e))?;
+        std::fs::rename(&tmp, path).map_err(|e| io_error("save vault settings", e))
+    }
+
+    fn update(
+        &self,
+        vault_address: &str,
+        f: impl FnOnce(&mut VaultRecord),
+    ) -> Result<(), HeirError> {
+        let mut record = self.load(vault_address)?;
+        f(&mut record);
+        self.save(vault_address, &record)
+    }
+
+    /// Keep claims from spending `outpoint` (`txid:vout`).
+    pub fn freeze_utxo(&self, vault_address: String, outpoint: String) -> Result<(), HeirError> {
+        let outpoint = parse_outpoint(&outpoint)?;
+        self.update(&vault_address, |record| {
+            if let Err(i) = record.frozen.binary_search(&outpoint) {
+                record.frozen.insert(i, outpoint);
+            }
+        })
+    }
+
+    /// Let claims spend `outpoint` again. Unknown outpoints are ignored.
+    pub fn unfreeze_utxo(&self, vault_address: String, outpoint: String) -> Result<(), HeirError> {
+        let outpoint = outpoint.trim().to_string();
+        self.update(&vault_address, |record| record.frozen.retain(|o| *o != outpoint))
+    }
+
+    /// Frozen outpoints of the vault, sorted.
+    pub fn frozen_utxos(&self, vault_address: String) -> Result<Vec<String>, HeirError> {
+        Ok(self.load(&vault_address)?.frozen)
+    }
+
+    /// Label `outpoint`; an empty label removes it.
+    pub fn set_utxo_label(
+        &self,
+        vault_address: String,
+        outpoint: String,
+        label: String,
+    ) -> Result<(), HeirError> {
+        let outpoint = parse_outpoint(&outpoint)?;
+        let label = label.trim().to_string();
+        self.update(&vault_address, |record| {
+            if label.is_empty() {
+                record.labels.remove(&outpoint);
+            } else {
+                record.labels.insert(outpoint, label);
+            }
+        })
+    }
+
+    /// Label of `outpoint`, if the heir gave it one.
+    pub fn utxo_label(&self, vault_address: String, outpoint: String) -> Result<Option<String>, HeirError> {
+        let outpoint = parse_outpoint(&outpoint)?;
+        Ok(self.load(&vault_address)?.labels.remove(&outpoint))
+    }
+
+    /// Labels of a claim PSBT's inputs, in input order, for the review screen.
+    pub fn psbt_input_labels(
+        &self,
+        vault_address: String,
+        psbt_base64: String,
+    ) -> Result<Vec<Option<String>>, HeirError> {
+        let psbt = decode_psbt(&psbt_base64)?;
+        let labels = self.load(&vault_address)?.labels;
+        Ok(psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|input| labels.get(&input.previous_output.to_string()).cloned())
+            .collect())
+    }
+
+    /// Labels and frozen flags as BIP329 JSON Lines, for import into other
+    /// wallets. Frozen outputs are exported as not spendable.
+    pub fn export_bip329(&self, vault_address: String) -> Result<String, HeirError> {
+        let record = self.load(&vault_address)?;
+        let mut outpoints: Vec<&String> = record.labels.keys().chain(&record.frozen).collect();
+        outpoints.sort();
+        outpoints.dedup();
+        let lines: Vec<String> = outpoints
+            .into_iter()
+            .map(|outpoint| {
+                let mut entry = serde_json::json!({ "type": "output", "ref": outpoint });
+                if let Some(label) = record.labels.get(outpoint) {
+                    entry["label"] = serde_json::json!(label);
+                }
+                if record.frozen.contains(outpoint) {
+                    entry["spendable"] = serde_json::json!(false);
+                }
+                entry.to_string()
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    /// [`fetch_vault_status`] with frozen UTXOs flagged and labels filled in.
+    pub fn fetch_vault_status(
+        &self,
+        vault_json: String,
+        backend: &Backend,
+    ) -> Result<VaultStatus, HeirError> {
+        let record = self.load(&parse_backup(&vault_json)?.vault_address)?;
+        let mut status = fetch_vault_status(vault_json, backend)?;
+        record.annotate(&mut status);
+        Ok(status)
+    }
+
+    /// Verify and remember a vault backup. Adding it again replaces the
+    /// stored backup and keeps labels and frozen flags.
+    pub fn add_vault(&self, vault_json: String) -> Result<VaultInfo, HeirError> {
+        let info = import_vault_backup(vault_json)?;
+        let backup_json = info.canonical_json.clone();
+        self.update(&info.vault_address, |record| record.backup_json = Some(backup_json))?;
+        Ok(info)
+    }
+
+    /// Forget a vault and everything stored about it.
+    pub fn remove_vault(&self, vault_address: String) -> Result<(), HeirError> {
+        std::fs::remove_file(self.path(&vault_address)).map_err(|_| {
+            HeirError::new(ErrorKind::InvalidInput, format!("No stored vault {}", vault_address))
+        })
+    }
+
+    /// Addresses of the vaults added with [`VaultStore::add_vault`], sorted.
+    pub fn list_vaults(&self) -> Result<Vec<String>, HeirError> {
+        Ok(self.records()?.into_iter().map(|(address, _, _)| address).collect())
+    }
+
+    /// Stored vaults as (address, record, backup JSON).
+    fn records(&self) -> Result<Vec<(String, VaultRecord, String)>, HeirError> {
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| io_error("read vault store", e))?;
+        let mut records = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| io_error("read vault store", e))?.path();
+            let Some(address) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("vault-"))
+                .and_then(|name| name.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            let mut record = self.load(address)?;
+            if let Some(backup_json) = record.backup_json.take() {
+                records.push((address.to_string(), record, backup_json));
+            }
+        }
+        records.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(records)
+    }
+
+    /// Status of every stored vault on the backend's network, fetched with
+    /// batched requests over the backend's one connection.
+    pub fn fetch_all_vault_statuses(&self, backend: &Backend) -> Result<PortfolioStatus, HeirError> {
+        let chain = backend.chain();
+        let mut vaults = Vec::new();
+        for (address, record, backup_json) in self.records()? {
+            let backup = parse_backup(&backup_json)?;
+            let vault_address = verified_vault_address(&backup_json, &backup)?;
+            // Vaults on other networks need their own backend
+            if vault_address.is_valid_for_network(chain.network()) {
+                vaults.push((address, record, backup, vault_address));
+            }
+        }
+
+        let addresses: Vec<_> = vaults.iter().map(|(_, _, _, address)| address.clone()).collect();
+        let current_height = chain.tip_height()?;
+        // One oversized history fails the whole batch; retry one by one
+        let histories = match chain.histories(&addresses) {
+            Ok(histories) => histories.into_iter().map(Some).collect(),
+            Err(e) if e.kind == ErrorKind::HistoryTooLarge => addresses
+                .iter()
+                .map(|address| history_unless_too_large(chain.history(address)))
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(e) => return Err(e),
+        };
+        let unspent = chain.list_unspent_many(&addresses)?;
+
+        let mut entries = Vec::with_capacity(vaults.len());
+        for (((vault_address, record, backup, address), history), utxos) in
+            vaults.into_iter().zip(histories).zip(unspent)
+        {
+            let mut status = status_from_chain(
+                u32::from(backup.timelock_blocks),
+                &address,
+                chain,
+                current_height,
+                utxos,
+                history.as_deref(),
+            )?;
+            record.annotate(&mut status);
+            entries.push(PortfolioEntry {
+                vault_address,
+                status,
+            });
+        }
+
+        let total = |f: fn(&VaultStatus) -> u64| entries.iter().map(|e| f(&e.status)).sum();
+        let claimable = |status: &VaultStatus| -> u64 {
+            status
+                .utxos
+                .iter()
+                .filter(|u| u.claimable && !u.frozen)
+                .map(|u| u.value_sat)
+                .sum()
+        };
+        Ok(PortfolioStatus {
+            total_balance_sat: total(|s| s.balance_sat),
+            total_claimable_sat: total(claimable),
+            total_unconfirmed_sat: total(|s| s.unconfirmed_sat),
+            claimable_vault_count: entries.iter().filter(|e| claimable(&e.status) > 0).count(),
+            vaults: entries,
+        })
+    }
+
+    /// `options` with this vault's frozen UTXOs added, for the claim builders.
+    pub fn claim_options(
+        &self,
+        vault_json: String,
+        mut options: ClaimOptions,
+    ) -> Result<ClaimOptions, HeirError> {
+        let frozen = self.frozen_utxos(parse_backup(&vault_json)?.vault_address)?;
+        options.frozen_outpoints.extend(frozen);
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::Network;
+
+    fn temp_store(name: &str) -> ClaimStore {
+        let dir = std::env::temp_dir().join(format!("heir-store-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        ClaimStore::open(dir.to_string_lossy().into_owned()).unwrap()
+    }
+
+    #[test]
+    fn test_save_resume_and_update() {
+        let store = temp_store("roundtrip");
+        let v = generate_test_vectors(4).unwrap();
+
+        let saved = store
+            .save_claim(v.unsigned_psbt_base64.clone(), v.destination.clone())
+            .unwrap();
+        assert_eq!(saved.stage, ClaimStage::Unsigned);
+        assert_eq!(saved.fee_sat, v.fee_sat);
+
+        let updated = store
+            .save_claim(v.signed_psbt_base64.clone(), "ignored".into())
+            .unwrap();
+        assert_eq!(updated.id, saved.id);
+        assert_eq!(updated.destination, v.destination);
+        assert_eq!(updated.created_at, saved.created_at);
+        assert_ne!(updated.stage, ClaimStage::Unsigned);
+
+        let listed = store.list_pending_claims().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(store.resume_claim(saved.id.clone()).unwrap().psbt_base64, v.signed_psbt_base64);
+
+        store.remove_claim(saved.id.clone()).unwrap();
+        assert!(store.list_pending_claims().unwrap().is_empty());
+        assert_eq!(store.resume_claim(saved.id).unwrap_err().kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_freeze_and_unfreeze() {
+        let dir = std::env::temp_dir().join(format!("heir-vault-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = VaultStore::open(dir.to_string_lossy().into_owned()).unwrap();
+        let v = generate_test_vectors(5).unwrap();
+
+        store
+            .freeze_utxo(v.vault_address.clone(), v.funding_outpoint.clone())
+            .unwrap();
+        // Freezing twice keeps one entry
+        store
+            .freeze_utxo(v.vault_address.clone(), v.funding_outpoint.clone())
+            .unwrap();
+        assert_eq!(store.frozen_utxos(v.vault_address.clone()).unwrap(), vec![v.funding_outpoint.clone()]);
+        assert!(store.freeze_utxo(v.vault_address.clone(), "nope".into()).is_err());
+
+        let sim = crate::api::simulated::SimulatedBackend::new(Network::Testnet);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(v.vault_address.clone(), txid.into(), vout.parse().unwrap(), v.funding_value_sat, 1)
+            .unwrap();
+        let backend = Backend::simulated(&sim);
+        let status = store.fetch_vault_status(v.backup_json.clone(), &backend).unwrap();
+        assert!(status.utxos[0].frozen);
+        assert_eq!(status.frozen_sat, v.funding_value_sat);
+
+        let options = store.claim_options(v.backup_json.clone(), ClaimOptions::default()).unwrap();
+        assert_eq!(options.frozen_outpoints, vec![v.funding_outpoint.clone()]);
+
+        store
+            .unfreeze_utxo(v.vault_address.clone(), v.funding_outpoint.clone())
+            .unwrap();
+        assert!(store.frozen_utxos(v.vault_address).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_utxo_labels() {
+        let dir = std::env::temp_dir().join(format!("heir-vault-labels-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = VaultStore::open(dir.to_string_lossy().into_owned()).unwrap();
+        let v = generate_test_vectors(6).unwrap();
+        let label = "2019 cold storage deposit";
+
+        store
+            .set_utxo_label(v.vault_address.clone(), v.funding_outpoint.clone(), label.into())
+            .unwrap();
+        store
+            .freeze_utxo(v.vault_address.clone(), v.funding_outpoint.clone())
+            .unwrap();
+        assert_eq!(
+            store
+                .psbt_input_labels(v.vault_address.clone(), v.unsigned_psbt_base64.clone())
+                .unwrap(),
+            vec![Some(label.to_string())]
+        );
+
+        let export = store.export_bip329(v.vault_address.clone()).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(&export).unwrap();
+        assert_eq!(entry["type"], "output");
+        assert_eq!(entry["ref"], v.funding_outpoint.as_str());
+        assert_eq!(entry["label"], label);
+        assert_eq!(entry["spendable"], false);
+
+        store
+            .set_utxo_label(v.vault_address.clone(), v.funding_outpoint.clone(), " ".into())
+            .unwrap();
+        assert_eq!(store.utxo_label(v.vault_address, v.funding_outpoint).unwrap(), None);
+    }
+
+    #[test]
+    fn test_fetch_all_vault_statuses() {
+        let dir = std::env::temp_dir().join(format!("heir-vault-portfolio-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = VaultStore::open(dir.to_string_lossy().into_owned()).unwrap();
+        let a = generate_test_vectors(8).unwrap();
+        let b = generate_test_vectors(9).unwrap();
+        store.add_vault(a.backup_json.clone()).unwrap();
+        store.add_vault(b.backup_json.clone()).unwrap();
+        // Labels alone do not make a stored vault
+        store
+            .set_utxo_label("tb1qunrelated".into(), a.funding_outpoint.clone(), "x".into())
+            .unwrap();
+        assert_eq!(store.list_vaults().unwrap().len(), 2);
+
+        let sim = crate::api::simulated::SimulatedBackend::new(Network::Testnet);
+        sim.set_height(10_000);
+        sim.add_utxo(a.vault_address.clone(), "81".repeat(32), 0, 30_000, 1_000)
+            .unwrap();
+        sim.add_utxo(b.vault_address.clone(), "82".repeat(32), 0, 20_000, 9_990)
+            .unwrap();
+
+        let portfolio = store.fetch_all_vault_statuses(&Backend::simulated(&sim)).unwrap();
+        assert_eq!(portfolio.vaults.len(), 2);
+        assert_eq!(portfolio.total_balance_sat, 50_000);
+        assert_eq!(portfolio.total_claimable_sat, 30_000);
+        assert_eq!(portfolio.claimable_vault_count, 1);
+
+        store.remove_vault(a.vault_address).unwrap();
+        assert_eq!(store.list_vaults().unwrap(), vec![b.vault_address]);
+    }
+}
@@ -0,0 +1,194 @@
+//! Projected cost of different ways to claim a vault.
+//!
+//! A vault with many UTXOs can cost a noticeable share of its value to sweep
+//! at a fast fee rate. The comparison uses live fee estimates and the
+//! vault's spendable outputs so the heir sees the trade-off in sats and
+//! hours before choosing.
+
+use serde::{Deserialize, Serialize};
+
+use super::fees::{fee_estimates, FAST_TARGET_BLOCKS, SLOW_TARGET_BLOCKS};
+use super::timelock::block_interval_secs;
+use super::{
+    fetch_vault_status, parse_backup, parse_network, recovery_tree_depth, Backend, ErrorKind,
+    HeirError,
+};
+
+/// A way of claiming the vault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClaimStrategy {
+    /// One transaction at the fast fee rate.
+    SweepNow,
+    /// One transaction at the economy fee rate.
+    WaitForLowFees,
+    /// Half of the UTXOs now at the fast rate, the rest later at the
+    /// economy rate.
+    SplitIntoTwo,
+}
+
+/// Projection for one [`ClaimStrategy`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrategyEstimate {
+    pub strategy: ClaimStrategy,
+    pub transactions: u32,
+    pub total_vbytes: u64,
+    pub total_fee_sat: u64,
+    /// What the heir receives after fees.
+    pub net_sat: u64,
+    /// Fee as a share of the claimed amount, in percent.
+    pub fee_percent: f64,
+    /// Blocks until the last transaction is expected to confirm.
+    pub expected_confirmation_blocks: u16,
+    pub expected_confirmation_minutes: u64,
+}
+
+/// One claim transaction: inputs, fee rate and confirmation target.
+struct Leg {
+    inputs: usize,
+    fee_rate_sat_vb: f64,
+    target_blocks: u16,
+}
+
+/// Compare sweeping now, waiting for low fees, and splitting the claim in
+/// two, for the vault's currently claimable, non-dust outputs.
+pub fn compare_claim_strategies(
+    vault_json: String,
+    backend: &Backend,
+) -> Result<Vec<StrategyEstimate>, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let network = parse_network(&backup.network)?;
+    let status = fetch_vault_status(vault_json, backend)?;
+
+    let spendable: Vec<u64> = status
+        .utxos
+        .iter()
+        .filter(|u| u.claimable && !u.dust)
+        .map(|u| u.value_sat)
+        .collect();
+    if spendable.is_empty() {
+        return Err(HeirError::new(
+            ErrorKind::NoUtxos,
+            "Nothing in the vault can be claimed yet",
+        ));
+    }
+    let total_sat: u64 = spendable.iter().sum();
+
+    // Claiming now or waiting are the fast and slow fee presets
+    let estimates = fee_estimates(backend.chain())?;
+    let fast = estimates.fast as f64;
+    let economy = estimates.slow as f64;
+
+    let depth = recovery_tree_depth(&backup);
+    let first_half = spendable.len().div_ceil(2);
+    let plans = [
+        (
+            ClaimStrategy::SweepNow,
+            vec![Leg {
+                inputs: spendable.len(),
+                fee_rate_sat_vb: fast,
+                target_blocks: FAST_TARGET_BLOCKS,
+            }],
+        ),
+        (
+            ClaimStrategy::WaitForLowFees,
+            vec![Leg {
+                inputs: spendable.len(),
+                fee_rate_sat_vb: economy,
+                target_blocks: SLOW_TARGET_BLOCKS,
+            }],
+        ),
+        (
+            ClaimStrategy::SplitIntoTwo,
+            vec![
+                Leg {
+                    inputs: first_half,
+                    fee_rate_sat_vb: fast,
+                    target_blocks: FAST_TARGET_BLOCKS,
+                },
+                Leg {
+                    inputs: spendable.len() - first_half,
+                    fee_rate_sat_vb: economy,
+                    target_blocks: SLOW_TARGET_BLOCKS,
+                },
+            ],
+        ),
+    ];
+
+    Ok(plans
+        .into_iter()
+        // A single UTXO cannot be split
+        .filter(|(_, legs)| legs.iter().all(|leg| leg.inputs > 0))
+        .map(|(strategy, legs)| {
+            let mut total_vbytes = 0;
+            let mut total_fee_sat = 0;
+            for leg in &legs {
+                let vbytes =
+                    nostring_inherit::taproot::estimate_heir_claim_vbytes(leg.inputs, 1, depth)
+                        as u64;
+                total_vbytes += vbytes;
+                total_fee_sat += (vbytes as f64 * leg.fee_rate_sat_vb).ceil() as u64;
+            }
+            let blocks = legs
+                .iter()
+                .map(|leg| leg.target_blocks)
+                .max()
+                .unwrap_or_default();
+            StrategyEstimate {
+                strategy,
+                transactions: legs.len() as u32,
+                total_vbytes,
+                total_fee_sat,
+                net_sat: total_sat.saturating_sub(total_fee_sat),
+                fee_percent: total_fee_sat as f64 * 100.0 / total_sat as f64,
+                expected_confirmation_blocks: blocks,
+                expected_confirmation_minutes: u64::from(blocks) * block_interval_secs(network)
+                    / 60,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::Network;
+
+    #[test]
+    fn test_compare_claim_strategies() {
+        let v = generate_test_vectors(10).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(10_000);
+        sim.set_fee_rate(20.0);
+        for txid in ["91", "92", "93"] {
+            sim.add_utxo(v.vault_address.clone(), txid.repeat(32), 0, 40_000, 1_000)
+                .unwrap();
+        }
+
+        let estimates = compare_claim_strategies(v.backup_json, &Backend::simulated(&sim)).unwrap();
+        assert_eq!(estimates.len(), 3);
+        let sweep = &estimates[0];
+        let split = &estimates[2];
+        assert_eq!(sweep.strategy, ClaimStrategy::SweepNow);
+        assert_eq!(sweep.net_sat + sweep.total_fee_sat, 120_000);
+        assert_eq!(split.transactions, 2);
+        // Two transactions repeat the fixed overhead
+        assert!(split.total_vbytes > sweep.total_vbytes);
+        assert_eq!(split.expected_confirmation_blocks, SLOW_TARGET_BLOCKS);
+    }
+
+    #[test]
+    fn test_single_utxo_cannot_split() {
+        let v = generate_test_vectors(10).unwrap();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(10_000);
+        sim.set_fee_rate(5.0);
+        sim.add_utxo(v.vault_address.clone(), "94".repeat(32), 0, 40_000, 1_000)
+            .unwrap();
+        let estimates = compare_claim_strategies(v.backup_json, &Backend::simulated(&sim)).unwrap();
+        assert!(estimates
+            .iter()
+            .all(|e| e.strategy != ClaimStrategy::SplitIntoTwo));
+    }
+}
@@ -0,0 +1,160 @@
+//! Conversions between CSV timelocks (blocks) and wall-clock durations.
+//!
+//! Every block/day conversion in the crate goes through here so the
+//! eligibility screens, policy text, and status agree on the same math.
+
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
+
+use super::{ErrorKind, HeirError};
+
+/// Target block interval on mainnet, in seconds.
+pub const BITCOIN_BLOCK_INTERVAL_SECS: u64 = 600;
+/// Target block interval on testnet3. Actual intervals vary widely.
+pub const TESTNET_BLOCK_INTERVAL_SECS: u64 = 600;
+/// Target block interval on the default signet.
+pub const SIGNET_BLOCK_INTERVAL_SECS: u64 = 600;
+/// Nominal interval on regtest, where blocks are mined on demand.
+pub const REGTEST_BLOCK_INTERVAL_SECS: u64 = 600;
+
+/// Longest block-based relative timelock BIP68 can encode. The sequence
+/// field holds 16 bits of blocks, about 455 days at 10-minute blocks.
+pub const MAX_CSV_BLOCKS: u32 = 0xFFFF;
+
+const SECS_PER_DAY: f64 = 86_400.0;
+
+/// Expected seconds between blocks on `network`.
+pub(crate) fn block_interval_secs(network: Network) -> u64 {
+    match network {
+        Network::Testnet | Network::Testnet4 => TESTNET_BLOCK_INTERVAL_SECS,
+        Network::Signet => SIGNET_BLOCK_INTERVAL_SECS,
+        Network::Regtest => REGTEST_BLOCK_INTERVAL_SECS,
+        _ => BITCOIN_BLOCK_INTERVAL_SECS,
+    }
+}
+
+/// Approximate days for a (possibly negative) block count.
+pub(crate) fn blocks_to_days(blocks: i64, network: Network) -> f64 {
+    blocks as f64 * block_interval_secs(network) as f64 / SECS_PER_DAY
+}
+
+/// Expected wall-clock length of a timelock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelockDuration {
+    pub blocks: u32,
+    pub seconds: u64,
+    pub days: f64,
+}
+
+/// Expected duration of `blocks` on `network`.
+pub fn timelock_to_duration(
+    blocks: u32,
+    network: super::Network,
+) -> Result<TimelockDuration, HeirError> {
+    let network = Network::from(network);
+    Ok(TimelockDuration {
+        blocks,
+        seconds: u64::from(blocks) * block_interval_secs(network),
+        days: blocks_to_days(i64::from(blocks), network),
+    })
+}
+
+/// Blocks needed to cover at least `days` on `network`, rounded up.
+pub fn duration_to_blocks(days: f64, network: super::Network) -> Result<u32, HeirError> {
+    let network = Network::from(network);
+    if !days.is_finite() || days < 0.0 {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid duration: {} days", days),
+        ));
+    }
+    let blocks = (days * SECS_PER_DAY / block_interval_secs(network) as f64).ceil();
+    if blocks > f64::from(MAX_CSV_BLOCKS) {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Duration of {} days is too long for a timelock (at most {} blocks)",
+                days, MAX_CSV_BLOCKS
+            ),
+        ));
+    }
+    Ok(blocks as u32)
+}
+
+/// Reject a timelock the CSV opcode cannot enforce.
+pub(crate) fn check_csv_blocks(blocks: u64) -> Result<u32, HeirError> {
+    u32::try_from(blocks)
+        .ok()
+        .filter(|&b| b <= MAX_CSV_BLOCKS)
+        .ok_or_else(|| {
+            HeirError::new(
+                ErrorKind::InvalidBackup,
+                format!(
+                    "Timelock of {} blocks exceeds the consensus maximum of {} blocks",
+                    blocks, MAX_CSV_BLOCKS
+                ),
+            )
+        })
+}
+
+/// Find an out-of-range `timelock_blocks` in raw backup JSON, vault-level or
+/// in a recovery leaf, so it is reported as such rather than as a generic
+/// parse failure.
+pub(crate) fn check_backup_timelocks(json: &str) -> Result<(), HeirError> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(json) else {
+        return Ok(());
+    };
+    let leaves = value
+        .get("recovery_leaves")
+        .and_then(serde_json::Value::as_array)
+        .into_iter()
+        .flatten();
+    for entry in std::iter::once(&value).chain(leaves) {
+        if let Some(blocks) = entry.get("timelock_blocks").and_then(serde_json::Value::as_u64) {
+            check_csv_blocks(blocks)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Network::{Mainnet, Signet};
+
+    #[test]
+    fn test_timelock_to_duration() {
+        let d = timelock_to_duration(144, Mainnet).unwrap();
+        assert_eq!(d.seconds, 86_400);
+        assert!((d.days - 1.0).abs() < 1e-9);
+        assert!((blocks_to_days(-144, Network::Testnet) + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_duration_to_blocks_rounds_up() {
+        assert_eq!(duration_to_blocks(1.0, Mainnet).unwrap(), 144);
+        assert_eq!(duration_to_blocks(1.001, Mainnet).unwrap(), 145);
+        assert_eq!(duration_to_blocks(182.5, Signet).unwrap(), 26280);
+    }
+
+    #[test]
+    fn test_duration_to_blocks_rejects_bad_input() {
+        let err = duration_to_blocks(-1.0, Mainnet).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+        assert!(duration_to_blocks(f64::NAN, Mainnet).is_err());
+        assert!(duration_to_blocks(1e12, Mainnet).is_err());
+        // 500 days needs 72000 blocks, beyond what CSV can encode
+        assert!(duration_to_blocks(500.0, Mainnet).is_err());
+        assert_eq!(duration_to_blocks(455.0, Mainnet).unwrap(), 65520);
+    }
+
+    #[test]
+    fn test_check_csv_blocks() {
+        assert_eq!(check_csv_blocks(26280).unwrap(), 26280);
+        assert_eq!(check_csv_blocks(65535).unwrap(), MAX_CSV_BLOCKS);
+        let err = check_csv_blocks(70_000).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidBackup);
+        assert!(err.message.contains("65535"));
+        assert!(check_csv_blocks(u64::MAX).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! Timelock tranches: heirs whose rights grow over time.
+//!
+//! An heir may appear in several recovery leaves with increasing timelocks,
+//! each recording in `allowed_percent` how much of the vault the owner meant
+//! that leaf to release (e.g. 50% after six months, 100% after a year).
+//! Script cannot cap an amount, so the builder enforces the share: the claim
+//! pays the allowed part to the heir and returns the rest to the vault
+//! address, where its timelock starts again.
+
+use bitcoin::ScriptBuf;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::policy::{analyze_leaf_script, heir_labels_for_keys};
+use super::{
+    build_split_claim_psbt, compute_eligibility, parse_backup, parse_network, timelock,
+    verified_vault_address, Backend, ClaimOptions, ClaimPsbt, ErrorKind, HeirError,
+};
+
+const FIELD: &str = "allowed_percent";
+
+/// One recovery leaf in the order it opens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tranche {
+    /// Recovery leaf to pass as `heir_index` when building the claim.
+    pub leaf_index: usize,
+    pub heir_labels: Vec<String>,
+    pub after_blocks: u32,
+    pub after_days: f64,
+    /// Share of the vault this leaf may release.
+    pub allowed_percent: f64,
+    pub eligible: bool,
+    pub blocks_remaining: i64,
+}
+
+fn invalid(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidBackup, message)
+}
+
+/// Each recovery leaf's allowed share, in backup order, or `None` if no
+/// leaf records one. Leaves without the field release everything.
+pub(crate) fn leaf_allowances(vault_json: &str) -> Result<Option<Vec<f64>>, HeirError> {
+    let value: Value =
+        serde_json::from_str(vault_json).map_err(|_| invalid("Backup is not valid JSON"))?;
+    let leaves = match value.get("recovery_leaves").and_then(Value::as_array) {
+        Some(leaves) => leaves,
+        None => return Ok(None),
+    };
+    if leaves
+        .iter()
+        .all(|leaf| leaf.get(FIELD).is_none_or(Value::is_null))
+    {
+        return Ok(None);
+    }
+    leaves
+        .iter()
+        .map(|leaf| match leaf.get(FIELD) {
+            None | Some(Value::Null) => Ok(100.0),
+            Some(v) => v
+                .as_f64()
+                .filter(|pct| pct.is_finite() && *pct > 0.0 && *pct <= 100.0)
+                .ok_or_else(|| invalid(format!("Invalid {}: {}", FIELD, v))),
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// Copy validated allowances into a re-serialized backup.
+pub(crate) fn add_allowances(backup: &mut Value, allowances: &[f64]) {
+    if let Some(leaves) = backup
+        .get_mut("recovery_leaves")
+        .and_then(Value::as_array_mut)
+    {
+        for (leaf, pct) in leaves.iter_mut().zip(allowances) {
+            leaf[FIELD] = serde_json::json!(pct);
+        }
+    }
+}
+
+/// Every recovery leaf with its delay, share and eligibility for an output
+/// confirmed at `confirmation_height`, earliest first.
+pub fn tranche_schedule(
+    vault_json: String,
+    current_height: u64,
+    confirmation_height: u64,
+) -> Result<Vec<Tranche>, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let network = parse_network(&backup.network)?;
+    let allowances = leaf_allowances(&vault_json)?;
+
+    let mut tranches = Vec::with_capacity(backup.recovery_leaves.len());
+    for (leaf_index, leaf) in backup.recovery_leaves.iter().enumerate() {
+        let script = ScriptBuf::from_hex(&leaf.script_hex)
+            .map_err(|e| invalid(format!("Invalid recovery leaf script: {}", e)))?;
+        let analysis = analyze_leaf_script(&script);
+        let after_blocks = analysis
+            .csv_blocks
+            .unwrap_or_else(|| u32::from(leaf.timelock_blocks));
+        let eligibility =
+            compute_eligibility(after_blocks, current_height, confirmation_height, network);
+        tranches.push(Tranche {
+            leaf_index,
+            heir_labels: heir_labels_for_keys(&backup, &analysis.keys),
+            after_blocks,
+            after_days: timelock::blocks_to_days(i64::from(after_blocks), network),
+            allowed_percent: allowances.as_ref().map_or(100.0, |a| a[leaf_index]),
+            eligible: eligibility.eligible,
+            blocks_remaining: eligibility.blocks_remaining,
+        });
+    }
+    tranches.sort_by_key(|t| (t.after_blocks, t.leaf_index));
+    Ok(tranches)
+}
+
+/// Build a claim for `heir_label` through the largest tranche every vault
+/// output has matured for. Anything above the tranche's share goes back to
+/// the vault address.
+pub fn build_tranche_claim_psbt(
+    vault_json: String,
+    backend: &Backend,
+    destination_address: String,
+    heir_label: String,
+    fee_rate_sat_vb: u64,
+    options: ClaimOptions,
+) -> Result<ClaimPsbt, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    backend.require_network(parse_network(&backup.network)?)?;
+    if !backup.heirs.iter().any(|heir| heir.label == heir_label) {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!("No heir named {} in this vault", heir_label),
+        ));
+    }
+
+    // Every input of the claim must have matured, so the newest output counts
+    let chain = backend.chain();
+    let vault_address = verified_vault_address(&vault_json, &backup)?;
+    let current_height = chain.tip_height()?;
+    let newest = chain
+        .list_unspent(&vault_address)?
+        .iter()
+        .filter(|u| !options.frozen_outpoints.contains(&u.outpoint.to_string()))
+        .map(|u| {
+            if u.height == 0 {
+                current_height + 1
+            } else {
+                u64::from(u.height)
+            }
+        })
+        .max()
+        .ok_or_else(|| HeirError::new(ErrorKind::NoUtxos, "No UTXOs found in vault"))?;
+
+    let tranches: Vec<Tranche> = tranche_schedule(vault_json.clone(), current_height, newest)?
+        .into_iter()
+        .filter(|t| t.heir_labels.contains(&heir_label))
+        .collect();
+    let best = tranches
+        .iter()
+        .filter(|t| t.eligible)
+        .max_by(|a, b| {
+            a.allowed_percent
+                .total_cmp(&b.allowed_percent)
+                .then(b.leaf_index.cmp(&a.leaf_index))
+        })
+        .ok_or_else(|| {
+            let next = tranches.iter().map(|t| t.blocks_remaining).min();
+            HeirError::new(
+                ErrorKind::InvalidInput,
+                match next {
+                    Some(blocks) => format!(
+                        "No tranche for {} has matured yet; the first opens in {} blocks",
+                        heir_label, blocks
+                    ),
+                    None => format!("{} has no recovery leaf in this vault", heir_label),
+                },
+            )
+        })?;
+
+    let mut allocations = vec![(destination_address, best.allowed_percent)];
+    if best.allowed_percent < 100.0 {
+        allocations.push((vault_address.to_string(), 100.0 - best.allowed_percent));
+    }
+    build_split_claim_psbt(
+        vault_json,
+        backend,
+        allocations,
+        best.leaf_index,
+        fee_rate_sat_vb,
+        options,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::Network;
+
+    /// Vector backup whose only leaf releases `pct` percent.
+    fn backup_with_allowance(seed: u32, pct: f64) -> String {
+        let v = generate_test_vectors(seed).unwrap();
+        let mut value: Value = serde_json::from_str(&v.backup_json).unwrap();
+        value["recovery_leaves"][0][FIELD] = serde_json::json!(pct);
+        value.to_string()
+    }
+
+    #[test]
+    fn test_schedule_orders_tranches() {
+        let v = generate_test_vectors(20).unwrap();
+        let mut value: Value = serde_json::from_str(&v.backup_json).unwrap();
+        // Same heir, a second leaf opening at twice the delay
+        let mut later = value["recovery_leaves"][0].clone();
+        let script = later["script_hex"]
+            .as_str()
+            .unwrap()
+            .replace("029000b2", "022001b2");
+        later["script_hex"] = serde_json::json!(script);
+        value["recovery_leaves"][0][FIELD] = serde_json::json!(50.0);
+        value["recovery_leaves"]
+            .as_array_mut()
+            .unwrap()
+            .insert(0, later);
+
+        let schedule = tranche_schedule(value.to_string(), 1_200, 1_000).unwrap();
+        assert_eq!(schedule.len(), 2);
+        assert_eq!(schedule[0].after_blocks, 144);
+        assert_eq!(schedule[0].leaf_index, 1);
+        assert_eq!(schedule[0].allowed_percent, 50.0);
+        assert!(schedule[0].eligible);
+        assert_eq!(schedule[1].after_blocks, 288);
+        assert_eq!(schedule[1].allowed_percent, 100.0);
+        assert_eq!(schedule[1].blocks_remaining, 88);
+        assert_eq!(schedule[1].heir_labels, vec!["Synthetic Heir"]);
+    }
+
+    #[test]
+    fn test_tranche_claim_returns_remainder_to_vault() {
+        let v = generate_test_vectors(21).unwrap();
+        let json = backup_with_allowance(21, 50.0);
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(1_000);
+        sim.add_utxo(v.vault_address.clone(), "b3".repeat(32), 0, 80_000, 900)
+            .unwrap();
+        let backend = Backend::simulated(&sim);
+
+        let early = build_tranche_claim_psbt(
+            json.clone(),
+            &backend,
+            v.destination.clone(),
+            "Synthetic Heir".into(),
+            2,
+            ClaimOptions::default(),
+        )
+        .unwrap_err();
+        assert!(
+            early.message.contains("opens in 44 blocks"),
+            "{}",
+            early.message
+        );
+
+        sim.mine_blocks(44);
+        let claim = build_tranche_claim_psbt(
+            json,
+            &backend,
+            v.destination.clone(),
+            "Synthetic Heir".into(),
+            2,
+            ClaimOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(claim.outputs.len(), 2);
+        assert_eq!(claim.outputs[0].address, v.destination);
+        assert_eq!(claim.outputs[1].address, v.vault_address);
+        assert_eq!(claim.outputs[0].amount_sat, claim.output_sat / 2);
+    }
+
+    #[test]
+    fn test_invalid_allowance() {
+        assert_eq!(
+            leaf_allowances(&backup_with_allowance(22, 0.0))
+                .unwrap_err()
+                .kind,
+            ErrorKind::InvalidBackup
+        );
+        let v = generate_test_vectors(22).unwrap();
+        assert_eq!(leaf_allowances(&v.backup_json).unwrap(), None);
+    }
+}
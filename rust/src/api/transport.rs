@@ -0,0 +1,252 @@
+//! Electrum over a byte stream provided by the host app.
+//!
+//! Some embedding environments cannot open raw sockets from Rust (OEM
+//! Android builds with restrictive network policies, sandboxed desktop
+//! apps). The app implements connect/send/receive/close over its own socket
+//! API and the Electrum client speaks JSON-RPC over them. The host owns TLS:
+//! it gets the full `ssl://` or `tcp://` URL and exchanges plaintext bytes.
+//!
+//! Only compiled with the `electrum` feature (on by default).
+
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use bitcoin::Network;
+use flutter_rust_bridge::DartFnFuture;
+
+use super::electrum::{Connector, ElectrumBackend};
+use super::{ErrorKind, HeirError};
+use crate::trace::span;
+
+/// Largest chunk requested from the host per `receive`.
+const RECEIVE_CHUNK: usize = 16 * 1024;
+
+/// A byte stream the Electrum client can run over.
+pub(crate) trait Transport: Send {
+    /// Open a connection to `url`, closing any previous one.
+    fn connect(&mut self, url: &str) -> io::Result<()>;
+    fn send(&mut self, data: &[u8]) -> io::Result<()>;
+    /// Up to `max_len` bytes; empty once the server has closed the connection.
+    fn receive(&mut self, max_len: usize) -> io::Result<Vec<u8>>;
+    fn close(&mut self);
+}
+
+type SharedTransport = Arc<Mutex<dyn Transport>>;
+
+fn lock(transport: &SharedTransport) -> io::Result<std::sync::MutexGuard<'_, dyn Transport>> {
+    transport
+        .lock()
+        .map_err(|_| io::Error::other("transport lock poisoned"))
+}
+
+/// One connection over a [`Transport`]; closes it when dropped.
+pub(crate) struct TransportStream {
+    transport: SharedTransport,
+    buffer: Vec<u8>,
+    offset: usize,
+}
+
+impl Read for TransportStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset == self.buffer.len() {
+            self.buffer = lock(&self.transport)?.receive(buf.len().clamp(1, RECEIVE_CHUNK))?;
+            self.offset = 0;
+        }
+        let n = buf.len().min(self.buffer.len() - self.offset);
+        buf[..n].copy_from_slice(&self.buffer[self.offset..self.offset + n]);
+        self.offset += n;
+        Ok(n)
+    }
+}
+
+impl Write for TransportStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        lock(&self.transport)?.send(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for TransportStream {
+    fn drop(&mut self) {
+        if let Ok(mut transport) = self.transport.lock() {
+            transport.close();
+        }
+    }
+}
+
+/// Connects through a [`Transport`] instead of Rust sockets.
+pub(crate) struct TransportConnector {
+    transport: SharedTransport,
+}
+
+impl TransportConnector {
+    pub(crate) fn new(transport: impl Transport + 'static) -> Self {
+        Self {
+            transport: Arc::new(Mutex::new(transport)),
+        }
+    }
+}
+
+impl Connector for TransportConnector {
+    type Client = electrum_client::RawClient<TransportStream>;
+
+    fn connect(&self, url: &str) -> Result<Self::Client, HeirError> {
+        span!("electrum.connect");
+        lock(&self.transport)
+            .and_then(|mut transport| transport.connect(url))
+            .map_err(|e| {
+                HeirError::new(
+                    ErrorKind::Connection,
+                    format!("Electrum connection failed: {}", e),
+                )
+            })?;
+        Ok(TransportStream {
+            transport: self.transport.clone(),
+            buffer: Vec::new(),
+            offset: 0,
+        }
+        .into())
+    }
+}
+
+type Callback<A, R> = Box<dyn Fn(A) -> DartFnFuture<R> + Send + Sync>;
+
+/// Socket callbacks implemented by the app. `connect` and `send` resolve to
+/// an error message, or null on success; `receive` resolves to the bytes
+/// read, empty once the connection is closed.
+pub struct HostTransport {
+    connect: Callback<String, Option<String>>,
+    send: Callback<Vec<u8>, Option<String>>,
+    receive: Callback<u32, Vec<u8>>,
+    close: Callback<(), ()>,
+}
+
+/// Bundle the app's socket callbacks for [`super::Backend::electrum_over_transport`].
+pub fn create_host_transport(
+    connect: impl Fn(String) -> DartFnFuture<Option<String>> + Send + Sync + 'static,
+    send: impl Fn(Vec<u8>) -> DartFnFuture<Option<String>> + Send + Sync + 'static,
+    receive: impl Fn(u32) -> DartFnFuture<Vec<u8>> + Send + Sync + 'static,
+    close: impl Fn() -> DartFnFuture<()> + Send + Sync + 'static,
+) -> HostTransport {
+    HostTransport {
+        connect: Box::new(connect),
+        send: Box::new(send),
+        receive: Box::new(receive),
+        close: Box::new(move |()| close()),
+    }
+}
+
+fn host_result(error: Option<String>) -> io::Result<()> {
+    error.map_or(Ok(()), |message| Err(io::Error::other(message)))
+}
+
+// Backend calls run on a worker thread, so waiting on the Dart side is fine
+impl Transport for HostTransport {
+    fn connect(&mut self, url: &str) -> io::Result<()> {
+        host_result(futures::executor::block_on((self.connect)(url.to_string())))
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        host_result(futures::executor::block_on((self.send)(data.to_vec())))
+    }
+
+    fn receive(&mut self, max_len: usize) -> io::Result<Vec<u8>> {
+        let max_len = u32::try_from(max_len).unwrap_or(u32::MAX);
+        Ok(futures::executor::block_on((self.receive)(max_len)))
+    }
+
+    fn close(&mut self) {
+        futures::executor::block_on((self.close)(()));
+    }
+}
+
+/// Electrum backend running over `transport`.
+pub(crate) fn electrum_backend(
+    url: String,
+    network: Network,
+    transport: HostTransport,
+) -> ElectrumBackend<TransportConnector> {
+    ElectrumBackend::with_connector(TransportConnector::new(transport), url, network)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out canned chunks and records what was sent.
+    #[derive(Default)]
+    struct Loopback {
+        incoming: Vec<Vec<u8>>,
+        sent: Arc<Mutex<Vec<u8>>>,
+        closed: Arc<Mutex<bool>>,
+    }
+
+    impl Transport for Loopback {
+        fn connect(&mut self, url: &str) -> io::Result<()> {
+            if url.starts_with("tcp://") {
+                Ok(())
+            } else {
+                Err(io::Error::other("unsupported scheme"))
+            }
+        }
+
+        fn send(&mut self, data: &[u8]) -> io::Result<()> {
+            self.sent.lock().unwrap().extend_from_slice(data);
+            Ok(())
+        }
+
+        fn receive(&mut self, _max_len: usize) -> io::Result<Vec<u8>> {
+            Ok(if self.incoming.is_empty() {
+                Vec::new()
+            } else {
+                self.incoming.remove(0)
+            })
+        }
+
+        fn close(&mut self) {
+            *self.closed.lock().unwrap() = true;
+        }
+    }
+
+    fn stream(transport: Loopback) -> TransportStream {
+        TransportStream {
+            transport: Arc::new(Mutex::new(transport)),
+            buffer: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_stream_reads_across_chunks() {
+        let transport = Loopback {
+            incoming: vec![b"hel".to_vec(), b"lo\n".to_vec()],
+            ..Default::default()
+        };
+        let sent = transport.sent.clone();
+        let closed = transport.closed.clone();
+
+        let mut stream = stream(transport);
+        stream.write_all(b"ping\n").unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "hello\n");
+        assert_eq!(sent.lock().unwrap().as_slice(), b"ping\n");
+
+        drop(stream);
+        assert!(*closed.lock().unwrap());
+    }
+
+    #[test]
+    fn test_connect_failure_is_connection_error() {
+        let connector = TransportConnector::new(Loopback::default());
+        let Err(err) = connector.connect("ssl://example.com:50002") else {
+            panic!("unsupported scheme accepted");
+        };
+        assert_eq!(err.kind, ErrorKind::Connection);
+        assert!(connector.connect("tcp://example.com:50001").is_ok());
+    }
+}
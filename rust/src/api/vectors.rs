@@ -0,0 +1,375 @@
+//! Deterministic golden data for binding and integration tests.
+//!
+//! [`generate_test_vectors`] builds a synthetic testnet vault from a seed and
+//! walks it through the whole claim flow, so the app and third-party tools
+//! can check their handling against artifacts produced by this crate. The
+//! vault construction is shared with the signet demo.
+
+use std::str::FromStr;
+
+use base64::Engine;
+use bitcoin::bip32::{ChildNumber, Fingerprint, Xpub};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{Keypair, Message, PublicKey, Secp256k1, SecretKey};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::{Address, Amount, Network, NetworkKind, OutPoint, TxOut, Txid, Witness};
+use miniscript::DescriptorPublicKey;
+use serde::{Deserialize, Serialize};
+
+use nostring_ccd::register_cosigner_with_chain_code;
+use nostring_ccd::types::ChainCode;
+use nostring_inherit::backup::{extract_recovery_leaves, HeirBackupEntry, VaultBackup};
+use nostring_inherit::policy::{PathInfo, Timelock};
+use nostring_inherit::taproot::{build_heir_claim_psbt, create_inheritable_vault};
+
+use super::psbt::annotate_claim_psbt;
+use super::{
+    finalize_psbt, import_vault_backup, network_name, reconstruction_error, ErrorKind, HeirError,
+};
+
+/// Timelock used by every generated vault.
+const VECTOR_TIMELOCK_BLOCKS: u16 = 144;
+/// Value of the single mock UTXO funding the vault.
+const VECTOR_UTXO_SAT: u64 = 100_000;
+/// Absolute fee of the claim transaction.
+const VECTOR_FEE_SAT: u64 = 300;
+
+/// Golden artifacts for one synthetic vault.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestVectors {
+    pub seed: u32,
+    pub network: String,
+    pub backup_json: String,
+    pub vault_address: String,
+    /// Heir secret key (hex). Synthetic, for reproducing the signature only.
+    pub heir_secret_key_hex: String,
+    pub funding_outpoint: String,
+    pub funding_value_sat: u64,
+    pub destination: String,
+    pub fee_sat: u64,
+    pub unsigned_psbt_base64: String,
+    pub signed_psbt_base64: String,
+    pub tx_hex: String,
+    pub txid: String,
+}
+
+/// One field whose supplied value differs from the crate's computation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldMismatch {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Outcome of [`verify_against_vector`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorReport {
+    pub matches: bool,
+    pub mismatches: Vec<FieldMismatch>,
+}
+
+fn vector_error(e: impl std::fmt::Display) -> HeirError {
+    HeirError::new(ErrorKind::Internal, format!("Synthetic vault construction failed: {}", e))
+}
+
+/// 32 bytes derived from the seed, domain-separated by `tag`.
+fn seeded_bytes(seed: u32, tag: &str) -> [u8; 32] {
+    let mut preimage = b"nostring-heir/test-vector/".to_vec();
+    preimage.extend_from_slice(&seed.to_be_bytes());
+    preimage.extend_from_slice(tag.as_bytes());
+    sha256::Hash::hash(&preimage).to_byte_array()
+}
+
+/// Derive a secret key for `role` from the seed.
+fn seeded_key(seed: u32, role: &str) -> Result<SecretKey, HeirError> {
+    SecretKey::from_slice(&seeded_bytes(seed, role)).map_err(vector_error)
+}
+
+/// Public keys and chain code of a vault built for tests or demos.
+pub(crate) struct SyntheticKeys {
+    pub owner: PublicKey,
+    pub cosigner: PublicKey,
+    pub heir: PublicKey,
+    pub chain_code: [u8; 32],
+}
+
+/// Depth-0 xpub wrapping a bare public key.
+pub(crate) fn bare_xpub(public_key: PublicKey, network: Network) -> Result<Xpub, HeirError> {
+    Ok(Xpub {
+        network: NetworkKind::from(network),
+        depth: 0,
+        parent_fingerprint: Fingerprint::default(),
+        child_number: ChildNumber::from_normal_idx(0).map_err(vector_error)?,
+        public_key,
+        chain_code: bitcoin::bip32::ChainCode::from([0u8; 32]),
+    })
+}
+
+/// Build a single-heir vault backup from bare keys.
+pub(crate) fn synthetic_backup(
+    keys: &SyntheticKeys,
+    timelock_blocks: u16,
+    network: Network,
+) -> Result<VaultBackup, HeirError> {
+    let delegated = register_cosigner_with_chain_code(
+        keys.cosigner,
+        ChainCode(keys.chain_code),
+        "synthetic",
+    );
+
+    let heir_xonly = keys.heir.x_only_public_key().0;
+    let heir_desc = DescriptorPublicKey::from_str(&heir_xonly.to_string()).map_err(vector_error)?;
+    let timelock = Timelock::from_blocks(timelock_blocks).map_err(vector_error)?;
+
+    let vault = create_inheritable_vault(
+        &keys.owner,
+        &delegated,
+        0,
+        PathInfo::Single(heir_desc),
+        timelock,
+        0,
+        network,
+    )
+    .map_err(vector_error)?;
+
+    Ok(VaultBackup {
+        version: 1,
+        network: network_name(network).into(),
+        owner_pubkey: hex::encode(keys.owner.serialize()),
+        cosigner_pubkey: hex::encode(keys.cosigner.serialize()),
+        chain_code: hex::encode(keys.chain_code),
+        address_index: 0,
+        timelock_blocks,
+        threshold: 1,
+        heirs: vec![HeirBackupEntry {
+            label: "Synthetic Heir".into(),
+            xpub: bare_xpub(keys.heir, network)?.to_string(),
+            fingerprint: "00000000".into(),
+            derivation_path: "m/86'/1'/0'".into(),
+            recovery_index: 0,
+            npub: None,
+        }],
+        vault_address: vault.address.to_string(),
+        taproot_internal_key: Some(hex::encode(vault.aggregate_xonly.serialize())),
+        recovery_leaves: extract_recovery_leaves(&vault),
+        created_at: None,
+    })
+}
+
+/// Produce deterministic backup, PSBT, and transaction artifacts for `seed`.
+///
+/// The same seed always yields byte-identical output.
+pub fn generate_test_vectors(seed: u32) -> Result<TestVectors, HeirError> {
+    let secp = Secp256k1::new();
+    let network = Network::Testnet;
+
+    let heir_sk = seeded_key(seed, "heir")?;
+    let heir_keypair = Keypair::from_secret_key(&secp, &heir_sk);
+    let keys = SyntheticKeys {
+        owner: seeded_key(seed, "owner")?.public_key(&secp),
+        cosigner: seeded_key(seed, "cosigner")?.public_key(&secp),
+        heir: heir_keypair.public_key(),
+        chain_code: seeded_bytes(seed, "chain-code"),
+    };
+    let dest_pk = seeded_key(seed, "destination")?.public_key(&secp);
+
+    let backup = synthetic_backup(&keys, VECTOR_TIMELOCK_BLOCKS, network)?;
+    let backup_json = serde_json::to_string(&backup).map_err(vector_error)?;
+    let vault = backup.reconstruct().map_err(reconstruction_error)?;
+
+    // Mock funding output, txid derived from the seed
+    let funding_txid = Txid::from_byte_array(seeded_bytes(seed, "funding"));
+    let funding_outpoint = OutPoint::new(funding_txid, 0);
+    let funding_txout = TxOut {
+        value: Amount::from_sat(VECTOR_UTXO_SAT),
+        script_pubkey: vault.address.script_pubkey(),
+    };
+
+    let destination = Address::p2wpkh(&bitcoin::CompressedPublicKey(dest_pk), network);
+
+    let mut psbt = build_heir_claim_psbt(
+        &vault,
+        0,
+        &[(funding_outpoint, funding_txout.clone())],
+        &destination,
+        Amount::from_sat(VECTOR_FEE_SAT),
+    )
+    .map_err(vector_error)?;
+    let recovery_scripts: Vec<_> = vault.recovery_scripts.iter().map(|(_, s)| s.clone()).collect();
+    annotate_claim_psbt(&mut psbt, &backup, &vault.taproot_spend_info, &recovery_scripts)?;
+    let unsigned_psbt_base64 =
+        base64::engine::general_purpose::STANDARD.encode(psbt.serialize());
+
+    // Script-path signature, no aux randomness so the output is reproducible
+    let (_timelock, recovery_script) = &vault.recovery_scripts[0];
+    let leaf_hash = TapLeafHash::from_script(recovery_script, LeafVersion::TapScript);
+    let sighash = SighashCache::new(&psbt.unsigned_tx)
+        .taproot_script_spend_signature_hash(
+            0,
+            &Prevouts::All(&[funding_txout]),
+            leaf_hash,
+            TapSighashType::Default,
+        )
+        .map_err(vector_error)?;
+    let msg = Message::from_digest(sighash.to_byte_array());
+    let signature = bitcoin::taproot::Signature {
+        signature: secp.sign_schnorr_no_aux_rand(&msg, &heir_keypair),
+        sighash_type: TapSighashType::Default,
+    };
+
+    let control_block = vault
+        .taproot_spend_info
+        .control_block(&(recovery_script.clone(), LeafVersion::TapScript))
+        .ok_or_else(|| vector_error("missing control block for recovery leaf"))?;
+
+    let mut witness = Witness::new();
+    witness.push(signature.serialize());
+    witness.push(recovery_script.as_bytes());
+    witness.push(control_block.serialize());
+
+    let mut signed = psbt;
+    signed.inputs[0].final_script_witness = Some(witness);
+    let signed_psbt_base64 =
+        base64::engine::general_purpose::STANDARD.encode(signed.serialize());
+
+    let finalized = finalize_psbt(signed_psbt_base64.clone())?;
+
+    Ok(TestVectors {
+        seed,
+        network: backup.network,
+        backup_json,
+        vault_address: vault.address.to_string(),
+        heir_secret_key_hex: hex::encode(heir_sk.secret_bytes()),
+        funding_outpoint: funding_outpoint.to_string(),
+        funding_value_sat: VECTOR_UTXO_SAT,
+        destination: destination.to_string(),
+        fee_sat: VECTOR_FEE_SAT,
+        unsigned_psbt_base64,
+        signed_psbt_base64,
+        tx_hex: finalized.tx_hex,
+        txid: finalized.txid,
+    })
+}
+
+/// Check a supplied vector (a serialized [`TestVectors`]) against this crate.
+///
+/// Every field is compared with a fresh [`generate_test_vectors`] run for the
+/// same seed. Independently, the supplied backup must reproduce the supplied
+/// address, and the supplied signed PSBT must finalize to the supplied
+/// transaction. Used in the app's CI to catch binding drift.
+pub fn verify_against_vector(vector_json: String) -> Result<VectorReport, HeirError> {
+    let supplied: TestVectors = serde_json::from_str(&vector_json).map_err(|e| {
+        HeirError::new(ErrorKind::InvalidInput, format!("Invalid test vector JSON: {}", e))
+    })?;
+    let expected = generate_test_vectors(supplied.seed)?;
+
+    let mut mismatches = Vec::new();
+    let mut check = |field: &str, expected: &str, actual: &str| {
+        if expected != actual {
+            mismatches.push(FieldMismatch {
+                field: field.to_string(),
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            });
+        }
+    };
+
+    check("network", &expected.network, &supplied.network);
+    check("vault_address", &expected.vault_address, &supplied.vault_address);
+    check("heir_secret_key_hex", &expected.heir_secret_key_hex, &supplied.heir_secret_key_hex);
+    check("funding_outpoint", &expected.funding_outpoint, &supplied.funding_outpoint);
+    check(
+        "funding_value_sat",
+        &expected.funding_value_sat.to_string(),
+        &supplied.funding_value_sat.to_string(),
+    );
+    check("destination", &expected.destination, &supplied.destination);
+    check("fee_sat", &expected.fee_sat.to_string(), &supplied.fee_sat.to_string());
+    check("unsigned_psbt_base64", &expected.unsigned_psbt_base64, &supplied.unsigned_psbt_base64);
+    check("signed_psbt_base64", &expected.signed_psbt_base64, &supplied.signed_psbt_base64);
+    check("tx_hex", &expected.tx_hex, &supplied.tx_hex);
+    check("txid", &expected.txid, &supplied.txid);
+
+    // Key order may differ between serializers, so compare backups structurally
+    let expected_backup: serde_json::Value =
+        serde_json::from_str(&expected.backup_json).map_err(vector_error)?;
+    match serde_json::from_str::<serde_json::Value>(&supplied.backup_json) {
+        Ok(actual) if actual == expected_backup => {}
+        Ok(actual) => check("backup_json", &expected_backup.to_string(), &actual.to_string()),
+        Err(e) => check("backup_json", &expected_backup.to_string(), &e.to_string()),
+    }
+
+    // Address derivation from the supplied backup alone
+    match import_vault_backup(supplied.backup_json.clone()) {
+        Ok(info) => check("backup_json.derived_address", &supplied.vault_address, &info.vault_address),
+        Err(e) => check("backup_json.derived_address", &supplied.vault_address, &e.message),
+    }
+
+    // Signature and witness layout from the supplied signed PSBT alone
+    match finalize_psbt(supplied.signed_psbt_base64.clone()) {
+        Ok(tx) => check("signed_psbt_base64.finalized_tx", &supplied.tx_hex, &tx.tx_hex),
+        Err(e) => check("signed_psbt_base64.finalized_tx", &supplied.tx_hex, &e.message),
+    }
+
+    Ok(VectorReport {
+        matches: mismatches.is_empty(),
+        mismatches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::import_vault_backup;
+
+    #[test]
+    fn test_vectors_are_deterministic() {
+        let a = generate_test_vectors(7).unwrap();
+        let b = generate_test_vectors(7).unwrap();
+        assert_eq!(a.backup_json, b.backup_json);
+        assert_eq!(a.unsigned_psbt_base64, b.unsigned_psbt_base64);
+        assert_eq!(a.signed_psbt_base64, b.signed_psbt_base64);
+        assert_eq!(a.tx_hex, b.tx_hex);
+    }
+
+    #[test]
+    fn test_vectors_differ_by_seed() {
+        let a = generate_test_vectors(1).unwrap();
+        let b = generate_test_vectors(2).unwrap();
+        assert_ne!(a.vault_address, b.vault_address);
+        assert_ne!(a.txid, b.txid);
+    }
+
+    #[test]
+    fn test_verify_own_vector_matches() {
+        let v = generate_test_vectors(11).unwrap();
+        let report = verify_against_vector(serde_json::to_string(&v).unwrap()).unwrap();
+        assert!(report.matches, "Unexpected mismatches: {:?}", report.mismatches);
+    }
+
+    #[test]
+    fn test_verify_reports_mismatching_fields() {
+        let mut v = generate_test_vectors(11).unwrap();
+        v.txid = "00".repeat(32);
+        v.fee_sat += 1;
+        let report = verify_against_vector(serde_json::to_string(&v).unwrap()).unwrap();
+        assert!(!report.matches);
+        let fields: Vec<&str> = report.mismatches.iter().map(|m| m.field.as_str()).collect();
+        assert_eq!(fields, vec!["fee_sat", "txid"]);
+    }
+
+    #[test]
+    fn test_verify_rejects_malformed_vector() {
+        let err = verify_against_vector("{}".into()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_vector_backup_imports() {
+        let v = generate_test_vectors(3).unwrap();
+        let info = import_vault_backup(v.backup_json).unwrap();
+        assert_eq!(info.vault_address, v.vault_address);
+        assert_eq!(info.timelock_blocks, u32::from(VECTOR_TIMELOCK_BLOCKS));
+    }
+}
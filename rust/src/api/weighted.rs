@@ -0,0 +1,567 @@
+//! Weighted heir thresholds.
+//!
+//! A backup may give each heir a `weight` and set a `weight_threshold`: any
+//! group of heirs whose weights add up to the threshold can claim (e.g. a
+//! spouse counting 2 and each child 1, threshold 3). Tapscript has no
+//! weighted signature count, so the policy is compiled to one recovery leaf
+//! per minimal qualifying group, each needing every member's signature.
+//! Every leaf is an ordinary `multi_a` + CSV script, so policy analysis,
+//! PSBT key origins and the miniscript finalizer handle it unchanged.
+//!
+//! The upstream backup model knows neither field, so they are read from the
+//! raw JSON as in `allocation`, and weighted vaults are rebuilt here rather
+//! than by `VaultBackup::reconstruct()`.
+
+use std::str::FromStr;
+
+use bitcoin::bip32::Xpub;
+use bitcoin::opcodes::all::{
+    OP_CHECKSIG, OP_CHECKSIGADD, OP_CHECKSIGVERIFY, OP_CSV, OP_NUMEQUALVERIFY,
+};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, XOnlyPublicKey};
+use bitcoin::taproot::{TaprootBuilder, TaprootBuilderError, TaprootSpendInfo};
+use bitcoin::{Address, ScriptBuf};
+use miniscript::DescriptorPublicKey;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use nostring_ccd::register_cosigner_with_chain_code;
+use nostring_ccd::types::ChainCode;
+use nostring_inherit::backup::VaultBackup;
+use nostring_inherit::policy::{PathInfo, Timelock};
+use nostring_inherit::taproot::create_inheritable_vault;
+
+use super::{parse_backup, parse_network, reconstruction_error, ErrorKind, HeirError};
+use crate::trace::span;
+
+const WEIGHT_FIELD: &str = "weight";
+const THRESHOLD_FIELD: &str = "weight_threshold";
+
+/// Qualifying groups grow exponentially with the number of heirs.
+pub const MAX_WEIGHTED_HEIRS: usize = 10;
+/// Largest weight one heir can carry.
+pub const MAX_HEIR_WEIGHT: u32 = 1_000;
+
+/// One heir's weight.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeirWeight {
+    pub label: String,
+    pub weight: u32,
+}
+
+/// Weights and threshold recorded in a backup.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightedPolicy {
+    /// In backup order.
+    pub heirs: Vec<HeirWeight>,
+    /// Combined weight a group needs to claim.
+    pub threshold: u32,
+    pub total_weight: u32,
+}
+
+/// A group of heirs that can claim together through one recovery leaf.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightedClaimPath {
+    /// Recovery leaf to pass as `heir_index` when building the claim.
+    pub heir_index: usize,
+    pub heir_labels: Vec<String>,
+    pub combined_weight: u32,
+}
+
+/// Weights in backup order and the threshold, as read from the raw JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Weights {
+    pub weights: Vec<u32>,
+    pub threshold: u32,
+}
+
+/// A weighted vault rebuilt from its backup.
+pub(crate) struct WeightedVault {
+    pub address: Address,
+    pub spend_info: TaprootSpendInfo,
+    /// Heir indices of each leaf's group, in leaf order.
+    pub groups: Vec<Vec<usize>>,
+    pub recovery_scripts: Vec<ScriptBuf>,
+}
+
+fn invalid(message: impl Into<String>) -> HeirError {
+    HeirError::new(ErrorKind::InvalidBackup, message)
+}
+
+fn read_weight(value: &Value, field: &str) -> Result<Option<u32>, HeirError> {
+    match value.get(field) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v
+            .as_u64()
+            .filter(|w| (1..=u64::from(MAX_HEIR_WEIGHT)).contains(w))
+            .map(|w| Some(w as u32))
+            .ok_or_else(|| invalid(format!("Invalid {}: {}", field, v))),
+    }
+}
+
+/// Heir weights and threshold, or `None` if the backup has none. Weights
+/// must be given for every heir or for none, together with a threshold the
+/// heirs can reach.
+pub(crate) fn heir_weights(vault_json: &str) -> Result<Option<Weights>, HeirError> {
+    let value: Value =
+        serde_json::from_str(vault_json).map_err(|_| invalid("Backup is not valid JSON"))?;
+    let heirs = value
+        .get("heirs")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("Backup has no heirs"))?;
+
+    let weights: Vec<Option<u32>> = heirs
+        .iter()
+        .map(|heir| read_weight(heir, WEIGHT_FIELD))
+        .collect::<Result<_, _>>()?;
+    let threshold = match read_weight(&value, THRESHOLD_FIELD)? {
+        None if weights.iter().all(Option::is_none) => return Ok(None),
+        None => return Err(invalid("Heir weights need a weight_threshold")),
+        Some(t) => t,
+    };
+    let weights: Vec<u32> = weights
+        .into_iter()
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid("Weights must be given for every heir"))?;
+    if weights.len() > MAX_WEIGHTED_HEIRS {
+        return Err(invalid(format!(
+            "Weighted thresholds support at most {} heirs",
+            MAX_WEIGHTED_HEIRS
+        )));
+    }
+    let total: u32 = weights.iter().sum();
+    if total < threshold {
+        return Err(invalid(format!(
+            "Heir weights add up to {}, below the threshold of {}",
+            total, threshold
+        )));
+    }
+    Ok(Some(Weights { weights, threshold }))
+}
+
+/// Copy validated weights into a re-serialized backup.
+pub(crate) fn add_weights(backup: &mut Value, weights: &Weights) {
+    if let Some(heirs) = backup.get_mut("heirs").and_then(Value::as_array_mut) {
+        for (heir, weight) in heirs.iter_mut().zip(&weights.weights) {
+            heir[WEIGHT_FIELD] = serde_json::json!(weight);
+        }
+    }
+    backup[THRESHOLD_FIELD] = serde_json::json!(weights.threshold);
+}
+
+/// Every group of heirs that reaches `threshold` and would fall below it
+/// without any one member, smallest groups first.
+pub(crate) fn minimal_signer_sets(weights: &[u32], threshold: u32) -> Vec<Vec<usize>> {
+    let n = weights.len();
+    let mut sets: Vec<Vec<usize>> = (1u32..1 << n)
+        .filter_map(|mask| {
+            let members: Vec<usize> = (0..n).filter(|i| mask & (1 << i) != 0).collect();
+            let total: u32 = members.iter().map(|&i| weights[i]).sum();
+            let minimal = members.iter().all(|&i| total - weights[i] < threshold);
+            (total >= threshold && minimal).then_some(members)
+        })
+        .collect();
+    sets.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    sets
+}
+
+/// Leaf requiring every key in `keys` after `csv_blocks`:
+/// `and_v(v:multi_a(n, keys), older(csv_blocks))`, or `and_v(v:pk, ...)`
+/// for a single key.
+pub(crate) fn weighted_leaf_script(keys: &[XOnlyPublicKey], csv_blocks: u16) -> ScriptBuf {
+    let builder = match keys {
+        [key] => Builder::new()
+            .push_x_only_key(key)
+            .push_opcode(OP_CHECKSIGVERIFY),
+        _ => keys
+            .iter()
+            .enumerate()
+            .fold(Builder::new(), |builder, (i, key)| {
+                let op = if i == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD };
+                builder.push_x_only_key(key).push_opcode(op)
+            })
+            .push_int(keys.len() as i64)
+            .push_opcode(OP_NUMEQUALVERIFY),
+    };
+    builder
+        .push_int(i64::from(csv_blocks))
+        .push_opcode(OP_CSV)
+        .into_script()
+}
+
+/// Add `scripts` as a balanced subtree, left to right.
+fn add_subtree(
+    builder: TaprootBuilder,
+    scripts: &[ScriptBuf],
+    depth: u8,
+) -> Result<TaprootBuilder, TaprootBuilderError> {
+    match scripts {
+        [script] => builder.add_leaf(depth, script.clone()),
+        _ => {
+            let (left, right) = scripts.split_at(scripts.len().div_ceil(2));
+            add_subtree(add_subtree(builder, left, depth + 1)?, right, depth + 1)
+        }
+    }
+}
+
+/// MuSig2 aggregate of the owner and co-signer keys, as upstream derives
+/// it. It does not depend on the recovery path, so a single-heir vault
+/// built from the same keys yields it.
+pub(crate) fn internal_key(backup: &VaultBackup) -> Result<XOnlyPublicKey, HeirError> {
+    let network = parse_network(&backup.network)?;
+    let owner = PublicKey::from_str(&backup.owner_pubkey)
+        .map_err(|e| invalid(format!("Invalid owner key: {}", e)))?;
+    let cosigner = PublicKey::from_str(&backup.cosigner_pubkey)
+        .map_err(|e| invalid(format!("Invalid co-signer key: {}", e)))?;
+    let chain_code: [u8; 32] = hex::decode(&backup.chain_code)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| invalid("Invalid chain code"))?;
+    let delegated = register_cosigner_with_chain_code(cosigner, ChainCode(chain_code), "backup");
+
+    let probe_key = heir_keys(backup)?
+        .first()
+        .map(|key| DescriptorPublicKey::from_str(&key.to_string()))
+        .ok_or_else(|| invalid("Backup has no heirs"))?
+        .map_err(reconstruction_error)?;
+    let timelock = Timelock::from_blocks(backup.timelock_blocks).map_err(reconstruction_error)?;
+    let probe = create_inheritable_vault(
+        &owner,
+        &delegated,
+        backup.address_index,
+        PathInfo::Single(probe_key),
+        timelock,
+        0,
+        network,
+    )
+    .map_err(reconstruction_error)?;
+    Ok(probe.aggregate_xonly)
+}
+
+/// Each heir's x-only key, in backup order.
+pub(crate) fn heir_keys(backup: &VaultBackup) -> Result<Vec<XOnlyPublicKey>, HeirError> {
+    backup
+        .heirs
+        .iter()
+        .map(|heir| {
+            Xpub::from_str(&heir.xpub)
+                .map(|xpub| xpub.public_key.x_only_public_key().0)
+                .map_err(|e| invalid(format!("Invalid xpub for {}: {}", heir.label, e)))
+        })
+        .collect()
+}
+
+/// Build the taproot tree for a weighted policy.
+pub(crate) fn build_weighted_vault(
+    backup: &VaultBackup,
+    weights: &Weights,
+) -> Result<WeightedVault, HeirError> {
+    let network = parse_network(&backup.network)?;
+    if weights.weights.len() != backup.heirs.len() {
+        return Err(invalid("Weights must be given for every heir"));
+    }
+    let keys = heir_keys(backup)?;
+    let groups = minimal_signer_sets(&weights.weights, weights.threshold);
+    let recovery_scripts: Vec<ScriptBuf> = groups
+        .iter()
+        .map(|group| {
+            let group_keys: Vec<_> = group.iter().map(|&i| keys[i]).collect();
+            weighted_leaf_script(&group_keys, backup.timelock_blocks)
+        })
+        .collect();
+
+    let secp = Secp256k1::verification_only();
+    let spend_info = add_subtree(TaprootBuilder::new(), &recovery_scripts, 0)
+        .map_err(|e| invalid(format!("Cannot build the recovery tree: {}", e)))?
+        .finalize(&secp, internal_key(backup)?)
+        .map_err(|_| invalid("Cannot build the recovery tree"))?;
+    let address = Address::p2tr_tweaked(spend_info.output_key(), network);
+
+    Ok(WeightedVault {
+        address,
+        spend_info,
+        groups,
+        recovery_scripts,
+    })
+}
+
+/// Rebuild a weighted vault and check it against the backup's address and
+/// recovery leaves.
+pub(crate) fn verify_weighted_vault(
+    backup: &VaultBackup,
+    weights: &Weights,
+) -> Result<WeightedVault, HeirError> {
+    span!("vault.reconstruct");
+    let vault = build_weighted_vault(backup, weights)?;
+    let failed = |message: String| HeirError::new(ErrorKind::VerificationFailed, message);
+    if vault.address.to_string() != backup.vault_address {
+        return Err(failed(format!(
+            "Vault verification failed: weighted policy derives {}, not the backup's address",
+            vault.address
+        )));
+    }
+    let leaves_match = backup.recovery_leaves.len() == vault.recovery_scripts.len()
+        && backup
+            .recovery_leaves
+            .iter()
+            .zip(&vault.recovery_scripts)
+            .all(|(leaf, script)| {
+                leaf.script_hex
+                    .eq_ignore_ascii_case(&script.to_hex_string())
+            });
+    if !leaves_match {
+        return Err(failed(
+            "Vault verification failed: recovery leaves do not match the weighted policy".into(),
+        ));
+    }
+    Ok(vault)
+}
+
+/// Heir weights and threshold of a backup, or `None` for a backup without
+/// weighted thresholds.
+pub fn weighted_policy(vault_json: String) -> Result<Option<WeightedPolicy>, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    Ok(heir_weights(&vault_json)?.map(|weights| WeightedPolicy {
+        heirs: backup
+            .heirs
+            .iter()
+            .zip(&weights.weights)
+            .map(|(heir, &weight)| HeirWeight {
+                label: heir.label.clone(),
+                weight,
+            })
+            .collect(),
+        threshold: weights.threshold,
+        total_weight: weights.weights.iter().sum(),
+    }))
+}
+
+/// Recovery leaves the heirs named in `signer_labels` can claim through
+/// together. Empty if their combined weight is below the threshold.
+pub fn weighted_claim_paths(
+    vault_json: String,
+    signer_labels: Vec<String>,
+) -> Result<Vec<WeightedClaimPath>, HeirError> {
+    let backup = parse_backup(&vault_json)?;
+    let weights = heir_weights(&vault_json)?.ok_or_else(|| {
+        HeirError::new(
+            ErrorKind::InvalidInput,
+            "This backup has no weighted threshold",
+        )
+    })?;
+    if let Some(unknown) = signer_labels
+        .iter()
+        .find(|label| !backup.heirs.iter().any(|heir| &heir.label == *label))
+    {
+        return Err(HeirError::new(
+            ErrorKind::InvalidInput,
+            format!("No heir named {} in this vault", unknown),
+        ));
+    }
+    let vault = verify_weighted_vault(&backup, &weights)?;
+
+    Ok(vault
+        .groups
+        .iter()
+        .enumerate()
+        .filter(|(_, group)| {
+            group
+                .iter()
+                .all(|&i| signer_labels.contains(&backup.heirs[i].label))
+        })
+        .map(|(heir_index, group)| WeightedClaimPath {
+            heir_index,
+            heir_labels: group
+                .iter()
+                .map(|&i| backup.heirs[i].label.clone())
+                .collect(),
+            combined_weight: group.iter().map(|&i| weights.weights[i]).sum(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::api::policy::{analyze_leaf_script, describe_policy};
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::generate_test_vectors;
+    use crate::api::{build_claim_psbt, decode_psbt, import_vault_backup, Backend, Network};
+    use bitcoin::Sequence;
+
+    /// Vector backup with two extra heirs: "Synthetic Heir" weighs 2,
+    /// "Child A" and "Child B" 1 each, threshold 3.
+    pub(crate) fn weighted_backup_json(seed: u32) -> String {
+        let v = generate_test_vectors(seed).unwrap();
+        let mut value: Value = serde_json::from_str(&v.backup_json).unwrap();
+        let template = value["heirs"][0].clone();
+        for (label, byte) in [("Child A", 0x21u8), ("Child B", 0x22)] {
+            let secp = Secp256k1::new();
+            let sk = bitcoin::secp256k1::SecretKey::from_slice(&[byte; 32]).unwrap();
+            let mut xpub = Xpub::from_str(template["xpub"].as_str().unwrap()).unwrap();
+            xpub.public_key = sk.public_key(&secp);
+            let mut heir = template.clone();
+            heir["label"] = serde_json::json!(label);
+            heir["xpub"] = serde_json::json!(xpub.to_string());
+            value["heirs"].as_array_mut().unwrap().push(heir);
+        }
+        let weights = Weights {
+            weights: vec![2, 1, 1],
+            threshold: 3,
+        };
+        add_weights(&mut value, &weights);
+
+        let backup: VaultBackup = serde_json::from_value(value.clone()).unwrap();
+        let vault = build_weighted_vault(&backup, &weights).unwrap();
+        value["vault_address"] = serde_json::json!(vault.address.to_string());
+        value["threshold"] = serde_json::json!(weights.threshold);
+        value["recovery_leaves"] = vault
+            .recovery_scripts
+            .iter()
+            .enumerate()
+            .map(|(index, script)| {
+                let control = vault
+                    .spend_info
+                    .control_block(&(script.clone(), bitcoin::taproot::LeafVersion::TapScript))
+                    .unwrap();
+                serde_json::json!({
+                    "leaf_index": index,
+                    "script_hex": script.to_hex_string(),
+                    "control_block_hex": hex::encode(control.serialize()),
+                    "timelock_blocks": backup.timelock_blocks,
+                    "leaf_version": 192,
+                })
+            })
+            .collect();
+        value.to_string()
+    }
+
+    #[test]
+    fn test_minimal_signer_sets() {
+        // Spouse 2, two children 1 each, threshold 3
+        assert_eq!(
+            minimal_signer_sets(&[2, 1, 1], 3),
+            vec![vec![0, 1], vec![0, 2]]
+        );
+        // Plain 2-of-3 is every pair
+        assert_eq!(
+            minimal_signer_sets(&[1, 1, 1], 2),
+            vec![vec![0, 1], vec![0, 2], vec![1, 2]]
+        );
+        assert_eq!(
+            minimal_signer_sets(&[3, 1, 1], 3),
+            vec![vec![0], vec![1, 2]]
+        );
+    }
+
+    #[test]
+    fn test_leaf_script_analyzes_as_k_of_k() {
+        let secp = Secp256k1::new();
+        let keys: Vec<_> = [1u8, 2]
+            .iter()
+            .map(|b| {
+                bitcoin::secp256k1::SecretKey::from_slice(&[*b; 32])
+                    .unwrap()
+                    .x_only_public_key(&secp)
+                    .0
+            })
+            .collect();
+        let analysis = analyze_leaf_script(&weighted_leaf_script(&keys, 144));
+        assert_eq!(analysis.keys, keys);
+        assert_eq!(analysis.threshold, 2);
+        assert_eq!(analysis.csv_blocks, Some(144));
+
+        let single = analyze_leaf_script(&weighted_leaf_script(&keys[..1], 144));
+        assert_eq!(single.threshold, 1);
+    }
+
+    #[test]
+    fn test_heir_weights_validation() {
+        let v = generate_test_vectors(17).unwrap();
+        assert_eq!(heir_weights(&v.backup_json).unwrap(), None);
+
+        let mut value: Value = serde_json::from_str(&v.backup_json).unwrap();
+        value["heirs"][0][WEIGHT_FIELD] = serde_json::json!(1);
+        assert_eq!(
+            heir_weights(&value.to_string()).unwrap_err().kind,
+            ErrorKind::InvalidBackup
+        );
+        value[THRESHOLD_FIELD] = serde_json::json!(2);
+        let err = heir_weights(&value.to_string()).unwrap_err();
+        assert!(
+            err.message.contains("below the threshold"),
+            "{}",
+            err.message
+        );
+        value[THRESHOLD_FIELD] = serde_json::json!(1);
+        assert_eq!(
+            heir_weights(&value.to_string()).unwrap(),
+            Some(Weights {
+                weights: vec![1],
+                threshold: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_weighted_backup_imports_and_describes() {
+        let json = weighted_backup_json(18);
+        let info = import_vault_backup(json.clone()).unwrap();
+        assert!(info.canonical_json.contains("\"weight_threshold\":3"));
+
+        let policy = describe_policy(json.clone()).unwrap();
+        assert_eq!(policy.weight_threshold, Some(3));
+        assert_eq!(policy.clauses.len(), 3);
+        assert_eq!(
+            policy.clauses[1].heir_labels,
+            vec!["Synthetic Heir", "Child A"]
+        );
+        assert_eq!(policy.clauses[1].combined_weight, Some(3));
+
+        let paths =
+            weighted_claim_paths(json.clone(), vec!["Child A".into(), "Child B".into()]).unwrap();
+        assert!(paths.is_empty());
+        let paths = weighted_claim_paths(
+            json.clone(),
+            vec!["Synthetic Heir".into(), "Child B".into()],
+        )
+        .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].heir_index, 1);
+        assert_eq!(paths[0].combined_weight, 3);
+
+        // Dropping a leaf no longer matches the policy
+        let mut value: Value = serde_json::from_str(&json).unwrap();
+        value["recovery_leaves"].as_array_mut().unwrap().pop();
+        assert_eq!(
+            import_vault_backup(value.to_string()).unwrap_err().kind,
+            ErrorKind::VerificationFailed
+        );
+    }
+
+    #[test]
+    fn test_weighted_claim_psbt_uses_group_leaf() {
+        let json = weighted_backup_json(19);
+        let v = generate_test_vectors(19).unwrap();
+        let address = serde_json::from_str::<Value>(&json).unwrap()["vault_address"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(2_000);
+        sim.add_utxo(address, "a7".repeat(32), 0, 90_000, 1_000)
+            .unwrap();
+
+        let claim = build_claim_psbt(json, &Backend::simulated(&sim), v.destination, 1, 2).unwrap();
+        let psbt = decode_psbt(&claim.psbt_base64).unwrap();
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            Sequence::from_height(144)
+        );
+        assert_eq!(psbt.inputs[0].tap_scripts.len(), 2);
+        // All three heirs are listed as signers of the leaves they appear in
+        assert_eq!(psbt.inputs[0].tap_key_origins.len(), 3);
+        assert_eq!(claim.output_sat, 90_000 - claim.fee_sat);
+    }
+}
@@ -0,0 +1,121 @@
+//! Data for home-screen widgets.
+//!
+//! iOS and Android widgets run in a separate process with no network
+//! access and only a few milliseconds to draw. The app refreshes a vault
+//! while it is open, calls [`eligibility_widget_data`] with the result and
+//! writes [`WidgetData::to_json`] to the storage it shares with the widget,
+//! which then only has to draw the numbers.
+
+use serde::{Deserialize, Serialize};
+
+use super::readonly::load_watched;
+use super::{HeirError, VaultState, VaultStatus};
+
+/// A vault status as the app last fetched it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedStatus {
+    pub status: VaultStatus,
+    /// When `status` was fetched, in unix seconds.
+    pub refreshed_at: u64,
+}
+
+/// Everything a widget draws, computed ahead of time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WidgetData {
+    pub vault_address: String,
+    pub state: VaultState,
+    pub balance_sat: u64,
+    pub eligible: bool,
+    /// Share of the timelock that has passed, 0 to 100. Zero for a vault
+    /// with no confirmed funds.
+    pub percent_elapsed: u8,
+    /// Negative once the timelock has expired, as in [`VaultStatus`].
+    pub blocks_remaining: i64,
+    pub days_remaining: f64,
+    /// When the numbers were true, in unix seconds. Widgets should say how
+    /// old they are rather than count down on their own.
+    pub last_refresh: u64,
+}
+
+impl WidgetData {
+    /// Compact JSON for the storage shared with the widget.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("widget data serializes")
+    }
+}
+
+/// Widget payload for `vault_json` (a full backup or a read-only copy)
+/// from its cached status. Makes no network calls.
+pub fn eligibility_widget_data(
+    vault_json: String,
+    cached_status: CachedStatus,
+) -> Result<WidgetData, HeirError> {
+    let vault = load_watched(&vault_json)?;
+    let status = cached_status.status;
+    let percent_elapsed = if status.confirmed_sat == 0 || vault.timelock_blocks == 0 {
+        0
+    } else {
+        let timelock = i64::from(vault.timelock_blocks);
+        let elapsed = (timelock - status.blocks_remaining).clamp(0, timelock);
+        (elapsed * 100 / timelock) as u8
+    };
+    Ok(WidgetData {
+        vault_address: vault.address.to_string(),
+        state: status.state,
+        balance_sat: status.balance_sat,
+        eligible: status.eligible,
+        percent_elapsed,
+        blocks_remaining: status.blocks_remaining,
+        days_remaining: status.days_remaining,
+        last_refresh: cached_status.refreshed_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::readonly::export_read_only_vault;
+    use crate::api::simulated::SimulatedBackend;
+    use crate::api::vectors::{generate_test_vectors, TestVectors};
+    use crate::api::{fetch_vault_status, Backend, Network};
+
+    fn cached(v: &TestVectors, height: u64) -> CachedStatus {
+        let sim = SimulatedBackend::new(Network::Testnet);
+        sim.set_height(height);
+        let (txid, vout) = v.funding_outpoint.split_once(':').unwrap();
+        sim.add_utxo(
+            v.vault_address.clone(),
+            txid.into(),
+            vout.parse().unwrap(),
+            v.funding_value_sat,
+            1,
+        )
+        .unwrap();
+        CachedStatus {
+            status: fetch_vault_status(v.backup_json.clone(), &Backend::simulated(&sim)).unwrap(),
+            refreshed_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_locked_vault_from_read_only_copy() {
+        let v = generate_test_vectors(43).unwrap();
+        let copy = export_read_only_vault(v.backup_json.clone()).unwrap();
+        let data = eligibility_widget_data(copy, cached(&v, 50)).unwrap();
+        assert_eq!(data.vault_address, v.vault_address);
+        assert_eq!(data.state, VaultState::FundedLocked);
+        assert_eq!(data.blocks_remaining, 95);
+        // 49 of 144 blocks
+        assert_eq!(data.percent_elapsed, 34);
+        assert_eq!(data.last_refresh, 1_700_000_000);
+        assert!(data.to_json().contains("\"percent_elapsed\":34"));
+    }
+
+    #[test]
+    fn test_expired_vault_caps_at_hundred() {
+        let v = generate_test_vectors(44).unwrap();
+        let data = eligibility_widget_data(v.backup_json.clone(), cached(&v, 1_000)).unwrap();
+        assert!(data.eligible);
+        assert_eq!(data.percent_elapsed, 100);
+    }
+}
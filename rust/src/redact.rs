@@ -0,0 +1,140 @@
+//! Redaction of secret material before it reaches `Debug` output, error
+//! messages, or log lines.
+//!
+//! Backup contents are routinely echoed back by parsers ("invalid type:
+//! string \"...\"") and by upstream crates. Anything that could be key
+//! material is masked here before it crosses the FFI boundary.
+
+/// Replacement text for redacted material.
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// Extended private key prefixes (BIP32/49/84, main and test networks).
+const XPRV_PREFIXES: &[&str] = &[
+    "xprv", "tprv", "yprv", "zprv", "uprv", "vprv", "Yprv", "Zprv", "Uprv", "Vprv",
+];
+
+/// Shortest hex run treated as key material (a 32-byte chain code or secret).
+const MIN_SECRET_HEX_LEN: usize = 64;
+
+/// Shortest run of lowercase words treated as a mnemonic phrase.
+const MIN_MNEMONIC_WORDS: usize = 12;
+
+/// Mask chain codes, extended private keys, and mnemonic phrases in `input`.
+pub(crate) fn redact_secrets(input: &str) -> String {
+    let tokens = tokenize(input);
+    let mut out = String::with_capacity(input.len());
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let run = mnemonic_run_len(&tokens[i..]);
+        if run > 0 {
+            out.push_str(REDACTED);
+            i += run;
+            continue;
+        }
+
+        let token = tokens[i];
+        if is_secret_token(token) {
+            out.push_str(REDACTED);
+        } else {
+            out.push_str(token);
+        }
+        i += 1;
+    }
+
+    out
+}
+
+/// Split into alternating runs of alphanumeric and non-alphanumeric characters.
+fn tokenize(input: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev_alnum: Option<bool> = None;
+
+    for (idx, ch) in input.char_indices() {
+        let alnum = ch.is_ascii_alphanumeric();
+        if prev_alnum.is_some_and(|p| p != alnum) {
+            tokens.push(&input[start..idx]);
+            start = idx;
+        }
+        prev_alnum = Some(alnum);
+    }
+    if start < input.len() {
+        tokens.push(&input[start..]);
+    }
+
+    tokens
+}
+
+fn is_secret_token(token: &str) -> bool {
+    if token.len() >= MIN_SECRET_HEX_LEN && token.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return true;
+    }
+    XPRV_PREFIXES.iter().any(|p| token.starts_with(p)) && token.len() > 100
+}
+
+fn is_mnemonic_word(token: &str) -> bool {
+    (3..=8).contains(&token.len()) && token.bytes().all(|b| b.is_ascii_lowercase())
+}
+
+/// Number of tokens covered by a mnemonic-looking run starting at `tokens[0]`,
+/// or 0 if there is none. Words must be separated by single spaces.
+fn mnemonic_run_len(tokens: &[&str]) -> usize {
+    let mut words = 0;
+    let mut consumed = 0;
+    let mut last_word_end = 0;
+
+    while consumed < tokens.len() && is_mnemonic_word(tokens[consumed]) {
+        words += 1;
+        consumed += 1;
+        last_word_end = consumed;
+        if consumed < tokens.len() && tokens[consumed] == " " {
+            consumed += 1;
+        } else {
+            break;
+        }
+    }
+
+    if words >= MIN_MNEMONIC_WORDS {
+        last_word_end
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_chain_code() {
+        let cc = "ab".repeat(32);
+        let out = redact_secrets(&format!("invalid type: string \"{}\", expected u8", cc));
+        assert!(!out.contains(&cc));
+        assert!(out.contains(REDACTED));
+        assert!(out.starts_with("invalid type: string \""));
+    }
+
+    #[test]
+    fn test_redacts_xprv() {
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let out = redact_secrets(&format!("bad key {}", xprv));
+        assert_eq!(out, format!("bad key {}", REDACTED));
+    }
+
+    #[test]
+    fn test_redacts_mnemonic() {
+        let phrase = "abandon abandon abandon abandon abandon abandon \
+                      abandon abandon abandon abandon abandon about";
+        let out = redact_secrets(&format!("seed: {}.", phrase));
+        assert_eq!(out, format!("seed: {}.", REDACTED));
+    }
+
+    #[test]
+    fn test_leaves_ordinary_text_alone() {
+        let msg = "Fee rate exceeds 500 sat/vB safety limit (heir 0)";
+        assert_eq!(redact_secrets(msg), msg);
+        // Short hex values such as fingerprints are not secrets
+        assert_eq!(redact_secrets("fingerprint aabbccdd"), "fingerprint aabbccdd");
+    }
+}
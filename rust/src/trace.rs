@@ -0,0 +1,12 @@
+//! Timing spans for field profiling, compiled in only with the `tracing`
+//! feature. Without it, [`span!`] expands to nothing.
+
+/// Enter a named span that lasts until the end of the enclosing block.
+macro_rules! span {
+    ($name:literal) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($name).entered();
+    };
+}
+
+pub(crate) use span;
@@ -283,11 +283,12 @@ fn test_e2e_live_electrum_status() {
     };
 
     let json = serde_json::to_string(&backup).unwrap();
-    let status = nostring_heir_ffi::api::fetch_vault_status(
-        json,
+    let backend = nostring_heir_ffi::api::Backend::electrum(
         "ssl://electrum.blockstream.info:60002".into(),
+        nostring_heir_ffi::api::Network::Testnet,
     )
     .unwrap();
+    let status = nostring_heir_ffi::api::fetch_vault_status(json, &backend).unwrap();
 
     println!("Balance: {} sats", status.balance_sat);
     println!("UTXOs: {}", status.utxo_count);